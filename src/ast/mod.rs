@@ -28,6 +28,30 @@ pub struct Crate {
 #[derive(Debug)]
 pub struct Item {
     pub kind: ItemKind,
+    pub attrs: Vec<Attribute>,
+    pub span: Span,
+}
+
+/// Outer attribute `#[meta]`
+#[derive(Debug)]
+pub struct Attribute {
+    pub meta: MetaItem,
+    pub span: Span,
+}
+
+/// `name`, `name = "value"` or `name(meta, ...)`
+#[derive(Debug)]
+pub struct MetaItem {
+    pub name: String,
+    pub kind: MetaItemKind,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum MetaItemKind {
+    Word,
+    NameValue(String),
+    List(Vec<MetaItem>),
 }
 
 #[derive(Debug)]
@@ -36,6 +60,10 @@ pub enum ItemKind {
     Struct(StructItem),
     ExternBlock(ExternBlock),
     Mod(Module),
+    TyAlias(TyAlias),
+    Impl(Impl),
+    Const(ConstItem),
+    Static(StaticItem),
 }
 
 #[derive(Debug)]
@@ -53,13 +81,66 @@ pub struct ExternBlock {
 #[derive(Debug)]
 pub struct StructItem {
     pub ident: Ident,
+    pub generics: Generics,
+    /// Fields of tuple structs are named `0`, `1`, ...
     pub fields: Vec<(Ident, Ty)>,
+    pub kind: StructKind,
     pub id: NodeId,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StructKind {
+    /// `struct S { a: T }`
+    Named,
+    /// `struct S(T);`
+    Tuple,
+    /// `struct S;`
+    Unit,
+}
+
+/// `type ident = ty;`
+#[derive(Debug)]
+pub struct TyAlias {
+    pub ident: Ident,
+    pub ty: Ty,
+}
+
+/// `const IDENT: ty = expr;`
+#[derive(Debug)]
+pub struct ConstItem {
+    pub ident: Ident,
+    pub ty: Ty,
+    pub expr: Expr,
+}
+
+/// `static IDENT: ty = expr;` or `static mut IDENT: ty = expr;`
+#[derive(Debug)]
+pub struct StaticItem {
+    pub ident: Ident,
+    pub mutbl: Mutability,
+    pub ty: Ty,
+    pub expr: Expr,
+}
+
+/// `impl<'a> Trait for Ty { fn ... }`
+#[derive(Debug)]
+pub struct Impl {
+    pub generics: Generics,
+    pub of_trait: Path,
+    pub self_ty: Ty,
+    pub items: Vec<Func>,
+}
+
 #[derive(Debug)]
 pub struct Func {
     pub name: Ident,
+    pub generics: Generics,
+    /// `unsafe fn`
+    pub unsafety: Unsafety,
+    /// `const fn`, which can be called in constants
+    pub constness: Constness,
+    /// `#[inline]` attributes, which are given by the enclosing item
+    pub inline: InlineAttr,
     pub params: Vec<(Ident, Ty)>,
     pub ret_ty: Ty,
     /// Extern abi
@@ -68,9 +149,16 @@ pub struct Func {
     pub id: NodeId,
 }
 
+/// Generic parameters `<'a, 'b>`. Only lifetime parameters are supported
+#[derive(Debug, Default, Clone)]
+pub struct Generics {
+    pub lifetimes: Vec<Lifetime>,
+}
+
 #[derive(Debug)]
 pub struct Stmt {
     pub kind: StmtKind,
+    pub attrs: Vec<Attribute>,
     pub id: NodeId,
     pub span: Span,
 }
@@ -87,6 +175,7 @@ pub enum StmtKind {
 #[derive(Debug)]
 pub struct LetStmt {
     pub ident: Ident,
+    pub mutbl: Mutability,
     pub ty: Option<Ty>,
     pub init: Option<Expr>,
 }
@@ -102,22 +191,89 @@ pub struct Expr {
 pub enum ExprKind {
     Binary(BinOp, Box<Expr>, Box<Expr>),
     Unary(UnOp, Box<Expr>),
-    NumLit(u32),
+    /// `&expr` or `&mut expr`
+    AddrOf(Mutability, Box<Expr>),
+    NumLit(u128),
+    /// Floating-point literal with its suffix
+    FloatLit(f64, Option<FloatTy>),
     BoolLit(bool),
     StrLit(String),
+    CharLit(char),
     Unit,
     Path(Path),
     Assign(Box<Expr>, Box<Expr>),
     Return(Box<Expr>),
     Call(Box<Expr>, Vec<Expr>),
+    /// `recv.method(args)`. Only the built-in methods of raw pointers exist
+    MethodCall(Box<Expr>, Ident, Vec<Expr>),
     Block(Block),
     /// cond, then (only block expr), else
     If(Box<Expr>, Box<Expr>, Option<Box<Expr>>),
     Index(Box<Expr>, Box<Expr>),
     Field(Box<Expr>, Ident),
-    Struct(Path, Vec<(Ident, Box<Expr>)>),
+    /// `S { a: expr, b, ..base }`
+    Struct(Path, Vec<(Ident, Box<Expr>)>, Option<Box<Expr>>),
     Array(Vec<Expr>),
     Cast(Box<Expr>, Ty),
+    /// `Box::new(expr)`, which moves the value to the heap
+    Box(Box<Expr>),
+    /// `core::ptr::null()` or `core::ptr::null_mut()`
+    NullPtr(Mutability),
+    /// Expanded from `panic!` and friends. Prints the formatted message and aborts
+    Panic(FormatArgs),
+    /// Expanded from `print!` and friends
+    Print(PrintDest, FormatArgs),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrintDest {
+    Stdout,
+    Stderr,
+}
+
+/// Format string and arguments of a formatting macro
+#[derive(Debug)]
+pub struct FormatArgs {
+    pub pieces: Vec<FormatPiece>,
+    pub args: Vec<Expr>,
+}
+
+impl FormatArgs {
+    /// A message without placeholders. It is not parsed, so braces are printed as they are
+    pub fn lit(msg: String) -> Self {
+        FormatArgs {
+            pieces: vec![FormatPiece::Lit(msg)],
+            args: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum FormatPiece {
+    Lit(String),
+    Placeholder(FormatSpec),
+}
+
+/// `{arg:align width.precision?}`
+#[derive(Debug, Clone)]
+pub struct FormatSpec {
+    /// Index into `FormatArgs::args`
+    pub arg: usize,
+    pub align: Option<FormatAlign>,
+    /// `{:0N}`
+    pub zero_pad: bool,
+    pub width: Option<usize>,
+    pub precision: Option<usize>,
+    /// `{:?}`
+    pub debug: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatAlign {
+    /// `<`
+    Left,
+    /// `>`
+    Right,
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
@@ -144,25 +300,31 @@ impl std::fmt::Debug for Path {
 #[derive(Debug)]
 pub struct Block {
     pub stmts: Vec<Stmt>,
+    /// `unsafe { }`
+    pub unsafety: Unsafety,
     pub span: Span,
     pub id: NodeId,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum BinOp {
     Add,
     Sub,
     Mul,
+    Div,
+    Rem,
     Eq,
     Ne,
     Gt,
     Lt,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum UnOp {
     Plus,
     Minus,
+    /// `*expr`
+    Deref,
 }
 
 #[derive(Debug)]
@@ -175,13 +337,160 @@ pub struct Ty {
 pub enum TyKind {
     Unit,
     Bool,
-    I32,
+    Int(IntTy),
+    F32,
+    F64,
     Str,
-    Array(Box<Ty>, usize),
-    Adt(Path),
-    Ref(Option<Region>, Box<Ty>),
-    ConstPtr(Box<Ty>),
+    Char,
+    /// `[T; len]`, where the length is evaluated at compile time
+    Array(Box<Ty>, Box<Expr>),
+    /// `S` or `S<'a>`
+    Adt(Path, Vec<Lifetime>),
+    Ref(Option<Lifetime>, Mutability, Box<Ty>),
+    /// `*const T` or `*mut T`
+    RawPtr(Mutability, Box<Ty>),
+    /// Built-in `Box<T>`
+    Box(Box<Ty>),
+    Never,
+    /// `Self` in impls
+    SelfTy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unsafety {
+    Safe,
+    Unsafe,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Constness {
+    Const,
+    NotConst,
+}
+
+/// `#[inline]` hints for the inliner, which runs with `-O`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InlineAttr {
+    /// Inlined only if the function is small
+    #[default]
+    None,
+    /// `#[inline]`
+    Hint,
+    /// `#[inline(always)]`
+    Always,
+    /// `#[inline(never)]`
     Never,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mutability {
+    Not,
+    Mut,
+}
+
+/// Name of a lifetime without the leading quote (e.g. `a` and `static`)
 pub type Region = String;
+
+/// `'a`
+#[derive(Debug, Clone)]
+pub struct Lifetime {
+    pub name: Region,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatTy {
+    F32,
+    F64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntTy {
+    I8,
+    I16,
+    I32,
+    I64,
+    Isize,
+    U8,
+    U16,
+    U32,
+    U64,
+    Usize,
+}
+
+impl IntTy {
+    pub fn from_name(name: &str) -> Option<IntTy> {
+        Some(match name {
+            "i8" => IntTy::I8,
+            "i16" => IntTy::I16,
+            "i32" => IntTy::I32,
+            "i64" => IntTy::I64,
+            "isize" => IntTy::Isize,
+            "u8" => IntTy::U8,
+            "u16" => IntTy::U16,
+            "u32" => IntTy::U32,
+            "u64" => IntTy::U64,
+            "usize" => IntTy::Usize,
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IntTy::I8 => "i8",
+            IntTy::I16 => "i16",
+            IntTy::I32 => "i32",
+            IntTy::I64 => "i64",
+            IntTy::Isize => "isize",
+            IntTy::U8 => "u8",
+            IntTy::U16 => "u16",
+            IntTy::U32 => "u32",
+            IntTy::U64 => "u64",
+            IntTy::Usize => "usize",
+        }
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            IntTy::I8 | IntTy::I16 | IntTy::I32 | IntTy::I64 | IntTy::Isize
+        )
+    }
+
+    /// Pointer-sized integers are 64-bit as only x86-64 is supported
+    pub fn bit_width(&self) -> usize {
+        match self {
+            IntTy::I8 | IntTy::U8 => 8,
+            IntTy::I16 | IntTy::U16 => 16,
+            IntTy::I32 | IntTy::U32 => 32,
+            IntTy::I64 | IntTy::U64 | IntTy::Isize | IntTy::Usize => 64,
+        }
+    }
+
+    pub fn min_value(&self) -> i128 {
+        if self.is_signed() {
+            -(1 << (self.bit_width() - 1))
+        } else {
+            0
+        }
+    }
+
+    pub fn max_value(&self) -> i128 {
+        if self.is_signed() {
+            (1 << (self.bit_width() - 1)) - 1
+        } else {
+            (1 << self.bit_width()) - 1
+        }
+    }
+
+    /// Truncate `n` to the width of the type, like `as` casts
+    pub fn truncate(&self, n: i128) -> i128 {
+        let bits = self.bit_width();
+        let n = n & ((1 << bits) - 1);
+        if n > self.max_value() {
+            n - (1 << bits)
+        } else {
+            n
+        }
+    }
+}
@@ -12,6 +12,14 @@ pub trait Visitor<'ctx>: Sized {
     fn visit_func_post(&mut self, _func: &'ctx Func) {}
     fn visit_struct_item(&mut self, _struct: &'ctx StructItem) {}
     fn visit_struct_item_post(&mut self, _struct: &'ctx StructItem) {}
+    fn visit_ty_alias(&mut self, _alias: &'ctx TyAlias) {}
+    fn visit_ty_alias_post(&mut self, _alias: &'ctx TyAlias) {}
+    fn visit_const_item(&mut self, _item: &'ctx ConstItem) {}
+    fn visit_const_item_post(&mut self, _item: &'ctx ConstItem) {}
+    fn visit_static_item(&mut self, _item: &'ctx StaticItem) {}
+    fn visit_static_item_post(&mut self, _item: &'ctx StaticItem) {}
+    fn visit_impl(&mut self, _impl: &'ctx Impl) {}
+    fn visit_impl_post(&mut self, _impl: &'ctx Impl) {}
     fn visit_extern_block(&mut self, _block: &'ctx ExternBlock) {}
     fn visit_extern_block_post(&mut self, _block: &'ctx ExternBlock) {}
     fn visit_stmt(&mut self, _stmt: &'ctx Stmt) {}
@@ -29,10 +37,6 @@ pub fn go<'ctx, V: Visitor<'ctx>>(v: &mut V, krate: &'ctx Crate) {
     walk_crate(v, krate);
 }
 
-pub fn go_func<'ctx, V: Visitor<'ctx>>(v: &mut V, func: &'ctx Func) {
-    walk_func(v, func);
-}
-
 fn walk_crate<'ctx, V: Visitor<'ctx>>(v: &mut V, krate: &'ctx Crate) {
     v.visit_crate(krate);
     for item in &krate.items {
@@ -56,6 +60,18 @@ fn walk_item<'ctx, V: Visitor<'ctx>>(v: &mut V, item: &'ctx Item) {
         ItemKind::Mod(module) => {
             walk_module_item(v, module);
         }
+        ItemKind::TyAlias(alias) => {
+            walk_ty_alias(v, alias);
+        }
+        ItemKind::Impl(imp) => {
+            walk_impl(v, imp);
+        }
+        ItemKind::Const(const_item) => {
+            walk_const_item(v, const_item);
+        }
+        ItemKind::Static(static_item) => {
+            walk_static_item(v, static_item);
+        }
     }
     v.visit_item_post(item);
 }
@@ -82,6 +98,36 @@ fn walk_struct_item<'ctx, V: Visitor<'ctx>>(v: &mut V, struct_item: &'ctx Struct
     v.visit_struct_item_post(struct_item);
 }
 
+fn walk_ty_alias<'ctx, V: Visitor<'ctx>>(v: &mut V, alias: &'ctx TyAlias) {
+    v.visit_ty_alias(alias);
+    walk_type(v, &alias.ty);
+    v.visit_ty_alias_post(alias);
+}
+
+fn walk_const_item<'ctx, V: Visitor<'ctx>>(v: &mut V, const_item: &'ctx ConstItem) {
+    v.visit_const_item(const_item);
+    walk_type(v, &const_item.ty);
+    walk_expr(v, &const_item.expr);
+    v.visit_const_item_post(const_item);
+}
+
+fn walk_static_item<'ctx, V: Visitor<'ctx>>(v: &mut V, static_item: &'ctx StaticItem) {
+    v.visit_static_item(static_item);
+    walk_type(v, &static_item.ty);
+    walk_expr(v, &static_item.expr);
+    v.visit_static_item_post(static_item);
+}
+
+fn walk_impl<'ctx, V: Visitor<'ctx>>(v: &mut V, imp: &'ctx Impl) {
+    v.visit_impl(imp);
+    walk_path(v, &imp.of_trait);
+    walk_type(v, &imp.self_ty);
+    for func in &imp.items {
+        walk_func(v, func);
+    }
+    v.visit_impl_post(imp);
+}
+
 fn walk_extern_block<'ctx, V: Visitor<'ctx>>(v: &mut V, block: &'ctx ExternBlock) {
     v.visit_extern_block(block);
     for func in &block.funcs {
@@ -104,7 +150,12 @@ fn walk_stmt<'ctx, V: Visitor<'ctx>>(v: &mut V, stmt: &'ctx Stmt) {
         StmtKind::Semi(expr) => walk_expr(v, expr),
         StmtKind::Expr(expr) => walk_expr(v, expr),
         StmtKind::Let(let_stmt) => {
-            let LetStmt { ident: _, ty, init } = let_stmt;
+            let LetStmt {
+                ident: _,
+                mutbl: _,
+                ty,
+                init,
+            } = let_stmt;
             if let Some(ty) = ty {
                 walk_type(v, ty);
             }
@@ -119,17 +170,26 @@ fn walk_stmt<'ctx, V: Visitor<'ctx>>(v: &mut V, stmt: &'ctx Stmt) {
 fn walk_type<'ctx, V: Visitor<'ctx>>(v: &mut V, ty: &'ctx Ty) {
     v.visit_type(ty);
     match &ty.kind {
-        TyKind::Bool | TyKind::I32 | TyKind::Never | TyKind::Str | TyKind::Unit => (),
-        TyKind::Array(elem_ty, _n) => {
+        TyKind::Bool
+        | TyKind::Int(_)
+        | TyKind::F32
+        | TyKind::F64
+        | TyKind::Never
+        | TyKind::Str
+        | TyKind::Char
+        | TyKind::SelfTy
+        | TyKind::Unit => (),
+        TyKind::Array(elem_ty, len) => {
             walk_type(v, elem_ty);
+            walk_expr(v, len);
         }
-        TyKind::Ref(_region, inner_ty) => {
+        TyKind::Ref(_region, _mutbl, inner_ty) => {
             walk_type(v, inner_ty);
         }
-        TyKind::Adt(path) => {
+        TyKind::Adt(path, _lifetimes) => {
             walk_path(v, path);
         }
-        TyKind::ConstPtr(referent) => {
+        TyKind::RawPtr(_, referent) | TyKind::Box(referent) => {
             walk_type(v, &referent);
         }
     }
@@ -139,12 +199,18 @@ fn walk_type<'ctx, V: Visitor<'ctx>>(v: &mut V, ty: &'ctx Ty) {
 fn walk_expr<'ctx, V: Visitor<'ctx>>(v: &mut V, expr: &'ctx Expr) {
     v.visit_expr(expr);
     match &expr.kind {
-        ExprKind::NumLit(_) | ExprKind::BoolLit(_) | ExprKind::StrLit(_) | ExprKind::Unit => (),
+        ExprKind::NumLit(_)
+        | ExprKind::FloatLit(..)
+        | ExprKind::BoolLit(_)
+        | ExprKind::StrLit(_)
+        | ExprKind::CharLit(_)
+        | ExprKind::Unit
+        | ExprKind::NullPtr(_) => (),
         ExprKind::Binary(_, l, r) | ExprKind::Assign(l, r) => {
             walk_expr(v, l);
             walk_expr(v, r);
         }
-        ExprKind::Unary(_op, inner) => {
+        ExprKind::Unary(_, inner) | ExprKind::AddrOf(_, inner) | ExprKind::Box(inner) => {
             walk_expr(v, inner);
         }
         ExprKind::Path(path) => {
@@ -159,6 +225,17 @@ fn walk_expr<'ctx, V: Visitor<'ctx>>(v: &mut V, expr: &'ctx Expr) {
                 walk_expr(v, arg);
             }
         }
+        ExprKind::MethodCall(recv, _method, args) => {
+            walk_expr(v, recv);
+            for arg in args {
+                walk_expr(v, arg);
+            }
+        }
+        ExprKind::Print(_, fmt) | ExprKind::Panic(fmt) => {
+            for arg in &fmt.args {
+                walk_expr(v, arg);
+            }
+        }
         ExprKind::Block(block) => {
             walk_block(v, block);
         }
@@ -176,11 +253,14 @@ fn walk_expr<'ctx, V: Visitor<'ctx>>(v: &mut V, expr: &'ctx Expr) {
         ExprKind::Field(receiver, _field) => {
             walk_expr(v, receiver);
         }
-        ExprKind::Struct(path, fds) => {
+        ExprKind::Struct(path, fds, base) => {
             walk_path(v, path);
             for (_ident, expr) in fds {
                 walk_expr(v, expr);
             }
+            if let Some(base) = base {
+                walk_expr(v, base);
+            }
         }
         ExprKind::Array(elems) => {
             for e in elems {
@@ -0,0 +1,336 @@
+use super::{
+    runtime::{PANIC_EXIT_CODE, PANIC_FN},
+    Codegen, LLImm, LLValue,
+};
+use crate::{
+    backend_llvm::{
+        frame::compute_frame,
+        llvm::{LLReg, LLTy},
+        mem2reg,
+    },
+    mir::{self, Body, ForeignFn, Program, Statement, StatementKind, Terminator, TerminatorKind},
+};
+use std::rc::Rc;
+
+impl<'a, 'ctx> Codegen<'a, 'ctx> {
+    pub fn gen_program(&mut self, program: &Program) {
+        for foreign_fn in &program.foreign_fns {
+            self.gen_foreign_fn(foreign_fn);
+        }
+        for static_item in &program.statics {
+            let init = self.eval_constant(&static_item.init);
+            println!(
+                "@{} = global {}",
+                static_item.name.demangle(),
+                init.to_string_with_type()
+            );
+        }
+        if !program.statics.is_empty() {
+            println!();
+        }
+        for body in &program.bodies {
+            self.gen_body(body);
+        }
+    }
+
+    /// Functions declared in `extern` blocks
+    fn gen_foreign_fn(&mut self, foreign_fn: &ForeignFn) {
+        let name = foreign_fn.name.demangle();
+        let ret_llty = self.ty_to_llty(&foreign_fn.ret_ty);
+        let params: Vec<String> = foreign_fn
+            .param_tys
+            .iter()
+            .map(|ty| self.ty_to_llty(ty))
+            .filter(|llty| !llty.is_void())
+            .map(|llty| {
+                if llty.eval_to_ptr() {
+                    "ptr".to_string()
+                } else {
+                    llty.to_string()
+                }
+            })
+            .collect();
+        println!(
+            "declare {} @{}({})",
+            ret_llty.to_string(),
+            name,
+            params.join(", ")
+        );
+        println!();
+        self.declared_funcs.insert(name);
+    }
+
+    fn gen_body(&mut self, body: &Body) {
+        let ssa = mem2reg::analyze(self, body);
+        let frame = compute_frame(self, body, &ssa.promoted);
+        self.push_frame(frame);
+
+        let ret_llty = Rc::new(self.ty_to_llty(&body.return_ty()));
+        // We use `sret` to return ADTs or arrays. In this case, actual return type become `void`
+        let actual_ret_llty = if ret_llty.is_void() || ret_llty.eval_to_ptr() {
+            &LLTy::Void
+        } else {
+            &ret_llty
+        };
+
+        let mut params = vec![];
+        if ret_llty.eval_to_ptr() {
+            params.push(format!("ptr sret({}) %_0", ret_llty.to_string()));
+        }
+        // parameters which are passed via registers but allocated on stack
+        let mut spilled_params = vec![];
+        for arg in body.args() {
+            if self.peek_frame().is_promoted(arg) {
+                let llty = self.ty_to_llty(&body.locals[arg].ty);
+                let reg = LLReg::new(format!("%_{arg}"), Rc::new(llty));
+                params.push(reg.to_string_with_type());
+                self.peek_frame_mut()
+                    .set_local_value(arg, LLValue::Reg(reg));
+                continue;
+            }
+            let Some(reg) = self.peek_frame().get_local_reg(arg) else {
+                continue;
+            };
+            let llty = reg.llty.peel_ptr().unwrap();
+            if llty.eval_to_ptr() {
+                // argument passed via memory (i.e. call by reference)
+                params.push(reg.to_string_with_type());
+            } else {
+                let arg_reg = LLReg::new(format!("{}.arg", reg.name), llty);
+                params.push(arg_reg.to_string_with_type());
+                spilled_params.push((reg, arg_reg));
+            }
+        }
+        println!(
+            "define {} @{}({}) {{",
+            actual_ret_llty.to_string(),
+            body.name.demangle(),
+            params.join(", ")
+        );
+
+        println!("start:");
+        // allocate locals except those passed via memory
+        for local in (body.arg_count + 1)..body.locals.len() {
+            if let Some(reg) = self.peek_frame().get_local_reg(local) {
+                println!(
+                    "\t{} = alloca {}",
+                    reg.name,
+                    reg.llty.peel_ptr().unwrap().to_string()
+                );
+            }
+        }
+        if !ret_llty.eval_to_ptr()
+            && let Some(reg) = self.peek_frame().get_local_reg(mir::RETURN_PLACE)
+        {
+            println!(
+                "\t{} = alloca {}",
+                reg.name,
+                reg.llty.peel_ptr().unwrap().to_string()
+            );
+        }
+        for (ptr, arg) in &spilled_params {
+            println!("\t{} = alloca {}", ptr.name, arg.llty.to_string());
+            println!(
+                "\tstore {}, {}",
+                arg.to_string_with_type(),
+                ptr.to_string_with_type()
+            );
+        }
+        if let Some(reg) = self.peek_frame().get_zst_reg() {
+            println!("\t{} = alloca i8", reg.name);
+        }
+        for reg in self.peek_frame().get_utf8_bufs() {
+            println!(
+                "\t{} = alloca {}",
+                reg.name,
+                reg.llty.peel_ptr().unwrap().to_string()
+            );
+        }
+        println!("\tbr label %bb{}", mir::START_BLOCK);
+
+        // values of the promoted locals at the end of each block
+        let mut end_values = vec![vec![]; body.blocks.len()];
+        for &bb in &ssa.order {
+            println!("bb{bb}:");
+            if bb != mir::START_BLOCK {
+                self.gen_phis(body, &ssa, bb, &end_values);
+            }
+            let block = &body.blocks[bb];
+            for stmt in &block.stmts {
+                self.gen_stmt(stmt);
+            }
+            self.gen_terminator(&block.terminator, ret_llty.eval_to_ptr());
+            end_values[bb] = (0..body.locals.len())
+                .map(|local| self.peek_frame().get_local_value(local))
+                .collect();
+        }
+
+        println!("}}");
+        println!();
+
+        self.pop_frame();
+    }
+
+    /// Merge the values of the promoted locals at the end of the predecessors of `bb`.
+    /// The predecessors are generated before `bb` as blocks are in reverse postorder
+    fn gen_phis(
+        &mut self,
+        body: &Body,
+        ssa: &mem2reg::Ssa,
+        bb: mir::BasicBlock,
+        end_values: &[Vec<Option<LLValue>>],
+    ) {
+        for local in (0..body.locals.len()).filter(|local| ssa.promoted[*local]) {
+            let llty = Rc::new(self.ty_to_llty(&body.locals[local].ty));
+            if !ssa.live_ins[bb].contains(&local) {
+                // dead locals are assigned before being read again
+                let undef = LLValue::Imm(LLImm::Undef(llty));
+                self.peek_frame_mut().set_local_value(local, undef);
+                continue;
+            }
+            let incoming: Vec<(LLValue, mir::BasicBlock)> = ssa.preds[bb]
+                .iter()
+                .map(|pred| (end_values[*pred][local].clone().unwrap(), *pred))
+                .collect();
+            let val = &incoming[0].0;
+            let val = if incoming
+                .iter()
+                .all(|(v, _)| v.to_string() == val.to_string())
+            {
+                val.clone()
+            } else {
+                let reg = LLReg::new(format!("%_{local}.bb{bb}"), llty);
+                let incoming: Vec<String> = incoming
+                    .iter()
+                    .map(|(v, pred)| format!("[{}, %bb{pred}]", v.to_string()))
+                    .collect();
+                println!(
+                    "\t{} = phi {} {}",
+                    reg.name,
+                    reg.llty.to_string(),
+                    incoming.join(", ")
+                );
+                LLValue::Reg(reg)
+            };
+            self.peek_frame_mut().set_local_value(local, val);
+        }
+    }
+
+    fn gen_stmt(&mut self, stmt: &Statement) {
+        println!("; {}", stmt.kind);
+        match &stmt.kind {
+            StatementKind::Assign(place, rvalue) => {
+                let ty = self.peek_frame().get_place_ty(place, self);
+                if self.ty_to_llty(&ty).is_void() {
+                    // nothing is stored
+                    return;
+                }
+                if place.projs.is_empty() && self.peek_frame().is_promoted(place.local) {
+                    let val = self.eval_rvalue(rvalue);
+                    self.peek_frame_mut().set_local_value(place.local, val);
+                    return;
+                }
+                let ptr = self.gen_place_ptr(place);
+                self.gen_rvalue_into(&ptr, rvalue);
+            }
+            StatementKind::Drop(place) => {
+                let ty = self.peek_frame().get_place_ty(place, self);
+                let ptr = self.gen_place_ptr(place);
+                self.gen_drop_in_place(&ptr, &ty);
+            }
+            StatementKind::Deallocate(place) => {
+                let boxed = self.eval_place(place);
+                println!("\tcall void @free(ptr {})", boxed.to_string());
+                self.uses_heap = true;
+            }
+            StatementKind::Print(dest, pieces, args) => self.gen_print(*dest, pieces, args),
+        }
+    }
+
+    fn gen_terminator(&mut self, terminator: &Terminator, uses_sret: bool) {
+        println!("; {}", terminator.kind);
+        match &terminator.kind {
+            TerminatorKind::Goto(bb) => println!("\tbr label %bb{bb}"),
+            TerminatorKind::If(cond, then_bb, else_bb) => {
+                let cond = self.eval_operand(cond);
+                println!(
+                    "\tbr {}, label %bb{then_bb}, label %bb{else_bb}",
+                    cond.to_string_with_type()
+                );
+            }
+            TerminatorKind::Call {
+                func,
+                args,
+                dest,
+                target,
+            } => {
+                let mut arg_vals = vec![];
+                for arg in args {
+                    let arg_val = self.eval_operand(arg);
+                    if !arg_val.llty().is_void() {
+                        arg_vals.push(arg_val.to_string_with_type());
+                    }
+                }
+
+                let ret_ty = self.peek_frame().get_place_ty(dest, self);
+                let ret_llty = self.ty_to_llty(&ret_ty);
+                let dest_is_promoted =
+                    dest.projs.is_empty() && self.peek_frame().is_promoted(dest.local);
+                let dest_ptr =
+                    (!ret_llty.is_void() && !dest_is_promoted).then(|| self.gen_place_ptr(dest));
+                if ret_llty.eval_to_ptr() {
+                    // the result is written to the destination directly
+                    let dest_ptr = dest_ptr.as_ref().unwrap();
+                    arg_vals.insert(
+                        0,
+                        format!("ptr sret({}) {}", ret_llty.to_string(), dest_ptr.name),
+                    );
+                }
+
+                let call = format!("@{}({})", func.demangle(), arg_vals.join(", "));
+                if ret_llty.is_void() || ret_llty.eval_to_ptr() {
+                    // instructions returning void cannot have a reg name
+                    println!("\tcall void {call}");
+                } else {
+                    let reg = self.peek_frame_mut().get_fresh_reg();
+                    println!("\t{reg} = call {} {call}", ret_llty.to_string());
+                    let val = LLValue::Reg(LLReg::new(reg, Rc::new(ret_llty)));
+                    match dest_ptr {
+                        Some(dest_ptr) => self.store_value(&dest_ptr, &val),
+                        None => self.peek_frame_mut().set_local_value(dest.local, val),
+                    }
+                }
+                match target {
+                    Some(bb) => println!("\tbr label %bb{bb}"),
+                    None => println!("\tunreachable"),
+                }
+            }
+            TerminatorKind::Return => {
+                let ret_val = match self.peek_frame().get_local_reg(mir::RETURN_PLACE) {
+                    Some(ret_ptr) if !uses_sret => Some(LLValue::Reg(self.load_ptr(&ret_ptr))),
+                    _ => self.peek_frame().get_local_value(mir::RETURN_PLACE),
+                };
+                match ret_val {
+                    Some(val) => println!("\tret {}", val.to_string_with_type()),
+                    None => println!("\tret void"),
+                }
+            }
+            TerminatorKind::Panic(None) => {
+                println!("\tcall void (i32) @exit(i32 {PANIC_EXIT_CODE})");
+                println!("\tunreachable");
+                self.uses_runtime = true;
+            }
+            TerminatorKind::Panic(Some(msg)) => {
+                let msg = self.add_str_const(msg);
+                let loc = self.add_str_const(&terminator.span.to_location());
+                println!(
+                    "\tcall void @{}(ptr {}, ptr {})",
+                    PANIC_FN, msg.name, loc.name
+                );
+                println!("\tunreachable");
+                self.uses_runtime = true;
+            }
+            TerminatorKind::Unreachable => println!("\tunreachable"),
+        }
+    }
+}
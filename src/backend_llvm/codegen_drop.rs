@@ -0,0 +1,103 @@
+use super::{
+    frame::Frame,
+    llvm::{LLReg, LLTy},
+    Codegen,
+};
+use crate::middle::ty::{Ty, TyKind};
+use std::rc::Rc;
+
+impl<'a, 'ctx> Codegen<'a, 'ctx> {
+    /// Drop the value of type `ty` pointed by `ptr`
+    pub fn gen_drop_in_place(&mut self, ptr: &Rc<LLReg>, ty: &Rc<Ty>) {
+        let i = match self.drop_glue_tys.iter().position(|t| t == ty) {
+            Some(i) => i,
+            None => {
+                self.drop_glue_tys.push(Rc::clone(ty));
+                self.drop_glue_tys.len() - 1
+            }
+        };
+        println!("\tcall void {}(ptr {})", drop_glue_name(i), ptr.name);
+    }
+
+    /// Generate `drop_in_place` functions for all types dropped so far.
+    /// Drop glue of a type may require drop glue of other types
+    pub fn gen_drop_glues(&mut self) {
+        let mut i = 0;
+        while i < self.drop_glue_tys.len() {
+            let ty = Rc::clone(&self.drop_glue_tys[i]);
+            self.gen_drop_glue(i, &ty);
+            i += 1;
+        }
+    }
+
+    fn gen_drop_glue(&mut self, i: usize, ty: &Rc<Ty>) {
+        let llty = self.ty_to_llty(ty);
+        println!("; Drop glue of `{}`", ty);
+        println!("define void {}(ptr %ptr) {{", drop_glue_name(i));
+        println!("start:");
+        self.push_frame(Frame::new());
+        let ptr = LLReg::new(
+            "%ptr".to_string(),
+            Rc::new(LLTy::Ptr(Rc::new(llty.clone()))),
+        );
+
+        match &ty.kind {
+            TyKind::Box(inner) => {
+                let boxed = self.load_ptr(&ptr);
+                if self.ctx.needs_drop(inner) {
+                    self.gen_drop_in_place(&boxed, inner);
+                }
+                println!("\tcall void @free(ptr {})", boxed.name);
+                self.uses_heap = true;
+            }
+            TyKind::Adt(name) => {
+                // the destructor runs before the fields are dropped
+                if let Some(drop_fn) = self.ctx.lookup_drop_impl(name) {
+                    println!("\tcall void @{}(ptr %ptr)", drop_fn.cpath.demangle());
+                }
+                let adt = self.ctx.lookup_adt_def(name).unwrap();
+                for (fd, fd_ty) in &adt.fields {
+                    if self.ctx.needs_drop(fd_ty) {
+                        let fd_ptr = self.gen_field_lval(&ptr, fd);
+                        self.gen_drop_in_place(&fd_ptr, fd_ty);
+                    }
+                }
+            }
+            TyKind::Array(elem, n) => {
+                let elem_llty = self.ty_to_llty(elem);
+                let cond_label = self.get_fresh_label_name();
+                let body_label = self.get_fresh_label_name();
+                let end_label = self.get_fresh_label_name();
+                println!("\tbr label %{cond_label}");
+                self.print_label(&cond_label, "Cond");
+                // numbered in the order of definition
+                let [index, cmp, elem_reg, next] =
+                    [(); 4].map(|_| self.peek_frame_mut().get_fresh_reg());
+                println!("\t{index} = phi i32 [0, %start], [{next}, %{body_label}]");
+                println!("\t{cmp} = icmp slt i32 {index}, {n}");
+                println!("\tbr i1 {cmp}, label %{body_label}, label %{end_label}");
+                self.print_label(&body_label, "Body");
+                println!(
+                    "\t{elem_reg} = getelementptr {}, ptr %ptr, i32 0, i32 {index}",
+                    llty.to_string()
+                );
+                let elem_ptr = LLReg::new(elem_reg, Rc::new(LLTy::Ptr(Rc::new(elem_llty))));
+                self.gen_drop_in_place(&elem_ptr, elem);
+                println!("\t{next} = add i32 {index}, 1");
+                println!("\tbr label %{cond_label}");
+                self.print_label(&end_label, "End");
+            }
+            _ => panic!("ICE: `{}` does not need drop", ty),
+        }
+
+        println!("\tret void");
+        println!("}}");
+        println!();
+        self.pop_frame();
+    }
+}
+
+/// Drop glue is numbered since type names contain characters invalid in symbols
+fn drop_glue_name(i: usize) -> String {
+    format!("@__mini_rustc_drop_in_place.{i}")
+}
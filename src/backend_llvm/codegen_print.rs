@@ -0,0 +1,186 @@
+use super::{
+    runtime::{ENCODE_UTF8_FN, ESCAPE_DEBUG_FN, FORMAT_FLOAT_FN},
+    Codegen,
+};
+use crate::{
+    ast::{FormatAlign, FormatPiece, PrintDest},
+    middle::ty::TyKind,
+    mir::Operand,
+    parse::conversion_spec,
+};
+
+impl<'a, 'ctx> Codegen<'a, 'ctx> {
+    /// Generate a call to `printf` (or `dprintf` for stderr) for `print!` and friends.
+    /// A printf format string is generated from the format spec and the types of the arguments:
+    /// integers are printed with `%d`, `%u`, `%ld` or `%lu`, floats with `%f` if precision is
+    /// given, and bools, chars, strs and other floats with `%s`
+    pub fn gen_print(&mut self, dest: PrintDest, pieces: &[FormatPiece], args: &[Operand]) {
+        // each argument is evaluated once in order even if it is referenced more than once
+        let arg_vals: Vec<_> = args.iter().map(|arg| self.eval_operand(arg)).collect();
+        let arg_tys: Vec<_> = args
+            .iter()
+            .map(|arg| self.peek_frame().get_operand_ty(arg, self))
+            .collect();
+        // `char` arguments are encoded to their own buffers
+        let mut utf8_bufs = self.peek_frame().get_utf8_bufs().into_iter();
+        let mut char_bufs = vec![None; args.len()];
+        for (i, ty) in arg_tys.iter().enumerate() {
+            if ty.kind == TyKind::Char {
+                let buf = utf8_bufs.next().unwrap();
+                println!(
+                    "\tcall void @{}({}, ptr {})",
+                    ENCODE_UTF8_FN,
+                    arg_vals[i].to_string_with_type(),
+                    buf.name
+                );
+                char_bufs[i] = Some(buf);
+            }
+        }
+
+        let mut printf_fmt = String::new();
+        let mut printf_args = vec![];
+        // strings formatted by the runtime are freed after printing
+        let mut formatted = vec![];
+        for piece in pieces {
+            let spec = match piece {
+                FormatPiece::Lit(s) => {
+                    printf_fmt.push_str(&s.replace('%', "%%"));
+                    continue;
+                }
+                FormatPiece::Placeholder(spec) => spec,
+            };
+            let val = &arg_vals[spec.arg];
+            match &arg_tys[spec.arg].kind {
+                TyKind::Int(int_ty) => {
+                    // variadic arguments narrower than `int` are passed as `int`
+                    let arg = if int_ty.bit_width() < 32 {
+                        let ext = if int_ty.is_signed() { "sext" } else { "zext" };
+                        let reg = self.peek_frame_mut().get_fresh_reg();
+                        println!("\t{reg} = {ext} {} to i32", val.to_string_with_type());
+                        format!("i32 {reg}")
+                    } else {
+                        val.to_string_with_type()
+                    };
+                    let conversion = match (int_ty.is_signed(), int_ty.bit_width()) {
+                        (true, 64) => "ld",
+                        (true, _) => "d",
+                        (false, 64) => "lu",
+                        (false, _) => "u",
+                    };
+                    // precision is ignored for integers
+                    printf_fmt.push_str(&conversion_spec(
+                        spec,
+                        FormatAlign::Right,
+                        false,
+                        conversion,
+                    ));
+                    printf_args.push(arg);
+                }
+                TyKind::F32 | TyKind::F64 => {
+                    let is_f32 = arg_tys[spec.arg].kind == TyKind::F32;
+                    // variadic `float` arguments are promoted to `double`
+                    let arg = if is_f32 {
+                        let reg = self.peek_frame_mut().get_fresh_reg();
+                        println!("\t{reg} = fpext {} to double", val.to_string_with_type());
+                        format!("double {reg}")
+                    } else {
+                        val.to_string_with_type()
+                    };
+                    if spec.precision.is_some() {
+                        printf_fmt.push_str(&conversion_spec(spec, FormatAlign::Right, true, "f"));
+                        printf_args.push(arg);
+                    } else {
+                        printf_fmt.push_str(&conversion_spec(spec, FormatAlign::Right, false, "s"));
+                        let reg = self.format_float(&arg, is_f32, spec.debug);
+                        printf_args.push(format!("ptr {reg}"));
+                        formatted.push(reg);
+                    }
+                }
+                TyKind::Bool => {
+                    let true_str = self.add_str_const("true");
+                    let false_str = self.add_str_const("false");
+                    let reg = self.peek_frame_mut().get_fresh_reg();
+                    println!(
+                        "\t{reg} = select {}, ptr {}, ptr {}",
+                        val.to_string_with_type(),
+                        true_str.name,
+                        false_str.name
+                    );
+                    printf_fmt.push_str(&conversion_spec(spec, FormatAlign::Left, true, "s"));
+                    printf_args.push(format!("ptr {reg}"));
+                }
+                TyKind::Char => {
+                    let buf = char_bufs[spec.arg].as_ref().unwrap();
+                    if spec.debug {
+                        // Debug of char ignores width and precision
+                        printf_fmt.push_str("%s");
+                        let reg = self.escape_for_debug(&buf.name, '\'');
+                        printf_args.push(format!("ptr {reg}"));
+                        formatted.push(reg);
+                    } else {
+                        printf_fmt.push_str(&conversion_spec(spec, FormatAlign::Left, true, "s"));
+                        printf_args.push(format!("ptr {}", buf.name));
+                    }
+                }
+                // &str
+                TyKind::Ref(..) => {
+                    if spec.debug {
+                        // Debug of str ignores width and precision
+                        printf_fmt.push_str("%s");
+                        let reg = self.escape_for_debug(&val.to_string(), '"');
+                        printf_args.push(format!("ptr {reg}"));
+                        formatted.push(reg);
+                    } else {
+                        printf_fmt.push_str(&conversion_spec(spec, FormatAlign::Left, true, "s"));
+                        printf_args.push(format!("ptr {}", val.to_string()));
+                    }
+                }
+                _ => panic!("ICE: argument of print is not formattable"),
+            }
+        }
+
+        let printf_fmt = self.add_str_const(&printf_fmt);
+        let args: String = printf_args.iter().map(|arg| format!(", {arg}")).collect();
+        // the result is named since unnamed values consume register numbers
+        let reg = self.peek_frame_mut().get_fresh_reg();
+        match dest {
+            PrintDest::Stdout => println!(
+                "\t{reg} = call i32 (ptr, ...) @printf(ptr {}{})",
+                printf_fmt.name, args
+            ),
+            PrintDest::Stderr => println!(
+                "\t{reg} = call i32 (i32, ptr, ...) @dprintf(i32 2, ptr {}{})",
+                printf_fmt.name, args
+            ),
+        }
+        for reg in formatted {
+            println!("\tcall void @free(ptr {reg})");
+        }
+        self.uses_runtime = true;
+    }
+
+    /// Escape the string at `ptr` for `{:?}` into a new buffer, enclosed in `quote`
+    fn escape_for_debug(&mut self, ptr: &str, quote: char) -> String {
+        let reg = self.peek_frame_mut().get_fresh_reg();
+        println!(
+            "\t{reg} = call ptr @{ESCAPE_DEBUG_FN}(ptr {ptr}, i32 {})",
+            u32::from(quote)
+        );
+        self.uses_escape_debug = true;
+        self.uses_heap = true;
+        reg
+    }
+
+    /// Format the float `arg` like `{}` or `{:?}` into a new buffer
+    fn format_float(&mut self, arg: &str, is_f32: bool, debug: bool) -> String {
+        let reg = self.peek_frame_mut().get_fresh_reg();
+        println!(
+            "\t{reg} = call ptr @{FORMAT_FLOAT_FN}({arg}, i32 {}, i32 {})",
+            i32::from(is_f32),
+            i32::from(debug)
+        );
+        self.uses_format_float = true;
+        self.uses_heap = true;
+        reg
+    }
+}
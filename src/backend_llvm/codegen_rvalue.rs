@@ -0,0 +1,320 @@
+use super::{Codegen, LLValue};
+use crate::{
+    backend_llvm::{LLImm, LLReg, LLTy},
+    middle::ty::{Ty, TyKind},
+    mir::{AggregateKind, BinOp, ConstKind, Constant, Operand, Place, Rvalue, UnOp},
+};
+use std::rc::Rc;
+
+impl<'a, 'ctx> Codegen<'a, 'ctx> {
+    /// Evaluate the rvalue and write it to the memory pointed by `ptr`
+    pub fn gen_rvalue_into(&mut self, ptr: &Rc<LLReg>, rvalue: &Rvalue) {
+        match rvalue {
+            Rvalue::Aggregate(kind, ops) => self.gen_aggregate(ptr, kind, ops),
+            _ => {
+                let val = self.eval_rvalue(rvalue);
+                self.store_value(ptr, &val);
+            }
+        }
+    }
+
+    // rvalue struct/array -> sturct*/array*
+    // otherwise: rvalue: LLTY -> LLTY/void
+    pub fn eval_rvalue(&mut self, rvalue: &Rvalue) -> LLValue {
+        match rvalue {
+            Rvalue::Use(op) => self.eval_operand(op),
+            Rvalue::BinaryOp(binop, lhs, rhs) => self.gen_binary(*binop, lhs, rhs),
+            Rvalue::UnaryOp(UnOp::Neg, inner) => {
+                let inner_val = self.eval_operand(inner);
+                let reg = self.peek_frame_mut().get_fresh_reg();
+                if inner_val.llty().is_float() {
+                    println!("\t{reg} = fneg {}", inner_val.to_string_with_type());
+                } else {
+                    assert!(inner_val.llty().is_integer());
+                    println!(
+                        "\t{reg} = sub {} 0, {}",
+                        inner_val.llty().to_string(),
+                        inner_val.to_string()
+                    );
+                }
+                LLValue::Reg(LLReg::new(reg, inner_val.llty()))
+            }
+            Rvalue::Cast(inner, to_ty) => self.gen_cast(inner, to_ty),
+            Rvalue::Ref(_, place) => {
+                let ty = self.peek_frame().get_place_ty(place, self);
+                if self.ty_to_llty(&ty).is_void() {
+                    // zero-sized values are given the address of a dummy byte
+                    LLValue::Reg(self.peek_frame().get_zst_reg().unwrap())
+                } else {
+                    LLValue::Reg(self.gen_place_ptr(place))
+                }
+            }
+            Rvalue::Box(inner) => self.gen_box(inner),
+            Rvalue::Aggregate(..) => panic!("ICE: aggregates are written to memory"),
+        }
+    }
+
+    // operand struct/array -> sturct*/array*
+    // otherwise: operand: LLTY -> LLTY/void
+    /// Value of the place. Aggregates evaluate to pointers to them
+    pub fn eval_place(&mut self, place: &Place) -> LLValue {
+        let ty = self.peek_frame().get_place_ty(place, self);
+        let llty = self.ty_to_llty(&ty);
+        if llty.is_void() {
+            return LLValue::Imm(LLImm::Void);
+        }
+        if place.projs.is_empty()
+            && let Some(val) = self.peek_frame().get_local_value(place.local)
+        {
+            return val;
+        }
+        let ptr = self.gen_place_ptr(place);
+        if llty.eval_to_ptr() {
+            LLValue::Reg(ptr)
+        } else {
+            LLValue::Reg(self.load_ptr(&ptr))
+        }
+    }
+
+    pub fn eval_operand(&mut self, op: &Operand) -> LLValue {
+        match op {
+            Operand::Copy(place, _) | Operand::Move(place, _) => self.eval_place(place),
+            Operand::Const(cons) => self.eval_constant(cons),
+        }
+    }
+
+    pub fn eval_constant(&mut self, cons: &Constant) -> LLValue {
+        let llty = self.ty_to_llty(&cons.ty);
+        match &cons.kind {
+            // literals out of the range of the type are truncated
+            ConstKind::Int(n) => match llty {
+                LLTy::I8 => LLValue::Imm(LLImm::I8(*n as i8)),
+                LLTy::I16 => LLValue::Imm(LLImm::I16(*n as i16)),
+                LLTy::I64 => LLValue::Imm(LLImm::I64(*n)),
+                _ => LLValue::Imm(LLImm::I32(*n as i32)),
+            },
+            ConstKind::Float(f) => match llty {
+                LLTy::F32 => LLValue::Imm(LLImm::F32(*f as f32)),
+                LLTy::F64 => LLValue::Imm(LLImm::F64(*f)),
+                _ => panic!("ICE"),
+            },
+            ConstKind::Bool(b) => LLValue::Imm(LLImm::I1(*b)),
+            ConstKind::Char(c) => LLValue::Imm(LLImm::I32(*c as i32)),
+            ConstKind::Str(s) => LLValue::PtrConst(self.add_str_const(s)),
+            ConstKind::Unit => LLValue::Imm(LLImm::Void),
+            ConstKind::Null => LLValue::Imm(LLImm::Null(Rc::new(llty))),
+            ConstKind::Static(name) => {
+                LLValue::Reg(LLReg::new(format!("@{}", name.demangle()), Rc::new(llty)))
+            }
+        }
+    }
+
+    fn gen_binary(&mut self, binop: BinOp, lhs: &Operand, rhs: &Operand) -> LLValue {
+        let lhs_ty = self.peek_frame().get_operand_ty(lhs, self);
+        let l = self.eval_operand(lhs);
+        let r = self.eval_operand(rhs);
+        if binop == BinOp::Offset {
+            // the `i32` index of `offset` is sign-extended by getelementptr
+            let reg = self.peek_frame_mut().get_fresh_reg();
+            let pointee_llty = l.llty().peel_ptr().unwrap();
+            println!(
+                "\t{reg} = getelementptr {}, {}, {}",
+                pointee_llty.to_string(),
+                l.to_string_with_type(),
+                r.to_string_with_type()
+            );
+            return LLValue::Reg(LLReg::new(reg, l.llty()));
+        }
+
+        let rhs_lhs_llty = self.ty_to_llty(&lhs_ty);
+        // bool is `i1`, and pointers are compared only with null
+        assert!(
+            rhs_lhs_llty.is_integer()
+                || rhs_lhs_llty.is_float()
+                || (matches!(rhs_lhs_llty, LLTy::I1 | LLTy::Ptr(_))
+                    && matches!(binop, BinOp::Eq | BinOp::Ne))
+        );
+
+        let is_float = rhs_lhs_llty.is_float();
+        let is_signed = is_signed_int(&lhs_ty);
+        let (inst, llty) = match binop {
+            BinOp::Add if is_float => ("fadd", rhs_lhs_llty),
+            BinOp::Add => ("add", rhs_lhs_llty),
+            BinOp::Sub if is_float => ("fsub", rhs_lhs_llty),
+            BinOp::Sub => ("sub", rhs_lhs_llty),
+            BinOp::Mul if is_float => ("fmul", rhs_lhs_llty),
+            BinOp::Mul => ("mul", rhs_lhs_llty),
+            BinOp::Div if is_float => ("fdiv", rhs_lhs_llty),
+            BinOp::Div if is_signed => ("sdiv", rhs_lhs_llty),
+            BinOp::Div => ("udiv", rhs_lhs_llty),
+            BinOp::Rem if is_float => ("frem", rhs_lhs_llty),
+            BinOp::Rem if is_signed => ("srem", rhs_lhs_llty),
+            BinOp::Rem => ("urem", rhs_lhs_llty),
+            // ordered comparisons are false for NaN, but `!=` is true
+            BinOp::Eq if is_float => ("fcmp oeq", LLTy::I1),
+            BinOp::Eq => ("icmp eq", LLTy::I1),
+            BinOp::Ne if is_float => ("fcmp une", LLTy::I1),
+            BinOp::Ne => ("icmp ne", LLTy::I1),
+            BinOp::Gt if is_float => ("fcmp ogt", LLTy::I1),
+            BinOp::Gt if is_signed => ("icmp sgt", LLTy::I1),
+            BinOp::Gt => ("icmp ugt", LLTy::I1),
+            BinOp::Lt if is_float => ("fcmp olt", LLTy::I1),
+            BinOp::Lt if is_signed => ("icmp slt", LLTy::I1),
+            BinOp::Lt => ("icmp ult", LLTy::I1),
+            BinOp::Offset => unreachable!(),
+        };
+        let reg_name = self.peek_frame_mut().get_fresh_reg();
+        println!(
+            "\t{reg_name} = {inst} {}, {}",
+            l.to_string_with_type(),
+            r.to_string()
+        );
+        LLValue::Reg(LLReg::new(reg_name, Rc::new(llty)))
+    }
+
+    /// Generate code for `inner as T`
+    // ref: https://doc.rust-lang.org/reference/expressions/operator-expr.html#type-cast-expressions
+    fn gen_cast(&mut self, inner: &Operand, to_ty: &Ty) -> LLValue {
+        let from_ty = self.peek_frame().get_operand_ty(inner, self);
+        let from = self.ty_to_llty(&from_ty);
+        let to = self.ty_to_llty(to_ty);
+        let mut val = self.eval_operand(inner);
+
+        // integers are cast to `usize` before being cast to pointers
+        if let LLTy::Ptr(_) = to
+            && from != LLTy::I64
+            && from.is_integer()
+        {
+            val = self.gen_int_resize(val, is_signed_int(&from_ty), &LLTy::I64);
+        }
+        let inst = match (&from, &to) {
+            (LLTy::Ptr(_), LLTy::Ptr(_)) => return val,
+            (LLTy::Ptr(_), _) => "ptrtoint",
+            (_, LLTy::Ptr(_)) => "inttoptr",
+            _ if from == to => return val,
+            // integers, `bool`, `char` and `u8` to `char`
+            (f, t) if f.bit_width().is_some() && t.is_integer() => {
+                return self.gen_int_resize(val, is_signed_int(&from_ty), t);
+            }
+            (f, t) if f.is_integer() && t.is_float() => {
+                if is_signed_int(&from_ty) {
+                    "sitofp"
+                } else {
+                    "uitofp"
+                }
+            }
+            // float-to-int casts saturate, and NaN is cast to 0
+            (f, t) if f.is_float() && t.is_integer() => {
+                let conv = if is_signed_int(to_ty) {
+                    "fptosi"
+                } else {
+                    "fptoui"
+                };
+                let intrinsic = format!("llvm.{conv}.sat.{}.{}", t.to_string(), f.to_string());
+                self.intrinsics.insert(format!(
+                    "declare {} @{intrinsic}({})",
+                    t.to_string(),
+                    f.to_string()
+                ));
+                let reg = self.peek_frame_mut().get_fresh_reg();
+                println!(
+                    "\t{reg} = call {} @{intrinsic}({})",
+                    t.to_string(),
+                    val.to_string_with_type()
+                );
+                return LLValue::Reg(LLReg::new(reg, Rc::new(to)));
+            }
+            (LLTy::F32, LLTy::F64) => "fpext",
+            (LLTy::F64, LLTy::F32) => "fptrunc",
+            _ => panic!("ICE: invalid cast from `{}` to `{}`", from_ty, to_ty),
+        };
+        let reg = self.peek_frame_mut().get_fresh_reg();
+        println!(
+            "\t{reg} = {inst} {} to {}",
+            val.to_string_with_type(),
+            to.to_string()
+        );
+        LLValue::Reg(LLReg::new(reg, Rc::new(to)))
+    }
+
+    /// Sign-extend, zero-extend or truncate an integer to `to`
+    fn gen_int_resize(&mut self, val: LLValue, is_signed: bool, to: &LLTy) -> LLValue {
+        let from_width = val.llty().bit_width().unwrap();
+        let to_width = to.bit_width().unwrap();
+        let inst = if from_width < to_width && is_signed {
+            "sext"
+        } else if from_width < to_width {
+            "zext"
+        } else if from_width > to_width {
+            "trunc"
+        } else {
+            return val;
+        };
+        let reg = self.peek_frame_mut().get_fresh_reg();
+        println!(
+            "\t{reg} = {inst} {} to {}",
+            val.to_string_with_type(),
+            to.to_string()
+        );
+        LLValue::Reg(LLReg::new(reg, Rc::new(to.clone())))
+    }
+
+    /// Allocate memory on the heap and move the value there
+    fn gen_box(&mut self, inner: &Operand) -> LLValue {
+        let inner_ty = self.peek_frame().get_operand_ty(inner, self);
+        let box_llty = Rc::new(self.ty_to_llty(&Ty::new(TyKind::Box(Rc::clone(&inner_ty)))));
+        let inner_llty = self.ty_to_llty(&inner_ty);
+        let size = if inner_llty.is_void() {
+            0
+        } else {
+            self.get_size(&inner_llty)
+        };
+        let val = self.eval_operand(inner);
+        let reg = self.peek_frame_mut().get_fresh_reg();
+        println!("\t{reg} = call ptr @malloc(i64 {size})");
+        self.uses_heap = true;
+        let ptr = LLReg::new(reg, box_llty);
+        self.store_value(&ptr, &val);
+        LLValue::Reg(ptr)
+    }
+
+    /// Initialize the struct or the array pointed by `ptr` field by field
+    fn gen_aggregate(&mut self, ptr: &Rc<LLReg>, kind: &AggregateKind, ops: &[Operand]) {
+        match kind {
+            AggregateKind::Adt(name) => {
+                let adt = self.ctx.lookup_adt_def(name).unwrap();
+                for ((fd, _), op) in adt.fields.iter().zip(ops) {
+                    let val = self.eval_operand(op);
+                    if val.llty().is_void() {
+                        continue;
+                    }
+                    let fd_ptr = self.gen_field_lval(ptr, fd);
+                    self.store_value(&fd_ptr, &val);
+                }
+            }
+            AggregateKind::Array => {
+                let arr_llty = ptr.llty.peel_ptr().unwrap();
+                // `[N x elem_ty]*` => `elem_ty*`
+                let elem_llty = Rc::new(LLTy::Ptr(arr_llty.get_element_type().unwrap()));
+                for (i, op) in ops.iter().enumerate() {
+                    let val = self.eval_operand(op);
+                    let elem_reg = self.peek_frame_mut().get_fresh_reg();
+                    println!(
+                        "\t{} = getelementptr {}, {}, i32 0, i32 {}",
+                        elem_reg,
+                        arr_llty.to_string(),
+                        ptr.to_string_with_type(),
+                        i
+                    );
+                    let elem_ptr = LLReg::new(elem_reg, Rc::clone(&elem_llty));
+                    self.store_value(&elem_ptr, &val);
+                }
+            }
+        }
+    }
+}
+
+/// Whether the type is a signed integer type
+fn is_signed_int(ty: &Ty) -> bool {
+    matches!(ty.kind, TyKind::Int(int_ty) if int_ty.is_signed())
+}
@@ -5,8 +5,12 @@ use crate::resolve::CanonicalPath;
 pub enum LLTy {
     Void,
     I1,  // bool
-    I8,  // &'static str
-    I32, // i32
+    I8,  // i8, u8 and &'static str
+    I16, // i16, u16
+    I32, // i32, u32
+    I64, // i64, u64, isize, usize
+    F32, // f32
+    F64, // f64
     Ptr(Rc<LLTy>),
     Array(Rc<LLTy>, usize),
     Adt(Rc<CanonicalPath>),
@@ -18,7 +22,11 @@ impl LLTy {
             LLTy::Void => "void".to_string(),
             LLTy::I1 => "i1".to_string(),
             LLTy::I8 => "i8".to_string(),
+            LLTy::I16 => "i16".to_string(),
             LLTy::I32 => "i32".to_string(),
+            LLTy::I64 => "i64".to_string(),
+            LLTy::F32 => "float".to_string(),
+            LLTy::F64 => "double".to_string(),
             LLTy::Ptr(inner) => format!("{}*", inner.to_string()),
             LLTy::Array(elem_ty, n) => format!("[{} x {}]", n, elem_ty.to_string()),
             LLTy::Adt(name) => format!("%Struct.{}", name.demangle()),
@@ -26,11 +34,23 @@ impl LLTy {
     }
 
     pub fn is_integer(&self) -> bool {
-        matches!(self, LLTy::I32)
+        matches!(self, LLTy::I8 | LLTy::I16 | LLTy::I32 | LLTy::I64)
     }
 
-    pub fn is_signed_integer(&self) -> bool {
-        matches!(self, LLTy::I32)
+    /// Width of integer types in bits
+    pub fn bit_width(&self) -> Option<usize> {
+        match self {
+            LLTy::I1 => Some(1),
+            LLTy::I8 => Some(8),
+            LLTy::I16 => Some(16),
+            LLTy::I32 => Some(32),
+            LLTy::I64 => Some(64),
+            _ => None,
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, LLTy::F32 | LLTy::F64)
     }
 
     pub fn peel_ptr(&self) -> Option<Rc<LLTy>> {
@@ -65,6 +85,7 @@ impl LLTy {
     }
 }
 
+#[derive(Debug, Clone)]
 pub enum LLValue {
     Reg(Rc<LLReg>),
     Imm(LLImm),
@@ -114,33 +135,67 @@ impl LLReg {
     }
 }
 
+#[derive(Debug, Clone)]
 pub enum LLImm {
+    I8(i8),
+    I16(i16),
     I32(i32),
+    I64(i64),
     I1(bool),
+    F32(f32),
+    F64(f64),
+    /// Null pointer of the type
+    Null(Rc<LLTy>),
+    /// Value of uninitialized locals
+    Undef(Rc<LLTy>),
     Void,
 }
 
 impl LLImm {
     pub fn to_string(&self) -> String {
         match self {
+            LLImm::I8(n) => format!("{n}"),
+            LLImm::I16(n) => format!("{n}"),
             LLImm::I32(n) => format!("{n}"),
+            LLImm::I64(n) => format!("{n}"),
             LLImm::I1(b) => format!("{}", if *b { 1 } else { 0 }),
+            // LLVM requires `float` constants to be exactly representable
+            // in `float`, so both are written as the hex of a double
+            LLImm::F32(f) => format!("0x{:016X}", (*f as f64).to_bits()),
+            LLImm::F64(f) => format!("0x{:016X}", f.to_bits()),
+            LLImm::Null(_) => "null".to_string(),
+            LLImm::Undef(_) => "undef".to_string(),
             LLImm::Void => "void".to_string(),
         }
     }
 
     pub fn to_string_with_type(&self) -> String {
         match self {
+            LLImm::I8(n) => format!("i8 {n}"),
+            LLImm::I16(n) => format!("i16 {n}"),
             LLImm::I32(n) => format!("i32 {n}"),
+            LLImm::I64(n) => format!("i64 {n}"),
             LLImm::I1(b) => format!("i1 {}", if *b { 1 } else { 0 }),
+            LLImm::F32(_) | LLImm::F64(_) | LLImm::Null(_) | LLImm::Undef(_) => {
+                format!("{} {}", self.llty().to_string(), self.to_string())
+            }
             LLImm::Void => "void".to_string(),
         }
     }
 
     pub fn llty(&self) -> Rc<LLTy> {
+        if let LLImm::Null(llty) | LLImm::Undef(llty) = self {
+            return Rc::clone(llty);
+        }
         Rc::new(match self {
+            LLImm::I8(_) => LLTy::I8,
+            LLImm::I16(_) => LLTy::I16,
             LLImm::I32(_) => LLTy::I32,
+            LLImm::I64(_) => LLTy::I64,
             LLImm::I1(_) => LLTy::I1,
+            LLImm::F32(_) => LLTy::F32,
+            LLImm::F64(_) => LLTy::F64,
+            LLImm::Null(_) | LLImm::Undef(_) => unreachable!(),
             LLImm::Void => LLTy::Void,
         })
     }
@@ -161,6 +216,7 @@ impl LLAdtDef {
     }
 }
 
+#[derive(Debug)]
 pub struct LLConst {
     pub name: String,
     pub llty: Rc<LLTy>,
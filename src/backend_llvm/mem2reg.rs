@@ -0,0 +1,181 @@
+//! Promotion of scalar locals to SSA values, like LLVM's mem2reg pass
+//!
+//! Locals of scalar types whose addresses are never taken are kept in LLVM registers
+//! instead of `alloca`s. Blocks are generated in reverse postorder, so the values of a
+//! local at the end of all the predecessors of a block are known when the block starts.
+//! If they differ, a `phi` merges them. Only locals which are live at the start of the
+//! block get `phi`s. Bodies with cycles keep all their locals in memory.
+
+use super::Codegen;
+use crate::mir::{
+    BasicBlock, Body, Local, Operand, Place, Proj, Rvalue, StatementKind, TerminatorKind,
+    RETURN_PLACE, START_BLOCK,
+};
+use std::collections::BTreeSet;
+
+pub struct Ssa {
+    /// Blocks reachable from the start block in reverse postorder
+    pub order: Vec<BasicBlock>,
+    /// Whether each local is kept in SSA values
+    pub promoted: Vec<bool>,
+    /// Predecessors of each block, once for each edge
+    pub preds: Vec<Vec<BasicBlock>>,
+    /// Promoted locals which may be read before being assigned after the start of each block
+    pub live_ins: Vec<BTreeSet<Local>>,
+}
+
+pub fn analyze(codegen: &Codegen, body: &Body) -> Ssa {
+    let (order, is_acyclic) = reverse_postorder(body);
+    let promoted = if is_acyclic {
+        promotable_locals(codegen, body)
+    } else {
+        vec![false; body.locals.len()]
+    };
+    let mut preds = vec![vec![]; body.blocks.len()];
+    for &bb in &order {
+        for succ in body.blocks[bb].terminator.kind.successors() {
+            preds[succ].push(bb);
+        }
+    }
+    let live_ins = live_ins(body, &order, &promoted);
+    Ssa {
+        order,
+        promoted,
+        preds,
+        live_ins,
+    }
+}
+
+/// Blocks reachable from the start block in reverse postorder, and whether they form no cycle
+fn reverse_postorder(body: &Body) -> (Vec<BasicBlock>, bool) {
+    // blocks are visited, then finished once all their successors are
+    let mut visited = vec![false; body.blocks.len()];
+    let mut finished = vec![false; body.blocks.len()];
+    let mut postorder = vec![];
+    let mut is_acyclic = true;
+    let mut stack = vec![(
+        START_BLOCK,
+        body.blocks[START_BLOCK].terminator.kind.successors(),
+    )];
+    visited[START_BLOCK] = true;
+    while let Some((bb, succs)) = stack.last_mut() {
+        let bb = *bb;
+        match succs.pop() {
+            Some(succ) if !visited[succ] => {
+                visited[succ] = true;
+                let succs = body.blocks[succ].terminator.kind.successors();
+                stack.push((succ, succs));
+            }
+            // an edge back to a block being visited
+            Some(succ) if !finished[succ] => is_acyclic = false,
+            Some(_) => (),
+            None => {
+                finished[bb] = true;
+                postorder.push(bb);
+                stack.pop();
+            }
+        }
+    }
+    postorder.reverse();
+    (postorder, is_acyclic)
+}
+
+/// Locals of scalar types which are neither borrowed nor dropped in place
+fn promotable_locals(codegen: &Codegen, body: &Body) -> Vec<bool> {
+    let mut promoted: Vec<bool> = body
+        .locals
+        .iter()
+        .map(|decl| {
+            let llty = codegen.ty_to_llty(&decl.ty);
+            !llty.is_void() && !llty.eval_to_ptr()
+        })
+        .collect();
+    for block in &body.blocks {
+        for stmt in &block.stmts {
+            match &stmt.kind {
+                StatementKind::Assign(_, Rvalue::Ref(_, place)) | StatementKind::Drop(place)
+                    if place.projs.is_empty() =>
+                {
+                    promoted[place.local] = false;
+                }
+                _ => (),
+            }
+        }
+    }
+    promoted
+}
+
+/// Locals read through the place, which are the local itself unless the place is assigned
+/// directly, and the indices
+fn place_uses(place: &Place, is_assigned: bool) -> impl Iterator<Item = Local> + '_ {
+    let local = (!is_assigned || !place.projs.is_empty()).then_some(place.local);
+    local
+        .into_iter()
+        .chain(place.projs.iter().filter_map(|proj| match proj {
+            Proj::Index(index) => Some(*index),
+            Proj::Deref | Proj::Field(_) => None,
+        }))
+}
+
+fn operand_uses(op: &Operand) -> Vec<Local> {
+    match op {
+        Operand::Copy(place, _) | Operand::Move(place, _) => place_uses(place, false).collect(),
+        Operand::Const(_) => vec![],
+    }
+}
+
+/// Backward liveness of the promoted locals. A single pass in postorder suffices as the
+/// blocks form no cycle when any local is promoted
+fn live_ins(body: &Body, order: &[BasicBlock], promoted: &[bool]) -> Vec<BTreeSet<Local>> {
+    let mut live_ins = vec![BTreeSet::new(); body.blocks.len()];
+    if !promoted.contains(&true) {
+        return live_ins;
+    }
+    for &bb in order.iter().rev() {
+        let block = &body.blocks[bb];
+        let mut live: BTreeSet<Local> = block
+            .terminator
+            .kind
+            .successors()
+            .into_iter()
+            .flat_map(|succ| live_ins[succ].clone())
+            .collect();
+        match &block.terminator.kind {
+            TerminatorKind::If(cond, _, _) => live.extend(operand_uses(cond)),
+            TerminatorKind::Call { args, dest, .. } => {
+                if dest.projs.is_empty() {
+                    live.remove(&dest.local);
+                }
+                live.extend(place_uses(dest, true));
+                live.extend(args.iter().flat_map(operand_uses));
+            }
+            TerminatorKind::Return => {
+                live.insert(RETURN_PLACE);
+            }
+            TerminatorKind::Goto(_) | TerminatorKind::Panic(_) | TerminatorKind::Unreachable => {}
+        }
+        for stmt in block.stmts.iter().rev() {
+            match &stmt.kind {
+                StatementKind::Assign(place, rvalue) => {
+                    if place.projs.is_empty() {
+                        live.remove(&place.local);
+                    }
+                    live.extend(place_uses(place, true));
+                    match rvalue {
+                        Rvalue::Ref(_, borrowed) => live.extend(place_uses(borrowed, false)),
+                        _ => live.extend(rvalue.operands().into_iter().flat_map(operand_uses)),
+                    }
+                }
+                StatementKind::Drop(place) | StatementKind::Deallocate(place) => {
+                    live.extend(place_uses(place, false));
+                }
+                StatementKind::Print(_, _, args) => {
+                    live.extend(args.iter().flat_map(operand_uses));
+                }
+            }
+        }
+        live.retain(|local| promoted[*local]);
+        live_ins[bb] = live;
+    }
+    live_ins
+}
@@ -1,35 +1,53 @@
-mod codegen_crate;
-mod codegen_expr;
+mod codegen_body;
+mod codegen_drop;
+mod codegen_print;
+mod codegen_rvalue;
 mod codegen_utils;
 mod frame;
 mod llvm;
+mod mem2reg;
+mod runtime;
 
 use self::frame::Frame;
 use self::llvm::*;
-use crate::ast::Crate;
 use crate::middle::ty::{AdtDef, Ty, TyKind};
 use crate::middle::Ctxt;
+use crate::mir::Program;
 use crate::resolve::CanonicalPath;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::rc::Rc;
 
-pub fn compile<'ctx, 'gen: 'ctx>(ctx: &'gen mut Ctxt<'ctx>, krate: &'gen Crate) -> Result<(), ()> {
+pub fn compile<'ctx, 'a: 'ctx>(ctx: &'a mut Ctxt<'ctx>, program: &Program) -> Result<(), ()> {
     let mut codegen = Codegen::new(ctx);
-    codegen.go(krate)?;
+    codegen.go(program)?;
     Ok(())
 }
 
-pub struct Codegen<'gen, 'ctx> {
-    ctx: &'gen mut Ctxt<'ctx>,
+pub struct Codegen<'a, 'ctx> {
+    ctx: &'a mut Ctxt<'ctx>,
     current_frame: Option<Frame>,
     ll_adt_defs: HashMap<Rc<CanonicalPath>, Rc<LLAdtDef>>,
     next_label_id: usize,
     constants: Vec<Rc<LLConst>>,
     next_str_id: usize,
+    /// Names of external functions declared by the crate
+    declared_funcs: HashSet<String>,
+    /// Set if runtime functions need to be emitted
+    uses_runtime: bool,
+    /// Set if strings are escaped for `{:?}` by the runtime
+    uses_escape_debug: bool,
+    /// Set if floats are formatted by the runtime
+    uses_format_float: bool,
+    /// Set if `malloc` and `free` need to be declared
+    uses_heap: bool,
+    /// Declarations of the LLVM intrinsics called by the crate
+    intrinsics: BTreeSet<String>,
+    /// Types whose drop glue is called. Generated after all functions
+    drop_glue_tys: Vec<Rc<Ty>>,
 }
 
-impl<'ctx, 'gen> Codegen<'ctx, 'gen> {
-    fn new(ctx: &'gen mut Ctxt<'ctx>) -> Self {
+impl<'ctx, 'a> Codegen<'ctx, 'a> {
+    fn new(ctx: &'a mut Ctxt<'ctx>) -> Self {
         Codegen {
             ctx,
             current_frame: None,
@@ -37,6 +55,13 @@ impl<'ctx, 'gen> Codegen<'ctx, 'gen> {
             next_label_id: 1,
             constants: vec![],
             next_str_id: 1,
+            declared_funcs: HashSet::new(),
+            uses_runtime: false,
+            uses_escape_debug: false,
+            uses_format_float: false,
+            uses_heap: false,
+            intrinsics: BTreeSet::new(),
+            drop_glue_tys: vec![],
         }
     }
 
@@ -52,21 +77,54 @@ impl<'ctx, 'gen> Codegen<'ctx, 'gen> {
         format!("@.str.{i}")
     }
 
+    fn print_label(&self, label: &str, comment: &str) {
+        println!("{}:\t; {}", label, comment);
+    }
+
+    fn add_str_const(&mut self, s: &str) -> Rc<LLConst> {
+        let llcons = Rc::new(LLConst {
+            name: self.get_fresh_str_name(),
+            string_lit: s.to_string(),
+            // +1 for \00
+            llty: Rc::new(LLTy::Array(Rc::new(LLTy::I8), s.len() + 1)),
+        });
+        self.constants.push(Rc::clone(&llcons));
+        llcons
+    }
+
     // TODO: memoize
     fn ty_to_llty(&self, ty: &Ty) -> LLTy {
         match &ty.kind {
             TyKind::Unit => LLTy::Void,
-            TyKind::I32 => LLTy::I32,
-            TyKind::Bool => LLTy::I8,
+            TyKind::Int(int_ty) => match int_ty.bit_width() {
+                8 => LLTy::I8,
+                16 => LLTy::I16,
+                32 => LLTy::I32,
+                _ => LLTy::I64,
+            },
+            TyKind::F32 => LLTy::F32,
+            TyKind::F64 => LLTy::F64,
+            // unicode scalar value
+            TyKind::Char => LLTy::I32,
+            TyKind::Bool => LLTy::I1,
             TyKind::Array(elem_ty, n) => LLTy::Array(Rc::new(self.ty_to_llty(elem_ty)), *n),
             TyKind::Adt(name) => LLTy::Adt(Rc::clone(name)),
             TyKind::Never => LLTy::Void,
-            TyKind::Ref(inner) | TyKind::ConstPtr(inner) => match &inner.kind {
+            TyKind::Box(inner) => match self.ty_to_llty(inner) {
+                // boxes of ZSTs still need some pointer type
+                LLTy::Void => LLTy::Ptr(Rc::new(LLTy::I8)),
+                inner_llty => LLTy::Ptr(Rc::new(inner_llty)),
+            },
+            TyKind::Ref(inner, _) | TyKind::RawPtr(inner, _) => match &inner.kind {
                 // FIXME: should be [N x i8]
                 TyKind::Str => LLTy::Ptr(Rc::new(LLTy::I8)),
-                _ => LLTy::Ptr(Rc::new(self.ty_to_llty(inner))),
+                _ => match self.ty_to_llty(inner) {
+                    LLTy::Void => LLTy::Ptr(Rc::new(LLTy::I8)),
+                    inner_llty => LLTy::Ptr(Rc::new(inner_llty)),
+                },
             },
             TyKind::Error => panic!("ICE: typecheck failed but codegen has started"),
+            TyKind::Infer(_) => panic!("ICE: type variable {:?} remains after typecheck", ty),
             TyKind::Str => todo!(),
             TyKind::Fn(_, _) => todo!(),
         }
@@ -108,7 +166,7 @@ impl<'ctx, 'gen> Codegen<'ctx, 'gen> {
     }
 
     /// Generate code for top-level
-    fn go(&mut self, krate: &'gen Crate) -> Result<(), ()> {
+    fn go(&mut self, program: &Program) -> Result<(), ()> {
         println!(r#"target triple = "x86_64-unknown-linux-gnu""#);
         println!();
         println!("declare void @llvm.memcpy.p0i8.p0i8.i64(i8* noalias nocapture writeonly, i8* noalias nocapture readonly, i64, i1 immarg) #1");
@@ -133,7 +191,33 @@ impl<'ctx, 'gen> Codegen<'ctx, 'gen> {
         }
 
         println!();
-        self.gen_crate(krate)?;
+        self.gen_program(program);
+        self.gen_drop_glues();
+
+        if self.uses_heap {
+            println!("; Heap");
+            for (name, decl) in [
+                ("malloc", "declare ptr @malloc(i64)"),
+                ("free", "declare void @free(ptr)"),
+            ] {
+                if !self.declared_funcs.contains(name) {
+                    println!("{decl}");
+                }
+            }
+            println!();
+        }
+
+        if !self.intrinsics.is_empty() {
+            println!("; Intrinsics");
+            for decl in &self.intrinsics {
+                println!("{decl}");
+            }
+            println!();
+        }
+
+        if self.uses_runtime {
+            self.gen_runtime();
+        }
 
         // string literals
         for cons in &self.constants {
@@ -141,7 +225,7 @@ impl<'ctx, 'gen> Codegen<'ctx, 'gen> {
                 "{} = constant {} c\"{}\\00\"",
                 cons.name,
                 cons.llty.to_string(),
-                cons.string_lit
+                escape_str(&cons.string_lit)
             );
         }
 
@@ -151,10 +235,14 @@ impl<'ctx, 'gen> Codegen<'ctx, 'gen> {
     pub fn get_size(&self, llty: &LLTy) -> usize {
         match llty {
             LLTy::I32 => 4,
+            LLTy::I64 => 8,
+            LLTy::I16 => 2,
+            LLTy::F32 => 4,
+            LLTy::F64 => 8,
             LLTy::I8 => 1,
             LLTy::I1 => 1,
-            LLTy::Ptr(_) => 1,
-            LLTy::Array(elem_llty, n) => self.get_align(elem_llty) * n,
+            LLTy::Ptr(_) => 8,
+            LLTy::Array(elem_llty, n) => self.get_size(elem_llty) * n,
             LLTy::Void => panic!(),
             LLTy::Adt(name) => {
                 let lladt = self.get_lladt(name).unwrap();
@@ -177,9 +265,13 @@ impl<'ctx, 'gen> Codegen<'ctx, 'gen> {
     pub fn get_align(&self, llty: &LLTy) -> usize {
         match llty {
             LLTy::I32 => 4,
+            LLTy::I64 => 8,
+            LLTy::I16 => 2,
+            LLTy::F32 => 4,
+            LLTy::F64 => 8,
             LLTy::I8 => 1,
             LLTy::I1 => 1,
-            LLTy::Ptr(_) => 1,
+            LLTy::Ptr(_) => 8,
             LLTy::Array(elem_llty, _) => self.get_align(elem_llty),
             LLTy::Void => panic!(),
             LLTy::Adt(name) => {
@@ -201,6 +293,19 @@ impl<'ctx, 'gen> Codegen<'ctx, 'gen> {
     }
 }
 
+/// Escape a string to be printed in `c"..."`
+fn escape_str(s: &str) -> String {
+    let mut ret = String::new();
+    for b in s.bytes() {
+        if b == b'"' || b == b'\\' || !(b' '..=b'~').contains(&b) {
+            ret.push_str(&format!("\\{:02X}", b));
+        } else {
+            ret.push(b as char);
+        }
+    }
+    ret
+}
+
 // e.g. ofs: 1, align: 4 => 3
 fn padding_size(ofs: usize, align: usize) -> usize {
     if ofs % align == 0 {
@@ -0,0 +1,313 @@
+use super::Codegen;
+
+/// Panic entry point: `void @__mini_rustc_panic(ptr msg, ptr location)`
+/// Prints the message to stderr and exits with code 101 like rustc
+pub const PANIC_FN: &str = "__mini_rustc_panic";
+
+/// `void @__mini_rustc_encode_utf8(i32 c, ptr buf)`
+/// Writes `char` c to buf as a NUL-terminated UTF-8 string so that printf can print it with `%s`
+pub const ENCODE_UTF8_FN: &str = "__mini_rustc_encode_utf8";
+
+/// Size of the buffer passed to `ENCODE_UTF8_FN` (up to 4 bytes and NUL)
+pub const UTF8_BUF_SIZE: usize = 5;
+
+/// `ptr @__mini_rustc_escape_debug(ptr s, i32 quote)`
+/// Returns s enclosed in `quote` and escaped like `{:?}` in a buffer allocated by `malloc`
+pub const ESCAPE_DEBUG_FN: &str = "__mini_rustc_escape_debug";
+
+/// Bytes which are escaped by `ESCAPE_DEBUG_FN` with their escapes other than `\u{..}`
+/// of control characters. The quote and backslash are escaped by prefixing a backslash
+const DEBUG_ESCAPES: [(u8, &str); 3] = [(b'\t', "\\t"), (b'\r', "\\r"), (b'\n', "\\n")];
+
+/// `ptr @__mini_rustc_format_float(double x, i32 is_f32, i32 debug)`
+/// Returns x formatted like `{}` (or `{:?}` if debug is nonzero) in a buffer allocated by
+/// `malloc`. The shortest digits which are read back to x are printed. Like Rust, `{:?}`
+/// uses the exponent form for values below 1e-4 or from 1e16, and `{}` never does
+pub const FORMAT_FLOAT_FN: &str = "__mini_rustc_format_float";
+
+/// Size of the buffer of `FORMAT_FLOAT_FN`. The longest output is that of the smallest
+/// subnormal, which has 324 digits after the point
+const FLOAT_BUF_SIZE: usize = 400;
+
+/// Number of significant digits which are enough to read back any `f64`
+const MAX_FLOAT_DIGITS: usize = 17;
+
+/// Range of decimal exponents which `{:?}` prints floats without the exponent in
+const DEBUG_DECIMAL_EXPS: (i32, i32) = (-4, 15);
+
+pub const PANIC_EXIT_CODE: i32 = 101;
+
+impl<'a, 'ctx> Codegen<'a, 'ctx> {
+    /// Generate runtime functions and declarations of libc functions they use
+    pub fn gen_runtime(&mut self) {
+        let panic_fmt = self.add_str_const("thread 'main' panicked at %s:\n%s\n");
+
+        println!("; Runtime");
+        // do not redeclare functions which the crate declared in `extern` blocks
+        for (name, decl) in [
+            ("printf", "declare i32 @printf(ptr, ...)"),
+            ("dprintf", "declare i32 @dprintf(i32, ptr, ...)"),
+            ("exit", "declare void @exit(i32)"),
+        ] {
+            if !self.declared_funcs.contains(name) {
+                println!("{decl}");
+            }
+        }
+        let mut libc_funcs = vec![];
+        if self.uses_escape_debug || self.uses_format_float {
+            libc_funcs.extend([
+                ("strlen", "declare i64 @strlen(ptr)"),
+                ("sprintf", "declare i32 @sprintf(ptr, ptr, ...)"),
+            ]);
+        }
+        if self.uses_format_float {
+            libc_funcs.extend([
+                ("strtod", "declare double @strtod(ptr, ptr)"),
+                ("strchr", "declare ptr @strchr(ptr, i32)"),
+                ("atoi", "declare i32 @atoi(ptr)"),
+            ]);
+        }
+        for (name, decl) in libc_funcs {
+            if !self.declared_funcs.contains(name) {
+                println!("{decl}");
+            }
+        }
+        println!();
+
+        println!("define void @{PANIC_FN}(ptr %msg, ptr %loc) noreturn {{");
+        println!("start:");
+        println!(
+            "\tcall i32 (i32, ptr, ...) @dprintf(i32 2, ptr {}, ptr %loc, ptr %msg)",
+            panic_fmt.name
+        );
+        println!("\tcall void (i32) @exit(i32 {PANIC_EXIT_CODE})");
+        println!("\tunreachable");
+        println!("}}");
+        println!();
+
+        self.gen_encode_utf8();
+        if self.uses_escape_debug {
+            self.gen_escape_debug();
+        }
+        if self.uses_format_float {
+            self.gen_format_float();
+        }
+    }
+
+    fn gen_encode_utf8(&self) {
+        // (marker bits, payload mask) of the leading byte of 1-4 byte sequences
+        const LEADING_BYTES: [(u32, u32); 4] =
+            [(0x00, 0x7F), (0xC0, 0x1F), (0xE0, 0x0F), (0xF0, 0x07)];
+
+        println!("define void @{ENCODE_UTF8_FN}(i32 %c, ptr %buf) {{");
+        println!("start:");
+        println!("\t%lt1 = icmp ult i32 %c, 128");
+        println!("\tbr i1 %lt1, label %len1, label %ge1");
+        println!("ge1:");
+        println!("\t%lt2 = icmp ult i32 %c, 2048");
+        println!("\tbr i1 %lt2, label %len2, label %ge2");
+        println!("ge2:");
+        println!("\t%lt3 = icmp ult i32 %c, 65536");
+        println!("\tbr i1 %lt3, label %len3, label %len4");
+        for (i, (marker, mask)) in LEADING_BYTES.iter().enumerate() {
+            let len = i + 1;
+            println!("len{len}:");
+            for j in 0..len {
+                // continuation bytes are `10xxxxxx`
+                let (marker, mask) = if j == 0 {
+                    (*marker, *mask)
+                } else {
+                    (0x80, 0x3F)
+                };
+                let shift = 6 * (len - 1 - j);
+                println!("\t%shr{len}.{j} = lshr i32 %c, {shift}");
+                println!("\t%and{len}.{j} = and i32 %shr{len}.{j}, {mask}");
+                println!("\t%or{len}.{j} = or i32 %and{len}.{j}, {marker}");
+                println!("\t%byte{len}.{j} = trunc i32 %or{len}.{j} to i8");
+                println!("\t%ptr{len}.{j} = getelementptr i8, ptr %buf, i32 {j}");
+                println!("\tstore i8 %byte{len}.{j}, ptr %ptr{len}.{j}");
+            }
+            println!("\t%end{len} = getelementptr i8, ptr %buf, i32 {len}");
+            println!("\tstore i8 0, ptr %end{len}");
+            println!("\tret void");
+        }
+        println!("}}");
+        println!();
+    }
+
+    fn gen_escape_debug(&mut self) {
+        let plain_fmt = self.add_str_const("%c");
+        let prefixed_fmt = self.add_str_const("\\%c");
+        let control_fmt = self.add_str_const("\\u{%x}");
+        let escape_fmts: Vec<_> = DEBUG_ESCAPES
+            .iter()
+            .map(|(b, escape)| (*b, self.add_str_const(escape)))
+            .collect();
+
+        println!("define ptr @{ESCAPE_DEBUG_FN}(ptr %s, i32 %quote) {{");
+        println!("start:");
+        // each byte is escaped to at most 6 bytes like `\u{7f}`, and the quotes and NUL are added
+        println!("\t%len = call i64 @strlen(ptr %s)");
+        println!("\t%max_len = mul i64 %len, 6");
+        println!("\t%size = add i64 %max_len, 3");
+        println!("\t%buf = call ptr @malloc(i64 %size)");
+        println!("\t%quote_byte = trunc i32 %quote to i8");
+        println!("\tstore i8 %quote_byte, ptr %buf");
+        // indices to the source and the buffer
+        println!("\t%i.addr = alloca i64");
+        println!("\t%j.addr = alloca i64");
+        println!("\tstore i64 0, ptr %i.addr");
+        println!("\tstore i64 1, ptr %j.addr");
+        println!("\tbr label %loop");
+        println!("loop:");
+        println!("\t%i = load i64, ptr %i.addr");
+        println!("\t%src = getelementptr i8, ptr %s, i64 %i");
+        println!("\t%c = load i8, ptr %src");
+        println!("\t%is_end = icmp eq i8 %c, 0");
+        println!("\tbr i1 %is_end, label %end, label %body");
+        println!("body:");
+        println!("\t%c32 = zext i8 %c to i32");
+        println!("\t%is_below_space = icmp ult i8 %c, 32");
+        println!("\t%is_del = icmp eq i8 %c, 127");
+        println!("\t%is_control = or i1 %is_below_space, %is_del");
+        println!(
+            "\t%fmt.0 = select i1 %is_control, ptr {}, ptr {}",
+            control_fmt.name, plain_fmt.name
+        );
+        for (i, (b, fmt)) in escape_fmts.iter().enumerate() {
+            println!("\t%is_escaped.{i} = icmp eq i8 %c, {b}");
+            println!(
+                "\t%fmt.{} = select i1 %is_escaped.{i}, ptr {}, ptr %fmt.{i}",
+                i + 1,
+                fmt.name
+            );
+        }
+        println!("\t%is_quote = icmp eq i32 %c32, %quote");
+        println!("\t%is_backslash = icmp eq i8 %c, {}", b'\\');
+        println!("\t%is_prefixed = or i1 %is_quote, %is_backslash");
+        println!(
+            "\t%fmt = select i1 %is_prefixed, ptr {}, ptr %fmt.{}",
+            prefixed_fmt.name,
+            escape_fmts.len()
+        );
+        println!("\t%j = load i64, ptr %j.addr");
+        println!("\t%dest = getelementptr i8, ptr %buf, i64 %j");
+        println!("\t%n = call i32 (ptr, ptr, ...) @sprintf(ptr %dest, ptr %fmt, i32 %c32)");
+        println!("\t%n64 = sext i32 %n to i64");
+        println!("\t%next_i = add i64 %i, 1");
+        println!("\tstore i64 %next_i, ptr %i.addr");
+        println!("\t%next_j = add i64 %j, %n64");
+        println!("\tstore i64 %next_j, ptr %j.addr");
+        println!("\tbr label %loop");
+        println!("end:");
+        println!("\t%buf_len = load i64, ptr %j.addr");
+        println!("\t%closing = getelementptr i8, ptr %buf, i64 %buf_len");
+        println!("\tstore i8 %quote_byte, ptr %closing");
+        println!("\t%nul = getelementptr i8, ptr %closing, i64 1");
+        println!("\tstore i8 0, ptr %nul");
+        println!("\tret ptr %buf");
+        println!("}}");
+        println!();
+    }
+
+    fn gen_format_float(&mut self) {
+        let nan_str = self.add_str_const("NaN");
+        let inf_str = self.add_str_const("inf");
+        let neg_inf_str = self.add_str_const("-inf");
+        let exp_fmt = self.add_str_const("%.*e");
+        let fixed_fmt = self.add_str_const("%.*f");
+        let sci_exp_fmt = self.add_str_const("e%d");
+
+        println!("define ptr @{FORMAT_FLOAT_FN}(double %x, i32 %is_f32, i32 %debug) {{");
+        println!("start:");
+        println!("\t%buf = call ptr @malloc(i64 {FLOAT_BUF_SIZE})");
+        // `x - x` is NaN for infinities
+        println!("\t%is_nan = fcmp uno double %x, %x");
+        println!("\t%diff = fsub double %x, %x");
+        println!("\t%is_not_finite = fcmp uno double %diff, %diff");
+        println!("\tbr i1 %is_not_finite, label %not_finite, label %finite");
+        println!("not_finite:");
+        println!("\t%is_pos = fcmp ogt double %x, 0.0");
+        println!(
+            "\t%inf = select i1 %is_pos, ptr {}, ptr {}",
+            inf_str.name, neg_inf_str.name
+        );
+        println!(
+            "\t%special = select i1 %is_nan, ptr {}, ptr %inf",
+            nan_str.name
+        );
+        println!("\tcall i32 (ptr, ptr, ...) @sprintf(ptr %buf, ptr %special)");
+        println!("\tret ptr %buf");
+        // the number of significant digits is increased until x is read back
+        println!("finite:");
+        println!("\t%digits.addr = alloca i32");
+        println!("\tstore i32 1, ptr %digits.addr");
+        println!("\t%x32 = fptrunc double %x to float");
+        println!("\t%f32 = icmp ne i32 %is_f32, 0");
+        println!("\tbr label %loop");
+        println!("loop:");
+        println!("\t%digits = load i32, ptr %digits.addr");
+        println!("\t%exp_prec = sub i32 %digits, 1");
+        println!(
+            "\tcall i32 (ptr, ptr, ...) @sprintf(ptr %buf, ptr {}, i32 %exp_prec, double %x)",
+            exp_fmt.name
+        );
+        println!("\t%y = call double @strtod(ptr %buf, ptr null)");
+        println!("\t%y32 = fptrunc double %y to float");
+        println!("\t%eq64 = fcmp oeq double %x, %y");
+        println!("\t%eq32 = fcmp oeq float %x32, %y32");
+        println!("\t%eq = select i1 %f32, i1 %eq32, i1 %eq64");
+        println!("\t%is_max = icmp sge i32 %digits, {MAX_FLOAT_DIGITS}");
+        println!("\t%found = or i1 %eq, %is_max");
+        println!("\t%next_digits = add i32 %digits, 1");
+        println!("\tstore i32 %next_digits, ptr %digits.addr");
+        println!("\tbr i1 %found, label %fixed, label %loop");
+        println!("fixed:");
+        println!("\t%e = call ptr @strchr(ptr %buf, i32 {})", b'e');
+        println!("\t%exp_str = getelementptr i8, ptr %e, i64 1");
+        println!("\t%exp = call i32 @atoi(ptr %exp_str)");
+        println!("\t%is_debug = icmp ne i32 %debug, 0");
+        println!("\t%is_small = icmp slt i32 %exp, {}", DEBUG_DECIMAL_EXPS.0);
+        println!("\t%is_large = icmp sgt i32 %exp, {}", DEBUG_DECIMAL_EXPS.1);
+        println!("\t%is_out_of_range = or i1 %is_small, %is_large");
+        println!("\t%is_nonzero = fcmp une double %x, 0.0");
+        println!("\t%is_sci.0 = and i1 %is_debug, %is_out_of_range");
+        println!("\t%is_sci = and i1 %is_sci.0, %is_nonzero");
+        println!("\tbr i1 %is_sci, label %sci, label %decimal");
+        // the exponent `e[+-]dd` is rewritten as Rust prints it (e.g. `e-7` and `e20`)
+        println!("sci:");
+        println!(
+            "\tcall i32 (ptr, ptr, ...) @sprintf(ptr %e, ptr {}, i32 %exp)",
+            sci_exp_fmt.name
+        );
+        println!("\tret ptr %buf");
+        // the digits are printed without the exponent
+        println!("decimal:");
+        println!("\t%frac_digits = sub i32 %exp_prec, %exp");
+        println!("\t%is_int = icmp slt i32 %frac_digits, 0");
+        println!("\t%prec = select i1 %is_int, i32 0, i32 %frac_digits");
+        println!(
+            "\tcall i32 (ptr, ptr, ...) @sprintf(ptr %buf, ptr {}, i32 %prec, double %x)",
+            fixed_fmt.name
+        );
+        println!("\tbr i1 %is_debug, label %check_point, label %end");
+        // Debug prints integral values with `.0`
+        println!("check_point:");
+        println!("\t%point = call ptr @strchr(ptr %buf, i32 {})", b'.');
+        println!("\t%has_point = icmp ne ptr %point, null");
+        println!("\tbr i1 %has_point, label %end, label %append");
+        println!("append:");
+        println!("\t%len = call i64 @strlen(ptr %buf)");
+        println!("\t%tail = getelementptr i8, ptr %buf, i64 %len");
+        println!("\tstore i8 {}, ptr %tail", b'.');
+        println!("\t%tail1 = getelementptr i8, ptr %tail, i64 1");
+        println!("\tstore i8 {}, ptr %tail1", b'0');
+        println!("\t%tail2 = getelementptr i8, ptr %tail, i64 2");
+        println!("\tstore i8 0, ptr %tail2");
+        println!("\tbr label %end");
+        println!("end:");
+        println!("\tret ptr %buf");
+        println!("}}");
+        println!();
+    }
+}
@@ -0,0 +1,348 @@
+use super::{
+    frame::compute_frame,
+    runtime::{PANIC_EXIT_CODE, PANIC_FN},
+    Class, Codegen,
+};
+use crate::mir::{
+    self, Body, ConstKind, Operand, Place, Program, Statement, StatementKind, Terminator,
+    TerminatorKind,
+};
+
+/// Registers of integer arguments, by size of 1, 2, 4 and 8 bytes
+const ARG_REGS: [[&str; 4]; 6] = [
+    ["%dil", "%di", "%edi", "%rdi"],
+    ["%sil", "%si", "%esi", "%rsi"],
+    ["%dl", "%dx", "%edx", "%rdx"],
+    ["%cl", "%cx", "%ecx", "%rcx"],
+    ["%r8b", "%r8w", "%r8d", "%r8"],
+    ["%r9b", "%r9w", "%r9d", "%r9"],
+];
+
+/// Number of `%xmm` registers for float arguments
+const NUM_FLOAT_ARG_REGS: usize = 8;
+
+/// Argument of a call
+pub enum CallArg<'a> {
+    Operand(&'a Operand),
+    /// Value of the place
+    Place(&'a Place),
+    /// Address of the place, e.g. the `sret` pointer
+    Addr(&'a Place),
+    Imm(i64),
+    /// Address of a label
+    Label(String),
+    /// 8-byte value in the slot at the offset from `%rbp`
+    Slot(i64),
+    /// `double` in the slot at the offset from `%rbp`
+    FloatSlot(i64),
+    /// Address of the slot at the offset from `%rbp`
+    SlotAddr(i64),
+}
+
+impl<'a, 'ctx> Codegen<'a, 'ctx> {
+    pub fn gen_program(&mut self, program: &Program) {
+        for (id, body) in program.bodies.iter().enumerate() {
+            self.gen_body(body, id);
+        }
+        if !program.statics.is_empty() {
+            println!("\t.data");
+        }
+        for static_item in &program.statics {
+            let init = &static_item.init;
+            let Class::Int { size, .. } = self.class(&init.ty) else {
+                panic!("ICE: static of type `{}`", init.ty);
+            };
+            let value = match init.kind {
+                ConstKind::Int(n) => n,
+                ConstKind::Bool(b) => i64::from(b),
+                ConstKind::Char(c) => i64::from(u32::from(c)),
+                _ => panic!("ICE: static initialized with `{}`", init),
+            };
+            let directive = match size {
+                1 => ".byte",
+                2 => ".short",
+                4 => ".long",
+                _ => ".quad",
+            };
+            println!("\t.balign {size}");
+            println!("\"{}\":", static_item.name.demangle());
+            println!("\t{directive} {value}");
+        }
+    }
+
+    fn gen_body(&mut self, body: &Body, id: usize) {
+        let frame = compute_frame(self, body, id);
+        let name = body.name.demangle();
+        println!("\t.globl \"{name}\"");
+        println!("\t.text");
+        println!("\"{name}\":");
+        println!("\tpush %rbp");
+        println!("\tmov %rsp, %rbp");
+        if frame.size != 0 {
+            println!("\tsub ${}, %rsp", frame.size);
+        }
+        self.current_frame = Some(frame);
+        self.depth = 0;
+        self.gen_params(body);
+
+        for (bb, block) in body.blocks.iter().enumerate() {
+            println!("{}:", self.peek_frame().block_label(bb));
+            for stmt in &block.stmts {
+                self.gen_stmt(stmt);
+            }
+            self.gen_terminator(&block.terminator, body);
+        }
+        println!();
+
+        self.current_frame = None;
+    }
+
+    /// Store the arguments passed via registers and the stack to the slots of the parameters
+    fn gen_params(&mut self, body: &Body) {
+        let mut num_ints = 0;
+        let mut num_floats = 0;
+        // arguments which do not fit in registers are above the return address
+        let mut stack_offset = 16;
+        // the `sret` pointer is the first argument
+        let has_sret = matches!(self.class(&body.return_ty()), Class::Memory { .. });
+        let locals = std::iter::once(mir::RETURN_PLACE)
+            .filter(|_| has_sret)
+            .chain(body.args());
+        for local in locals {
+            let (offset, indirect) = self.peek_frame().get_local_slot(local);
+            let ty = self.peek_frame().get_local_ty(local);
+            let size = match self.class(&ty) {
+                Class::Void => continue,
+                _ if indirect => 8,
+                Class::Int { size, .. } | Class::Float { size } | Class::Memory { size } => size,
+            };
+            let is_float = matches!(self.class(&ty), Class::Float { .. });
+            if is_float && num_floats < NUM_FLOAT_ARG_REGS {
+                let inst = if size == 4 { "movss" } else { "movsd" };
+                println!("\t{inst} %xmm{num_floats}, {offset}(%rbp)");
+                num_floats += 1;
+            } else if !is_float && num_ints < ARG_REGS.len() {
+                let reg = ARG_REGS[num_ints][size.trailing_zeros() as usize];
+                println!("\tmov {reg}, {offset}(%rbp)");
+                num_ints += 1;
+            } else {
+                println!("\tmov {stack_offset}(%rbp), %rax");
+                if indirect {
+                    println!("\tmov %rax, {offset}(%rbp)");
+                } else {
+                    println!("\tlea {offset}(%rbp), %rdi");
+                    self.store(&ty);
+                }
+                stack_offset += 8;
+            }
+        }
+    }
+
+    fn gen_stmt(&mut self, stmt: &Statement) {
+        println!("# {}", stmt.kind);
+        match &stmt.kind {
+            StatementKind::Assign(place, rvalue) => self.gen_assign(place, rvalue),
+            StatementKind::Drop(place) => {
+                let ty = self.peek_frame().get_place_ty(place, self);
+                self.gen_drop_in_place(CallArg::Addr(place), &ty);
+            }
+            StatementKind::Deallocate(place) => {
+                let free = self.func_symbol("free");
+                self.gen_call(&free, &[CallArg::Place(place)], Class::Void);
+            }
+            StatementKind::Print(dest, pieces, args) => self.gen_print(*dest, pieces, args),
+        }
+    }
+
+    fn gen_terminator(&mut self, terminator: &Terminator, body: &Body) {
+        println!("# {}", terminator.kind);
+        match &terminator.kind {
+            TerminatorKind::Goto(bb) => {
+                println!("\tjmp {}", self.peek_frame().block_label(*bb));
+            }
+            TerminatorKind::If(cond, then_bb, else_bb) => {
+                self.gen_operand(cond);
+                println!("\tcmp $0, %al");
+                println!("\tje {}", self.peek_frame().block_label(*else_bb));
+                println!("\tjmp {}", self.peek_frame().block_label(*then_bb));
+            }
+            TerminatorKind::Call {
+                func,
+                args,
+                dest,
+                target,
+            } => {
+                let ret_ty = self.peek_frame().get_place_ty(dest, self);
+                let ret_class = self.class(&ret_ty);
+                let mut call_args = vec![];
+                if let Class::Memory { .. } = ret_class {
+                    // the result is written to the destination directly
+                    call_args.push(CallArg::Addr(dest));
+                }
+                call_args.extend(args.iter().map(CallArg::Operand));
+                let func = self.func_symbol(&func.demangle());
+                self.gen_call(&func, &call_args, ret_class);
+                if let Class::Int { .. } | Class::Float { .. } = ret_class {
+                    self.push();
+                    self.gen_addr(dest);
+                    println!("\tmov %rax, %rdi");
+                    self.pop("%rax");
+                    self.store(&ret_ty);
+                }
+                match target {
+                    Some(bb) => println!("\tjmp {}", self.peek_frame().block_label(*bb)),
+                    None => println!("\tud2"),
+                }
+            }
+            TerminatorKind::Return => {
+                let ret_ty = body.return_ty();
+                match self.class(&ret_ty) {
+                    // the `sret` pointer is returned
+                    Class::Memory { .. } => {
+                        let (offset, _) = self.peek_frame().get_local_slot(mir::RETURN_PLACE);
+                        println!("\tmov {offset}(%rbp), %rax");
+                    }
+                    Class::Void => println!("\tmov $0, %eax"),
+                    class => {
+                        self.gen_place_value(&Place::local(mir::RETURN_PLACE));
+                        match class {
+                            Class::Float { size: 4 } => println!("\tmovd %eax, %xmm0"),
+                            Class::Float { .. } => println!("\tmovq %rax, %xmm0"),
+                            _ => (),
+                        }
+                    }
+                }
+                println!("\tmov %rbp, %rsp");
+                println!("\tpop %rbp");
+                println!("\tret");
+            }
+            TerminatorKind::Panic(None) => {
+                let exit_fn = self.func_symbol("exit");
+                self.gen_call(
+                    &exit_fn,
+                    &[CallArg::Imm(PANIC_EXIT_CODE.into())],
+                    Class::Void,
+                );
+                self.uses_runtime = true;
+            }
+            TerminatorKind::Panic(Some(msg)) => {
+                let msg = self.add_str(msg);
+                let loc = self.add_str(&terminator.span.to_location());
+                let panic_fn = self.func_symbol(PANIC_FN);
+                self.gen_call(
+                    &panic_fn,
+                    &[CallArg::Label(msg), CallArg::Label(loc)],
+                    Class::Void,
+                );
+                self.uses_runtime = true;
+            }
+            TerminatorKind::Unreachable => println!("\tud2"),
+        }
+    }
+
+    fn call_arg_class(&mut self, arg: &CallArg) -> Class {
+        match arg {
+            CallArg::Operand(op) => {
+                let ty = self.peek_frame().get_operand_ty(op, self);
+                self.class(&ty)
+            }
+            CallArg::Place(place) => {
+                let ty = self.peek_frame().get_place_ty(place, self);
+                self.class(&ty)
+            }
+            CallArg::FloatSlot(_) => Class::Float { size: 8 },
+            CallArg::Addr(_)
+            | CallArg::Imm(_)
+            | CallArg::Label(_)
+            | CallArg::Slot(_)
+            | CallArg::SlotAddr(_) => Class::Int {
+                size: 8,
+                signed: false,
+            },
+        }
+    }
+
+    fn gen_call_arg(&mut self, arg: &CallArg) {
+        match arg {
+            CallArg::Operand(op) => self.gen_operand(op),
+            CallArg::Place(place) => self.gen_place_value(place),
+            CallArg::Addr(place) => self.gen_addr(place),
+            CallArg::Imm(n) => println!("\tmov ${n}, %rax"),
+            CallArg::Label(label) => println!("\tlea {label}(%rip), %rax"),
+            CallArg::Slot(offset) | CallArg::FloatSlot(offset) => {
+                println!("\tmov {offset}(%rbp), %rax")
+            }
+            CallArg::SlotAddr(offset) => println!("\tlea {offset}(%rbp), %rax"),
+        }
+    }
+
+    /// Call the function, and leave the result in `%rax`.
+    /// Arguments are passed in registers from left to right, and those which do not fit
+    /// are pushed to the stack from right to left
+    pub fn gen_call(&mut self, func: &str, args: &[CallArg], ret: Class) {
+        let mut int_args = vec![];
+        let mut float_args = vec![];
+        let mut stack_args = vec![];
+        for arg in args {
+            match self.call_arg_class(arg) {
+                // ZSTs are not passed
+                Class::Void => (),
+                Class::Float { .. } if float_args.len() < NUM_FLOAT_ARG_REGS => {
+                    float_args.push(arg)
+                }
+                Class::Float { .. } => stack_args.push(arg),
+                _ if int_args.len() < ARG_REGS.len() => int_args.push(arg),
+                _ => stack_args.push(arg),
+            }
+        }
+
+        // the stack must be aligned to 16 bytes after the arguments are pushed
+        let padding = (self.depth + stack_args.len()) % 2;
+        if padding == 1 {
+            println!("\tsub $8, %rsp");
+            self.depth += 1;
+        }
+        for arg in stack_args.iter().rev() {
+            self.gen_call_arg(arg);
+            self.push();
+        }
+        for arg in float_args.iter().rev().chain(int_args.iter().rev()) {
+            self.gen_call_arg(arg);
+            self.push();
+        }
+        for regs in &ARG_REGS[..int_args.len()] {
+            self.pop(regs[3]);
+        }
+        for i in 0..float_args.len() {
+            self.pop("%rax");
+            println!("\tmovq %rax, %xmm{i}");
+        }
+        // variadic functions take the number of vector registers used in `%al`
+        println!("\tmov ${}, %eax", float_args.len());
+        println!("\tcall {func}");
+        let num_popped = stack_args.len() + padding;
+        if num_popped != 0 {
+            println!("\tadd ${}, %rsp", num_popped * 8);
+            self.depth -= num_popped;
+        }
+
+        match ret {
+            // callers extend integers narrower than 64 bits
+            Class::Int { .. } => self.extend(ret),
+            Class::Float { size: 4 } => println!("\tmovd %xmm0, %eax"),
+            Class::Float { .. } => println!("\tmovq %xmm0, %rax"),
+            Class::Void | Class::Memory { .. } => (),
+        }
+    }
+
+    /// Call the function whose arguments are already in registers
+    pub fn gen_aligned_call(&mut self, func: &str) {
+        if self.depth % 2 == 1 {
+            println!("\tsub $8, %rsp");
+            println!("\tcall {func}");
+            println!("\tadd $8, %rsp");
+        } else {
+            println!("\tcall {func}");
+        }
+    }
+}
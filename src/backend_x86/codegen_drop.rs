@@ -0,0 +1,104 @@
+use super::{codegen_body::CallArg, Class, Codegen};
+use crate::middle::ty::{Ty, TyKind};
+use std::rc::Rc;
+
+/// Slot of the pointer to the value to drop in drop glue
+const PTR_SLOT: i64 = -8;
+/// Slot of the box or the index in drop glue
+const TMP_SLOT: i64 = -16;
+
+impl<'a, 'ctx> Codegen<'a, 'ctx> {
+    /// Drop the value of type `ty` at the address given by `ptr`
+    pub fn gen_drop_in_place(&mut self, ptr: CallArg, ty: &Rc<Ty>) {
+        let i = match self.drop_glue_tys.iter().position(|t| t == ty) {
+            Some(i) => i,
+            None => {
+                self.drop_glue_tys.push(Rc::clone(ty));
+                self.drop_glue_tys.len() - 1
+            }
+        };
+        self.gen_call(&format!("\"{}\"", drop_glue_name(i)), &[ptr], Class::Void);
+    }
+
+    /// Generate `drop_in_place` functions for all types dropped so far.
+    /// Drop glue of a type may require drop glue of other types
+    pub fn gen_drop_glues(&mut self) {
+        let mut i = 0;
+        while i < self.drop_glue_tys.len() {
+            let ty = Rc::clone(&self.drop_glue_tys[i]);
+            self.gen_drop_glue(i, &ty);
+            i += 1;
+        }
+    }
+
+    fn gen_drop_glue(&mut self, i: usize, ty: &Rc<Ty>) {
+        println!("# Drop glue of `{}`", ty);
+        println!("\t.text");
+        println!("\"{}\":", drop_glue_name(i));
+        println!("\tpush %rbp");
+        println!("\tmov %rsp, %rbp");
+        println!("\tsub $16, %rsp");
+        println!("\tmov %rdi, {PTR_SLOT}(%rbp)");
+        self.depth = 0;
+
+        match &ty.kind {
+            TyKind::Box(inner) => {
+                println!("\tmov (%rdi), %rax");
+                println!("\tmov %rax, {TMP_SLOT}(%rbp)");
+                if self.ctx.needs_drop(inner) {
+                    self.gen_drop_in_place(CallArg::Slot(TMP_SLOT), inner);
+                }
+                let free = self.func_symbol("free");
+                self.gen_call(&free, &[CallArg::Slot(TMP_SLOT)], Class::Void);
+            }
+            TyKind::Adt(name) => {
+                // the destructor runs before the fields are dropped
+                if let Some(drop_fn) = self.ctx.lookup_drop_impl(name) {
+                    let drop_fn = self.func_symbol(&drop_fn.cpath.demangle());
+                    self.gen_call(&drop_fn, &[CallArg::Slot(PTR_SLOT)], Class::Void);
+                }
+                let adt = self.ctx.lookup_adt_def(name).unwrap();
+                let adt_layout = self.adt_layout(name);
+                for (fd, fd_ty) in &adt.fields {
+                    if self.ctx.needs_drop(fd_ty) {
+                        println!("\tmov {PTR_SLOT}(%rbp), %rax");
+                        println!("\tadd ${}, %rax", adt_layout.offsets[fd]);
+                        println!("\tmov %rax, {TMP_SLOT}(%rbp)");
+                        self.gen_drop_in_place(CallArg::Slot(TMP_SLOT), fd_ty);
+                    }
+                }
+            }
+            TyKind::Array(elem, n) => {
+                let elem_size = self.layout(elem).size;
+                let cond_label = self.get_fresh_label("cond");
+                let end_label = self.get_fresh_label("end");
+                // the index is kept in the slot as the elements are dropped by calls
+                println!("\tmovq $0, {TMP_SLOT}(%rbp)");
+                println!("{cond_label}:");
+                println!("\tcmpq ${n}, {TMP_SLOT}(%rbp)");
+                println!("\tje {end_label}");
+                println!("\timul ${elem_size}, {TMP_SLOT}(%rbp), %rax");
+                println!("\tadd {PTR_SLOT}(%rbp), %rax");
+                println!("\tpush %rax");
+                self.depth += 1;
+                self.gen_drop_in_place(CallArg::Slot(-24), elem);
+                println!("\tadd $8, %rsp");
+                self.depth -= 1;
+                println!("\tincq {TMP_SLOT}(%rbp)");
+                println!("\tjmp {cond_label}");
+                println!("{end_label}:");
+            }
+            _ => panic!("ICE: `{}` does not need drop", ty),
+        }
+
+        println!("\tmov %rbp, %rsp");
+        println!("\tpop %rbp");
+        println!("\tret");
+        println!();
+    }
+}
+
+/// Drop glue is numbered since type names contain characters invalid in symbols
+fn drop_glue_name(i: usize) -> String {
+    format!("__mini_rustc_drop_in_place.{i}")
+}
@@ -0,0 +1,197 @@
+use super::{
+    codegen_body::CallArg,
+    runtime::{ENCODE_UTF8_FN, ESCAPE_DEBUG_FN, FORMAT_FLOAT_FN},
+    Class, Codegen,
+};
+use crate::{
+    ast::{FormatAlign, FormatPiece, PrintDest},
+    middle::ty::TyKind,
+    mir::Operand,
+    parse::conversion_spec,
+};
+
+impl<'a, 'ctx> Codegen<'a, 'ctx> {
+    /// Generate a call to `printf` (or `dprintf` for stderr) for `print!` and friends.
+    /// The format string is generated as in the LLVM backend. Arguments are evaluated
+    /// to the print slots of the frame first, as evaluating them may involve calls
+    pub fn gen_print(&mut self, dest: PrintDest, pieces: &[FormatPiece], args: &[Operand]) {
+        let arg_tys: Vec<_> = args
+            .iter()
+            .map(|arg| self.peek_frame().get_operand_ty(arg, self))
+            .collect();
+        let mut num_chars = 0;
+        let mut char_bufs = vec![None; args.len()];
+        for (i, arg) in args.iter().enumerate() {
+            let slot = self.peek_frame().get_print_slot(i);
+            self.gen_operand(arg);
+            match arg_tys[i].kind {
+                // bools are printed as strs
+                TyKind::Bool => {
+                    let true_str = self.add_str("true");
+                    let false_str = self.add_str("false");
+                    println!("\tcmp $0, %al");
+                    println!("\tlea {true_str}(%rip), %rax");
+                    println!("\tlea {false_str}(%rip), %rdi");
+                    println!("\tcmove %rdi, %rax");
+                    println!("\tmov %rax, {slot}(%rbp)");
+                }
+                // chars are encoded to their own buffers
+                TyKind::Char => {
+                    println!("\tmov %rax, {slot}(%rbp)");
+                    let buf = self.peek_frame().get_utf8_buf(num_chars);
+                    num_chars += 1;
+                    let encode_utf8 = self.func_symbol(ENCODE_UTF8_FN);
+                    self.gen_call(
+                        &encode_utf8,
+                        &[CallArg::Slot(slot), CallArg::SlotAddr(buf)],
+                        Class::Void,
+                    );
+                    self.uses_runtime = true;
+                    char_bufs[i] = Some(buf);
+                }
+                // variadic `float` arguments are promoted to `double`
+                TyKind::F32 => {
+                    println!("\tmovd %eax, %xmm0");
+                    println!("\tcvtss2sd %xmm0, %xmm0");
+                    println!("\tmovq %xmm0, {slot}(%rbp)");
+                }
+                _ => println!("\tmov %rax, {slot}(%rbp)"),
+            }
+        }
+
+        let mut printf_fmt = String::new();
+        let mut printf_args = vec![];
+        // strings formatted by the runtime are freed after printing
+        let mut formatted_slots = vec![];
+        for piece in pieces {
+            let spec = match piece {
+                FormatPiece::Lit(s) => {
+                    printf_fmt.push_str(&s.replace('%', "%%"));
+                    continue;
+                }
+                FormatPiece::Placeholder(spec) => spec,
+            };
+            let slot = self.peek_frame().get_print_slot(spec.arg);
+            match &arg_tys[spec.arg].kind {
+                // integers are already extended to 64 bits, which is fine for `int` too
+                TyKind::Int(int_ty) => {
+                    let conversion = match (int_ty.is_signed(), int_ty.bit_width()) {
+                        (true, 64) => "ld",
+                        (true, _) => "d",
+                        (false, 64) => "lu",
+                        (false, _) => "u",
+                    };
+                    // precision is ignored for integers
+                    printf_fmt.push_str(&conversion_spec(
+                        spec,
+                        FormatAlign::Right,
+                        false,
+                        conversion,
+                    ));
+                    printf_args.push(CallArg::Slot(slot));
+                }
+                TyKind::F32 | TyKind::F64 => {
+                    if spec.precision.is_some() {
+                        printf_fmt.push_str(&conversion_spec(spec, FormatAlign::Right, true, "f"));
+                        printf_args.push(CallArg::FloatSlot(slot));
+                    } else {
+                        printf_fmt.push_str(&conversion_spec(spec, FormatAlign::Right, false, "s"));
+                        let is_f32 = arg_tys[spec.arg].kind == TyKind::F32;
+                        let formatted =
+                            self.format_float(slot, is_f32, spec.debug, formatted_slots.len());
+                        printf_args.push(CallArg::Slot(formatted));
+                        formatted_slots.push(formatted);
+                    }
+                }
+                TyKind::Bool => {
+                    printf_fmt.push_str(&conversion_spec(spec, FormatAlign::Left, true, "s"));
+                    printf_args.push(CallArg::Slot(slot));
+                }
+                TyKind::Char => {
+                    let buf = CallArg::SlotAddr(char_bufs[spec.arg].unwrap());
+                    if spec.debug {
+                        // Debug of char ignores width and precision
+                        printf_fmt.push_str("%s");
+                        let escaped = self.escape_for_debug(buf, '\'', formatted_slots.len());
+                        printf_args.push(CallArg::Slot(escaped));
+                        formatted_slots.push(escaped);
+                    } else {
+                        printf_fmt.push_str(&conversion_spec(spec, FormatAlign::Left, true, "s"));
+                        printf_args.push(buf);
+                    }
+                }
+                // &str
+                TyKind::Ref(..) => {
+                    if spec.debug {
+                        // Debug of str ignores width and precision
+                        printf_fmt.push_str("%s");
+                        let escaped =
+                            self.escape_for_debug(CallArg::Slot(slot), '"', formatted_slots.len());
+                        printf_args.push(CallArg::Slot(escaped));
+                        formatted_slots.push(escaped);
+                    } else {
+                        printf_fmt.push_str(&conversion_spec(spec, FormatAlign::Left, true, "s"));
+                        printf_args.push(CallArg::Slot(slot));
+                    }
+                }
+                _ => panic!("ICE: argument of print is not formattable"),
+            }
+        }
+
+        let printf_fmt = self.add_str(&printf_fmt);
+        let (func, mut call_args) = match dest {
+            PrintDest::Stdout => ("printf", vec![]),
+            PrintDest::Stderr => ("dprintf", vec![CallArg::Imm(2)]),
+        };
+        call_args.push(CallArg::Label(printf_fmt));
+        call_args.extend(printf_args);
+        let func = self.func_symbol(func);
+        self.gen_call(&func, &call_args, Class::Void);
+        let free = self.func_symbol("free");
+        for slot in formatted_slots {
+            self.gen_call(&free, &[CallArg::Slot(slot)], Class::Void);
+        }
+    }
+
+    /// Escape the string at `ptr` for `{:?}` into a new buffer, enclosed in `quote`.
+    /// The buffer is stored to the `i`th formatted slot, which is returned
+    fn escape_for_debug(&mut self, ptr: CallArg, quote: char, i: usize) -> i64 {
+        let slot = self.peek_frame().get_formatted_slot(i);
+        let escape_debug = self.func_symbol(ESCAPE_DEBUG_FN);
+        self.gen_call(
+            &escape_debug,
+            &[ptr, CallArg::Imm(i64::from(u32::from(quote)))],
+            Class::Int {
+                size: 8,
+                signed: false,
+            },
+        );
+        println!("\tmov %rax, {slot}(%rbp)");
+        self.uses_runtime = true;
+        self.uses_escape_debug = true;
+        slot
+    }
+
+    /// Format the float in the slot like `{}` or `{:?}` into a new buffer, which is stored
+    /// to the `i`th formatted slot like `escape_for_debug`
+    fn format_float(&mut self, float_slot: i64, is_f32: bool, debug: bool, i: usize) -> i64 {
+        let slot = self.peek_frame().get_formatted_slot(i);
+        let format_float = self.func_symbol(FORMAT_FLOAT_FN);
+        self.gen_call(
+            &format_float,
+            &[
+                CallArg::FloatSlot(float_slot),
+                CallArg::Imm(i64::from(is_f32)),
+                CallArg::Imm(i64::from(debug)),
+            ],
+            Class::Int {
+                size: 8,
+                signed: false,
+            },
+        );
+        println!("\tmov %rax, {slot}(%rbp)");
+        self.uses_runtime = true;
+        self.uses_format_float = true;
+        slot
+    }
+}
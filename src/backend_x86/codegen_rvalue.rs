@@ -0,0 +1,480 @@
+use super::{codegen_body::CallArg, Class, Codegen};
+use crate::middle::ty::{Ty, TyKind};
+use crate::mir::{AggregateKind, BinOp, ConstKind, Operand, Place, Proj, Rvalue, UnOp};
+use std::rc::Rc;
+
+impl<'a, 'ctx> Codegen<'a, 'ctx> {
+    /// Compute the address of the place into `%rax`
+    pub fn gen_addr(&mut self, place: &Place) {
+        let (offset, indirect) = self.peek_frame().get_local_slot(place.local);
+        if indirect {
+            println!("\tmov {offset}(%rbp), %rax");
+        } else {
+            println!("\tlea {offset}(%rbp), %rax");
+        }
+        let mut ty = self.peek_frame().get_local_ty(place.local);
+        for proj in &place.projs {
+            match (proj, &ty.kind) {
+                // `Box<T>`, `&T` and `*const T` hold the address
+                (Proj::Deref, _) => println!("\tmov (%rax), %rax"),
+                (Proj::Field(field), TyKind::Adt(name)) => {
+                    let offset = self.adt_layout(name).offsets[field];
+                    if offset != 0 {
+                        println!("\tadd ${offset}, %rax");
+                    }
+                }
+                (Proj::Index(index), TyKind::Array(elem, _)) => {
+                    let elem_size = self.layout(elem).size;
+                    self.push();
+                    self.gen_place_value(&Place::local(*index));
+                    println!("\timul ${elem_size}, %rax, %rdi");
+                    self.pop("%rax");
+                    println!("\tadd %rdi, %rax");
+                }
+                _ => panic!("ICE: cannot project `{}` with {:?}", ty, proj),
+            }
+            ty = crate::mir::project_ty(&ty, proj, self.ctx);
+        }
+    }
+
+    /// Load the value of type `ty` at the address in `%rax` to `%rax`.
+    /// The addresses of structs and arrays are left as they are
+    pub fn load(&mut self, ty: &Ty) {
+        match self.class(ty) {
+            Class::Int { size: 1, signed } => {
+                let inst = if signed { "movsbq" } else { "movzbq" };
+                println!("\t{inst} (%rax), %rax");
+            }
+            Class::Int { size: 2, signed } => {
+                let inst = if signed { "movswq" } else { "movzwq" };
+                println!("\t{inst} (%rax), %rax");
+            }
+            Class::Int {
+                size: 4,
+                signed: true,
+            } => println!("\tmovslq (%rax), %rax"),
+            // 32-bit moves clear the upper half
+            Class::Int { size: 4, .. } | Class::Float { size: 4 } => {
+                println!("\tmov (%rax), %eax")
+            }
+            Class::Int { .. } | Class::Float { .. } => println!("\tmov (%rax), %rax"),
+            Class::Void | Class::Memory { .. } => (),
+        }
+    }
+
+    /// Store the value in `%rax` to the address in `%rdi`.
+    /// Structs and arrays are copied from the address in `%rax`
+    pub fn store(&mut self, ty: &Ty) {
+        match self.class(ty) {
+            Class::Int { size, .. } | Class::Float { size } => {
+                let reg = ["%al", "%ax", "%eax", "%rax"][size.trailing_zeros() as usize];
+                println!("\tmov {reg}, (%rdi)");
+            }
+            Class::Memory { size } if size != 0 => {
+                println!("\tmov %rax, %rsi");
+                println!("\tmov ${size}, %rcx");
+                println!("\trep movsb");
+            }
+            Class::Void | Class::Memory { .. } => (),
+        }
+    }
+
+    /// Sign- or zero-extend the integer in the lower bits of `%rax` to 64 bits
+    pub fn extend(&mut self, class: Class) {
+        match class {
+            Class::Int { size: 1, signed } => {
+                let inst = if signed { "movsbq" } else { "movzbq" };
+                println!("\t{inst} %al, %rax");
+            }
+            Class::Int { size: 2, signed } => {
+                let inst = if signed { "movswq" } else { "movzwq" };
+                println!("\t{inst} %ax, %rax");
+            }
+            Class::Int {
+                size: 4,
+                signed: true,
+            } => println!("\tmovslq %eax, %rax"),
+            Class::Int { size: 4, .. } => println!("\tmov %eax, %eax"),
+            _ => (),
+        }
+    }
+
+    /// Load the value of the place to `%rax` as `gen_operand` does
+    pub fn gen_place_value(&mut self, place: &Place) {
+        let ty = self.peek_frame().get_place_ty(place, self);
+        if self.class(&ty) != Class::Void {
+            self.gen_addr(place);
+            self.load(&ty);
+        }
+    }
+
+    /// Evaluate the operand to `%rax`. Structs and arrays are evaluated to their addresses
+    pub fn gen_operand(&mut self, op: &Operand) {
+        let cons = match op {
+            Operand::Copy(place, _) | Operand::Move(place, _) => {
+                self.gen_place_value(place);
+                return;
+            }
+            Operand::Const(cons) => cons,
+        };
+        match &cons.kind {
+            ConstKind::Int(n) => {
+                // the bits are extended as values of the type are
+                let n = match &cons.ty.kind {
+                    TyKind::Int(int_ty) => int_ty.truncate(i128::from(*n)) as i64,
+                    _ => *n,
+                };
+                println!("\tmov ${n}, %rax");
+            }
+            ConstKind::Float(f) => match cons.ty.kind {
+                TyKind::F32 => println!("\tmov ${}, %eax", (*f as f32).to_bits()),
+                _ => println!("\tmov ${}, %rax", f.to_bits() as i64),
+            },
+            ConstKind::Bool(b) => println!("\tmov ${}, %rax", *b as i32),
+            ConstKind::Char(c) => println!("\tmov ${}, %rax", *c as u32),
+            ConstKind::Str(s) => {
+                let label = self.add_str(s);
+                println!("\tlea {label}(%rip), %rax");
+            }
+            ConstKind::Unit => (),
+            ConstKind::Null => println!("\tmov $0, %rax"),
+            ConstKind::Static(name) => println!("\tlea \"{}\"(%rip), %rax", name.demangle()),
+        }
+    }
+
+    /// Evaluate the rvalue to `%rax` and store it to the place
+    pub fn gen_assign(&mut self, place: &Place, rvalue: &Rvalue) {
+        let ty = self.peek_frame().get_place_ty(place, self);
+        if self.class(&ty) == Class::Void {
+            // nothing is stored
+            return;
+        }
+        self.gen_addr(place);
+        self.push();
+        if let Rvalue::Aggregate(kind, ops) = rvalue {
+            self.gen_aggregate(kind, ops);
+        } else {
+            self.gen_rvalue(rvalue);
+            self.pop("%rdi");
+            self.store(&ty);
+        }
+    }
+
+    fn gen_rvalue(&mut self, rvalue: &Rvalue) {
+        match rvalue {
+            Rvalue::Use(op) => self.gen_operand(op),
+            Rvalue::BinaryOp(binop, lhs, rhs) => self.gen_binary(*binop, lhs, rhs),
+            Rvalue::UnaryOp(UnOp::Neg, inner) => {
+                let ty = self.peek_frame().get_operand_ty(inner, self);
+                self.gen_operand(inner);
+                match self.class(&ty) {
+                    // flip the sign bit
+                    Class::Float { size } => println!("\tbtc ${}, %rax", size * 8 - 1),
+                    class => {
+                        println!("\tneg %rax");
+                        self.extend(class);
+                    }
+                }
+            }
+            Rvalue::Cast(inner, to_ty) => self.gen_cast(inner, to_ty),
+            Rvalue::Ref(_, place) => self.gen_addr(place),
+            Rvalue::Box(inner) => self.gen_box(inner),
+            Rvalue::Aggregate(..) => panic!("ICE: aggregates are written to places"),
+        }
+    }
+
+    fn gen_binary(&mut self, binop: BinOp, lhs: &Operand, rhs: &Operand) {
+        let lhs_ty = self.peek_frame().get_operand_ty(lhs, self);
+        let class = self.class(&lhs_ty);
+        self.gen_operand(rhs);
+        self.push();
+        self.gen_operand(lhs);
+        self.pop("%rdi");
+
+        if binop == BinOp::Offset {
+            let TyKind::RawPtr(pointee, _) = &lhs_ty.kind else {
+                panic!("ICE: offset of `{}`", lhs_ty);
+            };
+            let size = self.layout(pointee).size;
+            println!("\timul ${size}, %rdi");
+            println!("\tadd %rdi, %rax");
+            return;
+        }
+        if let Class::Float { size } = class {
+            self.gen_float_binary(binop, size);
+            return;
+        }
+
+        let Class::Int { signed, .. } = class else {
+            panic!("ICE: binary operation on `{}`", lhs_ty);
+        };
+        let cmp = match binop {
+            BinOp::Add | BinOp::Sub | BinOp::Mul => {
+                let inst = match binop {
+                    BinOp::Add => "add",
+                    BinOp::Sub => "sub",
+                    _ => "imul",
+                };
+                println!("\t{inst} %rdi, %rax");
+                // wrap around the width of the type
+                self.extend(class);
+                return;
+            }
+            BinOp::Div | BinOp::Rem => {
+                if signed {
+                    println!("\tcqo");
+                    println!("\tidiv %rdi");
+                } else {
+                    println!("\txor %edx, %edx");
+                    println!("\tdiv %rdi");
+                }
+                if binop == BinOp::Rem {
+                    println!("\tmov %rdx, %rax");
+                }
+                return;
+            }
+            BinOp::Eq => "sete",
+            BinOp::Ne => "setne",
+            BinOp::Gt if signed => "setg",
+            BinOp::Gt => "seta",
+            BinOp::Lt if signed => "setl",
+            BinOp::Lt => "setb",
+            BinOp::Offset => unreachable!(),
+        };
+        println!("\tcmp %rdi, %rax");
+        println!("\t{cmp} %al");
+        println!("\tmovzbq %al, %rax");
+    }
+
+    /// Floats to operate on are in `%rax` and `%rdi`
+    fn gen_float_binary(&mut self, binop: BinOp, size: usize) {
+        let (mov, suffix) = if size == 4 {
+            ("movd", "ss")
+        } else {
+            ("movq", "sd")
+        };
+        let (rax, rdi) = if size == 4 {
+            ("%eax", "%edi")
+        } else {
+            ("%rax", "%rdi")
+        };
+        println!("\t{mov} {rax}, %xmm0");
+        println!("\t{mov} {rdi}, %xmm1");
+        let inst = match binop {
+            BinOp::Add => "add",
+            BinOp::Sub => "sub",
+            BinOp::Mul => "mul",
+            BinOp::Div => "div",
+            BinOp::Rem => {
+                let func = if size == 4 { "fmodf" } else { "fmod" };
+                let func = self.func_symbol(func);
+                self.gen_aligned_call(&func);
+                println!("\t{mov} %xmm0, {rax}");
+                return;
+            }
+            // ordered comparisons are false for NaN, which sets the parity flag, but `!=` is true
+            BinOp::Eq => {
+                println!("\tucomi{suffix} %xmm1, %xmm0");
+                println!("\tsete %al");
+                println!("\tsetnp %dl");
+                println!("\tand %dl, %al");
+                println!("\tmovzbq %al, %rax");
+                return;
+            }
+            BinOp::Ne => {
+                println!("\tucomi{suffix} %xmm1, %xmm0");
+                println!("\tsetne %al");
+                println!("\tsetp %dl");
+                println!("\tor %dl, %al");
+                println!("\tmovzbq %al, %rax");
+                return;
+            }
+            BinOp::Gt | BinOp::Lt => {
+                if binop == BinOp::Gt {
+                    println!("\tucomi{suffix} %xmm1, %xmm0");
+                } else {
+                    println!("\tucomi{suffix} %xmm0, %xmm1");
+                }
+                println!("\tseta %al");
+                println!("\tmovzbq %al, %rax");
+                return;
+            }
+            BinOp::Offset => unreachable!(),
+        };
+        println!("\t{inst}{suffix} %xmm1, %xmm0");
+        println!("\t{mov} %xmm0, {rax}");
+    }
+
+    /// Generate code for `inner as T`
+    // ref: https://doc.rust-lang.org/reference/expressions/operator-expr.html#type-cast-expressions
+    fn gen_cast(&mut self, inner: &Operand, to_ty: &Ty) {
+        let from_ty = self.peek_frame().get_operand_ty(inner, self);
+        let from = self.class(&from_ty);
+        let to = self.class(to_ty);
+        self.gen_operand(inner);
+        match (from, to) {
+            (Class::Float { size: from_size }, Class::Float { size: to_size }) => {
+                match (from_size, to_size) {
+                    (4, 8) => {
+                        println!("\tmovd %eax, %xmm0");
+                        println!("\tcvtss2sd %xmm0, %xmm0");
+                        println!("\tmovq %xmm0, %rax");
+                    }
+                    (8, 4) => {
+                        println!("\tmovq %rax, %xmm0");
+                        println!("\tcvtsd2ss %xmm0, %xmm0");
+                        println!("\tmovd %xmm0, %eax");
+                    }
+                    _ => (),
+                }
+            }
+            (Class::Int { signed, .. }, Class::Float { size }) => {
+                self.gen_int_to_float(signed, size);
+            }
+            (Class::Float { size }, Class::Int { .. }) => self.gen_float_to_int(size, to_ty),
+            // integers are extended by their signedness to 64 bits, so casts between
+            // integers, `bool`, `char` and pointers only truncate or extend the bits
+            (Class::Int { .. }, Class::Int { .. }) => self.extend(to),
+            _ => panic!("ICE: invalid cast from `{}` to `{}`", from_ty, to_ty),
+        }
+    }
+
+    fn gen_int_to_float(&mut self, signed: bool, size: usize) {
+        let (mov, suffix) = if size == 4 {
+            ("movd", "ss")
+        } else {
+            ("movq", "sd")
+        };
+        let rax = if size == 4 { "%eax" } else { "%rax" };
+        if signed {
+            println!("\tcvtsi2{suffix}q %rax, %xmm0");
+        } else {
+            // unsigned integers not less than 2^63 are halved, keeping the lowest bit
+            // for rounding, converted and doubled
+            let big = self.get_fresh_label("cast");
+            let end = self.get_fresh_label("cast");
+            println!("\ttest %rax, %rax");
+            println!("\tjs {big}");
+            println!("\tcvtsi2{suffix}q %rax, %xmm0");
+            println!("\tjmp {end}");
+            println!("{big}:");
+            println!("\tmov %rax, %rdi");
+            println!("\tshr %rdi");
+            println!("\tand $1, %eax");
+            println!("\tor %rax, %rdi");
+            println!("\tcvtsi2{suffix}q %rdi, %xmm0");
+            println!("\tadd{suffix} %xmm0, %xmm0");
+            println!("{end}:");
+        }
+        println!("\t{mov} %xmm0, {rax}");
+    }
+
+    /// Float-to-int casts saturate, and NaN is cast to 0
+    fn gen_float_to_int(&mut self, size: usize, to_ty: &Ty) {
+        let TyKind::Int(int_ty) = &to_ty.kind else {
+            panic!("ICE: cast from float to `{}`", to_ty);
+        };
+        let (min, max) = (int_ty.min_value(), int_ty.max_value());
+        if size == 4 {
+            println!("\tmovd %eax, %xmm0");
+            println!("\tcvtss2sd %xmm0, %xmm0");
+        } else {
+            println!("\tmovq %rax, %xmm0");
+        }
+        let [nan, lower, upper, big, end] = ["nan", "min", "max", "big", "end"]
+            .map(|kind| self.get_fresh_label(&format!("cast.{kind}")));
+        println!("\tucomisd %xmm0, %xmm0");
+        println!("\tjp {nan}");
+        println!("\tmov ${}, %rax", (min as f64).to_bits() as i64);
+        println!("\tmovq %rax, %xmm1");
+        println!("\tucomisd %xmm1, %xmm0");
+        println!("\tjbe {lower}");
+        // `max + 1` is exact in `f64` while `max` may not be
+        println!("\tmov ${}, %rax", ((max + 1) as f64).to_bits() as i64);
+        println!("\tmovq %rax, %xmm1");
+        println!("\tucomisd %xmm1, %xmm0");
+        println!("\tjae {upper}");
+        if max == i128::from(u64::MAX) {
+            // `cvttsd2si` converts to `i64`
+            println!("\tmov ${}, %rax", 2f64.powi(63).to_bits() as i64);
+            println!("\tmovq %rax, %xmm1");
+            println!("\tucomisd %xmm1, %xmm0");
+            println!("\tjae {big}");
+        }
+        println!("\tcvttsd2si %xmm0, %rax");
+        println!("\tjmp {end}");
+        println!("{big}:");
+        println!("\tsubsd %xmm1, %xmm0");
+        println!("\tcvttsd2si %xmm0, %rax");
+        println!("\tbtc $63, %rax");
+        println!("\tjmp {end}");
+        println!("{nan}:");
+        println!("\tmov $0, %rax");
+        println!("\tjmp {end}");
+        println!("{lower}:");
+        println!("\tmov ${}, %rax", min as i64);
+        println!("\tjmp {end}");
+        println!("{upper}:");
+        println!("\tmov ${}, %rax", max as i64);
+        println!("{end}:");
+    }
+
+    /// Allocate memory on the heap and move the value there
+    fn gen_box(&mut self, inner: &Operand) {
+        let inner_ty = self.peek_frame().get_operand_ty(inner, self);
+        let size = self.layout(&inner_ty).size;
+        self.gen_operand(inner);
+        self.push();
+        let malloc = self.func_symbol("malloc");
+        self.gen_call(
+            &malloc,
+            &[CallArg::Imm(size as i64)],
+            Class::Int {
+                size: 8,
+                signed: false,
+            },
+        );
+        // the box is kept on the stack while the value is stored
+        println!("\tmov %rax, %rdi");
+        self.pop("%rax");
+        println!("\tpush %rdi");
+        self.depth += 1;
+        self.store(&inner_ty);
+        self.pop("%rax");
+    }
+
+    /// Initialize the struct or the array at the address pushed to the stack field by field,
+    /// and pop the address
+    fn gen_aggregate(&mut self, kind: &AggregateKind, ops: &[Operand]) {
+        let offsets: Vec<(usize, Rc<Ty>)> = match kind {
+            AggregateKind::Adt(name) => {
+                let adt = self.ctx.lookup_adt_def(name).unwrap();
+                let adt_layout = self.adt_layout(name);
+                adt.fields
+                    .iter()
+                    .map(|(fd, fd_ty)| (adt_layout.offsets[fd], Rc::clone(fd_ty)))
+                    .collect()
+            }
+            AggregateKind::Array => {
+                let Some(op) = ops.first() else {
+                    self.pop("%rax");
+                    return;
+                };
+                let elem_ty = self.peek_frame().get_operand_ty(op, self);
+                let elem_size = self.layout(&elem_ty).size;
+                (0..ops.len())
+                    .map(|i| (i * elem_size, Rc::clone(&elem_ty)))
+                    .collect()
+            }
+        };
+        for ((offset, ty), op) in offsets.into_iter().zip(ops) {
+            self.gen_operand(op);
+            println!("\tmov (%rsp), %rdi");
+            if offset != 0 {
+                println!("\tadd ${offset}, %rdi");
+            }
+            self.store(&ty);
+        }
+        self.pop("%rax");
+    }
+}
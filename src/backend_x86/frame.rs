@@ -0,0 +1,156 @@
+use super::runtime::UTF8_BUF_SIZE;
+use super::{Class, Codegen, Layout};
+use crate::ast::FormatPiece;
+use crate::middle::ty::{Ty, TyKind};
+use crate::mir::{self, Body, Operand, StatementKind};
+use std::rc::Rc;
+
+pub fn compute_frame(codegen: &mut Codegen, body: &Body, id: usize) -> Frame {
+    let mut frame = Frame {
+        id,
+        locals: vec![],
+        print_slots: vec![],
+        utf8_bufs: vec![],
+        formatted_slots: vec![],
+        size: 0,
+    };
+    // slots are allocated downwards from `%rbp`
+    let mut size: usize = 0;
+    let mut alloc = |layout: Layout| {
+        size = (size + layout.size).next_multiple_of(layout.align);
+        -(size as i64)
+    };
+
+    for (local, decl) in body.locals.iter().enumerate() {
+        // structs and arrays are passed by pointer, and returned through the pointer
+        // given by the caller
+        let indirect = matches!(codegen.class(&decl.ty), Class::Memory { .. })
+            && (local == mir::RETURN_PLACE || body.args().contains(&local));
+        let layout = if indirect {
+            Layout { size: 8, align: 8 }
+        } else {
+            codegen.layout(&decl.ty)
+        };
+        frame.locals.push(FrameLocal {
+            offset: alloc(layout),
+            indirect,
+            ty: Rc::clone(&decl.ty),
+        });
+    }
+
+    let mut num_print_args = 0;
+    let mut num_utf8_bufs = 0;
+    let mut num_formatted = 0;
+    for block in &body.blocks {
+        for stmt in &block.stmts {
+            if let StatementKind::Print(_, pieces, args) = &stmt.kind {
+                let chars = args
+                    .iter()
+                    .filter(|arg| arg.ty(body, codegen.ctx).kind == TyKind::Char)
+                    .count();
+                // strs and chars are escaped for each `{:?}`, and floats are formatted
+                // by the runtime unless precision is given
+                let formatted = pieces
+                    .iter()
+                    .filter(|piece| {
+                        let FormatPiece::Placeholder(spec) = piece else {
+                            return false;
+                        };
+                        match args[spec.arg].ty(body, codegen.ctx).kind {
+                            TyKind::Char | TyKind::Ref(..) => spec.debug,
+                            TyKind::F32 | TyKind::F64 => spec.precision.is_none(),
+                            _ => false,
+                        }
+                    })
+                    .count();
+                num_print_args = num_print_args.max(args.len());
+                num_utf8_bufs = num_utf8_bufs.max(chars);
+                num_formatted = num_formatted.max(formatted);
+            }
+        }
+    }
+    for _ in 0..num_print_args {
+        frame.print_slots.push(alloc(Layout { size: 8, align: 8 }));
+    }
+    for _ in 0..num_utf8_bufs {
+        let layout = Layout {
+            size: UTF8_BUF_SIZE,
+            align: 1,
+        };
+        frame.utf8_bufs.push(alloc(layout));
+    }
+    for _ in 0..num_formatted {
+        frame
+            .formatted_slots
+            .push(alloc(Layout { size: 8, align: 8 }));
+    }
+
+    frame.size = size.next_multiple_of(16);
+    frame
+}
+
+/// Stack frame of a function body. Slots are addressed by their offsets from `%rbp`
+pub struct Frame {
+    /// Number of the body, which labels of its blocks contain
+    id: usize,
+    locals: Vec<FrameLocal>,
+    /// Slots to which the arguments of a `print!` are evaluated
+    print_slots: Vec<i64>,
+    /// Buffers to which `char` arguments of `print!` are encoded in UTF-8
+    utf8_bufs: Vec<i64>,
+    /// Slots of the strings formatted by the runtime for a `print!` (e.g. strs escaped for
+    /// `{:?}`), which are freed after printing
+    formatted_slots: Vec<i64>,
+    /// Size of the slots, which is a multiple of 16 to keep the stack aligned
+    pub size: usize,
+}
+
+struct FrameLocal {
+    offset: i64,
+    /// The slot holds a pointer to the value instead of the value
+    indirect: bool,
+    ty: Rc<Ty>,
+}
+
+impl Frame {
+    pub fn block_label(&self, bb: mir::BasicBlock) -> String {
+        format!(".L.{}.bb{bb}", self.id)
+    }
+
+    /// Offset of the slot of the local, and whether the slot holds a pointer to the value
+    pub fn get_local_slot(&self, local: mir::Local) -> (i64, bool) {
+        let frame_local = &self.locals[local];
+        (frame_local.offset, frame_local.indirect)
+    }
+
+    pub fn get_local_ty(&self, local: mir::Local) -> Rc<Ty> {
+        Rc::clone(&self.locals[local].ty)
+    }
+
+    pub fn get_place_ty(&self, place: &mir::Place, codegen: &Codegen) -> Rc<Ty> {
+        let mut ty = self.get_local_ty(place.local);
+        for proj in &place.projs {
+            ty = mir::project_ty(&ty, proj, codegen.ctx);
+        }
+        ty
+    }
+
+    pub fn get_operand_ty(&self, op: &Operand, codegen: &Codegen) -> Rc<Ty> {
+        match op {
+            Operand::Copy(place, _) | Operand::Move(place, _) => self.get_place_ty(place, codegen),
+            Operand::Const(cons) => Rc::clone(&cons.ty),
+        }
+    }
+
+    pub fn get_print_slot(&self, i: usize) -> i64 {
+        self.print_slots[i]
+    }
+
+    pub fn get_utf8_buf(&self, i: usize) -> i64 {
+        self.utf8_bufs[i]
+    }
+
+    pub fn get_formatted_slot(&self, i: usize) -> i64 {
+        self.formatted_slots[i]
+    }
+}
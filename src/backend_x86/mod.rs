@@ -0,0 +1,227 @@
+//! x86-64 backend, which emits GNU assembly for `--emit=asm` so that programs can be built
+//! with `gcc` alone instead of `llc`
+//!
+//! Code is generated for a stack machine in the style of chibicc: each value is computed
+//! into `%rax`, and pushed to the stack while another value is computed. Every local lives
+//! in a slot of the stack frame. Floats are kept in `%rax` as their bits and moved to `%xmm`
+//! registers only for arithmetic and calls. Calls follow the System V ABI, except that
+//! structs and arrays are passed by pointer and returned through `sret` pointers as in the
+//! LLVM backend.
+
+mod codegen_body;
+mod codegen_drop;
+mod codegen_print;
+mod codegen_rvalue;
+mod frame;
+mod runtime;
+
+use self::frame::Frame;
+use crate::middle::ty::{Ty, TyKind};
+use crate::middle::Ctxt;
+use crate::mir::Program;
+use crate::resolve::CanonicalPath;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+pub fn compile(ctx: &Ctxt, program: &Program) {
+    let mut codegen = Codegen::new(ctx, program);
+    codegen.go(program);
+}
+
+/// How values of a type are held in `%rax`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    /// `()` and `!`, which have no values
+    Void,
+    /// Integers, `bool`, `char` and pointers, sign- or zero-extended to 64 bits
+    Int { size: usize, signed: bool },
+    /// Bits of `f32` and `f64`
+    Float { size: usize },
+    /// Structs and arrays, whose addresses are held instead
+    Memory { size: usize },
+}
+
+#[derive(Clone, Copy)]
+struct Layout {
+    size: usize,
+    align: usize,
+}
+
+struct AdtLayout {
+    layout: Layout,
+    /// Offsets of the fields
+    offsets: HashMap<Rc<String>, usize>,
+}
+
+pub struct Codegen<'a, 'ctx> {
+    ctx: &'a Ctxt<'ctx>,
+    current_frame: Option<Frame>,
+    /// Number of 8-byte values pushed since the frame was set up, so that calls are made
+    /// with the stack aligned to 16 bytes
+    depth: usize,
+    adt_layouts: HashMap<Rc<CanonicalPath>, Rc<AdtLayout>>,
+    /// Functions defined in the crate, which are called without the PLT
+    defined_funcs: HashSet<String>,
+    /// String literals, which are named `.L.str.{index}`
+    strs: Vec<String>,
+    next_label_id: usize,
+    /// Set if runtime functions need to be emitted
+    uses_runtime: bool,
+    /// Set if strings are escaped for `{:?}` by the runtime
+    uses_escape_debug: bool,
+    /// Set if floats are formatted by the runtime
+    uses_format_float: bool,
+    /// Types whose drop glue is called. Generated after all functions
+    drop_glue_tys: Vec<Rc<Ty>>,
+}
+
+impl<'a, 'ctx> Codegen<'a, 'ctx> {
+    fn new(ctx: &'a Ctxt<'ctx>, program: &Program) -> Self {
+        Codegen {
+            ctx,
+            current_frame: None,
+            depth: 0,
+            adt_layouts: HashMap::new(),
+            defined_funcs: program
+                .bodies
+                .iter()
+                .map(|body| body.name.demangle())
+                .collect(),
+            strs: vec![],
+            next_label_id: 1,
+            uses_runtime: false,
+            uses_escape_debug: false,
+            uses_format_float: false,
+            drop_glue_tys: vec![],
+        }
+    }
+
+    fn go(&mut self, program: &Program) {
+        self.gen_program(program);
+        self.gen_drop_glues();
+        if self.uses_runtime {
+            self.gen_runtime();
+        }
+
+        // string literals
+        if !self.strs.is_empty() {
+            println!("\t.section .rodata");
+        }
+        for (i, s) in self.strs.iter().enumerate() {
+            let bytes: Vec<String> = s.bytes().chain([0]).map(|b| b.to_string()).collect();
+            println!(".L.str.{i}:");
+            println!("\t.byte {}", bytes.join(","));
+        }
+        // the stack is not executable
+        println!("\t.section .note.GNU-stack,\"\",@progbits");
+    }
+
+    fn get_fresh_label(&mut self, kind: &str) -> String {
+        let i = self.next_label_id;
+        self.next_label_id += 1;
+        format!(".L.{kind}.{i}")
+    }
+
+    /// Label of the string literal, which is NUL-terminated
+    fn add_str(&mut self, s: &str) -> String {
+        self.strs.push(s.to_string());
+        format!(".L.str.{}", self.strs.len() - 1)
+    }
+
+    /// Operand of `call`. Symbols are quoted as mangled names may start with `$`
+    fn func_symbol(&self, name: &str) -> String {
+        if self.defined_funcs.contains(name) {
+            format!("\"{name}\"")
+        } else {
+            format!("\"{name}\"@PLT")
+        }
+    }
+
+    fn peek_frame(&self) -> &Frame {
+        self.current_frame.as_ref().unwrap()
+    }
+
+    fn push(&mut self) {
+        println!("\tpush %rax");
+        self.depth += 1;
+    }
+
+    fn pop(&mut self, reg: &str) {
+        println!("\tpop {reg}");
+        self.depth -= 1;
+    }
+
+    fn class(&mut self, ty: &Ty) -> Class {
+        match &ty.kind {
+            TyKind::Unit | TyKind::Never => Class::Void,
+            TyKind::Int(int_ty) => Class::Int {
+                size: int_ty.bit_width() / 8,
+                signed: int_ty.is_signed(),
+            },
+            TyKind::Bool => Class::Int {
+                size: 1,
+                signed: false,
+            },
+            TyKind::Char => Class::Int {
+                size: 4,
+                signed: false,
+            },
+            TyKind::Box(_) | TyKind::Ref(..) | TyKind::RawPtr(..) => Class::Int {
+                size: 8,
+                signed: false,
+            },
+            TyKind::F32 => Class::Float { size: 4 },
+            TyKind::F64 => Class::Float { size: 8 },
+            TyKind::Array(..) | TyKind::Adt(_) => Class::Memory {
+                size: self.layout(ty).size,
+            },
+            _ => panic!("ICE: `{}` has no values", ty),
+        }
+    }
+
+    fn layout(&mut self, ty: &Ty) -> Layout {
+        let (size, align) = match &ty.kind {
+            TyKind::Int(int_ty) => (int_ty.bit_width() / 8, int_ty.bit_width() / 8),
+            TyKind::F32 | TyKind::Char => (4, 4),
+            TyKind::F64 => (8, 8),
+            TyKind::Bool => (1, 1),
+            TyKind::Unit | TyKind::Never => (0, 1),
+            TyKind::Box(_) | TyKind::Ref(..) | TyKind::RawPtr(..) => (8, 8),
+            TyKind::Array(elem, n) => {
+                let elem = self.layout(elem);
+                (elem.size * n, elem.align)
+            }
+            TyKind::Adt(name) => return self.adt_layout(name).layout,
+            _ => panic!("ICE: `{}` has no layout", ty),
+        };
+        Layout { size, align }
+    }
+
+    /// Fields are laid out in order with padding as in C
+    fn adt_layout(&mut self, name: &Rc<CanonicalPath>) -> Rc<AdtLayout> {
+        if let Some(adt_layout) = self.adt_layouts.get(name) {
+            return Rc::clone(adt_layout);
+        }
+        let adt = self.ctx.lookup_adt_def(name).unwrap();
+        let mut offsets = HashMap::new();
+        let mut size: usize = 0;
+        let mut align = 1;
+        for (fd, fd_ty) in &adt.fields {
+            let fd_layout = self.layout(fd_ty);
+            size = size.next_multiple_of(fd_layout.align);
+            offsets.insert(Rc::clone(fd), size);
+            size += fd_layout.size;
+            align = align.max(fd_layout.align);
+        }
+        let adt_layout = Rc::new(AdtLayout {
+            layout: Layout {
+                size: size.next_multiple_of(align),
+                align,
+            },
+            offsets,
+        });
+        self.adt_layouts
+            .insert(Rc::clone(name), Rc::clone(&adt_layout));
+        adt_layout
+    }
+}
@@ -0,0 +1,355 @@
+use super::Codegen;
+
+/// Panic entry point: `void __mini_rustc_panic(char *msg, char *location)`
+/// Prints the message to stderr and exits with code 101 like rustc
+pub const PANIC_FN: &str = "__mini_rustc_panic";
+
+/// `void __mini_rustc_encode_utf8(uint32_t c, char *buf)`
+/// Writes `char` c to buf as a NUL-terminated UTF-8 string so that printf can print it with `%s`
+pub const ENCODE_UTF8_FN: &str = "__mini_rustc_encode_utf8";
+
+/// Size of the buffer passed to `ENCODE_UTF8_FN` (up to 4 bytes and NUL)
+pub const UTF8_BUF_SIZE: usize = 5;
+
+/// `char *__mini_rustc_escape_debug(char *s, int quote)`
+/// Returns s enclosed in `quote` and escaped like `{:?}` in a buffer allocated by `malloc`
+pub const ESCAPE_DEBUG_FN: &str = "__mini_rustc_escape_debug";
+
+/// Bytes which are escaped by `ESCAPE_DEBUG_FN` with their escapes other than `\u{..}`
+/// of control characters. The quote and backslash are escaped by prefixing a backslash
+const DEBUG_ESCAPES: [(u8, &str); 3] = [(b'\t', "\\t"), (b'\r', "\\r"), (b'\n', "\\n")];
+
+/// `char *__mini_rustc_format_float(double x, int is_f32, int debug)`
+/// Returns x formatted like `{}` (or `{:?}` if debug is nonzero) in a buffer allocated by
+/// `malloc`, as the runtime of the LLVM backend does
+pub const FORMAT_FLOAT_FN: &str = "__mini_rustc_format_float";
+
+/// Size of the buffer of `FORMAT_FLOAT_FN`. The longest output is that of the smallest
+/// subnormal, which has 324 digits after the point
+const FLOAT_BUF_SIZE: usize = 400;
+
+/// Number of significant digits which are enough to read back any `f64`
+const MAX_FLOAT_DIGITS: usize = 17;
+
+/// Range of decimal exponents which `{:?}` prints floats without the exponent in
+const DEBUG_DECIMAL_EXPS: (i32, i32) = (-4, 15);
+
+// slots of `ESCAPE_DEBUG_FN`
+const ESCAPE_SRC_SLOT: i64 = -8;
+const ESCAPE_QUOTE_SLOT: i64 = -16;
+const ESCAPE_BUF_SLOT: i64 = -24;
+const ESCAPE_LEN_SLOT: i64 = -32;
+
+// slots of `FORMAT_FLOAT_FN`
+const FLOAT_X_SLOT: i64 = -8;
+const FLOAT_IS_F32_SLOT: i64 = -16;
+const FLOAT_DEBUG_SLOT: i64 = -24;
+const FLOAT_BUF_SLOT: i64 = -32;
+const FLOAT_DIGITS_SLOT: i64 = -40;
+const FLOAT_EXP_SLOT: i64 = -48;
+const FLOAT_E_SLOT: i64 = -56;
+
+pub const PANIC_EXIT_CODE: i32 = 101;
+
+impl<'a, 'ctx> Codegen<'a, 'ctx> {
+    /// Generate runtime functions, which are local to the object file
+    pub fn gen_runtime(&mut self) {
+        let panic_fmt = self.add_str("thread 'main' panicked at %s:\n%s\n");
+
+        println!("# Runtime");
+        println!("\t.text");
+        println!("\"{PANIC_FN}\":");
+        println!("\tpush %rbp");
+        println!("\tmov %rsp, %rbp");
+        println!("\tmov %rdi, %rcx");
+        println!("\tmov %rsi, %rdx");
+        println!("\tlea {panic_fmt}(%rip), %rsi");
+        println!("\tmov $2, %edi");
+        println!("\tmov $0, %eax");
+        println!("\tcall {}", self.func_symbol("dprintf"));
+        println!("\tmov ${PANIC_EXIT_CODE}, %edi");
+        println!("\tcall {}", self.func_symbol("exit"));
+        println!();
+
+        self.gen_encode_utf8();
+        if self.uses_escape_debug {
+            self.gen_escape_debug();
+        }
+        if self.uses_format_float {
+            self.gen_format_float();
+        }
+    }
+
+    fn gen_encode_utf8(&mut self) {
+        // (marker bits, payload mask) of the leading byte of 1-4 byte sequences
+        const LEADING_BYTES: [(u32, u32); 4] =
+            [(0x00, 0x7F), (0xC0, 0x1F), (0xE0, 0x0F), (0xF0, 0x07)];
+
+        let len_labels: Vec<_> = (0..LEADING_BYTES.len())
+            .map(|_| self.get_fresh_label("len"))
+            .collect();
+        println!("\"{ENCODE_UTF8_FN}\":");
+        println!("\tcmp $128, %edi");
+        println!("\tjb {}", len_labels[0]);
+        println!("\tcmp $2048, %edi");
+        println!("\tjb {}", len_labels[1]);
+        println!("\tcmp $65536, %edi");
+        println!("\tjb {}", len_labels[2]);
+        println!("\tjmp {}", len_labels[3]);
+        for (i, (marker, mask)) in LEADING_BYTES.iter().enumerate() {
+            let len = i + 1;
+            println!("{}:", len_labels[i]);
+            for j in 0..len {
+                // continuation bytes are `10xxxxxx`
+                let (marker, mask) = if j == 0 {
+                    (*marker, *mask)
+                } else {
+                    (0x80, 0x3F)
+                };
+                let shift = 6 * (len - 1 - j);
+                println!("\tmov %edi, %eax");
+                println!("\tshr ${shift}, %eax");
+                println!("\tand ${mask}, %eax");
+                println!("\tor ${marker}, %eax");
+                println!("\tmov %al, {j}(%rsi)");
+            }
+            println!("\tmovb $0, {len}(%rsi)");
+            println!("\tret");
+        }
+        println!();
+    }
+
+    fn gen_escape_debug(&mut self) {
+        let plain_fmt = self.add_str("%c");
+        let prefixed_fmt = self.add_str("\\%c");
+        let control_fmt = self.add_str("\\u{%x}");
+        let escape_fmts: Vec<_> = DEBUG_ESCAPES
+            .iter()
+            .map(|(b, escape)| (*b, self.add_str(escape)))
+            .collect();
+        let loop_label = self.get_fresh_label("loop");
+        let control_label = self.get_fresh_label("control");
+        let escapes_label = self.get_fresh_label("escapes");
+        let prefixed_label = self.get_fresh_label("prefixed");
+        let print_label = self.get_fresh_label("print");
+        let end_label = self.get_fresh_label("end");
+        let strlen = self.func_symbol("strlen");
+        let malloc = self.func_symbol("malloc");
+        let sprintf = self.func_symbol("sprintf");
+
+        println!("\"{ESCAPE_DEBUG_FN}\":");
+        println!("\tpush %rbp");
+        println!("\tmov %rsp, %rbp");
+        println!("\tsub $32, %rsp");
+        println!("\tmov %rdi, {ESCAPE_SRC_SLOT}(%rbp)");
+        println!("\tmov %rsi, {ESCAPE_QUOTE_SLOT}(%rbp)");
+        // each byte is escaped to at most 6 bytes like `\u{7f}`, and the quotes and NUL are added
+        println!("\tcall {strlen}");
+        println!("\timul $6, %rax, %rdi");
+        println!("\tadd $3, %rdi");
+        println!("\tcall {malloc}");
+        println!("\tmov %rax, {ESCAPE_BUF_SLOT}(%rbp)");
+        println!("\tmov {ESCAPE_QUOTE_SLOT}(%rbp), %rcx");
+        println!("\tmov %cl, (%rax)");
+        println!("\tmovq $1, {ESCAPE_LEN_SLOT}(%rbp)");
+        println!("{loop_label}:");
+        println!("\tmov {ESCAPE_SRC_SLOT}(%rbp), %rax");
+        println!("\tmovzbl (%rax), %edx");
+        println!("\tcmp $0, %edx");
+        println!("\tje {end_label}");
+        // the format for the byte is chosen to `%rsi`, and the byte is passed in `%edx`
+        println!("\tlea {plain_fmt}(%rip), %rsi");
+        println!("\tcmp $127, %edx");
+        println!("\tje {control_label}");
+        println!("\tcmp $32, %edx");
+        println!("\tjae {escapes_label}");
+        println!("{control_label}:");
+        println!("\tlea {control_fmt}(%rip), %rsi");
+        println!("{escapes_label}:");
+        for (b, fmt) in &escape_fmts {
+            let next_label = self.get_fresh_label("next");
+            println!("\tcmp ${b}, %edx");
+            println!("\tjne {next_label}");
+            println!("\tlea {fmt}(%rip), %rsi");
+            println!("{next_label}:");
+        }
+        println!("\tcmp ${}, %edx", b'\\');
+        println!("\tje {prefixed_label}");
+        println!("\tcmp {ESCAPE_QUOTE_SLOT}(%rbp), %edx");
+        println!("\tjne {print_label}");
+        println!("{prefixed_label}:");
+        println!("\tlea {prefixed_fmt}(%rip), %rsi");
+        println!("{print_label}:");
+        println!("\tmov {ESCAPE_BUF_SLOT}(%rbp), %rdi");
+        println!("\tadd {ESCAPE_LEN_SLOT}(%rbp), %rdi");
+        println!("\tmov $0, %eax");
+        println!("\tcall {sprintf}");
+        println!("\tcltq");
+        println!("\tadd %rax, {ESCAPE_LEN_SLOT}(%rbp)");
+        println!("\tincq {ESCAPE_SRC_SLOT}(%rbp)");
+        println!("\tjmp {loop_label}");
+        println!("{end_label}:");
+        println!("\tmov {ESCAPE_BUF_SLOT}(%rbp), %rax");
+        println!("\tadd {ESCAPE_LEN_SLOT}(%rbp), %rax");
+        println!("\tmov {ESCAPE_QUOTE_SLOT}(%rbp), %rcx");
+        println!("\tmov %cl, (%rax)");
+        println!("\tmovb $0, 1(%rax)");
+        println!("\tmov {ESCAPE_BUF_SLOT}(%rbp), %rax");
+        println!("\tmov %rbp, %rsp");
+        println!("\tpop %rbp");
+        println!("\tret");
+        println!();
+    }
+
+    fn gen_format_float(&mut self) {
+        let nan_str = self.add_str("NaN");
+        let inf_str = self.add_str("inf");
+        let neg_inf_str = self.add_str("-inf");
+        let exp_fmt = self.add_str("%.*e");
+        let fixed_fmt = self.add_str("%.*f");
+        let sci_exp_fmt = self.add_str("e%d");
+        let nan_label = self.get_fresh_label("nan");
+        let inf_label = self.get_fresh_label("inf");
+        let special_label = self.get_fresh_label("special");
+        let loop_label = self.get_fresh_label("loop");
+        let f64_label = self.get_fresh_label("f64");
+        let compared_label = self.get_fresh_label("compared");
+        let next_label = self.get_fresh_label("next");
+        let fixed_label = self.get_fresh_label("fixed");
+        let sci_label = self.get_fresh_label("sci");
+        let decimal_label = self.get_fresh_label("decimal");
+        let prec_label = self.get_fresh_label("prec");
+        let end_label = self.get_fresh_label("end");
+        let malloc = self.func_symbol("malloc");
+        let sprintf = self.func_symbol("sprintf");
+        let strtod = self.func_symbol("strtod");
+        let strchr = self.func_symbol("strchr");
+        let atoi = self.func_symbol("atoi");
+        let strlen = self.func_symbol("strlen");
+
+        println!("\"{FORMAT_FLOAT_FN}\":");
+        println!("\tpush %rbp");
+        println!("\tmov %rsp, %rbp");
+        println!("\tsub $64, %rsp");
+        println!("\tmovq %xmm0, {FLOAT_X_SLOT}(%rbp)");
+        println!("\tmov %rdi, {FLOAT_IS_F32_SLOT}(%rbp)");
+        println!("\tmov %rsi, {FLOAT_DEBUG_SLOT}(%rbp)");
+        println!("\tmov ${FLOAT_BUF_SIZE}, %edi");
+        println!("\tcall {malloc}");
+        println!("\tmov %rax, {FLOAT_BUF_SLOT}(%rbp)");
+        println!("\tmovq $1, {FLOAT_DIGITS_SLOT}(%rbp)");
+        // `x - x` is NaN for infinities
+        println!("\tmovq {FLOAT_X_SLOT}(%rbp), %xmm0");
+        println!("\tucomisd %xmm0, %xmm0");
+        println!("\tjp {nan_label}");
+        println!("\tmovsd %xmm0, %xmm1");
+        println!("\tsubsd %xmm0, %xmm1");
+        println!("\tucomisd %xmm1, %xmm1");
+        println!("\tjp {inf_label}");
+        println!("\tjmp {loop_label}");
+        println!("{nan_label}:");
+        println!("\tlea {nan_str}(%rip), %rsi");
+        println!("\tjmp {special_label}");
+        println!("{inf_label}:");
+        println!("\tlea {inf_str}(%rip), %rsi");
+        println!("\tpxor %xmm1, %xmm1");
+        println!("\tucomisd %xmm1, %xmm0");
+        println!("\tja {special_label}");
+        println!("\tlea {neg_inf_str}(%rip), %rsi");
+        println!("{special_label}:");
+        println!("\tmov {FLOAT_BUF_SLOT}(%rbp), %rdi");
+        println!("\tmov $0, %eax");
+        println!("\tcall {sprintf}");
+        println!("\tjmp {end_label}");
+        // the number of significant digits is increased until x is read back
+        println!("{loop_label}:");
+        println!("\tmov {FLOAT_BUF_SLOT}(%rbp), %rdi");
+        println!("\tlea {exp_fmt}(%rip), %rsi");
+        println!("\tmov {FLOAT_DIGITS_SLOT}(%rbp), %rdx");
+        println!("\tdec %rdx");
+        println!("\tmovq {FLOAT_X_SLOT}(%rbp), %xmm0");
+        println!("\tmov $1, %eax");
+        println!("\tcall {sprintf}");
+        println!("\tmov {FLOAT_BUF_SLOT}(%rbp), %rdi");
+        println!("\tmov $0, %esi");
+        println!("\tcall {strtod}");
+        println!("\tmovq {FLOAT_X_SLOT}(%rbp), %xmm1");
+        println!("\tcmpl $0, {FLOAT_IS_F32_SLOT}(%rbp)");
+        println!("\tje {f64_label}");
+        println!("\tcvtsd2ss %xmm0, %xmm0");
+        println!("\tcvtsd2ss %xmm1, %xmm1");
+        println!("\tucomiss %xmm1, %xmm0");
+        println!("\tjmp {compared_label}");
+        println!("{f64_label}:");
+        println!("\tucomisd %xmm1, %xmm0");
+        println!("{compared_label}:");
+        println!("\tjp {next_label}");
+        println!("\tje {fixed_label}");
+        println!("{next_label}:");
+        println!("\tcmpq ${MAX_FLOAT_DIGITS}, {FLOAT_DIGITS_SLOT}(%rbp)");
+        println!("\tjge {fixed_label}");
+        println!("\tincq {FLOAT_DIGITS_SLOT}(%rbp)");
+        println!("\tjmp {loop_label}");
+        println!("{fixed_label}:");
+        println!("\tmov {FLOAT_BUF_SLOT}(%rbp), %rdi");
+        println!("\tmov ${}, %esi", b'e');
+        println!("\tcall {strchr}");
+        println!("\tmov %rax, {FLOAT_E_SLOT}(%rbp)");
+        println!("\tlea 1(%rax), %rdi");
+        println!("\tcall {atoi}");
+        println!("\tcltq");
+        println!("\tmov %rax, {FLOAT_EXP_SLOT}(%rbp)");
+        println!("\tcmpl $0, {FLOAT_DEBUG_SLOT}(%rbp)");
+        println!("\tje {decimal_label}");
+        println!("\tmovq {FLOAT_X_SLOT}(%rbp), %xmm0");
+        println!("\tpxor %xmm1, %xmm1");
+        println!("\tucomisd %xmm1, %xmm0");
+        println!("\tje {decimal_label}");
+        println!("\tcmpq ${}, {FLOAT_EXP_SLOT}(%rbp)", DEBUG_DECIMAL_EXPS.0);
+        println!("\tjl {sci_label}");
+        println!("\tcmpq ${}, {FLOAT_EXP_SLOT}(%rbp)", DEBUG_DECIMAL_EXPS.1);
+        println!("\tjle {decimal_label}");
+        // the exponent `e[+-]dd` is rewritten as Rust prints it (e.g. `e-7` and `e20`)
+        println!("{sci_label}:");
+        println!("\tmov {FLOAT_E_SLOT}(%rbp), %rdi");
+        println!("\tlea {sci_exp_fmt}(%rip), %rsi");
+        println!("\tmov {FLOAT_EXP_SLOT}(%rbp), %rdx");
+        println!("\tmov $0, %eax");
+        println!("\tcall {sprintf}");
+        println!("\tjmp {end_label}");
+        // the digits are printed without the exponent
+        println!("{decimal_label}:");
+        println!("\tmov {FLOAT_DIGITS_SLOT}(%rbp), %rdx");
+        println!("\tdec %rdx");
+        println!("\tsub {FLOAT_EXP_SLOT}(%rbp), %rdx");
+        println!("\tcmp $0, %rdx");
+        println!("\tjge {prec_label}");
+        println!("\tmov $0, %rdx");
+        println!("{prec_label}:");
+        println!("\tmov {FLOAT_BUF_SLOT}(%rbp), %rdi");
+        println!("\tlea {fixed_fmt}(%rip), %rsi");
+        println!("\tmovq {FLOAT_X_SLOT}(%rbp), %xmm0");
+        println!("\tmov $1, %eax");
+        println!("\tcall {sprintf}");
+        // Debug prints integral values with `.0`
+        println!("\tcmpl $0, {FLOAT_DEBUG_SLOT}(%rbp)");
+        println!("\tje {end_label}");
+        println!("\tmov {FLOAT_BUF_SLOT}(%rbp), %rdi");
+        println!("\tmov ${}, %esi", b'.');
+        println!("\tcall {strchr}");
+        println!("\tcmp $0, %rax");
+        println!("\tjne {end_label}");
+        println!("\tmov {FLOAT_BUF_SLOT}(%rbp), %rdi");
+        println!("\tcall {strlen}");
+        println!("\tadd {FLOAT_BUF_SLOT}(%rbp), %rax");
+        println!("\tmovb ${}, (%rax)", b'.');
+        println!("\tmovb ${}, 1(%rax)", b'0');
+        println!("\tmovb $0, 2(%rax)");
+        println!("{end_label}:");
+        println!("\tmov {FLOAT_BUF_SLOT}(%rbp), %rax");
+        println!("\tmov %rbp, %rsp");
+        println!("\tpop %rbp");
+        println!("\tret");
+        println!();
+    }
+}
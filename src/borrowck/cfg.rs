@@ -0,0 +1,493 @@
+//! Control-flow graph of a function body, on which borrows are checked
+//!
+//! Every value is stored in a local, which is either a variable or a temporary.
+//! Temporaries live until the end of the enclosing block, as they do in codegen.
+
+use super::has_drop_impl;
+use crate::hir::{
+    self, Block, DefKind, Expr, ExprKind, Mutability, Region, Res, StmtKind as HirStmtKind, UnOp,
+};
+use crate::middle::ty::{Ty, TyKind};
+use crate::middle::Ctxt;
+use crate::span::{Ident, Span};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub type LocalId = usize;
+pub type BlockId = usize;
+
+pub struct Body {
+    /// The first local is the return place
+    pub locals: Vec<LocalDecl>,
+    /// The first block is the entry
+    pub blocks: Vec<BasicBlock>,
+}
+
+pub struct LocalDecl {
+    /// `None` for temporaries
+    pub name: Option<Rc<String>>,
+    pub ty: Rc<Ty>,
+}
+
+pub struct BasicBlock {
+    pub stmts: Vec<Stmt>,
+    pub terminator: Terminator,
+}
+
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+pub enum StmtKind {
+    /// `place = f(operands)`, where the value written to the place is computed from the operands
+    Assign(Place, Vec<Operand>),
+    /// `local = &place` or `local = &mut place`
+    Borrow(LocalId, Mutability, Place),
+    /// The operands are read without being stored (e.g. indices and arguments not
+    /// referenced by the return value)
+    Use(Vec<Operand>),
+    /// The destructor of the local runs
+    Drop(LocalId),
+    /// The local goes out of scope
+    StorageDead(LocalId),
+}
+
+pub enum Terminator {
+    Goto(BlockId),
+    /// cond, then, else
+    If(Operand, BlockId, BlockId),
+    /// The value of the return place is returned
+    Return(Span),
+    Unreachable,
+}
+
+impl Terminator {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Goto(bb) => vec![*bb],
+            Terminator::If(_, then_bb, else_bb) => vec![*then_bb, *else_bb],
+            Terminator::Return(_) | Terminator::Unreachable => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Place {
+    pub local: LocalId,
+    pub projs: Vec<Proj>,
+}
+
+impl Place {
+    fn local(local: LocalId) -> Self {
+        Place {
+            local,
+            projs: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Proj {
+    /// Dereference of a reference or a raw pointer
+    Deref,
+    /// Dereference of a box, which owns its contents
+    DerefBox,
+    Field(Rc<String>),
+    Index,
+}
+
+pub enum Operand {
+    Copy(Place, Span),
+    Move(Place, Span),
+    Const,
+}
+
+impl Operand {
+    pub fn place(&self) -> Option<&Place> {
+        match self {
+            Operand::Copy(place, _) | Operand::Move(place, _) => Some(place),
+            Operand::Const => None,
+        }
+    }
+}
+
+pub fn build(ctx: &mut Ctxt, func: &hir::Func) -> Body {
+    let mut builder = Builder {
+        ctx,
+        body: Body {
+            locals: vec![],
+            blocks: vec![],
+        },
+        current: 0,
+        scopes: vec![vec![]],
+        bindings: HashMap::new(),
+    };
+    builder.current = builder.new_block();
+
+    let (param_tys, ret_ty) = builder
+        .ctx
+        .lookup_name_type(&func.def_id)
+        .unwrap()
+        .get_func_type()
+        .unwrap();
+    builder.new_local(None, ret_ty);
+    for (param, param_ty) in func.params.iter().zip(param_tys.iter()) {
+        builder.declare(&param.ident, &param.local_id, Rc::clone(param_ty));
+    }
+
+    let body = func.body.as_ref().unwrap();
+    let op = builder.lower_stmts(body);
+    let span = match (&body.expr, body.stmts.last()) {
+        (Some(expr), _) => expr.span.clone(),
+        (None, Some(stmt)) => stmt.span.clone(),
+        (None, None) => body.span.clone(),
+    };
+    builder.ret(op, span);
+    builder.body
+}
+
+struct Builder<'a, 'ctx> {
+    ctx: &'a mut Ctxt<'ctx>,
+    body: Body,
+    current: BlockId,
+    /// Locals declared in each of the enclosing blocks
+    scopes: Vec<Vec<LocalId>>,
+    bindings: HashMap<hir::LocalId, LocalId>,
+}
+
+impl Builder<'_, '_> {
+    fn new_block(&mut self) -> BlockId {
+        self.body.blocks.push(BasicBlock {
+            stmts: vec![],
+            terminator: Terminator::Unreachable,
+        });
+        self.body.blocks.len() - 1
+    }
+
+    fn terminate(&mut self, terminator: Terminator) {
+        self.body.blocks[self.current].terminator = terminator;
+    }
+
+    fn push(&mut self, kind: StmtKind, span: &Span) {
+        self.body.blocks[self.current].stmts.push(Stmt {
+            kind,
+            span: span.clone(),
+        });
+    }
+
+    /// All locals but the return place die before returning
+    fn ret(&mut self, op: Operand, span: Span) {
+        self.push(StmtKind::Assign(Place::local(0), vec![op]), &span);
+        let locals: Vec<LocalId> = self.scopes.iter().flatten().copied().collect();
+        for local in locals.into_iter().rev().filter(|local| *local != 0) {
+            self.kill_local(local, &span);
+        }
+        self.terminate(Terminator::Return(span));
+    }
+
+    fn new_local(&mut self, name: Option<Rc<String>>, ty: Rc<Ty>) -> LocalId {
+        self.body.locals.push(LocalDecl { name, ty });
+        let local = self.body.locals.len() - 1;
+        self.scopes.last_mut().unwrap().push(local);
+        local
+    }
+
+    fn declare(&mut self, ident: &Ident, local_id: &hir::LocalId, ty: Rc<Ty>) -> LocalId {
+        let local = self.new_local(Some(Rc::new(ident.source_name().to_string())), ty);
+        self.bindings.insert(Rc::clone(local_id), local);
+        local
+    }
+
+    fn new_temp(&mut self, expr_id: hir::HirId) -> LocalId {
+        let ty = self.ctx.get_type(expr_id);
+        self.new_local(None, ty)
+    }
+
+    /// Locals die in the reverse order of declaration at the end of their block
+    fn pop_scope(&mut self, span: &Span) {
+        let scope = self.scopes.pop().unwrap();
+        for local in scope.into_iter().rev() {
+            self.kill_local(local, span);
+        }
+    }
+
+    fn kill_local(&mut self, local: LocalId, span: &Span) {
+        if has_drop_impl(self.ctx, &self.body.locals[local].ty) {
+            self.push(StmtKind::Drop(local), span);
+        }
+        self.push(StmtKind::StorageDead(local), span);
+    }
+
+    fn lower_block(&mut self, block: &Block) -> Operand {
+        // the result is copied out of the block before its locals die
+        let result = self.new_temp(block.id);
+        self.scopes.push(vec![]);
+        let op = self.lower_stmts(block);
+        self.push(
+            StmtKind::Assign(Place::local(result), vec![op]),
+            &block.span,
+        );
+        self.pop_scope(&block.span.end());
+        Operand::Copy(Place::local(result), block.span.clone())
+    }
+
+    /// Returns the value of the block
+    fn lower_stmts(&mut self, block: &Block) -> Operand {
+        for stmt in &block.stmts {
+            match &stmt.kind {
+                HirStmtKind::Let(let_stmt) => {
+                    let ty = self.ctx.lookup_name_type(&let_stmt.local_id).unwrap();
+                    let local = self.declare(&let_stmt.ident, &let_stmt.local_id, ty);
+                    if let Some(init) = &let_stmt.init {
+                        let op = self.lower_expr(init);
+                        self.push(StmtKind::Assign(Place::local(local), vec![op]), &stmt.span);
+                    }
+                }
+                HirStmtKind::Expr(expr) | HirStmtKind::Semi(expr) => {
+                    self.lower_expr(expr);
+                }
+            }
+        }
+        match &block.expr {
+            Some(expr) => self.lower_expr(expr),
+            None => Operand::Const,
+        }
+    }
+
+    fn lower_expr(&mut self, expr: &Expr) -> Operand {
+        match &expr.kind {
+            ExprKind::NumLit(_)
+            | ExprKind::FloatLit(..)
+            | ExprKind::BoolLit(_)
+            | ExprKind::StrLit(_)
+            | ExprKind::CharLit(_)
+            | ExprKind::NullPtr(_)
+            | ExprKind::Unit => Operand::Const,
+            ExprKind::Path(path) => match path.res.local_id() {
+                Some(local_id) => self.operand(Place::local(self.bindings[local_id]), expr),
+                // functions and unit structs
+                None => Operand::Const,
+            },
+            ExprKind::Field(..) | ExprKind::Index(..) | ExprKind::Unary(UnOp::Deref, _) => {
+                let place = self.lower_place(expr);
+                self.operand(place, expr)
+            }
+            // raw pointers don't keep the borrow alive
+            ExprKind::Cast(inner, _) if self.ctx.get_type(expr.id).is_raw_ptr() => {
+                let op = self.lower_expr(inner);
+                self.push(StmtKind::Use(vec![op]), &expr.span);
+                Operand::Const
+            }
+            ExprKind::Unary(_, inner) | ExprKind::Cast(inner, _) | ExprKind::Box(inner) => {
+                let op = self.lower_expr(inner);
+                self.rvalue(expr, vec![op])
+            }
+            ExprKind::Binary(_, lhs, rhs) => {
+                let ops = vec![self.lower_expr(lhs), self.lower_expr(rhs)];
+                self.rvalue(expr, ops)
+            }
+            ExprKind::AddrOf(mutbl, inner) => self.lower_borrow(expr, *mutbl, inner).0,
+            ExprKind::Assign(lhs, rhs) => {
+                let op = self.lower_expr(rhs);
+                let place = self.lower_place(lhs);
+                self.push(StmtKind::Assign(place, vec![op]), &expr.span);
+                Operand::Const
+            }
+            ExprKind::Return(inner) => {
+                let op = self.lower_expr(inner);
+                self.ret(op, expr.span.clone());
+                self.current = self.new_block();
+                Operand::Const
+            }
+            ExprKind::Panic(fmt) => {
+                let ops = fmt.args.iter().map(|arg| self.lower_expr(arg)).collect();
+                self.rvalue(expr, ops);
+                self.terminate(Terminator::Unreachable);
+                self.current = self.new_block();
+                Operand::Const
+            }
+            ExprKind::Call(func, args) => {
+                let sig = match &func.kind {
+                    ExprKind::Path(path) => path
+                        .res
+                        .def_id()
+                        .and_then(|def_id| self.ctx.lookup_region_sig(def_id)),
+                    _ => None,
+                };
+                let Some(sig) = sig else {
+                    let ops = args.iter().map(|arg| self.lower_expr(arg)).collect();
+                    return self.rvalue(expr, ops);
+                };
+                // the result only references arguments sharing lifetimes with the return type
+                let flows_out = |region: &Region| region != "static" && sig.output.contains(region);
+                let mut flows = vec![];
+                let mut used = vec![];
+                for (i, arg) in args.iter().enumerate() {
+                    let Some(regions) = sig.inputs.get(i) else {
+                        flows.push(self.lower_expr(arg));
+                        continue;
+                    };
+                    if !regions.iter().any(flows_out) {
+                        used.push(self.lower_expr(arg));
+                    } else if let ExprKind::AddrOf(mutbl, inner) = &arg.kind
+                        && !flows_out(&regions[0])
+                        && !regions[1..].contains(&regions[0])
+                    {
+                        // only the data behind the reference is referenced by the result
+                        let (op, place) = self.lower_borrow(arg, *mutbl, inner);
+                        used.push(op);
+                        flows.push(Operand::Copy(place, arg.span.clone()));
+                    } else {
+                        flows.push(self.lower_expr(arg));
+                    }
+                }
+                if !used.is_empty() {
+                    self.push(StmtKind::Use(used), &expr.span);
+                }
+                self.rvalue(expr, flows)
+            }
+            ExprKind::MethodCall(recv, _, args) => {
+                let mut ops = vec![self.lower_expr(recv)];
+                ops.extend(args.iter().map(|arg| self.lower_expr(arg)));
+                self.rvalue(expr, ops)
+            }
+            ExprKind::Block(block) => self.lower_block(block),
+            ExprKind::If(cond, then, els) => {
+                let cond = self.lower_expr(cond);
+                let result = self.new_temp(expr.id);
+                let then_bb = self.new_block();
+                let else_bb = self.new_block();
+                let join_bb = self.new_block();
+                self.terminate(Terminator::If(cond, then_bb, else_bb));
+
+                self.current = then_bb;
+                let op = self.lower_expr(then);
+                self.push(StmtKind::Assign(Place::local(result), vec![op]), &then.span);
+                self.terminate(Terminator::Goto(join_bb));
+
+                self.current = else_bb;
+                if let Some(els) = els {
+                    let op = self.lower_expr(els);
+                    self.push(StmtKind::Assign(Place::local(result), vec![op]), &els.span);
+                }
+                self.terminate(Terminator::Goto(join_bb));
+
+                self.current = join_bb;
+                Operand::Copy(Place::local(result), expr.span.clone())
+            }
+            ExprKind::Struct(_, fds, base) => {
+                let mut ops: Vec<Operand> = fds.iter().map(|(_, fd)| self.lower_expr(fd)).collect();
+                if let Some(base) = base {
+                    let place = self.lower_place(base);
+                    ops.push(Operand::Copy(place, base.span.clone()));
+                }
+                self.rvalue(expr, ops)
+            }
+            ExprKind::Array(elems) => {
+                let ops = elems.iter().map(|elem| self.lower_expr(elem)).collect();
+                self.rvalue(expr, ops)
+            }
+            ExprKind::Print(_, fmt) => {
+                let ops = fmt.args.iter().map(|arg| self.lower_expr(arg)).collect();
+                self.rvalue(expr, ops)
+            }
+        }
+    }
+
+    /// Returns the reference and the borrowed place
+    fn lower_borrow(&mut self, expr: &Expr, mutbl: Mutability, inner: &Expr) -> (Operand, Place) {
+        let place = self.lower_place(inner);
+        let temp = self.new_temp(expr.id);
+        self.push(StmtKind::Borrow(temp, mutbl, place.clone()), &expr.span);
+        (Operand::Copy(Place::local(temp), expr.span.clone()), place)
+    }
+
+    /// Places which are not denoted by place expressions are temporaries
+    fn lower_place(&mut self, expr: &Expr) -> Place {
+        match &expr.kind {
+            ExprKind::Field(base, field) => {
+                let mut place = self.lower_place(base);
+                self.auto_deref(&mut place, &self.ctx.get_type(base.id));
+                place.projs.push(Proj::Field(Rc::clone(&field.symbol)));
+                place
+            }
+            ExprKind::Index(base, index) => {
+                let mut place = self.lower_place(base);
+                let op = self.lower_expr(index);
+                self.push(StmtKind::Use(vec![op]), &index.span);
+                self.auto_deref(&mut place, &self.ctx.get_type(base.id));
+                place.projs.push(Proj::Index);
+                place
+            }
+            ExprKind::Unary(UnOp::Deref, inner) => {
+                let mut place = self.lower_place(inner);
+                match &self.ctx.get_type(inner.id).kind {
+                    TyKind::Box(_) => place.projs.push(Proj::DerefBox),
+                    _ => place.projs.push(Proj::Deref),
+                }
+                place
+            }
+            // statics are accessed through raw pointers as in MIR, so borrows of them
+            // outlive the function
+            ExprKind::Path(path) if matches!(path.res, Res::Def(DefKind::Static(_), _)) => {
+                let ty = self.ctx.get_type(expr.id);
+                let ptr =
+                    self.new_local(None, Rc::new(Ty::new(TyKind::RawPtr(ty, Mutability::Mut))));
+                self.push(
+                    StmtKind::Assign(Place::local(ptr), vec![Operand::Const]),
+                    &expr.span,
+                );
+                Place {
+                    local: ptr,
+                    projs: vec![Proj::Deref],
+                }
+            }
+            // paths to locals are lowered to the locals
+            _ => match self.lower_expr(expr) {
+                Operand::Copy(place, _) | Operand::Move(place, _) => place,
+                Operand::Const => {
+                    let temp = self.new_temp(expr.id);
+                    self.push(
+                        StmtKind::Assign(Place::local(temp), vec![Operand::Const]),
+                        &expr.span,
+                    );
+                    Place::local(temp)
+                }
+            },
+        }
+    }
+
+    /// Fields and elements are accessed through boxes and references
+    fn auto_deref(&self, place: &mut Place, ty: &Rc<Ty>) {
+        let mut ty = Rc::clone(ty);
+        loop {
+            match &ty.kind {
+                TyKind::Box(inner) => {
+                    place.projs.push(Proj::DerefBox);
+                    ty = Rc::clone(inner);
+                }
+                TyKind::Ref(inner, _) => {
+                    place.projs.push(Proj::Deref);
+                    ty = Rc::clone(inner);
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn operand(&mut self, place: Place, expr: &Expr) -> Operand {
+        if self.ctx.is_moved(expr.id) {
+            Operand::Move(place, expr.span.clone())
+        } else {
+            Operand::Copy(place, expr.span.clone())
+        }
+    }
+
+    /// The value of `expr` is computed from the operands into a new temporary
+    fn rvalue(&mut self, expr: &Expr, ops: Vec<Operand>) -> Operand {
+        let temp = self.new_temp(expr.id);
+        self.push(StmtKind::Assign(Place::local(temp), ops), &expr.span);
+        Operand::Copy(Place::local(temp), expr.span.clone())
+    }
+}
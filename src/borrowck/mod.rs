@@ -0,0 +1,618 @@
+//! Borrow checking over the control-flow graph of each function
+//!
+//! A loan is created by each borrow expression and is held by the locals the
+//! resulting reference flows into. A loan is live at a point if a local holding
+//! it may be used later. Accesses to a borrowed place conflicting with a live loan
+//! are errors.
+//!
+//! Parameters hold a loan for each lifetime in their types, which stands for data
+//! outside of the function. Returned values may only hold loans for the lifetimes
+//! in the return type.
+
+mod cfg;
+
+use self::cfg::{Body, LocalId, Operand, Place, Proj, StmtKind, Terminator};
+use crate::hir::{self, Crate, Mutability, Region};
+use crate::middle::ty::{RegionSig, Ty, TyKind};
+use crate::middle::Ctxt;
+use crate::resolve::CanonicalPath;
+use crate::span::Span;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+
+pub fn borrowck(ctx: &mut Ctxt, krate: &Crate) -> Result<(), Vec<String>> {
+    let mut checker = BorrowChecker {
+        ctx,
+        errors: vec![],
+    };
+    hir::visitor::go(&mut checker, krate);
+    if checker.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(checker.errors)
+    }
+}
+
+struct BorrowChecker<'ctx, 'chk> {
+    ctx: &'chk mut Ctxt<'ctx>,
+    errors: Vec<String>,
+}
+
+impl<'chk> hir::visitor::Visitor<'chk> for BorrowChecker<'_, '_> {
+    fn visit_func(&mut self, func: &'chk hir::Func) {
+        if func.body.is_none() {
+            return;
+        }
+        let body = cfg::build(self.ctx, func);
+        let sig = self.ctx.lookup_region_sig(&func.def_id).unwrap();
+        let mut checker = BodyChecker::new(self.ctx, &body, &sig);
+        checker.check();
+        self.errors.append(&mut checker.errors);
+    }
+}
+
+type LoanId = usize;
+
+enum Loan {
+    /// `&place` or `&mut place` in the body
+    Borrow {
+        place: Place,
+        mutbl: Mutability,
+        span: Span,
+    },
+    /// Data outside of the function, which a lifetime in the signature stands for
+    Region(Region),
+}
+
+/// Loans which each local may hold
+type Holds = Vec<BTreeSet<LoanId>>;
+
+/// Locals which may be used later, with the span of one of the uses
+type Liveness = BTreeMap<LocalId, Span>;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AccessKind {
+    Read,
+    Move,
+    Write,
+    Borrow(Mutability),
+}
+
+struct BodyChecker<'a> {
+    body: &'a Body,
+    sig: &'a RegionSig,
+    loans: Vec<Loan>,
+    /// Loans created by the borrow statements, indexed by block and statement
+    loan_at: HashMap<(usize, usize), LoanId>,
+    /// Loans of the lifetimes of reference parameters, which values read through
+    /// the references do not hold (e.g. `s.r` for `s: &'b S<'a>` only holds `'a`)
+    outer_loans: HashMap<LocalId, LoanId>,
+    /// Whether values of each local may contain references
+    has_refs: Vec<bool>,
+    errors: Vec<String>,
+}
+
+impl<'a> BodyChecker<'a> {
+    fn new(ctx: &Ctxt, body: &'a Body, sig: &'a RegionSig) -> Self {
+        let mut loans = vec![];
+        for region in sig.inputs.iter().flatten() {
+            if region != "static"
+                && !loans
+                    .iter()
+                    .any(|loan| matches!(loan, Loan::Region(r) if r == region))
+            {
+                loans.push(Loan::Region(region.clone()));
+            }
+        }
+        let mut loan_at = HashMap::new();
+        for (bb, block) in body.blocks.iter().enumerate() {
+            for (i, stmt) in block.stmts.iter().enumerate() {
+                if let StmtKind::Borrow(_, mutbl, place) = &stmt.kind {
+                    loan_at.insert((bb, i), loans.len());
+                    loans.push(Loan::Borrow {
+                        place: place.clone(),
+                        mutbl: *mutbl,
+                        span: stmt.span.clone(),
+                    });
+                }
+            }
+        }
+        let mut outer_loans = HashMap::new();
+        for (i, regions) in sig.inputs.iter().enumerate() {
+            if let TyKind::Ref(..) = body.locals[i + 1].ty.kind
+                && let Some((outer, inner)) = regions.split_first()
+                && !inner.contains(outer)
+                && let Some(loan) = loans
+                    .iter()
+                    .position(|loan| matches!(loan, Loan::Region(r) if r == outer))
+            {
+                outer_loans.insert(i + 1, loan);
+            }
+        }
+        let has_refs = body
+            .locals
+            .iter()
+            .map(|local| has_refs(ctx, &local.ty))
+            .collect();
+        BodyChecker {
+            body,
+            sig,
+            loans,
+            loan_at,
+            outer_loans,
+            has_refs,
+            errors: vec![],
+        }
+    }
+
+    fn check(&mut self) {
+        let holds_in = self.compute_holds();
+        let live_out = self.compute_liveness();
+        let body = self.body;
+        for (bb, block) in body.blocks.iter().enumerate() {
+            // unreachable
+            let Some(mut holds) = holds_in[bb].clone() else {
+                continue;
+            };
+
+            // liveness after each statement
+            let mut live = live_out[bb].clone();
+            self.liveness_terminator(&block.terminator, &mut live);
+            let mut live_after = vec![live.clone(); block.stmts.len()];
+            for (i, stmt) in block.stmts.iter().enumerate().rev() {
+                live_after[i] = live.clone();
+                self.liveness_stmt(&stmt.kind, &stmt.span, &mut live);
+            }
+
+            for (i, stmt) in block.stmts.iter().enumerate() {
+                let live_loans = self.live_loans(&holds, &live_after[i]);
+                match &stmt.kind {
+                    StmtKind::Assign(place, ops) => {
+                        for op in ops {
+                            self.check_operand(op, &live_loans);
+                        }
+                        self.check_access(place, AccessKind::Write, &stmt.span, &live_loans);
+                    }
+                    StmtKind::Borrow(_, mutbl, place) => {
+                        let kind = AccessKind::Borrow(*mutbl);
+                        self.check_access(place, kind, &stmt.span, &live_loans);
+                    }
+                    StmtKind::Use(ops) => {
+                        for op in ops {
+                            self.check_operand(op, &live_loans);
+                        }
+                    }
+                    StmtKind::Drop(_) => (),
+                    StmtKind::StorageDead(local) => {
+                        // references to locals in the return place are reported when returning
+                        let mut live = live_after[i].clone();
+                        live.remove(&0);
+                        let live_loans = self.live_loans(&holds, &live);
+                        self.check_storage_dead(*local, &stmt.span, &live_loans)
+                    }
+                }
+                self.transfer_holds(bb, i, &mut holds);
+            }
+
+            match &block.terminator {
+                Terminator::If(op, _, _) => {
+                    let live_loans = self.live_loans(&holds, &live_out[bb]);
+                    self.check_operand(op, &live_loans);
+                }
+                Terminator::Return(span) => self.check_return(span, &holds),
+                Terminator::Goto(_) | Terminator::Unreachable => (),
+            }
+        }
+    }
+
+    /// Forward analysis of loans held by each local at the entry of each block
+    fn compute_holds(&self) -> Vec<Option<Holds>> {
+        let mut holds_in: Vec<Option<Holds>> = vec![None; self.body.blocks.len()];
+        let mut entry = vec![BTreeSet::new(); self.body.locals.len()];
+        // parameters are the locals following the return place
+        for (i, regions) in self.sig.inputs.iter().enumerate() {
+            entry[i + 1] = (0..self.loans.len())
+                .filter(|loan| matches!(&self.loans[*loan], Loan::Region(r) if regions.contains(r)))
+                .collect();
+        }
+        holds_in[0] = Some(entry);
+        let mut worklist = vec![0];
+        while let Some(bb) = worklist.pop() {
+            let mut holds = holds_in[bb].clone().unwrap();
+            for i in 0..self.body.blocks[bb].stmts.len() {
+                self.transfer_holds(bb, i, &mut holds);
+            }
+            for succ in self.body.blocks[bb].terminator.successors() {
+                let changed = match &mut holds_in[succ] {
+                    Some(succ_holds) => {
+                        let mut changed = false;
+                        for (to, from) in succ_holds.iter_mut().zip(holds.iter()) {
+                            let len = to.len();
+                            to.extend(from.iter().copied());
+                            changed |= to.len() != len;
+                        }
+                        changed
+                    }
+                    None => {
+                        holds_in[succ] = Some(holds.clone());
+                        true
+                    }
+                };
+                if changed {
+                    worklist.push(succ);
+                }
+            }
+        }
+        holds_in
+    }
+
+    fn transfer_holds(&self, bb: usize, i: usize, holds: &mut Holds) {
+        let stmt = &self.body.blocks[bb].stmts[i];
+        match &stmt.kind {
+            StmtKind::Assign(place, ops) => {
+                if !self.has_refs[place.local] {
+                    return;
+                }
+                let mut loans = BTreeSet::new();
+                for op in ops {
+                    if let Some(src) = op.place() {
+                        let outer = self
+                            .outer_loans
+                            .get(&src.local)
+                            .filter(|_| src.projs.first() == Some(&Proj::Deref));
+                        loans.extend(
+                            holds[src.local]
+                                .iter()
+                                .filter(|loan| Some(*loan) != outer)
+                                .copied(),
+                        );
+                    }
+                }
+                if place.projs.contains(&Proj::Deref) {
+                    // references may be written to the referents of the local
+                    let referents: Vec<LocalId> = holds[place.local]
+                        .iter()
+                        .filter_map(|loan| match &self.loans[*loan] {
+                            Loan::Borrow { place, .. } => Some(place.local),
+                            Loan::Region(_) => None,
+                        })
+                        .filter(|local| self.has_refs[*local])
+                        .collect();
+                    for referent in referents {
+                        holds[referent].extend(loans.iter().copied());
+                    }
+                }
+                if place.projs.is_empty() {
+                    holds[place.local] = loans;
+                } else {
+                    holds[place.local].extend(loans);
+                }
+            }
+            StmtKind::Borrow(local, _, place) => {
+                // references derived from the place keep its loans alive
+                let mut loans = holds[place.local].clone();
+                loans.insert(self.loan_at[&(bb, i)]);
+                holds[*local] = loans;
+            }
+            StmtKind::Use(_) | StmtKind::Drop(_) => (),
+            StmtKind::StorageDead(local) => holds[*local].clear(),
+        }
+    }
+
+    /// Backward analysis of locals live at the exit of each block
+    fn compute_liveness(&self) -> Vec<Liveness> {
+        let mut live_out = vec![Liveness::new(); self.body.blocks.len()];
+        loop {
+            let mut changed = false;
+            for (bb, block) in self.body.blocks.iter().enumerate().rev() {
+                let mut live = Liveness::new();
+                for succ in block.terminator.successors() {
+                    let mut succ_live = live_out[succ].clone();
+                    let succ_block = &self.body.blocks[succ];
+                    self.liveness_terminator(&succ_block.terminator, &mut succ_live);
+                    for stmt in succ_block.stmts.iter().rev() {
+                        self.liveness_stmt(&stmt.kind, &stmt.span, &mut succ_live);
+                    }
+                    for (local, span) in succ_live {
+                        live.entry(local).or_insert(span);
+                    }
+                }
+                if live.keys().ne(live_out[bb].keys()) {
+                    live_out[bb] = live;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return live_out;
+            }
+        }
+    }
+
+    fn liveness_stmt(&self, kind: &StmtKind, span: &Span, live: &mut Liveness) {
+        match kind {
+            StmtKind::Assign(place, ops) => {
+                if place.projs.is_empty() {
+                    live.remove(&place.local);
+                } else {
+                    live.insert(place.local, span.clone());
+                }
+                for op in ops {
+                    if let Some(src) = op.place() {
+                        live.insert(src.local, span.clone());
+                    }
+                }
+            }
+            StmtKind::Borrow(local, _, place) => {
+                live.remove(local);
+                live.insert(place.local, span.clone());
+            }
+            StmtKind::Use(ops) => {
+                for op in ops {
+                    if let Some(src) = op.place() {
+                        live.insert(src.local, span.clone());
+                    }
+                }
+            }
+            StmtKind::Drop(local) => {
+                live.insert(*local, span.clone());
+            }
+            StmtKind::StorageDead(local) => {
+                live.remove(local);
+            }
+        }
+    }
+
+    fn liveness_terminator(&self, terminator: &Terminator, live: &mut Liveness) {
+        match terminator {
+            Terminator::If(Operand::Copy(place, span) | Operand::Move(place, span), _, _) => {
+                live.insert(place.local, span.clone());
+            }
+            Terminator::Return(span) => {
+                live.insert(0, span.clone());
+            }
+            _ => (),
+        }
+    }
+
+    /// Loans held by live locals, with the span of a later use
+    fn live_loans(&self, holds: &Holds, live: &Liveness) -> Vec<(LoanId, Span)> {
+        let mut live_loans: Vec<(LoanId, Span)> = vec![];
+        for (local, span) in live {
+            for loan in &holds[*local] {
+                if live_loans.iter().all(|(l, _)| l != loan) {
+                    live_loans.push((*loan, span.clone()));
+                }
+            }
+        }
+        live_loans
+    }
+
+    fn check_operand(&mut self, op: &Operand, live_loans: &[(LoanId, Span)]) {
+        match op {
+            Operand::Copy(place, span) => {
+                self.check_access(place, AccessKind::Read, span, live_loans)
+            }
+            Operand::Move(place, span) => {
+                self.check_access(place, AccessKind::Move, span, live_loans)
+            }
+            Operand::Const => (),
+        }
+    }
+
+    fn check_access(
+        &mut self,
+        place: &Place,
+        kind: AccessKind,
+        span: &Span,
+        live_loans: &[(LoanId, Span)],
+    ) {
+        for (loan_id, use_span) in live_loans {
+            let Loan::Borrow {
+                place: borrowed,
+                mutbl,
+                span: borrow_span,
+            } = &self.loans[*loan_id]
+            else {
+                continue;
+            };
+            if !places_conflict(borrowed, place, kind) {
+                continue;
+            }
+            let desc = self.describe_place(place);
+            let msg = match (kind, mutbl) {
+                (AccessKind::Read, Mutability::Not) => continue,
+                (AccessKind::Read, Mutability::Mut) => {
+                    format!("Cannot use {} because it was mutably borrowed", desc)
+                }
+                (AccessKind::Move, _) => {
+                    format!("Cannot move out of {} because it is borrowed", desc)
+                }
+                (AccessKind::Write, _) => {
+                    format!("Cannot assign to {} because it is borrowed", desc)
+                }
+                (AccessKind::Borrow(Mutability::Not), Mutability::Not) => continue,
+                (AccessKind::Borrow(Mutability::Not), Mutability::Mut) => format!(
+                    "Cannot borrow {} as immutable because it is also borrowed as mutable",
+                    desc
+                ),
+                (AccessKind::Borrow(Mutability::Mut), Mutability::Not) => format!(
+                    "Cannot borrow {} as mutable because it is also borrowed as immutable",
+                    desc
+                ),
+                (AccessKind::Borrow(Mutability::Mut), Mutability::Mut) => {
+                    format!("Cannot borrow {} as mutable more than once at a time", desc)
+                }
+            };
+            self.errors.push(format!(
+                "{} at {}\n    {} is borrowed here at {}\n    borrow later used here at {}",
+                msg,
+                span.to_location(),
+                self.describe_place(borrowed),
+                borrow_span.to_location(),
+                use_span.to_location()
+            ));
+            return;
+        }
+    }
+
+    fn check_storage_dead(&mut self, local: LocalId, span: &Span, live_loans: &[(LoanId, Span)]) {
+        for (loan_id, use_span) in live_loans {
+            let Loan::Borrow {
+                place,
+                span: borrow_span,
+                ..
+            } = &self.loans[*loan_id]
+            else {
+                continue;
+            };
+            // the referents of references are not owned by the local
+            if place.local != local || place.projs.contains(&Proj::Deref) {
+                continue;
+            }
+            let msg = match &self.body.locals[local].name {
+                Some(name) => format!(
+                    "`{}` does not live long enough at {}\n    `{}` dropped here while still borrowed at {}",
+                    name,
+                    borrow_span.to_location(),
+                    name,
+                    span.to_location()
+                ),
+                None => format!(
+                    "Temporary value dropped while borrowed at {}\n    temporary value is freed here at {}",
+                    borrow_span.to_location(),
+                    span.to_location()
+                ),
+            };
+            self.errors.push(format!(
+                "{}\n    borrow later used here at {}",
+                msg,
+                use_span.to_location()
+            ));
+            return;
+        }
+    }
+
+    /// Returned values must not reference data owned by the function, and may only
+    /// reference data which the lifetimes in the return type stand for
+    fn check_return(&mut self, span: &Span, holds: &Holds) {
+        for loan_id in &holds[0] {
+            let (place, borrow_span) = match &self.loans[*loan_id] {
+                Loan::Borrow { place, span, .. } => (place, span),
+                Loan::Region(region) => {
+                    if self.sig.output.contains(region) {
+                        continue;
+                    }
+                    let Some(output) = self.sig.output.first() else {
+                        continue;
+                    };
+                    self.errors.push(format!(
+                        "Lifetime may not live long enough at {}\n    returning this value requires that `'{}` must outlive `'{}`",
+                        span.to_location(),
+                        region,
+                        output
+                    ));
+                    return;
+                }
+            };
+            if place.projs.contains(&Proj::Deref) {
+                continue;
+            }
+            let owner = match &self.body.locals[place.local].name {
+                Some(name) => format!("local variable `{}`", name),
+                None => "temporary value".to_string(),
+            };
+            self.errors.push(format!(
+                "Cannot return value referencing {} at {}\n    {} is borrowed here at {}",
+                owner,
+                span.to_location(),
+                self.describe_place(place),
+                borrow_span.to_location()
+            ));
+            return;
+        }
+    }
+
+    fn describe_place(&self, place: &Place) -> String {
+        let Some(name) = &self.body.locals[place.local].name else {
+            return "temporary value".to_string();
+        };
+        let mut desc = name.to_string();
+        for (i, proj) in place.projs.iter().enumerate() {
+            match proj {
+                // fields and elements are accessed through pointers implicitly
+                Proj::Deref | Proj::DerefBox
+                    if matches!(place.projs.get(i + 1), Some(Proj::Field(_) | Proj::Index)) => {}
+                Proj::Deref | Proj::DerefBox => desc = format!("*{}", desc),
+                Proj::Field(field) => desc = format!("{}.{}", desc, field),
+                Proj::Index => desc = format!("{}[_]", desc),
+            }
+        }
+        format!("`{}`", desc)
+    }
+}
+
+/// Whether an access to `place` conflicts with a loan of `borrowed`
+fn places_conflict(borrowed: &Place, place: &Place, kind: AccessKind) -> bool {
+    if borrowed.local != place.local {
+        return false;
+    }
+    for (a, b) in borrowed.projs.iter().zip(place.projs.iter()) {
+        if let (Proj::Field(a), Proj::Field(b)) = (a, b)
+            && a != b
+        {
+            return false;
+        }
+    }
+    // overwriting a reference does not affect data borrowed through it
+    !(kind == AccessKind::Write
+        && borrowed.projs.len() > place.projs.len()
+        && borrowed.projs[place.projs.len()..].contains(&Proj::Deref))
+}
+
+/// Whether values of `ty` may contain references
+fn has_refs(ctx: &Ctxt, ty: &Ty) -> bool {
+    fn go(ctx: &Ctxt, ty: &Ty, visited: &mut Vec<Rc<CanonicalPath>>) -> bool {
+        match &ty.kind {
+            TyKind::Ref(..) => true,
+            TyKind::Box(inner) | TyKind::Array(inner, _) => go(ctx, inner, visited),
+            // recursive structs are visited once
+            TyKind::Adt(name) => {
+                if visited.contains(name) {
+                    return false;
+                }
+                visited.push(Rc::clone(name));
+                ctx.lookup_adt_def(name)
+                    .unwrap()
+                    .fields
+                    .iter()
+                    .any(|(_, fd_ty)| go(ctx, fd_ty, visited))
+            }
+            _ => false,
+        }
+    }
+    go(ctx, ty, &mut vec![])
+}
+
+/// Whether a user-defined destructor runs when values of `ty` are dropped
+fn has_drop_impl(ctx: &Ctxt, ty: &Ty) -> bool {
+    fn go(ctx: &Ctxt, ty: &Ty, visited: &mut Vec<Rc<CanonicalPath>>) -> bool {
+        match &ty.kind {
+            TyKind::Box(inner) | TyKind::Array(inner, _) => go(ctx, inner, visited),
+            TyKind::Adt(name) => {
+                if visited.contains(name) {
+                    return false;
+                }
+                visited.push(Rc::clone(name));
+                ctx.lookup_drop_impl(name).is_some()
+                    || ctx
+                        .lookup_adt_def(name)
+                        .unwrap()
+                        .fields
+                        .iter()
+                        .any(|(_, fd_ty)| go(ctx, fd_ty, visited))
+            }
+            _ => false,
+        }
+    }
+    go(ctx, ty, &mut vec![])
+}
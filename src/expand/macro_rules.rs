@@ -0,0 +1,683 @@
+use crate::lexer::{BinOp, Token, TokenKind, TokenStream};
+use crate::parse::{self, CfgSet, Parser};
+use crate::span::Span;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Macro defined by `macro_rules!`
+/// ref: https://doc.rust-lang.org/reference/macros-by-example.html
+#[derive(Debug)]
+pub struct MacroRules {
+    pub name: String,
+    pub rules: Vec<MacroRule>,
+}
+
+#[derive(Debug)]
+pub struct MacroRule {
+    matchers: Vec<Matcher>,
+    transcribers: Vec<Transcriber>,
+}
+
+#[derive(Debug)]
+enum Matcher {
+    Token(TokenKind),
+    /// `( ... )`, `[ ... ]`, or `{ ... }`
+    Delimited(TokenKind, Vec<Matcher>, TokenKind),
+    /// `$name:kind`
+    Fragment(String, FragmentKind),
+    /// `$( ... ) sep? op`
+    Repetition(Vec<Matcher>, Option<TokenKind>, RepOp),
+}
+
+#[derive(Debug)]
+enum Transcriber {
+    Token(Token),
+    /// `$name`
+    Var(String, Token),
+    /// `$( ... ) sep? op`
+    Repetition(Vec<Transcriber>, Option<Token>, RepOp),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FragmentKind {
+    Expr,
+    Ident,
+    Ty,
+    Tt,
+    Block,
+    Literal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RepOp {
+    /// `*`
+    ZeroOrMore,
+    /// `+`
+    OneOrMore,
+    /// `?`
+    ZeroOrOne,
+}
+
+/// Tokens matched by a fragment or a repetition
+#[derive(Debug)]
+enum NamedMatch {
+    Fragment(Vec<Token>, FragmentKind),
+    Seq(Vec<NamedMatch>),
+}
+
+type Bindings = HashMap<String, NamedMatch>;
+
+enum MatchError {
+    /// Try the next rule
+    NoMatch,
+    /// A fragment parser failed. Reported immediately like rustc.
+    Fatal,
+}
+
+/// Token produced by transcription
+pub struct TranscribedToken {
+    pub token: Token,
+    /// Whether the token comes from the macro definition or from the invocation
+    pub from_definition: bool,
+}
+
+pub fn is_open_delim(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::OpenParen | TokenKind::OpenBracket | TokenKind::OpenBrace
+    )
+}
+
+pub fn is_close_delim(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::CloseParen | TokenKind::CloseBracket | TokenKind::CloseBrace
+    )
+}
+
+/// Returns the index of the delimiter closing `tokens[open]`
+pub fn find_close_delim(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, t) in tokens.iter().enumerate().skip(open) {
+        if is_open_delim(&t.kind) {
+            depth += 1;
+        } else if is_close_delim(&t.kind) {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+impl MacroRules {
+    /// macroRulesDef ::= macroRule (";" macroRule)* ";"?
+    /// macroRule ::= delimited "=>" delimited
+    /// NOTE: `tokens` do not contain delimiters of the definition
+    pub fn parse(name: String, tokens: &[Token]) -> Option<MacroRules> {
+        let mut rules = vec![];
+        let mut i = 0;
+        while i < tokens.len() {
+            let matcher_end = delimited_group_end(tokens, i)?;
+            let matchers = parse_matchers(&tokens[i + 1..matcher_end])?;
+            i = matcher_end + 1;
+
+            if tokens.get(i).map(|t| &t.kind) != Some(&TokenKind::FatArrow) {
+                eprintln!(
+                    "Expected `=>` in macro `{}`, but found `{}`",
+                    name,
+                    snippet_at(tokens, i)
+                );
+                return None;
+            }
+            i += 1;
+
+            let transcriber_end = delimited_group_end(tokens, i)?;
+            let transcribers = parse_transcribers(&tokens[i + 1..transcriber_end])?;
+            i = transcriber_end + 1;
+
+            rules.push(MacroRule {
+                matchers,
+                transcribers,
+            });
+
+            if i < tokens.len() {
+                if tokens[i].kind != TokenKind::Semi {
+                    eprintln!(
+                        "Expected `;` in macro `{}`, but found `{}`",
+                        name,
+                        tokens[i].span.to_snippet()
+                    );
+                    return None;
+                }
+                i += 1;
+            }
+        }
+
+        if rules.is_empty() {
+            eprintln!("Macro `{}` must have at least one rule", name);
+            return None;
+        }
+        Some(MacroRules { name, rules })
+    }
+
+    /// Find the first rule matching `args` and transcribe it
+    pub fn expand(&self, args: &[Token], call_site: &Span) -> Option<Vec<TranscribedToken>> {
+        for rule in &self.rules {
+            let mut bindings = HashMap::new();
+            let mut pos = 0;
+            match match_seq(&rule.matchers, args, &mut pos, &mut bindings, call_site) {
+                Ok(()) if pos == args.len() => {
+                    let mut out = vec![];
+                    transcribe_seq(
+                        &rule.transcribers,
+                        &bindings,
+                        &mut vec![],
+                        call_site,
+                        &mut out,
+                    )?;
+                    return Some(out);
+                }
+                Ok(()) | Err(MatchError::NoMatch) => (),
+                Err(MatchError::Fatal) => return None,
+            }
+        }
+        eprintln!(
+            "No rules of macro `{}` matched `{}` at {}",
+            self.name,
+            call_site.to_snippet(),
+            call_site.to_location()
+        );
+        None
+    }
+}
+
+fn snippet_at(tokens: &[Token], i: usize) -> String {
+    tokens
+        .get(i)
+        .map(|t| t.span.to_snippet())
+        .unwrap_or_else(|| "<end of macro>".to_string())
+}
+
+/// Returns the index of the close delimiter if `tokens[i]` is an open delimiter
+fn delimited_group_end(tokens: &[Token], i: usize) -> Option<usize> {
+    if let Some(t) = tokens.get(i)
+        && is_open_delim(&t.kind)
+    {
+        if let Some(end) = find_close_delim(tokens, i) {
+            return Some(end);
+        }
+        eprintln!("Unclosed delimiter `{}`", t.span.to_snippet());
+        return None;
+    }
+    eprintln!(
+        "Expected delimiter in macro definition, but found `{}`",
+        snippet_at(tokens, i)
+    );
+    None
+}
+
+/// Parse `sep? op` after `$( ... )`. Returns the separator, the operator, and the next index.
+fn parse_rep_op(tokens: &[Token], i: usize) -> Option<(Option<Token>, RepOp, usize)> {
+    let to_op = |kind: &TokenKind| match kind {
+        TokenKind::BinOp(BinOp::Star) => Some(RepOp::ZeroOrMore),
+        TokenKind::BinOp(BinOp::Plus) => Some(RepOp::OneOrMore),
+        TokenKind::Question => Some(RepOp::ZeroOrOne),
+        _ => None,
+    };
+    let Some(t) = tokens.get(i) else {
+        eprintln!("Expected repetition operator, but found end of macro");
+        return None;
+    };
+    if let Some(op) = to_op(&t.kind) {
+        return Some((None, op, i + 1));
+    }
+    if let Some(op) = tokens.get(i + 1).and_then(|t| to_op(&t.kind)) {
+        return Some((Some(t.clone()), op, i + 2));
+    }
+    eprintln!(
+        "Expected one of `*`, `+`, or `?`, but found `{}`",
+        snippet_at(tokens, i + 1)
+    );
+    None
+}
+
+fn parse_matchers(tokens: &[Token]) -> Option<Vec<Matcher>> {
+    let mut matchers = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        let t = &tokens[i];
+        if t.kind == TokenKind::Dollar {
+            match tokens.get(i + 1).map(|t| &t.kind) {
+                // $name:kind
+                Some(TokenKind::Ident(name)) => {
+                    let kind = match (tokens.get(i + 2), tokens.get(i + 3)) {
+                        (
+                            Some(Token {
+                                kind: TokenKind::Colon,
+                                ..
+                            }),
+                            Some(Token {
+                                kind: TokenKind::Ident(kind),
+                                span,
+                            }),
+                        ) => match kind.as_str() {
+                            "expr" => FragmentKind::Expr,
+                            "ident" => FragmentKind::Ident,
+                            "ty" => FragmentKind::Ty,
+                            "tt" => FragmentKind::Tt,
+                            "block" => FragmentKind::Block,
+                            "literal" => FragmentKind::Literal,
+                            _ => {
+                                eprintln!(
+                                    "Invalid fragment specifier `{}` at {}",
+                                    kind,
+                                    span.to_location()
+                                );
+                                return None;
+                            }
+                        },
+                        _ => {
+                            eprintln!(
+                                "Missing fragment specifier for `${}` at {}",
+                                name,
+                                t.span.to_location()
+                            );
+                            return None;
+                        }
+                    };
+                    matchers.push(Matcher::Fragment(name.clone(), kind));
+                    i += 4;
+                }
+                // $( ... ) sep? op
+                Some(TokenKind::OpenParen) => {
+                    let end = delimited_group_end(tokens, i + 1)?;
+                    let inner = parse_matchers(&tokens[i + 2..end])?;
+                    let (sep, op, next) = parse_rep_op(tokens, end + 1)?;
+                    matchers.push(Matcher::Repetition(inner, sep.map(|t| t.kind), op));
+                    i = next;
+                }
+                _ => {
+                    eprintln!("Expected fragment after `$` at {}", t.span.to_location());
+                    return None;
+                }
+            }
+        } else if is_open_delim(&t.kind) {
+            let end = delimited_group_end(tokens, i)?;
+            let inner = parse_matchers(&tokens[i + 1..end])?;
+            matchers.push(Matcher::Delimited(
+                t.kind.clone(),
+                inner,
+                tokens[end].kind.clone(),
+            ));
+            i = end + 1;
+        } else {
+            matchers.push(Matcher::Token(t.kind.clone()));
+            i += 1;
+        }
+    }
+    Some(matchers)
+}
+
+fn parse_transcribers(tokens: &[Token]) -> Option<Vec<Transcriber>> {
+    let mut transcribers = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        let t = &tokens[i];
+        if t.kind == TokenKind::Dollar {
+            match tokens.get(i + 1).map(|t| &t.kind) {
+                // $name
+                Some(TokenKind::Ident(name)) => {
+                    transcribers.push(Transcriber::Var(name.clone(), t.clone()));
+                    i += 2;
+                }
+                // $( ... ) sep? op
+                Some(TokenKind::OpenParen) => {
+                    let end = delimited_group_end(tokens, i + 1)?;
+                    let inner = parse_transcribers(&tokens[i + 2..end])?;
+                    let (sep, op, next) = parse_rep_op(tokens, end + 1)?;
+                    transcribers.push(Transcriber::Repetition(inner, sep, op));
+                    i = next;
+                }
+                _ => {
+                    transcribers.push(Transcriber::Token(t.clone()));
+                    i += 1;
+                }
+            }
+        } else {
+            transcribers.push(Transcriber::Token(t.clone()));
+            i += 1;
+        }
+    }
+    Some(transcribers)
+}
+
+fn match_seq(
+    matchers: &[Matcher],
+    tokens: &[Token],
+    pos: &mut usize,
+    bindings: &mut Bindings,
+    call_site: &Span,
+) -> Result<(), MatchError> {
+    for matcher in matchers {
+        match matcher {
+            Matcher::Token(kind) => {
+                if tokens.get(*pos).map(|t| &t.kind) == Some(kind) {
+                    *pos += 1;
+                } else {
+                    return Err(MatchError::NoMatch);
+                }
+            }
+            Matcher::Delimited(open, inner, close) => {
+                if tokens.get(*pos).map(|t| &t.kind) != Some(open) {
+                    return Err(MatchError::NoMatch);
+                }
+                let end = find_close_delim(tokens, *pos).unwrap();
+                if tokens[end].kind != *close {
+                    return Err(MatchError::NoMatch);
+                }
+                let inner_tokens = &tokens[*pos + 1..end];
+                let mut inner_pos = 0;
+                match_seq(inner, inner_tokens, &mut inner_pos, bindings, call_site)?;
+                if inner_pos != inner_tokens.len() {
+                    return Err(MatchError::NoMatch);
+                }
+                *pos = end + 1;
+            }
+            Matcher::Fragment(name, kind) => {
+                let len = match_fragment(*kind, &tokens[*pos..], call_site)?;
+                let matched = tokens[*pos..*pos + len].to_vec();
+                bindings.insert(name.clone(), NamedMatch::Fragment(matched, *kind));
+                *pos += len;
+            }
+            Matcher::Repetition(inner, sep, op) => {
+                let mut iterations: Vec<Bindings> = vec![];
+                loop {
+                    if *op == RepOp::ZeroOrOne && iterations.len() == 1 {
+                        break;
+                    }
+                    let mut p = *pos;
+                    if !iterations.is_empty()
+                        && let Some(sep) = sep
+                    {
+                        if tokens.get(p).map(|t| &t.kind) != Some(sep) {
+                            break;
+                        }
+                        p += 1;
+                    }
+                    let mut iteration = HashMap::new();
+                    match match_seq(inner, tokens, &mut p, &mut iteration, call_site) {
+                        // stop if nothing is consumed to avoid infinite loop
+                        Ok(()) if p == *pos => break,
+                        Ok(()) => {
+                            iterations.push(iteration);
+                            *pos = p;
+                        }
+                        Err(MatchError::NoMatch) => break,
+                        Err(MatchError::Fatal) => return Err(MatchError::Fatal),
+                    }
+                }
+                if *op == RepOp::OneOrMore && iterations.is_empty() {
+                    return Err(MatchError::NoMatch);
+                }
+
+                let mut names = vec![];
+                collect_matcher_names(inner, &mut names);
+                for name in names {
+                    let seq = iterations
+                        .iter_mut()
+                        .map(|it| it.remove(&name).unwrap())
+                        .collect();
+                    bindings.insert(name, NamedMatch::Seq(seq));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn collect_matcher_names(matchers: &[Matcher], names: &mut Vec<String>) {
+    for matcher in matchers {
+        match matcher {
+            Matcher::Token(_) => (),
+            Matcher::Fragment(name, _) => names.push(name.clone()),
+            Matcher::Delimited(_, inner, _) | Matcher::Repetition(inner, _, _) => {
+                collect_matcher_names(inner, names)
+            }
+        }
+    }
+}
+
+/// Returns the number of tokens matched by the fragment
+fn match_fragment(
+    kind: FragmentKind,
+    tokens: &[Token],
+    call_site: &Span,
+) -> Result<usize, MatchError> {
+    let Some(first) = tokens.first() else {
+        return Err(MatchError::NoMatch);
+    };
+    match kind {
+        FragmentKind::Ident => {
+            if matches!(first.kind, TokenKind::Ident(_)) {
+                Ok(1)
+            } else {
+                Err(MatchError::NoMatch)
+            }
+        }
+        FragmentKind::Literal => match (&first.kind, tokens.get(1).map(|t| &t.kind)) {
+            (
+                TokenKind::NumLit(_)
+                | TokenKind::FloatLit(..)
+                | TokenKind::StrLit(_)
+                | TokenKind::CharLit(_)
+                | TokenKind::True
+                | TokenKind::False,
+                _,
+            ) => Ok(1),
+            (
+                TokenKind::BinOp(BinOp::Minus),
+                Some(TokenKind::NumLit(_) | TokenKind::FloatLit(..)),
+            ) => Ok(2),
+            _ => Err(MatchError::NoMatch),
+        },
+        FragmentKind::Tt => {
+            if is_open_delim(&first.kind) {
+                Ok(find_close_delim(tokens, 0).unwrap() + 1)
+            } else if is_close_delim(&first.kind) {
+                Err(MatchError::NoMatch)
+            } else {
+                Ok(1)
+            }
+        }
+        FragmentKind::Expr | FragmentKind::Ty | FragmentKind::Block => {
+            let can_start = match kind {
+                FragmentKind::Expr => parse::is_expr_start(first),
+                FragmentKind::Ty => parse::is_type_start(first),
+                _ => first.kind == TokenKind::OpenBrace,
+            };
+            if !can_start {
+                return Err(MatchError::NoMatch);
+            }
+            // run the parser to find the end of the fragment
+            // only the length of the fragment matters here
+            let mut parser = Parser::new(
+                TokenStream::new(tokens.to_vec(), call_site.clone()),
+                Rc::new(CfgSet::default()),
+            );
+            let parsed = match kind {
+                FragmentKind::Expr => parser.parse_expr().is_some(),
+                FragmentKind::Ty => parser.parse_type().is_some(),
+                _ => parser.parse_block().is_some(),
+            };
+            if parsed {
+                Ok(parser.num_consumed_tokens())
+            } else {
+                eprintln!(
+                    "Could not parse macro argument `{}` as {:?}",
+                    call_site.to_snippet(),
+                    kind
+                );
+                Err(MatchError::Fatal)
+            }
+        }
+    }
+}
+
+/// `rep_indices`: indices of the current iterations of enclosing repetitions
+fn transcribe_seq(
+    transcribers: &[Transcriber],
+    bindings: &Bindings,
+    rep_indices: &mut Vec<usize>,
+    call_site: &Span,
+    out: &mut Vec<TranscribedToken>,
+) -> Option<()> {
+    for transcriber in transcribers {
+        match transcriber {
+            Transcriber::Token(t) => out.push(TranscribedToken {
+                token: Token {
+                    kind: t.kind.clone(),
+                    span: call_site.clone(),
+                },
+                from_definition: true,
+            }),
+            Transcriber::Var(name, dollar) => {
+                let Some(mut named_match) = bindings.get(name) else {
+                    // not a metavariable (e.g. used in a nested macro definition)
+                    out.push(TranscribedToken {
+                        token: Token {
+                            kind: dollar.kind.clone(),
+                            span: call_site.clone(),
+                        },
+                        from_definition: true,
+                    });
+                    out.push(TranscribedToken {
+                        token: Token {
+                            kind: TokenKind::Ident(name.clone()),
+                            span: call_site.clone(),
+                        },
+                        from_definition: true,
+                    });
+                    continue;
+                };
+                for idx in rep_indices.iter() {
+                    if let NamedMatch::Seq(seq) = named_match {
+                        named_match = &seq[*idx];
+                    }
+                }
+                let NamedMatch::Fragment(tokens, kind) = named_match else {
+                    eprintln!(
+                        "Variable `{}` is still repeating at this depth in `{}`",
+                        name,
+                        call_site.to_snippet()
+                    );
+                    return None;
+                };
+                // wrap expressions with parentheses to keep precedence
+                let wrap = *kind == FragmentKind::Expr && tokens.len() > 1;
+                if wrap {
+                    out.push(paren(TokenKind::OpenParen, call_site));
+                }
+                for t in tokens {
+                    out.push(TranscribedToken {
+                        token: t.clone(),
+                        from_definition: false,
+                    });
+                }
+                if wrap {
+                    out.push(paren(TokenKind::CloseParen, call_site));
+                }
+            }
+            Transcriber::Repetition(inner, sep, _op) => {
+                let n = repetition_count(inner, bindings, rep_indices, call_site)?;
+                for i in 0..n {
+                    if i != 0
+                        && let Some(sep) = sep
+                    {
+                        out.push(TranscribedToken {
+                            token: Token {
+                                kind: sep.kind.clone(),
+                                span: call_site.clone(),
+                            },
+                            from_definition: true,
+                        });
+                    }
+                    rep_indices.push(i);
+                    transcribe_seq(inner, bindings, rep_indices, call_site, out)?;
+                    rep_indices.pop();
+                }
+            }
+        }
+    }
+    Some(())
+}
+
+fn paren(kind: TokenKind, call_site: &Span) -> TranscribedToken {
+    TranscribedToken {
+        token: Token {
+            kind,
+            span: call_site.clone(),
+        },
+        from_definition: true,
+    }
+}
+
+/// Number of iterations of a repetition, decided by the metavariables repeating at this depth
+fn repetition_count(
+    transcribers: &[Transcriber],
+    bindings: &Bindings,
+    rep_indices: &[usize],
+    call_site: &Span,
+) -> Option<usize> {
+    let mut names = vec![];
+    collect_transcriber_names(transcribers, &mut names);
+
+    let mut count: Option<(usize, &String)> = None;
+    for name in &names {
+        let Some(mut named_match) = bindings.get(name) else {
+            continue;
+        };
+        for idx in rep_indices {
+            if let NamedMatch::Seq(seq) = named_match {
+                named_match = &seq[*idx];
+            }
+        }
+        if let NamedMatch::Seq(seq) = named_match {
+            match count {
+                Some((n, other)) if n != seq.len() => {
+                    eprintln!(
+                        "Meta-variable `{}` repeats {} times, but `{}` repeats {} times in `{}`",
+                        other,
+                        n,
+                        name,
+                        seq.len(),
+                        call_site.to_snippet()
+                    );
+                    return None;
+                }
+                _ => count = Some((seq.len(), name)),
+            }
+        }
+    }
+
+    if let Some((n, _)) = count {
+        Some(n)
+    } else {
+        eprintln!(
+            "Attempted to repeat an expression containing no syntax variables matched as repeating at this depth in `{}`",
+            call_site.to_snippet()
+        );
+        None
+    }
+}
+
+fn collect_transcriber_names(transcribers: &[Transcriber], names: &mut Vec<String>) {
+    for transcriber in transcribers {
+        match transcriber {
+            Transcriber::Token(_) => (),
+            Transcriber::Var(name, _) => names.push(name.clone()),
+            Transcriber::Repetition(inner, _, _) => collect_transcriber_names(inner, names),
+        }
+    }
+}
@@ -0,0 +1,223 @@
+mod macro_rules;
+
+use self::macro_rules::{find_close_delim, is_open_delim, MacroRules, TranscribedToken};
+use crate::lexer::{Token, TokenKind, TokenStream};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+const RECURSION_LIMIT: usize = 128;
+
+/// Expand `macro_rules!` macros on the token stream before parsing.
+/// The last token of `tokens` must be EOF.
+pub fn expand(tokens: Vec<Token>) -> Option<TokenStream> {
+    let eof_span = tokens.last().unwrap().span.clone();
+    let mut expander = MacroExpander::new();
+    let tokens = expander.expand_tokens(tokens, true, 0)?;
+    Some(TokenStream::new(tokens, eof_span))
+}
+
+struct MacroExpander {
+    /// Macros visible at the current position. A new scope starts at each `{`
+    scopes: Vec<HashMap<String, Rc<MacroRules>>>,
+    next_mark: u32,
+    next_expansion_id: u32,
+}
+
+impl MacroExpander {
+    fn new() -> Self {
+        MacroExpander {
+            scopes: vec![HashMap::new()],
+            next_mark: 1,
+            next_expansion_id: 1,
+        }
+    }
+
+    fn lookup_macro(&self, name: &str) -> Option<Rc<MacroRules>> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(mac) = scope.get(name) {
+                return Some(Rc::clone(mac));
+            }
+        }
+        None
+    }
+
+    fn get_fresh_mark(&mut self) -> u32 {
+        let mark = self.next_mark;
+        self.next_mark += 1;
+        mark
+    }
+
+    /// `at_stmt_start`: whether the first token is at the beginning of an item or a statement
+    fn expand_tokens(
+        &mut self,
+        tokens: Vec<Token>,
+        at_stmt_start: bool,
+        depth: usize,
+    ) -> Option<Vec<Token>> {
+        let mut output: Vec<Token> = vec![];
+        let mut i = 0;
+        while i < tokens.len() {
+            let t = &tokens[i];
+            match &t.kind {
+                // macroRules ::= "macro_rules" "!" ident delimited ";"?
+                TokenKind::Ident(kw)
+                    if kw == "macro_rules"
+                        && tokens.get(i + 1).map(|t| &t.kind) == Some(&TokenKind::Bang) =>
+                {
+                    let Some(Token {
+                        kind: TokenKind::Ident(name),
+                        ..
+                    }) = tokens.get(i + 2)
+                    else {
+                        eprintln!("Expected macro name at {}", t.span.to_location());
+                        return None;
+                    };
+                    let Some(end) = self.find_delimited(&tokens, i + 3) else {
+                        eprintln!(
+                            "Expected delimited macro definition of `{}` at {}",
+                            name,
+                            t.span.to_location()
+                        );
+                        return None;
+                    };
+                    let mac = MacroRules::parse(name.clone(), &tokens[i + 4..end])?;
+                    self.scopes
+                        .last_mut()
+                        .unwrap()
+                        .insert(name.clone(), Rc::new(mac));
+                    i = end + 1;
+                    if tokens.get(i).map(|t| &t.kind) == Some(&TokenKind::Semi) {
+                        i += 1;
+                    }
+                }
+                // macroInvocation ::= ident "!" delimited
+                TokenKind::Ident(name)
+                    if tokens.get(i + 1).map(|t| &t.kind) == Some(&TokenKind::Bang)
+                        && self.lookup_macro(name).is_some()
+                        && self.find_delimited(&tokens, i + 2).is_some() =>
+                {
+                    let mac = self.lookup_macro(name).unwrap();
+                    let end = self.find_delimited(&tokens, i + 2).unwrap();
+                    if depth >= RECURSION_LIMIT {
+                        eprintln!(
+                            "Recursion limit reached while expanding `{}!` at {}",
+                            name,
+                            t.span.to_location()
+                        );
+                        return None;
+                    }
+                    let call_site = t.span.concat(&tokens[end].span);
+                    // arguments are expanded first
+                    let args = self.expand_tokens(tokens[i + 3..end].to_vec(), false, depth + 1)?;
+                    let expanded = mac.expand(&args, &call_site)?;
+                    let expanded = self.apply_hygiene(expanded);
+                    let expanded = self.expand_tokens(expanded, true, depth + 1)?;
+
+                    // expansions in expression position are wrapped with parentheses
+                    // so that they are parsed as a single expression
+                    let prev = output.last().map(|t| &t.kind);
+                    let next = tokens.get(end + 1).map(|t| &t.kind);
+                    let is_stmt_start = match prev {
+                        None => at_stmt_start,
+                        Some(kind) => matches!(
+                            kind,
+                            TokenKind::Semi | TokenKind::OpenBrace | TokenKind::CloseBrace
+                        ),
+                    };
+                    let is_operand = matches!(
+                        next,
+                        Some(
+                            TokenKind::BinOp(_)
+                                | TokenKind::Dot
+                                | TokenKind::As
+                                | TokenKind::OpenBracket
+                        )
+                    );
+                    if is_stmt_start && !is_operand {
+                        output.extend(expanded);
+                    } else {
+                        let open = Token {
+                            kind: TokenKind::OpenParen,
+                            span: call_site.with_mark(self.get_fresh_mark()),
+                        };
+                        let close = Token {
+                            kind: TokenKind::CloseParen,
+                            span: call_site.with_mark(self.get_fresh_mark()),
+                        };
+                        output.push(open);
+                        output.extend(expanded);
+                        output.push(close);
+                    }
+                    i = end + 1;
+                }
+                TokenKind::OpenBrace => {
+                    self.scopes.push(HashMap::new());
+                    output.push(t.clone());
+                    i += 1;
+                }
+                TokenKind::CloseBrace => {
+                    if self.scopes.len() > 1 {
+                        self.scopes.pop();
+                    }
+                    output.push(t.clone());
+                    i += 1;
+                }
+                _ => {
+                    output.push(t.clone());
+                    i += 1;
+                }
+            }
+        }
+        Some(output)
+    }
+
+    /// Returns the index of the close delimiter if `tokens[i]` is an open delimiter
+    fn find_delimited(&self, tokens: &[Token], i: usize) -> Option<usize> {
+        if tokens.get(i).is_some_and(|t| is_open_delim(&t.kind)) {
+            find_close_delim(tokens, i)
+        } else {
+            None
+        }
+    }
+
+    /// Rename local variables declared by `let` in the macro definition
+    /// so that they do not conflict with variables at the call site.
+    /// Also gives unique marks to the tokens.
+    fn apply_hygiene(&mut self, tokens: Vec<TranscribedToken>) -> Vec<Token> {
+        let expansion_id = self.next_expansion_id;
+        self.next_expansion_id += 1;
+
+        let mut declared = HashSet::new();
+        for (i, t) in tokens.iter().enumerate() {
+            if let TokenKind::Ident(name) = &t.token.kind
+                && t.from_definition
+                && i > 0
+            {
+                let prev = &tokens[i - 1].token.kind;
+                let is_let = *prev == TokenKind::Let
+                    || (*prev == TokenKind::Mut
+                        && i > 1
+                        && tokens[i - 2].token.kind == TokenKind::Let);
+                if is_let {
+                    declared.insert(name.clone());
+                }
+            }
+        }
+
+        let mut ret = vec![];
+        for (i, t) in tokens.iter().enumerate() {
+            let mut token = t.token.clone();
+            if let TokenKind::Ident(name) = &token.kind
+                && t.from_definition
+                && declared.contains(name)
+                // field names are not renamed
+                && !(i > 0 && tokens[i - 1].token.kind == TokenKind::Dot)
+            {
+                token.kind = TokenKind::Ident(format!("{}.{}", name, expansion_id));
+            }
+            token.span = token.span.with_mark(self.get_fresh_mark());
+            ret.push(token);
+        }
+        ret
+    }
+}
@@ -0,0 +1,474 @@
+//! Lowering of the AST to HIR, which resolves all the paths
+
+use super::*;
+use crate::ast;
+use crate::middle::Ctxt;
+use std::collections::HashMap;
+
+pub fn lower_crate(ctx: &mut Ctxt, krate: &ast::Crate) -> Result<Crate, Vec<String>> {
+    // items can be used before their definitions
+    let mut collector = DefCollector {
+        ctx,
+        def_kinds: HashMap::new(),
+        ctors: HashMap::new(),
+        alias_paths: HashMap::new(),
+    };
+    ast::visitor::go(&mut collector, krate);
+    let DefCollector {
+        def_kinds,
+        ctors,
+        alias_paths,
+        ..
+    } = collector;
+
+    let mut lcx = LoweringContext {
+        ctx,
+        def_kinds,
+        ctors,
+        alias_paths,
+        in_impl: false,
+        errors: vec![],
+    };
+    let items = krate
+        .items
+        .iter()
+        .map(|item| lcx.lower_item(item))
+        .collect();
+    if lcx.errors.is_empty() {
+        Ok(Crate { items })
+    } else {
+        Err(lcx.errors)
+    }
+}
+
+struct DefCollector<'ctx, 'low> {
+    ctx: &'low mut Ctxt<'ctx>,
+    def_kinds: HashMap<DefId, DefKind>,
+    /// Tuple structs and unit structs to their constructors
+    ctors: HashMap<DefId, CtorKind>,
+    /// Type aliases to the items named by their paths (e.g. `type A = S;`)
+    alias_paths: HashMap<DefId, DefId>,
+}
+
+impl DefCollector<'_, '_> {
+    fn insert(&mut self, ident: &Ident, kind: DefKind) -> DefId {
+        let def_id = self.ctx.get_binding(ident).unwrap();
+        self.def_kinds.insert(Rc::clone(&def_id), kind);
+        def_id
+    }
+}
+
+impl<'low> ast::visitor::Visitor<'low> for DefCollector<'_, '_> {
+    fn visit_module_item(&mut self, module: &'low ast::Module) {
+        self.insert(&module.name, DefKind::Mod);
+    }
+
+    fn visit_func(&mut self, func: &'low ast::Func) {
+        self.insert(&func.name, DefKind::Fn);
+    }
+
+    fn visit_struct_item(&mut self, strct: &'low ast::StructItem) {
+        let def_id = self.insert(&strct.ident, DefKind::Struct);
+        match strct.kind {
+            StructKind::Named => (),
+            StructKind::Tuple => {
+                self.ctors.insert(def_id, CtorKind::Fn);
+            }
+            StructKind::Unit => {
+                self.ctors.insert(def_id, CtorKind::Const);
+            }
+        }
+    }
+
+    fn visit_ty_alias(&mut self, alias: &'low ast::TyAlias) {
+        let def_id = self.insert(&alias.ident, DefKind::TyAlias);
+        if let ast::TyKind::Adt(path, _) = &alias.ty.kind
+            && let Some(target) = self.ctx.resolve_path(path)
+        {
+            self.alias_paths.insert(def_id, target);
+        }
+    }
+
+    fn visit_const_item(&mut self, const_item: &'low ast::ConstItem) {
+        self.insert(&const_item.ident, DefKind::Const);
+    }
+
+    fn visit_static_item(&mut self, static_item: &'low ast::StaticItem) {
+        self.insert(&static_item.ident, DefKind::Static(static_item.mutbl));
+    }
+}
+
+struct LoweringContext<'ctx, 'low> {
+    ctx: &'low mut Ctxt<'ctx>,
+    def_kinds: HashMap<DefId, DefKind>,
+    ctors: HashMap<DefId, CtorKind>,
+    alias_paths: HashMap<DefId, DefId>,
+    /// Whether `Self` is available
+    in_impl: bool,
+    errors: Vec<String>,
+}
+
+/// Where a path is used, which decides what it may refer to
+#[derive(Clone, Copy)]
+enum PathSource {
+    Type,
+    Value,
+    /// `S { .. }`
+    Struct,
+}
+
+impl PathSource {
+    fn expected(self) -> &'static str {
+        match self {
+            PathSource::Type => "type",
+            PathSource::Value => "value",
+            PathSource::Struct => "struct",
+        }
+    }
+}
+
+impl LoweringContext<'_, '_> {
+    /// Binding of an item or a variable declared by `ident`
+    fn binding(&mut self, ident: &Ident) -> Rc<Binding> {
+        self.ctx.get_binding(ident).unwrap()
+    }
+
+    fn lower_path(&mut self, path: &ast::Path, source: PathSource) -> Path {
+        let res = match self.ctx.resolve_path(path) {
+            Some(binding) => match self.def_kinds.get(&binding) {
+                Some(kind) => Res::Def(*kind, binding),
+                None => Res::Local(binding),
+            },
+            None => {
+                let what = match source {
+                    PathSource::Type => "type",
+                    PathSource::Value | PathSource::Struct => "ident",
+                };
+                self.errors.push(format!(
+                    "Could not resolve {} `{}` at {}",
+                    what,
+                    path.span.to_snippet(),
+                    path.span.to_location()
+                ));
+                Res::Err
+            }
+        };
+        let mut alias = None;
+        let res = match (source, res) {
+            (PathSource::Value | PathSource::Struct, Res::Def(DefKind::TyAlias, def_id)) => {
+                let res = self.expand_alias(Rc::clone(&def_id));
+                alias = Some(def_id);
+                res
+            }
+            (_, res) => res,
+        };
+        let res = match (source, res) {
+            (_, Res::Err) => Res::Err,
+            (PathSource::Value, Res::Local(local_id)) => Res::Local(local_id),
+            (
+                PathSource::Value,
+                Res::Def(kind @ (DefKind::Fn | DefKind::Const | DefKind::Static(_)), def_id),
+            ) => Res::Def(kind, def_id),
+            (PathSource::Value, Res::Def(DefKind::Struct, def_id))
+                if self.ctors.contains_key(&def_id) =>
+            {
+                Res::Def(DefKind::Ctor(self.ctors[&def_id]), def_id)
+            }
+            (PathSource::Type, Res::Def(kind @ (DefKind::Struct | DefKind::TyAlias), def_id))
+            | (PathSource::Struct, Res::Def(kind @ DefKind::Struct, def_id)) => {
+                Res::Def(kind, def_id)
+            }
+            (source, res) => {
+                let desc = match &res {
+                    Res::Def(kind, _) => kind.desc(),
+                    _ => "local variable",
+                };
+                self.errors.push(format!(
+                    "Expected {}, found {} `{}` at {}",
+                    source.expected(),
+                    desc,
+                    path.span.to_snippet(),
+                    path.span.to_location()
+                ));
+                Res::Err
+            }
+        };
+        Path {
+            res,
+            alias,
+            span: path.span.clone(),
+        }
+    }
+
+    /// Structs can be constructed through aliases of them. Aliases of other types are
+    /// left as they are and reported by the caller
+    fn expand_alias(&self, alias: DefId) -> Res {
+        let mut def_id = Rc::clone(&alias);
+        // aliases of aliases, which may form a cycle
+        for _ in 0..self.alias_paths.len() {
+            let Some(target) = self.alias_paths.get(&def_id) else {
+                break;
+            };
+            match self.def_kinds.get(target) {
+                Some(DefKind::Struct) => return Res::Def(DefKind::Struct, Rc::clone(target)),
+                Some(DefKind::TyAlias) => def_id = Rc::clone(target),
+                _ => break,
+            }
+        }
+        Res::Def(DefKind::TyAlias, alias)
+    }
+
+    fn lower_item(&mut self, item: &ast::Item) -> Item {
+        let kind = match &item.kind {
+            ast::ItemKind::Func(func) => ItemKind::Func(self.lower_func(func)),
+            ast::ItemKind::Struct(strct) => ItemKind::Struct(StructItem {
+                ident: strct.ident.clone(),
+                def_id: self.binding(&strct.ident),
+                generics: strct.generics.clone(),
+                fields: strct
+                    .fields
+                    .iter()
+                    .map(|(ident, ty)| (ident.clone(), self.lower_ty(ty)))
+                    .collect(),
+                kind: strct.kind,
+            }),
+            ast::ItemKind::ExternBlock(block) => ItemKind::ExternBlock(ExternBlock {
+                funcs: block
+                    .funcs
+                    .iter()
+                    .map(|func| self.lower_func(func))
+                    .collect(),
+            }),
+            ast::ItemKind::Mod(module) => ItemKind::Mod(Module {
+                name: module.name.clone(),
+                def_id: self.binding(&module.name),
+                items: module
+                    .items
+                    .iter()
+                    .map(|item| self.lower_item(item))
+                    .collect(),
+            }),
+            ast::ItemKind::TyAlias(alias) => ItemKind::TyAlias(TyAlias {
+                ident: alias.ident.clone(),
+                def_id: self.binding(&alias.ident),
+                ty: self.lower_ty(&alias.ty),
+            }),
+            ast::ItemKind::Impl(imp) => {
+                // `Drop` is the only trait
+                if imp.of_trait.segments.len() != 1 || *imp.of_trait.segments[0].symbol != "Drop" {
+                    self.errors.push(format!(
+                        "Cannot find trait `{}` at {}",
+                        imp.of_trait.span.to_snippet(),
+                        imp.of_trait.span.to_location()
+                    ));
+                }
+                let self_ty = self.lower_ty(&imp.self_ty);
+                self.in_impl = true;
+                let items = imp.items.iter().map(|func| self.lower_func(func)).collect();
+                self.in_impl = false;
+                ItemKind::Impl(Impl {
+                    generics: imp.generics.clone(),
+                    self_ty,
+                    items,
+                })
+            }
+            ast::ItemKind::Const(const_item) => ItemKind::Const(ConstItem {
+                ident: const_item.ident.clone(),
+                def_id: self.binding(&const_item.ident),
+                ty: self.lower_ty(&const_item.ty),
+                expr: self.lower_expr(&const_item.expr),
+            }),
+            ast::ItemKind::Static(static_item) => ItemKind::Static(StaticItem {
+                ident: static_item.ident.clone(),
+                def_id: self.binding(&static_item.ident),
+                mutbl: static_item.mutbl,
+                ty: self.lower_ty(&static_item.ty),
+                expr: self.lower_expr(&static_item.expr),
+            }),
+        };
+        Item { kind }
+    }
+
+    fn lower_func(&mut self, func: &ast::Func) -> Func {
+        let params = func
+            .params
+            .iter()
+            .map(|(ident, ty)| Param {
+                ident: ident.clone(),
+                local_id: self.binding(ident),
+                ty: self.lower_ty(ty),
+            })
+            .collect();
+        // functions in extern blocks are always unsafe to call
+        let unsafety = if func.ext.is_some() {
+            Unsafety::Unsafe
+        } else {
+            func.unsafety
+        };
+        Func {
+            name: func.name.clone(),
+            def_id: self.binding(&func.name),
+            generics: func.generics.clone(),
+            unsafety,
+            constness: func.constness,
+            inline: func.inline,
+            params,
+            ret_ty: self.lower_ty(&func.ret_ty),
+            body: func.body.as_ref().map(|body| self.lower_block(body)),
+        }
+    }
+
+    fn lower_ty(&mut self, ty: &ast::Ty) -> Ty {
+        let kind = match &ty.kind {
+            ast::TyKind::Unit => TyKind::Unit,
+            ast::TyKind::Bool => TyKind::Bool,
+            ast::TyKind::Int(int_ty) => TyKind::Int(*int_ty),
+            ast::TyKind::F32 => TyKind::F32,
+            ast::TyKind::F64 => TyKind::F64,
+            ast::TyKind::Str => TyKind::Str,
+            ast::TyKind::Char => TyKind::Char,
+            ast::TyKind::Never => TyKind::Never,
+            ast::TyKind::Array(elem, len) => {
+                TyKind::Array(Box::new(self.lower_ty(elem)), self.lower_boxed(len))
+            }
+            ast::TyKind::Adt(path, lifetimes) => {
+                TyKind::Path(self.lower_path(path, PathSource::Type), lifetimes.clone())
+            }
+            ast::TyKind::Ref(lifetime, mutbl, referent) => {
+                TyKind::Ref(lifetime.clone(), *mutbl, Box::new(self.lower_ty(referent)))
+            }
+            ast::TyKind::RawPtr(mutbl, referent) => {
+                TyKind::RawPtr(*mutbl, Box::new(self.lower_ty(referent)))
+            }
+            ast::TyKind::Box(inner) => TyKind::Box(Box::new(self.lower_ty(inner))),
+            ast::TyKind::SelfTy => {
+                if !self.in_impl {
+                    self.errors.push(format!(
+                        "`Self` is only available in impls at {}",
+                        ty.span.to_location()
+                    ));
+                }
+                TyKind::SelfTy
+            }
+        };
+        Ty {
+            kind,
+            span: ty.span.clone(),
+        }
+    }
+
+    fn lower_block(&mut self, block: &ast::Block) -> Block {
+        let mut stmts: Vec<Stmt> = vec![];
+        let mut expr = None;
+        for (i, stmt) in block.stmts.iter().enumerate() {
+            let kind = match &stmt.kind {
+                // the tail expression is the value of the block
+                ast::StmtKind::Expr(tail) if i == block.stmts.len() - 1 => {
+                    expr = Some(Box::new(self.lower_expr(tail)));
+                    continue;
+                }
+                ast::StmtKind::Expr(expr) => StmtKind::Expr(Box::new(self.lower_expr(expr))),
+                ast::StmtKind::Semi(expr) => StmtKind::Semi(Box::new(self.lower_expr(expr))),
+                ast::StmtKind::Let(let_stmt) => StmtKind::Let(LetStmt {
+                    ident: let_stmt.ident.clone(),
+                    local_id: self.binding(&let_stmt.ident),
+                    mutbl: let_stmt.mutbl,
+                    ty: let_stmt.ty.as_ref().map(|ty| self.lower_ty(ty)),
+                    init: let_stmt.init.as_ref().map(|init| self.lower_boxed(init)),
+                }),
+            };
+            stmts.push(Stmt {
+                kind,
+                id: stmt.id,
+                span: stmt.span.clone(),
+            });
+        }
+        Block {
+            stmts,
+            expr,
+            unsafety: block.unsafety,
+            span: block.span.clone(),
+            id: block.id,
+        }
+    }
+
+    fn lower_expr(&mut self, expr: &ast::Expr) -> Expr {
+        let kind = match &expr.kind {
+            ast::ExprKind::Binary(op, l, r) => {
+                ExprKind::Binary(*op, self.lower_boxed(l), self.lower_boxed(r))
+            }
+            ast::ExprKind::Unary(op, inner) => ExprKind::Unary(*op, self.lower_boxed(inner)),
+            ast::ExprKind::AddrOf(mutbl, inner) => {
+                ExprKind::AddrOf(*mutbl, self.lower_boxed(inner))
+            }
+            ast::ExprKind::NumLit(n) => ExprKind::NumLit(*n),
+            ast::ExprKind::FloatLit(f, suffix) => ExprKind::FloatLit(*f, *suffix),
+            ast::ExprKind::BoolLit(b) => ExprKind::BoolLit(*b),
+            ast::ExprKind::StrLit(s) => ExprKind::StrLit(s.clone()),
+            ast::ExprKind::CharLit(c) => ExprKind::CharLit(*c),
+            ast::ExprKind::Unit => ExprKind::Unit,
+            ast::ExprKind::Path(path) => ExprKind::Path(self.lower_path(path, PathSource::Value)),
+            ast::ExprKind::Assign(l, r) => {
+                ExprKind::Assign(self.lower_boxed(l), self.lower_boxed(r))
+            }
+            ast::ExprKind::Return(inner) => ExprKind::Return(self.lower_boxed(inner)),
+            ast::ExprKind::Call(func, args) => {
+                ExprKind::Call(self.lower_boxed(func), self.lower_exprs(args))
+            }
+            ast::ExprKind::MethodCall(recv, method, args) => ExprKind::MethodCall(
+                self.lower_boxed(recv),
+                method.clone(),
+                self.lower_exprs(args),
+            ),
+            ast::ExprKind::Block(block) => ExprKind::Block(self.lower_block(block)),
+            ast::ExprKind::If(cond, then, els) => ExprKind::If(
+                self.lower_boxed(cond),
+                self.lower_boxed(then),
+                els.as_ref().map(|els| self.lower_boxed(els)),
+            ),
+            ast::ExprKind::Index(array, index) => {
+                ExprKind::Index(self.lower_boxed(array), self.lower_boxed(index))
+            }
+            ast::ExprKind::Field(receiver, field) => {
+                ExprKind::Field(self.lower_boxed(receiver), field.clone())
+            }
+            ast::ExprKind::Struct(path, fds, base) => ExprKind::Struct(
+                self.lower_path(path, PathSource::Struct),
+                fds.iter()
+                    .map(|(fd, fd_expr)| (fd.clone(), self.lower_expr(fd_expr)))
+                    .collect(),
+                base.as_ref().map(|base| self.lower_boxed(base)),
+            ),
+            ast::ExprKind::Array(elems) => ExprKind::Array(self.lower_exprs(elems)),
+            ast::ExprKind::Cast(inner, ty) => {
+                ExprKind::Cast(self.lower_boxed(inner), self.lower_ty(ty))
+            }
+            ast::ExprKind::Box(inner) => ExprKind::Box(self.lower_boxed(inner)),
+            ast::ExprKind::NullPtr(mutbl) => ExprKind::NullPtr(*mutbl),
+            ast::ExprKind::Panic(fmt) => ExprKind::Panic(FormatArgs {
+                pieces: fmt.pieces.clone(),
+                args: self.lower_exprs(&fmt.args),
+            }),
+            ast::ExprKind::Print(dest, fmt) => ExprKind::Print(
+                *dest,
+                FormatArgs {
+                    pieces: fmt.pieces.clone(),
+                    args: self.lower_exprs(&fmt.args),
+                },
+            ),
+        };
+        Expr {
+            kind,
+            id: expr.id,
+            span: expr.span.clone(),
+        }
+    }
+
+    fn lower_boxed(&mut self, expr: &ast::Expr) -> Box<Expr> {
+        Box::new(self.lower_expr(expr))
+    }
+
+    fn lower_exprs(&mut self, exprs: &[ast::Expr]) -> Vec<Expr> {
+        exprs.iter().map(|expr| self.lower_expr(expr)).collect()
+    }
+}
@@ -0,0 +1,330 @@
+//! High-level IR: the AST after name resolution
+//!
+//! Paths are replaced by what they resolve to, so later stages never look up names
+//! again. Items and local variables are identified by the bindings name resolution
+//! gives them. HIR nodes keep the ids of the AST nodes they are lowered from, so the
+//! tables in `Ctxt` are keyed by either.
+//!
+//! Some sugar is lowered on the way: the tail expression of a block is split from its
+//! statements, functions in `extern` blocks are `unsafe fn`s, and `impl Drop for T`
+//! keeps only its self type as `Drop` is the only trait.
+
+mod lowering;
+pub mod visitor;
+
+pub use self::lowering::lower_crate;
+pub use crate::ast::{
+    BinOp, Constness, FloatTy, FormatPiece, Generics, InlineAttr, IntTy, Lifetime, Mutability,
+    NodeId, PrintDest, Region, StructKind, UnOp, Unsafety,
+};
+
+use crate::middle::ty::CtorKind;
+use crate::resolve::Binding;
+use crate::span::{Ident, Span};
+use std::rc::Rc;
+
+pub type HirId = NodeId;
+
+/// Items are identified by their bindings, whose canonical paths are unique
+pub type DefId = Rc<Binding>;
+
+/// Parameters and `let` variables. Shadowing variables have distinct bindings
+pub type LocalId = Rc<Binding>;
+
+/// What a path refers to
+#[derive(Debug, Clone)]
+pub enum Res {
+    Def(DefKind, DefId),
+    Local(LocalId),
+    /// Paths which could not be resolved. Lowering fails if any exists
+    Err,
+}
+
+impl Res {
+    pub fn def_id(&self) -> Option<&DefId> {
+        match self {
+            Res::Def(_, def_id) => Some(def_id),
+            Res::Local(_) | Res::Err => None,
+        }
+    }
+
+    pub fn local_id(&self) -> Option<&LocalId> {
+        match self {
+            Res::Local(local_id) => Some(local_id),
+            Res::Def(..) | Res::Err => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefKind {
+    Mod,
+    Fn,
+    Struct,
+    /// Tuple structs and unit structs used as values
+    Ctor(CtorKind),
+    TyAlias,
+    Const,
+    Static(Mutability),
+}
+
+impl DefKind {
+    pub fn desc(&self) -> &'static str {
+        match self {
+            DefKind::Mod => "module",
+            DefKind::Fn => "function",
+            DefKind::Struct | DefKind::Ctor(_) => "struct",
+            DefKind::TyAlias => "type alias",
+            DefKind::Const => "constant",
+            DefKind::Static(_) => "static",
+        }
+    }
+}
+
+/// Resolved path. The span is kept for diagnostics
+#[derive(Debug, Clone)]
+pub struct Path {
+    pub res: Res,
+    /// Type alias through which a struct is named (e.g. `A { .. }` with `type A = S;`)
+    pub alias: Option<DefId>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Crate {
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug)]
+pub struct Item {
+    pub kind: ItemKind,
+}
+
+#[derive(Debug)]
+pub enum ItemKind {
+    Func(Func),
+    Struct(StructItem),
+    ExternBlock(ExternBlock),
+    Mod(Module),
+    TyAlias(TyAlias),
+    Impl(Impl),
+    Const(ConstItem),
+    Static(StaticItem),
+}
+
+#[derive(Debug)]
+pub struct Module {
+    pub name: Ident,
+    pub def_id: DefId,
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug)]
+pub struct ExternBlock {
+    pub funcs: Vec<Func>,
+}
+
+#[derive(Debug)]
+pub struct StructItem {
+    pub ident: Ident,
+    pub def_id: DefId,
+    pub generics: Generics,
+    /// Fields of tuple structs are named `0`, `1`, ...
+    pub fields: Vec<(Ident, Ty)>,
+    pub kind: StructKind,
+}
+
+/// `type ident = ty;`
+#[derive(Debug)]
+pub struct TyAlias {
+    pub ident: Ident,
+    pub def_id: DefId,
+    pub ty: Ty,
+}
+
+/// `const IDENT: ty = expr;`
+#[derive(Debug)]
+pub struct ConstItem {
+    pub ident: Ident,
+    pub def_id: DefId,
+    pub ty: Ty,
+    pub expr: Expr,
+}
+
+/// `static IDENT: ty = expr;` or `static mut IDENT: ty = expr;`
+#[derive(Debug)]
+pub struct StaticItem {
+    pub ident: Ident,
+    pub def_id: DefId,
+    pub mutbl: Mutability,
+    pub ty: Ty,
+    pub expr: Expr,
+}
+
+/// `impl<'a> Drop for Ty { fn drop(&mut self) { ... } }`
+#[derive(Debug)]
+pub struct Impl {
+    pub generics: Generics,
+    pub self_ty: Ty,
+    pub items: Vec<Func>,
+}
+
+#[derive(Debug)]
+pub struct Func {
+    pub name: Ident,
+    pub def_id: DefId,
+    pub generics: Generics,
+    /// `unsafe fn` and functions in `extern` blocks
+    pub unsafety: Unsafety,
+    pub constness: Constness,
+    pub inline: InlineAttr,
+    pub params: Vec<Param>,
+    pub ret_ty: Ty,
+    /// `None` for functions in `extern` blocks
+    pub body: Option<Block>,
+}
+
+#[derive(Debug)]
+pub struct Param {
+    pub ident: Ident,
+    pub local_id: LocalId,
+    pub ty: Ty,
+}
+
+#[derive(Debug)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub id: HirId,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum StmtKind {
+    /// Block-like expression without trailing semicolon which is not the tail of the block
+    Expr(Box<Expr>),
+    /// Expression with trailing semicolon
+    Semi(Box<Expr>),
+    Let(LetStmt),
+}
+
+#[derive(Debug)]
+pub struct LetStmt {
+    pub ident: Ident,
+    pub local_id: LocalId,
+    pub mutbl: Mutability,
+    pub ty: Option<Ty>,
+    pub init: Option<Box<Expr>>,
+}
+
+impl LetStmt {
+    /// `let _ = ...` binds nothing, so the value is not moved into a variable
+    pub fn is_wildcard(&self) -> bool {
+        self.ident.symbol.as_str() == "_"
+    }
+}
+
+#[derive(Debug)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    /// The tail expression, whose value is the value of the block
+    pub expr: Option<Box<Expr>>,
+    /// `unsafe { }`
+    pub unsafety: Unsafety,
+    pub span: Span,
+    pub id: HirId,
+}
+
+#[derive(Debug)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub id: HirId,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum ExprKind {
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Unary(UnOp, Box<Expr>),
+    /// `&expr` or `&mut expr`
+    AddrOf(Mutability, Box<Expr>),
+    NumLit(u128),
+    /// Floating-point literal with its suffix
+    FloatLit(f64, Option<FloatTy>),
+    BoolLit(bool),
+    StrLit(String),
+    CharLit(char),
+    Unit,
+    /// Local variables, functions and constructors
+    Path(Path),
+    Assign(Box<Expr>, Box<Expr>),
+    Return(Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>),
+    /// `recv.method(args)`. Only the built-in methods of raw pointers exist
+    MethodCall(Box<Expr>, Ident, Vec<Expr>),
+    Block(Block),
+    /// cond, then (only block expr), else
+    If(Box<Expr>, Box<Expr>, Option<Box<Expr>>),
+    Index(Box<Expr>, Box<Expr>),
+    Field(Box<Expr>, Ident),
+    /// `S { a: expr, b, ..base }`
+    Struct(Path, Vec<(Ident, Expr)>, Option<Box<Expr>>),
+    Array(Vec<Expr>),
+    Cast(Box<Expr>, Ty),
+    /// `Box::new(expr)`, which moves the value to the heap
+    Box(Box<Expr>),
+    /// `core::ptr::null()` or `core::ptr::null_mut()`
+    NullPtr(Mutability),
+    /// Prints the formatted message and aborts
+    Panic(FormatArgs),
+    /// `print!` and friends
+    Print(PrintDest, FormatArgs),
+}
+
+/// Format string and arguments of a formatting macro
+#[derive(Debug)]
+pub struct FormatArgs {
+    pub pieces: Vec<FormatPiece>,
+    pub args: Vec<Expr>,
+}
+
+impl FormatArgs {
+    /// The formatted string if it has no placeholders
+    pub fn as_lit(&self) -> Option<String> {
+        self.pieces
+            .iter()
+            .map(|piece| match piece {
+                FormatPiece::Lit(s) => Some(s.as_str()),
+                FormatPiece::Placeholder(_) => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct Ty {
+    pub kind: TyKind,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum TyKind {
+    Unit,
+    Bool,
+    Int(IntTy),
+    F32,
+    F64,
+    Str,
+    Char,
+    /// `[T; len]`. The length is not checked as code but evaluated at compile time
+    Array(Box<Ty>, Box<Expr>),
+    /// Structs or type aliases, with lifetime arguments (e.g. `S<'a>`)
+    Path(Path, Vec<Lifetime>),
+    Ref(Option<Lifetime>, Mutability, Box<Ty>),
+    /// `*const T` or `*mut T`
+    RawPtr(Mutability, Box<Ty>),
+    /// Built-in `Box<T>`
+    Box(Box<Ty>),
+    Never,
+    /// `Self` in impls
+    SelfTy,
+}
@@ -0,0 +1,237 @@
+use super::*;
+
+/// HIR visitor
+pub trait Visitor<'hir>: Sized {
+    fn visit_crate(&mut self, _krate: &'hir Crate) {}
+    fn visit_crate_post(&mut self, _krate: &'hir Crate) {}
+    fn visit_item(&mut self, _item: &'hir Item) {}
+    fn visit_item_post(&mut self, _item: &'hir Item) {}
+    fn visit_module_item(&mut self, _module: &'hir Module) {}
+    fn visit_module_item_post(&mut self, _module: &'hir Module) {}
+    fn visit_func(&mut self, _func: &'hir Func) {}
+    fn visit_func_post(&mut self, _func: &'hir Func) {}
+    fn visit_struct_item(&mut self, _struct: &'hir StructItem) {}
+    fn visit_struct_item_post(&mut self, _struct: &'hir StructItem) {}
+    fn visit_ty_alias(&mut self, _alias: &'hir TyAlias) {}
+    fn visit_ty_alias_post(&mut self, _alias: &'hir TyAlias) {}
+    fn visit_const_item(&mut self, _item: &'hir ConstItem) {}
+    fn visit_const_item_post(&mut self, _item: &'hir ConstItem) {}
+    fn visit_static_item(&mut self, _item: &'hir StaticItem) {}
+    fn visit_static_item_post(&mut self, _item: &'hir StaticItem) {}
+    fn visit_impl(&mut self, _impl: &'hir Impl) {}
+    fn visit_impl_post(&mut self, _impl: &'hir Impl) {}
+    fn visit_extern_block(&mut self, _block: &'hir ExternBlock) {}
+    fn visit_extern_block_post(&mut self, _block: &'hir ExternBlock) {}
+    fn visit_stmt(&mut self, _stmt: &'hir Stmt) {}
+    fn visit_stmt_post(&mut self, _stmt: &'hir Stmt) {}
+    fn visit_expr(&mut self, _expr: &'hir Expr) {}
+    fn visit_expr_post(&mut self, _expr: &'hir Expr) {}
+    fn visit_block(&mut self, _block: &'hir Block) {}
+    fn visit_block_post(&mut self, _block: &'hir Block) {}
+    fn visit_type(&mut self, _ty: &'hir Ty) {}
+    fn visit_type_post(&mut self, _ty: &'hir Ty) {}
+    /// Array lengths are not walked into by default
+    fn visit_array_len(&mut self, _len: &'hir Expr) {}
+}
+
+pub fn go<'hir, V: Visitor<'hir>>(v: &mut V, krate: &'hir Crate) {
+    walk_crate(v, krate);
+}
+
+fn walk_crate<'hir, V: Visitor<'hir>>(v: &mut V, krate: &'hir Crate) {
+    v.visit_crate(krate);
+    for item in &krate.items {
+        walk_item(v, item);
+    }
+    v.visit_crate_post(krate);
+}
+
+fn walk_item<'hir, V: Visitor<'hir>>(v: &mut V, item: &'hir Item) {
+    v.visit_item(item);
+    match &item.kind {
+        ItemKind::Func(func) => walk_func(v, func),
+        ItemKind::Struct(struct_item) => walk_struct_item(v, struct_item),
+        ItemKind::ExternBlock(extern_block) => walk_extern_block(v, extern_block),
+        ItemKind::Mod(module) => walk_module_item(v, module),
+        ItemKind::TyAlias(alias) => walk_ty_alias(v, alias),
+        ItemKind::Impl(imp) => walk_impl(v, imp),
+        ItemKind::Const(const_item) => walk_const_item(v, const_item),
+        ItemKind::Static(static_item) => walk_static_item(v, static_item),
+    }
+    v.visit_item_post(item);
+}
+
+fn walk_func<'hir, V: Visitor<'hir>>(v: &mut V, func: &'hir Func) {
+    v.visit_func(func);
+    for param in &func.params {
+        walk_type(v, &param.ty);
+    }
+    walk_type(v, &func.ret_ty);
+    if let Some(body) = &func.body {
+        walk_block(v, body)
+    }
+    v.visit_func_post(func);
+}
+
+fn walk_struct_item<'hir, V: Visitor<'hir>>(v: &mut V, struct_item: &'hir StructItem) {
+    v.visit_struct_item(struct_item);
+    for (_ident, ty) in &struct_item.fields {
+        walk_type(v, ty);
+    }
+    v.visit_struct_item_post(struct_item);
+}
+
+fn walk_ty_alias<'hir, V: Visitor<'hir>>(v: &mut V, alias: &'hir TyAlias) {
+    v.visit_ty_alias(alias);
+    walk_type(v, &alias.ty);
+    v.visit_ty_alias_post(alias);
+}
+
+fn walk_const_item<'hir, V: Visitor<'hir>>(v: &mut V, const_item: &'hir ConstItem) {
+    v.visit_const_item(const_item);
+    walk_type(v, &const_item.ty);
+    walk_expr(v, &const_item.expr);
+    v.visit_const_item_post(const_item);
+}
+
+fn walk_static_item<'hir, V: Visitor<'hir>>(v: &mut V, static_item: &'hir StaticItem) {
+    v.visit_static_item(static_item);
+    walk_type(v, &static_item.ty);
+    walk_expr(v, &static_item.expr);
+    v.visit_static_item_post(static_item);
+}
+
+fn walk_impl<'hir, V: Visitor<'hir>>(v: &mut V, imp: &'hir Impl) {
+    v.visit_impl(imp);
+    walk_type(v, &imp.self_ty);
+    for func in &imp.items {
+        walk_func(v, func);
+    }
+    v.visit_impl_post(imp);
+}
+
+fn walk_extern_block<'hir, V: Visitor<'hir>>(v: &mut V, block: &'hir ExternBlock) {
+    v.visit_extern_block(block);
+    for func in &block.funcs {
+        walk_func(v, func);
+    }
+    v.visit_extern_block_post(block);
+}
+
+fn walk_module_item<'hir, V: Visitor<'hir>>(v: &mut V, module: &'hir Module) {
+    v.visit_module_item(module);
+    for item in &module.items {
+        walk_item(v, item);
+    }
+    v.visit_module_item_post(module);
+}
+
+fn walk_stmt<'hir, V: Visitor<'hir>>(v: &mut V, stmt: &'hir Stmt) {
+    v.visit_stmt(stmt);
+    match &stmt.kind {
+        StmtKind::Semi(expr) | StmtKind::Expr(expr) => walk_expr(v, expr),
+        StmtKind::Let(let_stmt) => {
+            if let Some(ty) = &let_stmt.ty {
+                walk_type(v, ty);
+            }
+            if let Some(init) = &let_stmt.init {
+                walk_expr(v, init);
+            }
+        }
+    }
+    v.visit_stmt_post(stmt);
+}
+
+fn walk_type<'hir, V: Visitor<'hir>>(v: &mut V, ty: &'hir Ty) {
+    v.visit_type(ty);
+    match &ty.kind {
+        TyKind::Bool
+        | TyKind::Int(_)
+        | TyKind::F32
+        | TyKind::F64
+        | TyKind::Never
+        | TyKind::Str
+        | TyKind::Char
+        | TyKind::SelfTy
+        | TyKind::Unit
+        | TyKind::Path(..) => (),
+        TyKind::Array(inner, len) => {
+            walk_type(v, inner);
+            v.visit_array_len(len);
+        }
+        TyKind::Ref(_, _, inner) => walk_type(v, inner),
+        TyKind::RawPtr(_, inner) | TyKind::Box(inner) => walk_type(v, inner),
+    }
+    v.visit_type_post(ty);
+}
+
+pub fn walk_expr<'hir, V: Visitor<'hir>>(v: &mut V, expr: &'hir Expr) {
+    v.visit_expr(expr);
+    match &expr.kind {
+        ExprKind::NumLit(_)
+        | ExprKind::FloatLit(..)
+        | ExprKind::BoolLit(_)
+        | ExprKind::StrLit(_)
+        | ExprKind::CharLit(_)
+        | ExprKind::Unit
+        | ExprKind::Path(_)
+        | ExprKind::NullPtr(_) => (),
+        ExprKind::Binary(_, l, r) | ExprKind::Assign(l, r) | ExprKind::Index(l, r) => {
+            walk_expr(v, l);
+            walk_expr(v, r);
+        }
+        ExprKind::Unary(_, inner)
+        | ExprKind::AddrOf(_, inner)
+        | ExprKind::Box(inner)
+        | ExprKind::Return(inner)
+        | ExprKind::Field(inner, _) => walk_expr(v, inner),
+        ExprKind::Call(func, args) | ExprKind::MethodCall(func, _, args) => {
+            walk_expr(v, func);
+            for arg in args {
+                walk_expr(v, arg);
+            }
+        }
+        ExprKind::Print(_, fmt) | ExprKind::Panic(fmt) => {
+            for arg in &fmt.args {
+                walk_expr(v, arg);
+            }
+        }
+        ExprKind::Block(block) => walk_block(v, block),
+        ExprKind::If(cond, then, els) => {
+            walk_expr(v, cond);
+            walk_expr(v, then);
+            if let Some(els) = els {
+                walk_expr(v, els);
+            }
+        }
+        ExprKind::Struct(_, fds, base) => {
+            for (_ident, expr) in fds {
+                walk_expr(v, expr);
+            }
+            if let Some(base) = base {
+                walk_expr(v, base);
+            }
+        }
+        ExprKind::Array(elems) => {
+            for elem in elems {
+                walk_expr(v, elem);
+            }
+        }
+        ExprKind::Cast(inner, ty) => {
+            walk_expr(v, inner);
+            walk_type(v, ty);
+        }
+    }
+    v.visit_expr_post(expr);
+}
+
+fn walk_block<'hir, V: Visitor<'hir>>(v: &mut V, block: &'hir Block) {
+    v.visit_block(block);
+    for stmt in &block.stmts {
+        walk_stmt(v, stmt);
+    }
+    if let Some(expr) = &block.expr {
+        walk_expr(v, expr);
+    }
+    v.visit_block_post(block);
+}
@@ -0,0 +1,168 @@
+use super::Stop;
+use std::collections::BTreeMap;
+
+/// Allocations are placed this far apart, so accesses slightly out of bounds never hit
+/// a neighbouring allocation
+const GUARD_SIZE: u64 = 64;
+
+/// Address of the first allocation. Smaller addresses including null are never valid
+const BASE_ADDR: u64 = 0x10000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocKind {
+    /// Locals of a function, removed when it returns
+    Stack,
+    /// Allocated by `Box::new` or `malloc`, and freed by `free`
+    Heap,
+    /// String literals and statics, which are never freed
+    Static,
+}
+
+impl AllocKind {
+    fn desc(self) -> &'static str {
+        match self {
+            AllocKind::Stack => "stack",
+            AllocKind::Heap => "heap",
+            AllocKind::Static => "static",
+        }
+    }
+}
+
+struct Allocation {
+    bytes: Vec<u8>,
+    /// Whether each byte has been written
+    init: Vec<bool>,
+    kind: AllocKind,
+    /// Freed heap allocations are kept to report uses after free
+    live: bool,
+}
+
+/// Memory of the interpreted program. Pointers are plain addresses, which are never
+/// reused, and every access is checked against the allocation containing its address
+#[derive(Default)]
+pub struct Memory {
+    /// Allocations by their base addresses
+    allocs: BTreeMap<u64, Allocation>,
+    next_addr: u64,
+}
+
+impl Memory {
+    pub fn allocate(&mut self, size: usize, align: usize, kind: AllocKind) -> u64 {
+        let align = align.max(1) as u64;
+        let base = self.next_addr.max(BASE_ADDR).div_ceil(align) * align;
+        self.next_addr = base + size as u64 + GUARD_SIZE;
+        self.allocs.insert(
+            base,
+            Allocation {
+                bytes: vec![0; size],
+                init: vec![false; size],
+                kind,
+                live: true,
+            },
+        );
+        base
+    }
+
+    /// Free the allocation starting at `addr`, which must be of `kind`
+    pub fn deallocate(&mut self, addr: u64, kind: AllocKind) -> Result<(), Stop> {
+        let Some(alloc) = self.allocs.get_mut(&addr) else {
+            return Err(ub(format!(
+                "freeing {:#x}, which does not point to the start of an allocation",
+                addr
+            )));
+        };
+        if !alloc.live {
+            return Err(ub(format!("double free of {:#x}", addr)));
+        }
+        if alloc.kind != kind {
+            return Err(ub(format!(
+                "freeing {:#x}, which points to {} memory",
+                addr,
+                alloc.kind.desc()
+            )));
+        }
+        if kind == AllocKind::Stack {
+            self.allocs.remove(&addr);
+        } else {
+            alloc.live = false;
+            alloc.bytes = vec![];
+            alloc.init = vec![];
+        }
+        Ok(())
+    }
+
+    /// Allocation containing `[addr, addr + len)` and the offset of `addr` in it
+    fn get_alloc(&mut self, addr: u64, len: usize) -> Result<(&mut Allocation, usize), Stop> {
+        if addr == 0 {
+            return Err(ub("null pointer dereference".to_string()));
+        }
+        let dangling = || ub(format!("dangling pointer {:#x} is dereferenced", addr));
+        let Some((base, alloc)) = self.allocs.range_mut(..=addr).next_back() else {
+            return Err(dangling());
+        };
+        let offset = (addr - base) as usize;
+        if !alloc.live {
+            return Err(ub(format!("use of {:#x} after it is freed", addr)));
+        }
+        if offset + len > alloc.bytes.len() {
+            // past the guard, the allocation the pointer came from has been removed
+            if offset as u64 >= alloc.bytes.len() as u64 + GUARD_SIZE {
+                return Err(dangling());
+            }
+            return Err(ub(format!(
+                "out-of-bounds access of {} bytes at offset {} of an allocation of {} bytes",
+                len,
+                offset,
+                alloc.bytes.len()
+            )));
+        }
+        Ok((alloc, offset))
+    }
+
+    /// Read bytes which may be uninitialized (`None`)
+    pub fn read_bytes(&mut self, addr: u64, len: usize) -> Result<Vec<Option<u8>>, Stop> {
+        if len == 0 {
+            return Ok(vec![]);
+        }
+        let (alloc, offset) = self.get_alloc(addr, len)?;
+        Ok((offset..offset + len)
+            .map(|i| alloc.init[i].then_some(alloc.bytes[i]))
+            .collect())
+    }
+
+    /// Read bytes which must be initialized
+    pub fn read_init_bytes(&mut self, addr: u64, len: usize) -> Result<Vec<u8>, Stop> {
+        self.read_bytes(addr, len)?
+            .into_iter()
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| ub(format!("reading uninitialized memory at {:#x}", addr)))
+    }
+
+    pub fn write_bytes(&mut self, addr: u64, bytes: &[Option<u8>]) -> Result<(), Stop> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        let (alloc, offset) = self.get_alloc(addr, bytes.len())?;
+        for (i, byte) in bytes.iter().enumerate() {
+            alloc.bytes[offset + i] = byte.unwrap_or(0);
+            alloc.init[offset + i] = byte.is_some();
+        }
+        Ok(())
+    }
+
+    /// Read a NUL-terminated string without the NUL
+    pub fn read_c_str(&mut self, addr: u64) -> Result<Vec<u8>, Stop> {
+        let mut s = vec![];
+        loop {
+            let byte = self.read_init_bytes(addr + s.len() as u64, 1)?[0];
+            if byte == 0 {
+                return Ok(s);
+            }
+            s.push(byte);
+        }
+    }
+}
+
+pub fn ub(msg: String) -> Stop {
+    Stop::Ub(msg)
+}
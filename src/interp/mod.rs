@@ -0,0 +1,725 @@
+//! Interpreter of MIR, which runs programs for `--run` instead of emitting LLVM IR
+//!
+//! Values are laid out in memory as the LLVM backend lays them out, so the interpreter
+//! gives the reference semantics to test the backend against. Undefined behavior which
+//! compiled programs would silently run into, such as out-of-bounds accesses, reads of
+//! uninitialized memory and uses of freed memory, stops the program with an error.
+//! Common libc functions declared in `extern` blocks are emulated (see `shims`).
+
+mod memory;
+mod shims;
+
+use self::memory::{ub, AllocKind, Memory};
+use crate::middle::ty::{Ty, TyKind};
+use crate::middle::Ctxt;
+use crate::mir::{
+    AggregateKind, BasicBlock, BinOp, Body, ConstKind, Constant, ForeignFn, Operand, Place,
+    Program, Proj, Rvalue, StatementKind, Static, TerminatorKind, UnOp, RETURN_PLACE, START_BLOCK,
+};
+use crate::resolve::CanonicalPath;
+use crate::span::Span;
+use std::collections::HashMap;
+use std::io::Write;
+use std::rc::Rc;
+
+const PANIC_EXIT_CODE: i32 = 101;
+
+/// Programs with undefined behavior exit with this code after the error is reported
+const UB_EXIT_CODE: i32 = 1;
+
+/// Calls nested deeper than this overflow the stack, which aborts the program
+const MAX_CALL_DEPTH: usize = 100_000;
+const ABORT_EXIT_CODE: i32 = 134;
+
+/// Run `main` and return the exit code of the program
+pub fn run(ctx: &Ctxt, program: &Program) -> i32 {
+    let mut interp = Interpreter::new(ctx, program);
+    let code = match interp
+        .init_statics(&program.statics)
+        .and_then(|()| interp.run_main())
+    {
+        Ok(code) | Err(Stop::Exit(code)) => code,
+        Err(Stop::Ub(msg)) => {
+            let loc = interp.span.map_or(String::new(), |span| span.to_location());
+            eprintln!("Undefined behavior: {} at {}", msg, loc);
+            UB_EXIT_CODE
+        }
+        Err(Stop::Unsupported(msg)) => {
+            eprintln!("Cannot interpret the program: {}", msg);
+            UB_EXIT_CODE
+        }
+    };
+    let _ = std::io::stdout().flush();
+    code
+}
+
+/// Why the program stopped before `main` returned
+pub enum Stop {
+    /// `exit` is called or the program panicked
+    Exit(i32),
+    Ub(String),
+    /// e.g. calls to unknown foreign functions
+    Unsupported(String),
+}
+
+/// Value of a type. Structs and arrays are kept as raw bytes, which may be uninitialized
+#[derive(Debug, Clone)]
+enum Value {
+    /// Truncated to the width of the type, and sign-extended if it is signed
+    Int(i128),
+    F32(f32),
+    F64(f64),
+    Bool(bool),
+    Char(char),
+    /// Boxes, references and raw pointers
+    Ptr(u64),
+    Unit,
+    /// `None` for uninitialized bytes
+    Bytes(Vec<Option<u8>>),
+}
+
+#[derive(Clone, Copy)]
+struct Layout {
+    size: usize,
+    align: usize,
+}
+
+struct AdtLayout {
+    layout: Layout,
+    /// Offsets and types of the fields
+    fields: HashMap<Rc<String>, (usize, Rc<Ty>)>,
+}
+
+/// Call of a function body in progress
+struct Frame<'p> {
+    body: &'p Body,
+    /// Addresses of the locals
+    locals: Vec<u64>,
+    bb: BasicBlock,
+    /// Index of the next statement in the block
+    stmt: usize,
+    /// Place in the caller to store the return value to, and the block to continue at
+    ret: Option<(&'p Place, Option<BasicBlock>)>,
+}
+
+struct Interpreter<'ctx, 'p> {
+    ctx: &'p Ctxt<'ctx>,
+    bodies: HashMap<Rc<CanonicalPath>, &'p Body>,
+    foreign_fns: HashMap<Rc<CanonicalPath>, &'p ForeignFn>,
+    memory: Memory,
+    frames: Vec<Frame<'p>>,
+    adt_layouts: HashMap<Rc<CanonicalPath>, Rc<AdtLayout>>,
+    /// Addresses of string literals
+    strs: HashMap<Rc<String>, u64>,
+    statics: HashMap<Rc<CanonicalPath>, u64>,
+    /// Span of the statement being executed, which undefined behavior is reported at
+    span: Option<&'p Span>,
+}
+
+impl<'ctx, 'p> Interpreter<'ctx, 'p> {
+    fn new(ctx: &'p Ctxt<'ctx>, program: &'p Program) -> Self {
+        Interpreter {
+            ctx,
+            bodies: program
+                .bodies
+                .iter()
+                .map(|body| (Rc::clone(&body.name), body))
+                .collect(),
+            foreign_fns: program
+                .foreign_fns
+                .iter()
+                .map(|foreign_fn| (Rc::clone(&foreign_fn.name), foreign_fn))
+                .collect(),
+            memory: Memory::default(),
+            frames: vec![],
+            adt_layouts: HashMap::new(),
+            strs: HashMap::new(),
+            statics: HashMap::new(),
+            span: None,
+        }
+    }
+
+    /// Statics are placed in memory before `main` runs
+    fn init_statics(&mut self, statics: &[Static]) -> Result<(), Stop> {
+        for static_item in statics {
+            let ty = &static_item.init.ty;
+            let layout = self.layout(ty);
+            let addr = self
+                .memory
+                .allocate(layout.size, layout.align, AllocKind::Static);
+            let value = self.eval_constant(&static_item.init)?;
+            self.write_value(addr, ty, &value)?;
+            self.statics.insert(Rc::clone(&static_item.name), addr);
+        }
+        Ok(())
+    }
+
+    fn run_main(&mut self) -> Result<i32, Stop> {
+        let Some(main) = self
+            .bodies
+            .values()
+            .find(|body| body.name.demangle() == "main")
+            .copied()
+        else {
+            return Err(Stop::Unsupported("`main` function not found".to_string()));
+        };
+        match self.call_body(main, vec![])? {
+            Value::Int(code) => Ok(code as i32),
+            _ => Ok(0),
+        }
+    }
+
+    fn layout(&mut self, ty: &Ty) -> Layout {
+        let (size, align) = match &ty.kind {
+            TyKind::Int(int_ty) => (int_ty.bit_width() / 8, int_ty.bit_width() / 8),
+            TyKind::F32 | TyKind::Char => (4, 4),
+            TyKind::F64 => (8, 8),
+            TyKind::Bool => (1, 1),
+            TyKind::Unit | TyKind::Never => (0, 1),
+            TyKind::Box(_) | TyKind::Ref(..) | TyKind::RawPtr(..) => (8, 8),
+            TyKind::Array(elem, n) => {
+                let elem = self.layout(elem);
+                (elem.size * n, elem.align)
+            }
+            TyKind::Adt(name) => return self.adt_layout(name).layout,
+            _ => panic!("ICE: `{}` has no layout", ty),
+        };
+        Layout { size, align }
+    }
+
+    /// Fields are laid out in order with padding as in C
+    fn adt_layout(&mut self, name: &Rc<CanonicalPath>) -> Rc<AdtLayout> {
+        if let Some(adt_layout) = self.adt_layouts.get(name) {
+            return Rc::clone(adt_layout);
+        }
+        let adt = self.ctx.lookup_adt_def(name).unwrap();
+        let mut fields = HashMap::new();
+        let mut size: usize = 0;
+        let mut align = 1;
+        for (fd, fd_ty) in &adt.fields {
+            let fd_layout = self.layout(fd_ty);
+            size = size.next_multiple_of(fd_layout.align);
+            fields.insert(Rc::clone(fd), (size, Rc::clone(fd_ty)));
+            size += fd_layout.size;
+            align = align.max(fd_layout.align);
+        }
+        let adt_layout = Rc::new(AdtLayout {
+            layout: Layout {
+                size: size.next_multiple_of(align),
+                align,
+            },
+            fields,
+        });
+        self.adt_layouts
+            .insert(Rc::clone(name), Rc::clone(&adt_layout));
+        adt_layout
+    }
+
+    fn frame(&self) -> &Frame<'p> {
+        self.frames.last().unwrap()
+    }
+
+    /// Call a function body and run it until it returns
+    fn call_body(&mut self, body: &'p Body, args: Vec<Value>) -> Result<Value, Stop> {
+        self.push_frame(body, args, None)?;
+        self.run_frames(self.frames.len())
+    }
+
+    fn push_frame(
+        &mut self,
+        body: &'p Body,
+        args: Vec<Value>,
+        ret: Option<(&'p Place, Option<BasicBlock>)>,
+    ) -> Result<(), Stop> {
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            eprintln!("\nthread 'main' has overflowed its stack");
+            eprintln!("fatal runtime error: stack overflow");
+            return Err(Stop::Exit(ABORT_EXIT_CODE));
+        }
+        let locals = body
+            .locals
+            .iter()
+            .map(|local| {
+                let layout = self.layout(&local.ty);
+                self.memory
+                    .allocate(layout.size, layout.align, AllocKind::Stack)
+            })
+            .collect();
+        self.frames.push(Frame {
+            body,
+            locals,
+            bb: START_BLOCK,
+            stmt: 0,
+            ret,
+        });
+        for (arg, value) in body.args().zip(args) {
+            let addr = self.frame().locals[arg];
+            let ty = Rc::clone(&body.locals[arg].ty);
+            self.write_value(addr, &ty, &value)?;
+        }
+        Ok(())
+    }
+
+    /// Run until the frame at `depth` returns, and return its return value
+    fn run_frames(&mut self, depth: usize) -> Result<Value, Stop> {
+        loop {
+            let frame = self.frame();
+            let block = &frame.body.blocks[frame.bb];
+            if let Some(stmt) = block.stmts.get(frame.stmt) {
+                self.frames.last_mut().unwrap().stmt += 1;
+                self.span = Some(&stmt.span);
+                self.exec_stmt(&stmt.kind)?;
+                continue;
+            }
+
+            let terminator = &block.terminator;
+            self.span = Some(&terminator.span);
+            let next_bb = match &terminator.kind {
+                TerminatorKind::Goto(bb) => *bb,
+                TerminatorKind::If(cond, then_bb, else_bb) => match self.eval_operand(cond)? {
+                    Value::Bool(true) => *then_bb,
+                    _ => *else_bb,
+                },
+                TerminatorKind::Call {
+                    func,
+                    args,
+                    dest,
+                    target,
+                } => {
+                    let args = args
+                        .iter()
+                        .map(|arg| self.eval_operand(arg))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if let Some(body) = self.bodies.get(func).copied() {
+                        self.push_frame(body, args, Some((dest, *target)))?;
+                        continue;
+                    }
+                    let foreign_fn = self.foreign_fns[func];
+                    let value = self.call_foreign_fn(foreign_fn, args)?;
+                    let addr = self.place_addr(dest)?;
+                    self.write_value(addr, &foreign_fn.ret_ty, &value)?;
+                    self.diverging_target(*target)?
+                }
+                TerminatorKind::Return => {
+                    let value = self.read_local(RETURN_PLACE)?;
+                    let frame = self.frames.pop().unwrap();
+                    for addr in frame.locals {
+                        self.memory.deallocate(addr, AllocKind::Stack)?;
+                    }
+                    if self.frames.len() < depth {
+                        return Ok(value);
+                    }
+                    let (dest, target) = frame.ret.unwrap();
+                    let addr = self.place_addr(dest)?;
+                    let ty = dest.ty(self.frame().body, self.ctx);
+                    self.write_value(addr, &ty, &value)?;
+                    self.diverging_target(target)?
+                }
+                TerminatorKind::Panic(None) => {
+                    let _ = std::io::stdout().flush();
+                    return Err(Stop::Exit(PANIC_EXIT_CODE));
+                }
+                TerminatorKind::Panic(Some(msg)) => {
+                    let _ = std::io::stdout().flush();
+                    eprintln!(
+                        "thread 'main' panicked at {}:\n{}",
+                        terminator.span.to_location(),
+                        msg
+                    );
+                    return Err(Stop::Exit(PANIC_EXIT_CODE));
+                }
+                TerminatorKind::Unreachable => {
+                    return Err(ub("entering unreachable code".to_string()));
+                }
+            };
+            let frame = self.frames.last_mut().unwrap();
+            frame.bb = next_bb;
+            frame.stmt = 0;
+        }
+    }
+
+    /// Calls without targets are to diverging functions, which must not return
+    fn diverging_target(&self, target: Option<BasicBlock>) -> Result<BasicBlock, Stop> {
+        target.ok_or_else(|| ub("a function returning `!` returned".to_string()))
+    }
+
+    fn read_local(&mut self, local: usize) -> Result<Value, Stop> {
+        let addr = self.frame().locals[local];
+        let ty = Rc::clone(&self.frame().body.locals[local].ty);
+        self.read_value(addr, &ty)
+    }
+
+    fn exec_stmt(&mut self, stmt: &'p StatementKind) -> Result<(), Stop> {
+        match stmt {
+            StatementKind::Assign(place, rvalue) => {
+                let addr = self.place_addr(place)?;
+                let ty = place.ty(self.frame().body, self.ctx);
+                match rvalue {
+                    Rvalue::Aggregate(kind, ops) => self.write_aggregate(addr, &ty, kind, ops),
+                    _ => {
+                        let value = self.eval_rvalue(rvalue)?;
+                        self.write_value(addr, &ty, &value)
+                    }
+                }
+            }
+            StatementKind::Drop(place) => {
+                let addr = self.place_addr(place)?;
+                let ty = place.ty(self.frame().body, self.ctx);
+                self.drop_in_place(addr, &ty)
+            }
+            StatementKind::Deallocate(place) => {
+                let addr = self.place_addr(place)?;
+                let boxed = self.read_ptr(addr)?;
+                self.memory.deallocate(boxed, AllocKind::Heap)
+            }
+            StatementKind::Print(dest, pieces, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| {
+                        let ty = arg.ty(self.frame().body, self.ctx);
+                        Ok((self.eval_operand(arg)?, ty))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.print(*dest, pieces, args)
+            }
+        }
+    }
+
+    /// Run the destructors of the value, and free the boxes it owns
+    fn drop_in_place(&mut self, addr: u64, ty: &Ty) -> Result<(), Stop> {
+        match &ty.kind {
+            TyKind::Box(inner) => {
+                let boxed = self.read_ptr(addr)?;
+                if self.ctx.needs_drop(inner) {
+                    self.drop_in_place(boxed, inner)?;
+                }
+                self.memory.deallocate(boxed, AllocKind::Heap)
+            }
+            TyKind::Adt(name) => {
+                // the destructor runs before the fields are dropped
+                if let Some(drop_fn) = self.ctx.lookup_drop_impl(name) {
+                    let body = self.bodies[&drop_fn.cpath];
+                    let span = self.span;
+                    self.call_body(body, vec![Value::Ptr(addr)])?;
+                    self.span = span;
+                }
+                let adt_layout = self.adt_layout(name);
+                let adt = self.ctx.lookup_adt_def(name).unwrap();
+                for (fd, fd_ty) in &adt.fields {
+                    if self.ctx.needs_drop(fd_ty) {
+                        let (offset, _) = adt_layout.fields[fd];
+                        self.drop_in_place(addr + offset as u64, fd_ty)?;
+                    }
+                }
+                Ok(())
+            }
+            TyKind::Array(elem, n) => {
+                let elem_size = self.layout(elem).size;
+                for i in 0..*n {
+                    self.drop_in_place(addr + (i * elem_size) as u64, elem)?;
+                }
+                Ok(())
+            }
+            _ => panic!("ICE: `{}` does not need drop", ty),
+        }
+    }
+
+    fn place_addr(&mut self, place: &Place) -> Result<u64, Stop> {
+        let body = self.frame().body;
+        let mut addr = self.frame().locals[place.local];
+        let mut ty = Rc::clone(&body.locals[place.local].ty);
+        for proj in &place.projs {
+            match (proj, &ty.kind) {
+                (Proj::Deref, _) => addr = self.read_ptr(addr)?,
+                (Proj::Field(field), TyKind::Adt(name)) => {
+                    let (offset, _) = self.adt_layout(name).fields[field];
+                    addr += offset as u64;
+                }
+                (Proj::Index(index), TyKind::Array(elem, n)) => {
+                    let Value::Int(i) = self.read_local(*index)? else {
+                        panic!("ICE: index is not an integer");
+                    };
+                    if i < 0 || i >= *n as i128 {
+                        return Err(ub(format!(
+                            "index out of bounds: the len is {} but the index is {}",
+                            n, i
+                        )));
+                    }
+                    addr += (i as usize * self.layout(elem).size) as u64;
+                }
+                _ => panic!("ICE: cannot project `{}` with {:?}", ty, proj),
+            }
+            ty = crate::mir::project_ty(&ty, proj, self.ctx);
+        }
+        Ok(addr)
+    }
+
+    fn read_ptr(&mut self, addr: u64) -> Result<u64, Stop> {
+        let bytes = self.memory.read_init_bytes(addr, 8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_value(&mut self, addr: u64, ty: &Ty) -> Result<Value, Stop> {
+        let layout = self.layout(ty);
+        if matches!(ty.kind, TyKind::Adt(_) | TyKind::Array(..)) {
+            return Ok(Value::Bytes(self.memory.read_bytes(addr, layout.size)?));
+        }
+        if layout.size == 0 {
+            return Ok(Value::Unit);
+        }
+        let bytes = self.memory.read_init_bytes(addr, layout.size)?;
+        let mut buf = [0; 16];
+        buf[..bytes.len()].copy_from_slice(&bytes);
+        let bits = u128::from_le_bytes(buf);
+        let value = match &ty.kind {
+            TyKind::Int(int_ty) => Value::Int(int_ty.truncate(bits as i128)),
+            TyKind::F32 => Value::F32(f32::from_bits(bits as u32)),
+            TyKind::F64 => Value::F64(f64::from_bits(bits as u64)),
+            TyKind::Bool => match bits {
+                0 => Value::Bool(false),
+                1 => Value::Bool(true),
+                _ => return Err(ub(format!("invalid value {} for `bool`", bits))),
+            },
+            TyKind::Char => match char::from_u32(bits as u32) {
+                Some(c) => Value::Char(c),
+                None => return Err(ub(format!("invalid value {:#x} for `char`", bits))),
+            },
+            _ => Value::Ptr(bits as u64),
+        };
+        Ok(value)
+    }
+
+    fn write_value(&mut self, addr: u64, ty: &Ty, value: &Value) -> Result<(), Stop> {
+        let size = self.layout(ty).size;
+        let bits: u128 = match value {
+            Value::Bytes(bytes) => return self.memory.write_bytes(addr, bytes),
+            Value::Unit => return Ok(()),
+            Value::Int(n) => *n as u128,
+            Value::F32(f) => u128::from(f.to_bits()),
+            Value::F64(f) => u128::from(f.to_bits()),
+            Value::Bool(b) => u128::from(*b),
+            Value::Char(c) => u128::from(u32::from(*c)),
+            Value::Ptr(p) => u128::from(*p),
+        };
+        let bytes: Vec<Option<u8>> = bits.to_le_bytes()[..size]
+            .iter()
+            .map(|b| Some(*b))
+            .collect();
+        self.memory.write_bytes(addr, &bytes)
+    }
+
+    fn write_aggregate(
+        &mut self,
+        addr: u64,
+        ty: &Ty,
+        kind: &AggregateKind,
+        ops: &[Operand],
+    ) -> Result<(), Stop> {
+        match (kind, &ty.kind) {
+            (AggregateKind::Adt(name), _) => {
+                let adt_layout = self.adt_layout(name);
+                let adt = self.ctx.lookup_adt_def(name).unwrap();
+                for ((fd, fd_ty), op) in adt.fields.iter().zip(ops) {
+                    let value = self.eval_operand(op)?;
+                    let (offset, _) = adt_layout.fields[fd];
+                    self.write_value(addr + offset as u64, fd_ty, &value)?;
+                }
+            }
+            (AggregateKind::Array, TyKind::Array(elem, _)) => {
+                let elem_size = self.layout(elem).size;
+                for (i, op) in ops.iter().enumerate() {
+                    let value = self.eval_operand(op)?;
+                    self.write_value(addr + (i * elem_size) as u64, elem, &value)?;
+                }
+            }
+            _ => panic!("ICE: aggregate of type `{}`", ty),
+        }
+        Ok(())
+    }
+
+    fn eval_operand(&mut self, op: &Operand) -> Result<Value, Stop> {
+        match op {
+            Operand::Copy(place, _) | Operand::Move(place, _) => {
+                let addr = self.place_addr(place)?;
+                let ty = place.ty(self.frame().body, self.ctx);
+                self.read_value(addr, &ty)
+            }
+            Operand::Const(cons) => self.eval_constant(cons),
+        }
+    }
+
+    fn eval_constant(&mut self, cons: &Constant) -> Result<Value, Stop> {
+        let value = match (&cons.kind, &cons.ty.kind) {
+            // literals out of the range of the type are truncated
+            (ConstKind::Int(n), TyKind::Int(int_ty)) => Value::Int(int_ty.truncate(i128::from(*n))),
+            (ConstKind::Float(f), TyKind::F32) => Value::F32(*f as f32),
+            (ConstKind::Float(f), _) => Value::F64(*f),
+            (ConstKind::Bool(b), _) => Value::Bool(*b),
+            (ConstKind::Char(c), _) => Value::Char(*c),
+            (ConstKind::Str(s), _) => Value::Ptr(self.str_addr(s)?),
+            (ConstKind::Unit, _) => Value::Unit,
+            (ConstKind::Null, _) => Value::Ptr(0),
+            (ConstKind::Static(name), _) => Value::Ptr(self.statics[name]),
+            (ConstKind::Int(_), _) => panic!("ICE: integer constant of type `{}`", cons.ty),
+        };
+        Ok(value)
+    }
+
+    /// String literals are NUL-terminated as in the LLVM backend
+    fn str_addr(&mut self, s: &Rc<String>) -> Result<u64, Stop> {
+        if let Some(addr) = self.strs.get(s) {
+            return Ok(*addr);
+        }
+        let mut bytes: Vec<Option<u8>> = s.bytes().map(Some).collect();
+        bytes.push(Some(0));
+        let addr = self.memory.allocate(bytes.len(), 1, AllocKind::Static);
+        self.memory.write_bytes(addr, &bytes)?;
+        self.strs.insert(Rc::clone(s), addr);
+        Ok(addr)
+    }
+
+    fn eval_rvalue(&mut self, rvalue: &Rvalue) -> Result<Value, Stop> {
+        let value = match rvalue {
+            Rvalue::Use(op) => self.eval_operand(op)?,
+            Rvalue::BinaryOp(binop, lhs, rhs) => {
+                let ty = lhs.ty(self.frame().body, self.ctx);
+                let l = self.eval_operand(lhs)?;
+                let r = self.eval_operand(rhs)?;
+                self.eval_binary(*binop, l, r, &ty)?
+            }
+            Rvalue::UnaryOp(UnOp::Neg, inner) => {
+                let ty = inner.ty(self.frame().body, self.ctx);
+                match (self.eval_operand(inner)?, &ty.kind) {
+                    (Value::Int(n), TyKind::Int(int_ty)) => Value::Int(int_ty.truncate(-n)),
+                    (Value::F32(f), _) => Value::F32(-f),
+                    (Value::F64(f), _) => Value::F64(-f),
+                    (value, _) => panic!("ICE: cannot negate {:?}", value),
+                }
+            }
+            Rvalue::Cast(inner, to_ty) => {
+                let from_ty = inner.ty(self.frame().body, self.ctx);
+                let value = self.eval_operand(inner)?;
+                eval_cast(value, &from_ty, to_ty)
+            }
+            Rvalue::Ref(_, place) => Value::Ptr(self.place_addr(place)?),
+            Rvalue::Box(inner) => {
+                let ty = inner.ty(self.frame().body, self.ctx);
+                let value = self.eval_operand(inner)?;
+                let layout = self.layout(&ty);
+                let addr = self
+                    .memory
+                    .allocate(layout.size, layout.align, AllocKind::Heap);
+                self.write_value(addr, &ty, &value)?;
+                Value::Ptr(addr)
+            }
+            Rvalue::Aggregate(..) => panic!("ICE: aggregates are written to memory"),
+        };
+        Ok(value)
+    }
+
+    /// `ty` is the type of the left operand
+    fn eval_binary(&mut self, binop: BinOp, l: Value, r: Value, ty: &Ty) -> Result<Value, Stop> {
+        let value = match (l, r) {
+            (Value::Ptr(p), Value::Int(count)) if binop == BinOp::Offset => {
+                let TyKind::RawPtr(pointee, _) = &ty.kind else {
+                    panic!("ICE: offset of `{}`", ty);
+                };
+                let size = self.layout(pointee).size as i128;
+                Value::Ptr((p as i128).wrapping_add(count.wrapping_mul(size)) as u64)
+            }
+            (Value::Int(a), Value::Int(b)) => {
+                let TyKind::Int(int_ty) = ty.kind else {
+                    panic!("ICE: integer of type `{}`", ty);
+                };
+                match binop {
+                    BinOp::Add => Value::Int(int_ty.truncate(a.wrapping_add(b))),
+                    BinOp::Sub => Value::Int(int_ty.truncate(a.wrapping_sub(b))),
+                    BinOp::Mul => Value::Int(int_ty.truncate(a.wrapping_mul(b))),
+                    // division by zero and overflow are checked before
+                    BinOp::Div | BinOp::Rem if b == 0 => {
+                        return Err(ub("division by zero".to_string()));
+                    }
+                    BinOp::Div | BinOp::Rem
+                        if int_ty.is_signed() && a == int_ty.min_value() && b == -1 =>
+                    {
+                        return Err(ub("division overflow".to_string()));
+                    }
+                    BinOp::Div => Value::Int(a / b),
+                    BinOp::Rem => Value::Int(a % b),
+                    _ => Value::Bool(compare(binop, a.cmp(&b))),
+                }
+            }
+            (Value::F32(a), Value::F32(b)) => float_binary(binop, f64::from(a), f64::from(b))
+                .map_or_else(Value::Bool, |f| Value::F32(f as f32)),
+            (Value::F64(a), Value::F64(b)) => {
+                float_binary(binop, a, b).map_or_else(Value::Bool, Value::F64)
+            }
+            (Value::Bool(a), Value::Bool(b)) => Value::Bool(compare(binop, a.cmp(&b))),
+            (Value::Char(a), Value::Char(b)) => Value::Bool(compare(binop, a.cmp(&b))),
+            (Value::Ptr(a), Value::Ptr(b)) => Value::Bool(compare(binop, a.cmp(&b))),
+            (l, r) => panic!("ICE: invalid operands {:?} and {:?} of {:?}", l, r, binop),
+        };
+        Ok(value)
+    }
+}
+
+fn compare(binop: BinOp, ordering: std::cmp::Ordering) -> bool {
+    match binop {
+        BinOp::Eq => ordering.is_eq(),
+        BinOp::Ne => ordering.is_ne(),
+        BinOp::Gt => ordering.is_gt(),
+        BinOp::Lt => ordering.is_lt(),
+        _ => panic!("ICE: {:?} is not a comparison", binop),
+    }
+}
+
+/// Arithmetic gives `Ok`, and comparisons give `Err` with the result. Ordered
+/// comparisons are false for NaN, but `!=` is true
+fn float_binary(binop: BinOp, a: f64, b: f64) -> Result<f64, bool> {
+    match binop {
+        BinOp::Add => Ok(a + b),
+        BinOp::Sub => Ok(a - b),
+        BinOp::Mul => Ok(a * b),
+        BinOp::Div => Ok(a / b),
+        BinOp::Rem => Ok(a % b),
+        BinOp::Eq => Err(a == b),
+        BinOp::Ne => Err(a != b),
+        BinOp::Gt => Err(a > b),
+        BinOp::Lt => Err(a < b),
+        BinOp::Offset => panic!("ICE: offset of a float"),
+    }
+}
+
+/// `value as to_ty`
+// ref: https://doc.rust-lang.org/reference/expressions/operator-expr.html#type-cast-expressions
+fn eval_cast(value: Value, from_ty: &Ty, to_ty: &Ty) -> Value {
+    match (value, &to_ty.kind) {
+        (Value::Int(n), TyKind::Int(int_ty)) => Value::Int(int_ty.truncate(n)),
+        (Value::Bool(b), TyKind::Int(_)) => Value::Int(i128::from(b)),
+        (Value::Char(c), TyKind::Int(int_ty)) => {
+            Value::Int(int_ty.truncate(i128::from(u32::from(c))))
+        }
+        (Value::Ptr(p), TyKind::Int(int_ty)) => Value::Int(int_ty.truncate(i128::from(p))),
+        // only `u8` can be cast to `char`
+        (Value::Int(n), TyKind::Char) => Value::Char(char::from(n as u8)),
+        // integers are sign-extended to 64 bits before being cast to pointers
+        (Value::Int(n), TyKind::RawPtr(..)) => Value::Ptr(n as u64),
+        (Value::Int(n), TyKind::F32) => Value::F32(n as f32),
+        (Value::Int(n), TyKind::F64) => Value::F64(n as f64),
+        // float-to-int casts saturate, and NaN is cast to 0
+        (Value::F32(f), TyKind::Int(int_ty)) => Value::Int(saturate(f64::from(f), *int_ty)),
+        (Value::F64(f), TyKind::Int(int_ty)) => Value::Int(saturate(f, *int_ty)),
+        (Value::F32(f), TyKind::F64) => Value::F64(f64::from(f)),
+        (Value::F64(f), TyKind::F32) => Value::F32(f as f32),
+        (value, _) if from_ty.kind == to_ty.kind || matches!(value, Value::Ptr(_)) => value,
+        (value, _) => panic!("ICE: invalid cast of {:?} to `{}`", value, to_ty),
+    }
+}
+
+fn saturate(f: f64, int_ty: crate::ast::IntTy) -> i128 {
+    if f.is_nan() {
+        0
+    } else {
+        (f as i128).clamp(int_ty.min_value(), int_ty.max_value())
+    }
+}
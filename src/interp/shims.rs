@@ -0,0 +1,376 @@
+//! Emulation of the libc functions which programs declare in `extern` blocks, and of
+//! `print!`, which the LLVM backend lowers to `printf`
+
+use super::memory::{ub, AllocKind};
+use super::{Interpreter, Stop, Value, ABORT_EXIT_CODE};
+use crate::ast::{FormatAlign, FormatPiece, PrintDest};
+use crate::middle::ty::{Ty, TyKind};
+use crate::mir::ForeignFn;
+use crate::parse::conversion_spec;
+use std::io::Write;
+use std::rc::Rc;
+
+/// Argument of `printf`. Pointers are read as strings only by `%s`
+enum PrintfArg {
+    Int(i128),
+    Float(f64),
+    Ptr(u64),
+    Str(Vec<u8>),
+}
+
+impl Interpreter<'_, '_> {
+    pub(super) fn call_foreign_fn(
+        &mut self,
+        foreign_fn: &ForeignFn,
+        args: Vec<Value>,
+    ) -> Result<Value, Stop> {
+        let name = foreign_fn.name.demangle();
+        let value = match (name.as_str(), args.as_slice()) {
+            ("puts", [Value::Ptr(s)]) => {
+                let mut s = self.memory.read_c_str(*s)?;
+                s.push(b'\n');
+                write_out(PrintDest::Stdout, &s);
+                Value::Int(s.len() as i128)
+            }
+            ("putchar", [Value::Int(c)]) => {
+                write_out(PrintDest::Stdout, &[*c as u8]);
+                Value::Int(i128::from(*c as u8))
+            }
+            ("printf", [Value::Ptr(fmt), rest @ ..]) => {
+                let fmt = self.memory.read_c_str(*fmt)?;
+                let args = rest.iter().map(printf_arg).collect::<Vec<_>>();
+                let s = self.format_printf(&fmt, &args)?;
+                write_out(PrintDest::Stdout, &s);
+                Value::Int(s.len() as i128)
+            }
+            ("malloc", [Value::Int(size)]) => {
+                Value::Ptr(self.memory.allocate(*size as usize, 16, AllocKind::Heap))
+            }
+            ("calloc", [Value::Int(n), Value::Int(size)]) => {
+                let size = (*n * *size) as usize;
+                let addr = self.memory.allocate(size, 16, AllocKind::Heap);
+                self.memory.write_bytes(addr, &vec![Some(0); size])?;
+                Value::Ptr(addr)
+            }
+            ("free", [Value::Ptr(0)]) => Value::Unit,
+            ("free", [Value::Ptr(p)]) => {
+                self.memory.deallocate(*p, AllocKind::Heap)?;
+                Value::Unit
+            }
+            ("exit", [Value::Int(code)]) => {
+                let _ = std::io::stdout().flush();
+                return Err(Stop::Exit(*code as i32));
+            }
+            ("abort", []) => {
+                let _ = std::io::stdout().flush();
+                return Err(Stop::Exit(ABORT_EXIT_CODE));
+            }
+            ("abs" | "labs", [Value::Int(n)]) => Value::Int(int_of(&foreign_fn.ret_ty, n.abs())),
+            ("strlen", [Value::Ptr(s)]) => Value::Int(self.memory.read_c_str(*s)?.len() as i128),
+            ("sqrt", [Value::F64(f)]) => Value::F64(f.sqrt()),
+            ("ldexp", [Value::F64(f), Value::Int(e)]) => Value::F64(f * 2f64.powi(*e as i32)),
+            _ => {
+                return Err(Stop::Unsupported(format!(
+                    "cannot call foreign function `{}` in the interpreter",
+                    name
+                )));
+            }
+        };
+        Ok(value)
+    }
+
+    /// Format and print the arguments as the printf call generated by the LLVM backend
+    pub(super) fn print(
+        &mut self,
+        dest: PrintDest,
+        pieces: &[FormatPiece],
+        args: Vec<(Value, Rc<Ty>)>,
+    ) -> Result<(), Stop> {
+        let mut fmt = String::new();
+        let mut printf_args = vec![];
+        for piece in pieces {
+            let spec = match piece {
+                FormatPiece::Lit(s) => {
+                    fmt.push_str(&s.replace('%', "%%"));
+                    continue;
+                }
+                FormatPiece::Placeholder(spec) => spec,
+            };
+            let (value, ty) = &args[spec.arg];
+            match (value, &ty.kind) {
+                (Value::Int(n), TyKind::Int(int_ty)) => {
+                    let conversion = match (int_ty.is_signed(), int_ty.bit_width()) {
+                        (true, 64) => "ld",
+                        (true, _) => "d",
+                        (false, 64) => "lu",
+                        (false, _) => "u",
+                    };
+                    // precision is ignored for integers
+                    fmt.push_str(&conversion_spec(
+                        spec,
+                        FormatAlign::Right,
+                        false,
+                        conversion,
+                    ));
+                    printf_args.push(PrintfArg::Int(*n));
+                }
+                (Value::F32(_) | Value::F64(_), _) => {
+                    let PrintfArg::Float(f) = printf_arg(value) else {
+                        unreachable!()
+                    };
+                    if spec.precision.is_some() {
+                        fmt.push_str(&conversion_spec(spec, FormatAlign::Right, true, "f"));
+                        printf_args.push(PrintfArg::Float(f));
+                    } else {
+                        fmt.push_str(&conversion_spec(spec, FormatAlign::Right, false, "s"));
+                        let is_f32 = matches!(value, Value::F32(_));
+                        let s = format_float_shortest(f, is_f32, spec.debug);
+                        printf_args.push(PrintfArg::Str(s.into_bytes()));
+                    }
+                }
+                (Value::Bool(b), _) => {
+                    fmt.push_str(&conversion_spec(spec, FormatAlign::Left, true, "s"));
+                    let s = if *b { "true" } else { "false" };
+                    printf_args.push(PrintfArg::Str(s.as_bytes().to_vec()));
+                }
+                (Value::Char(c), _) => {
+                    let s = c.to_string().into_bytes();
+                    if spec.debug {
+                        fmt.push_str("%s");
+                        printf_args.push(PrintfArg::Str(escape_debug(&s, b'\'')));
+                    } else {
+                        fmt.push_str(&conversion_spec(spec, FormatAlign::Left, true, "s"));
+                        printf_args.push(PrintfArg::Str(s));
+                    }
+                }
+                (Value::Ptr(s), _) => {
+                    if spec.debug {
+                        fmt.push_str("%s");
+                        let s = self.memory.read_c_str(*s)?;
+                        printf_args.push(PrintfArg::Str(escape_debug(&s, b'"')));
+                    } else {
+                        fmt.push_str(&conversion_spec(spec, FormatAlign::Left, true, "s"));
+                        printf_args.push(PrintfArg::Ptr(*s));
+                    }
+                }
+                _ => panic!("ICE: argument of print is not formattable"),
+            }
+        }
+        let s = self.format_printf(fmt.as_bytes(), &printf_args)?;
+        write_out(dest, &s);
+        Ok(())
+    }
+
+    /// Format as `printf` of glibc. Supported are the flags `-` and `0`, width, precision,
+    /// the length modifiers `hh`, `h`, `l`, `ll` and `z`, and the conversions
+    /// `d`, `i`, `u`, `x`, `X`, `o`, `c`, `s`, `p`, `f` and `%`
+    fn format_printf(&mut self, fmt: &[u8], args: &[PrintfArg]) -> Result<Vec<u8>, Stop> {
+        let mut out = vec![];
+        let mut args = args.iter();
+        let mut i = 0;
+        while i < fmt.len() {
+            if fmt[i] != b'%' {
+                out.push(fmt[i]);
+                i += 1;
+                continue;
+            }
+            i += 1;
+            let mut left = false;
+            let mut zero = false;
+            while let Some(flag @ (b'-' | b'0')) = fmt.get(i) {
+                left |= *flag == b'-';
+                zero |= *flag == b'0';
+                i += 1;
+            }
+            let width = parse_num(fmt, &mut i).unwrap_or(0);
+            let precision = if fmt.get(i) == Some(&b'.') {
+                i += 1;
+                Some(parse_num(fmt, &mut i).unwrap_or(0))
+            } else {
+                None
+            };
+            let mut bits = 32;
+            while let Some(modifier @ (b'h' | b'l' | b'z')) = fmt.get(i) {
+                bits = match (modifier, bits) {
+                    (b'h', 32) => 16,
+                    (b'h', _) => 8,
+                    _ => 64,
+                };
+                i += 1;
+            }
+            let Some(&conversion) = fmt.get(i) else {
+                return Err(ub(
+                    "incomplete conversion in the format of `printf`".to_string()
+                ));
+            };
+            i += 1;
+            if conversion == b'%' {
+                out.push(b'%');
+                continue;
+            }
+            let Some(arg) = args.next() else {
+                return Err(ub(
+                    "too few arguments for the format of `printf`".to_string()
+                ));
+            };
+            let (body, numeric): (Vec<u8>, bool) = match (conversion, arg) {
+                (b'd' | b'i', PrintfArg::Int(n)) => {
+                    (sign_extend(*n, bits).to_string().into(), true)
+                }
+                (b'u', PrintfArg::Int(n)) => (zero_extend(*n, bits).to_string().into(), true),
+                (b'x', PrintfArg::Int(n)) => (format!("{:x}", zero_extend(*n, bits)).into(), true),
+                (b'X', PrintfArg::Int(n)) => (format!("{:X}", zero_extend(*n, bits)).into(), true),
+                (b'o', PrintfArg::Int(n)) => (format!("{:o}", zero_extend(*n, bits)).into(), true),
+                (b'c', PrintfArg::Int(n)) => (vec![*n as u8], false),
+                (b'p', PrintfArg::Ptr(0)) => (b"(nil)".to_vec(), false),
+                (b'p', PrintfArg::Ptr(p)) => (format!("{:#x}", p).into(), false),
+                (b'f', PrintfArg::Float(f)) => {
+                    (format_float(*f, precision.unwrap_or(6)).into(), true)
+                }
+                (b's', PrintfArg::Str(s)) => (truncate_str(s, precision), false),
+                (b's', PrintfArg::Ptr(p)) => {
+                    let s = self.memory.read_c_str(*p)?;
+                    (truncate_str(&s, precision), false)
+                }
+                _ => {
+                    return Err(ub(format!(
+                        "argument does not match `%{}` in the format of `printf`",
+                        char::from(conversion)
+                    )));
+                }
+            };
+            let pad = width.saturating_sub(body.len());
+            if left {
+                out.extend(&body);
+                out.extend(std::iter::repeat_n(b' ', pad));
+            } else if zero && numeric {
+                // zeros go after the sign
+                let sign = usize::from(body.first() == Some(&b'-'));
+                out.extend(&body[..sign]);
+                out.extend(std::iter::repeat_n(b'0', pad));
+                out.extend(&body[sign..]);
+            } else {
+                out.extend(std::iter::repeat_n(b' ', pad));
+                out.extend(&body);
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Enclose `s` in `quote` and escape it like `{:?}`, as the runtime of the backends does
+fn escape_debug(s: &[u8], quote: u8) -> Vec<u8> {
+    let mut out = vec![quote];
+    for &b in s {
+        match b {
+            b'\t' => out.extend(b"\\t"),
+            b'\r' => out.extend(b"\\r"),
+            b'\n' => out.extend(b"\\n"),
+            b'\\' => out.extend(b"\\\\"),
+            0 => out.extend(b"\\0"),
+            _ if b == quote => out.extend([b'\\', b]),
+            0..=31 | 127 => out.extend(format!("\\u{{{b:x}}}").into_bytes()),
+            _ => out.push(b),
+        }
+    }
+    out.push(quote);
+    out
+}
+
+fn printf_arg(value: &Value) -> PrintfArg {
+    match value {
+        Value::Int(n) => PrintfArg::Int(*n),
+        Value::Bool(b) => PrintfArg::Int(i128::from(*b)),
+        Value::Char(c) => PrintfArg::Int(i128::from(u32::from(*c))),
+        // variadic `float` arguments are promoted to `double`
+        Value::F32(f) => PrintfArg::Float(f64::from(*f)),
+        Value::F64(f) => PrintfArg::Float(*f),
+        Value::Ptr(p) => PrintfArg::Ptr(*p),
+        Value::Unit | Value::Bytes(_) => PrintfArg::Int(0),
+    }
+}
+
+fn parse_num(fmt: &[u8], i: &mut usize) -> Option<usize> {
+    let start = *i;
+    while fmt.get(*i).is_some_and(u8::is_ascii_digit) {
+        *i += 1;
+    }
+    std::str::from_utf8(&fmt[start..*i]).unwrap().parse().ok()
+}
+
+fn sign_extend(n: i128, bits: u32) -> i128 {
+    (n << (128 - bits)) >> (128 - bits)
+}
+
+fn zero_extend(n: i128, bits: u32) -> u128 {
+    (n as u128) & ((1 << bits) - 1)
+}
+
+fn format_float(f: f64, precision: usize) -> String {
+    if f.is_nan() {
+        "nan".to_string()
+    } else if f.is_infinite() {
+        if f > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        format!("{:.*}", precision, f)
+    }
+}
+
+/// Format like `{}` or `{:?}` as the runtime of the backends does. The shortest digits
+/// which are read back to the value are printed, and `{:?}` uses the exponent form for
+/// values below 1e-4 or from 1e16
+fn format_float_shortest(f: f64, is_f32: bool, debug: bool) -> String {
+    if f.is_nan() {
+        return "NaN".to_string();
+    } else if f.is_infinite() {
+        return if f > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    let reads_back = |s: &str| {
+        let read: f64 = s.parse().unwrap();
+        if is_f32 {
+            read as f32 == f as f32
+        } else {
+            read == f
+        }
+    };
+    // 17 significant digits are enough for any `f64`
+    let digits = (1..17).find(|digits| reads_back(&format!("{:.*e}", digits - 1, f)));
+    let digits = digits.unwrap_or(17);
+    let sci = format!("{:.*e}", digits - 1, f);
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    if debug && f != 0.0 && !(-4..=15).contains(&exp) {
+        return format!("{mantissa}e{exp}");
+    }
+    let s = format!("{:.*}", (digits as i32 - 1 - exp).max(0) as usize, f);
+    if debug && !s.contains('.') {
+        s + ".0"
+    } else {
+        s
+    }
+}
+
+/// Precision of `%s` is the maximum number of bytes printed
+fn truncate_str(s: &[u8], precision: Option<usize>) -> Vec<u8> {
+    let len = precision.map_or(s.len(), |precision| precision.min(s.len()));
+    s[..len].to_vec()
+}
+
+/// Result of an integer function of the type, which is `i32` for `abs`
+fn int_of(ty: &Ty, n: i128) -> i128 {
+    match &ty.kind {
+        TyKind::Int(int_ty) => int_ty.truncate(n),
+        _ => n,
+    }
+}
+
+fn write_out(dest: PrintDest, s: &[u8]) {
+    // stdout is flushed first, so the order of the output is kept when both go to a terminal
+    let _ = match dest {
+        PrintDest::Stdout => std::io::stdout().write_all(s),
+        PrintDest::Stderr => {
+            let _ = std::io::stdout().flush();
+            std::io::stderr().write_all(s)
+        }
+    };
+}
@@ -1,17 +1,22 @@
-use crate::span::Span;
+use crate::ast::IntTy;
+use crate::span::{SourceFile, Span};
 use std::{collections::VecDeque, iter::Peekable, rc::Rc, vec::IntoIter};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Token {
     pub kind: TokenKind,
     pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TokenKind {
     // keywords
-    I32,
+    /// `i32`, `u8`, `usize`, ...
+    Int(IntTy),
+    F32,
+    F64,
     Str,
+    Char,
     Let,
     Return,
     Fn,
@@ -24,6 +29,7 @@ pub enum TokenKind {
     Extern,
     Unsafe,
     Mod,
+    Type,
     Loop,
     While,
     Break,
@@ -31,8 +37,13 @@ pub enum TokenKind {
     As,
     Mut,
     Const,
+    Static,
+    Impl,
+    For,
     /// ->
     Arrow,
+    /// =>
+    FatArrow,
     /// !
     Bang,
     Eq,
@@ -44,6 +55,8 @@ pub enum TokenKind {
     ColCol,
     Comma,
     Dot,
+    /// ..
+    DotDot,
     /// (
     OpenParen,
     /// )
@@ -56,25 +69,37 @@ pub enum TokenKind {
     OpenBrace,
     /// }
     CloseBrace,
+    /// $
+    Dollar,
+    /// ?
+    Question,
+    /// #
+    Pound,
     BinOp(BinOp),
     /// Identifier
     Ident(String),
     Lifetime(String),
     /// Number
-    NumLit(u32),
+    NumLit(u128),
+    /// Floating-point literal and its suffix (e.g. `1.5e3f32` => `("1.5e3", Some("f32"))`)
+    FloatLit(String, Option<String>),
     /// String literal
     StrLit(String),
+    /// Character literal
+    CharLit(char),
     /// EOF
     Eof,
     /// Unknown character
     Unknown,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BinOp {
     Plus,
     Minus,
     Star,
+    Slash,
+    Percent,
     Eq,
     Ne,
     Gt,
@@ -91,25 +116,38 @@ pub struct Lexer {
     current_pos: usize,
     char_stream: Peekable<IntoIter<char>>,
     buffered_tokens: VecDeque<Token>,
-    src: Rc<String>,
+    file: Rc<SourceFile>,
 }
 
 impl Lexer {
     pub fn new(src: String) -> Self {
-        let char_stream = src.chars().collect::<Vec<char>>().into_iter().peekable();
+        Lexer::from_file(SourceFile::new("<anon>".to_string(), src))
+    }
+
+    pub fn from_file(file: SourceFile) -> Self {
+        let char_stream = file
+            .src
+            .chars()
+            .collect::<Vec<char>>()
+            .into_iter()
+            .peekable();
         Lexer {
             token_start_pos: 0,
             current_pos: 0,
             char_stream,
             buffered_tokens: VecDeque::new(),
-            src: Rc::new(src),
+            file: Rc::new(file),
         }
     }
 
     fn new_token(&mut self, kind: TokenKind) -> Token {
         let t = Token {
             kind,
-            span: Span::new(self.token_start_pos, self.current_pos, Rc::clone(&self.src)),
+            span: Span::new(
+                self.token_start_pos,
+                self.current_pos,
+                Rc::clone(&self.file),
+            ),
         };
         self.token_start_pos = self.current_pos;
         t
@@ -119,10 +157,20 @@ impl Lexer {
         self.char_stream.peek()
     }
 
+    /// Peek `n`-th character from the current position
+    fn peek_input_nth(&self, n: usize) -> Option<char> {
+        self.file.src[self.current_pos..].chars().nth(n)
+    }
+
+    fn starts_with_input(&self, s: &str) -> bool {
+        self.file.src[self.current_pos..].starts_with(s)
+    }
+
     fn skip_input(&mut self) -> Option<char> {
         let c = self.char_stream.next();
-        if c.is_some() {
-            self.current_pos += 1;
+        if let Some(c) = c {
+            // spans are byte offsets
+            self.current_pos += c.len_utf8();
         }
         c
     }
@@ -137,7 +185,8 @@ impl Lexer {
 
     fn skip_whitespaces(&mut self) {
         while let Some(c) = self.peek_input()
-            && is_space(*c) {
+            && is_space(*c)
+        {
             self.skip_input();
         }
         self.token_start_pos = self.current_pos;
@@ -151,15 +200,13 @@ impl Lexer {
         let tokenize_res = if let Some(c) = self.peek_input() {
             match c {
                 'A'..='Z' | 'a'..='z' | '_' => self.parse_keyword_or_ident(),
-                '\'' => self.parse_lifetime(),
+                '\'' => self.parse_lifetime_or_char_lit(),
                 '0'..='9' => self.parse_number_lit(),
-                // skip comments
-                // TODO: div `/`
+                // comments or `/`
                 '/' => {
                     // skip first '/'
                     self.skip_input().unwrap();
-                    let c = self.skip_input();
-                    if c == Some('/') {
+                    if self.peek_input() == Some(&'/') {
                         loop {
                             let c = self.peek_input();
                             if matches!(c, Some('\n') | None) {
@@ -171,7 +218,7 @@ impl Lexer {
                         }
                         return self.tokenize();
                     } else {
-                        self.new_token(TokenKind::Unknown)
+                        self.new_token(TokenKind::BinOp(BinOp::Slash))
                     }
                 }
                 '=' => {
@@ -179,6 +226,9 @@ impl Lexer {
                     if self.peek_input() == Some(&'=') {
                         self.skip_input();
                         self.new_token(TokenKind::BinOp(BinOp::Eq))
+                    } else if self.peek_input() == Some(&'>') {
+                        self.skip_input();
+                        self.new_token(TokenKind::FatArrow)
                     } else {
                         self.new_token(TokenKind::Eq)
                     }
@@ -232,7 +282,12 @@ impl Lexer {
                 }
                 '.' => {
                     self.skip_input();
-                    self.new_token(TokenKind::Dot)
+                    if self.peek_input() == Some(&'.') {
+                        self.skip_input();
+                        self.new_token(TokenKind::DotDot)
+                    } else {
+                        self.new_token(TokenKind::Dot)
+                    }
                 }
                 '(' => {
                     self.skip_input();
@@ -266,6 +321,22 @@ impl Lexer {
                     self.skip_input();
                     self.new_token(TokenKind::BinOp(BinOp::Star))
                 }
+                '%' => {
+                    self.skip_input();
+                    self.new_token(TokenKind::BinOp(BinOp::Percent))
+                }
+                '$' => {
+                    self.skip_input();
+                    self.new_token(TokenKind::Dollar)
+                }
+                '?' => {
+                    self.skip_input();
+                    self.new_token(TokenKind::Question)
+                }
+                '#' => {
+                    self.skip_input();
+                    self.new_token(TokenKind::Pound)
+                }
                 '\"' => self.parse_string_lit(),
                 // Unknown token
                 _ => {
@@ -297,9 +368,14 @@ impl Lexer {
             };
         }
         let s: String = chars.into_iter().collect();
+        if let Some(int_ty) = IntTy::from_name(&s) {
+            return self.new_token(TokenKind::Int(int_ty));
+        }
         match s.as_str() {
-            "i32" => self.new_token(TokenKind::I32),
+            "f32" => self.new_token(TokenKind::F32),
+            "f64" => self.new_token(TokenKind::F64),
             "str" => self.new_token(TokenKind::Str),
+            "char" => self.new_token(TokenKind::Char),
             "bool" => self.new_token(TokenKind::Bool),
             "true" => self.new_token(TokenKind::True),
             "false" => self.new_token(TokenKind::False),
@@ -312,6 +388,7 @@ impl Lexer {
             "extern" => self.new_token(TokenKind::Extern),
             "unsafe" => self.new_token(TokenKind::Unsafe),
             "mod" => self.new_token(TokenKind::Mod),
+            "type" => self.new_token(TokenKind::Type),
             "loop" => self.new_token(TokenKind::Loop),
             "while" => self.new_token(TokenKind::While),
             "break" => self.new_token(TokenKind::Break),
@@ -319,14 +396,42 @@ impl Lexer {
             "as" => self.new_token(TokenKind::As),
             "mut" => self.new_token(TokenKind::Mut),
             "const" => self.new_token(TokenKind::Const),
+            "static" => self.new_token(TokenKind::Static),
+            "impl" => self.new_token(TokenKind::Impl),
+            "for" => self.new_token(TokenKind::For),
             _ => self.new_token(TokenKind::Ident(s)),
         }
     }
 
-    fn parse_lifetime(&mut self) -> Token {
+    /// lifetime ::= "'" ident
+    /// charLit ::= "'" (char | escape) "'"
+    fn parse_lifetime_or_char_lit(&mut self) -> Token {
         // skip '\''
         self.skip_input();
         let mut chars = vec![];
+        match self.peek_input() {
+            Some('\\') => {
+                let Some(c) = self.parse_escape() else {
+                    return self.new_token(TokenKind::Unknown);
+                };
+                return self.finish_char_lit(c);
+            }
+            Some('\'') | Some('\n') | None => {
+                eprintln!("Expected character literal or lifetime");
+                return self.new_token(TokenKind::Unknown);
+            }
+            Some(_) => {
+                let c = self.skip_input().unwrap();
+                if self.peek_input() == Some(&'\'') {
+                    return self.finish_char_lit(c);
+                }
+                if !matches!(c, 'A'..='Z' | 'a'..='z' | '_') {
+                    eprintln!("Expected lifetime identifier, but found {:?}", c);
+                    return self.new_token(TokenKind::Unknown);
+                }
+                chars.push(c);
+            }
+        }
         while let Some(c) = &self.peek_input() {
             match c {
                 'A'..='Z' | 'a'..='z' | '_' | '0'..='9' => {
@@ -348,7 +453,98 @@ impl Lexer {
         }
     }
 
+    fn finish_char_lit(&mut self, c: char) -> Token {
+        // skip '\''
+        if self.skip_input() != Some('\'') {
+            eprintln!("Unterminated character literal");
+            return self.new_token(TokenKind::Unknown);
+        }
+        self.new_token(TokenKind::CharLit(c))
+    }
+
+    /// Parse an escape sequence starting with '\\'
+    fn parse_escape(&mut self) -> Option<char> {
+        // skip '\\'
+        self.skip_input();
+        let escp = match self.skip_input() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('\'') => '\'',
+            Some('"') => '"',
+            Some(c) => {
+                eprintln!("Escape \"\\{c}\" is not supported");
+                return None;
+            }
+            None => {
+                eprintln!("Unexpected EOF in escape sequence");
+                return None;
+            }
+        };
+        Some(escp)
+    }
+
+    /// numLit ::= digits
+    /// floatLit ::= digits "." digits? exponent? floatSuffix?
+    ///     | digits exponent floatSuffix?
+    ///     | digits floatSuffix
+    /// exponent ::= ("e" | "E") ("+" | "-")? digits
+    /// floatSuffix ::= "f32" | "f64"
+    /// https://doc.rust-lang.org/reference/tokens.html#floating-point-literals
     fn parse_number_lit(&mut self) -> Token {
+        let mut chars = self.parse_digits();
+        let mut is_float = false;
+
+        // `1.5` or `1.`, but not `1..2`, `1.foo` or `t.0.1`
+        if self.peek_input() == Some(&'.')
+            && !matches!(self.peek_input_nth(1), Some(c) if c == '.' || c == '_' || c.is_alphabetic())
+        {
+            self.skip_input();
+            chars.push('.');
+            chars.extend(self.parse_digits());
+            is_float = true;
+        }
+
+        // exponent
+        if matches!(self.peek_input(), Some('e' | 'E')) {
+            let has_sign = matches!(self.peek_input_nth(1), Some('+' | '-'));
+            let first_digit = self.peek_input_nth(if has_sign { 2 } else { 1 });
+            if matches!(first_digit, Some('0'..='9')) {
+                chars.push(self.skip_input().unwrap());
+                if has_sign {
+                    chars.push(self.skip_input().unwrap());
+                }
+                chars.extend(self.parse_digits());
+                is_float = true;
+            }
+        }
+
+        let suffix = if self.starts_with_input("f32") || self.starts_with_input("f64") {
+            let suffix: String = (0..3).map(|_| self.skip_input().unwrap()).collect();
+            Some(suffix)
+        } else {
+            None
+        };
+
+        let s: String = chars.into_iter().collect();
+        if is_float || suffix.is_some() {
+            self.new_token(TokenKind::FloatLit(s, suffix))
+        } else {
+            // literals are checked against their types after type inference
+            match s.parse::<u128>() {
+                Ok(n) => self.new_token(TokenKind::NumLit(n)),
+                Err(_) => {
+                    eprintln!("Integer literal is too large: {s}");
+                    self.new_token(TokenKind::Unknown)
+                }
+            }
+        }
+    }
+
+    /// Digits with `_` separators removed
+    fn parse_digits(&mut self) -> Vec<char> {
         let mut chars = vec![];
         while let Some(c) = &self.peek_input() {
             match c {
@@ -363,10 +559,7 @@ impl Lexer {
                 _ => break,
             };
         }
-
-        let s: String = chars.into_iter().collect();
-        let n = s.parse::<u32>().unwrap();
-        self.new_token(TokenKind::NumLit(n))
+        chars
     }
 
     fn parse_string_lit(&mut self) -> Token {
@@ -380,20 +573,12 @@ impl Lexer {
                     self.skip_input();
                     break;
                 }
-                /*
-                TODO: escape
                 '\\' => {
-                    self.skip_input();
-                    let escp = match self.skip_input().unwrap() {
-                        'n' => '\n',
-                        c => {
-                            eprintln!("Escape \"\\{c}\" is not supported");
-                            return Err(());
-                        }
+                    let Some(escp) = self.parse_escape() else {
+                        return self.new_token(TokenKind::Unknown);
                     };
                     chars.push(escp);
                 }
-                */
                 '\n' => {
                     eprintln!("Unexpected newline in string literal");
                     return self.new_token(TokenKind::Unknown);
@@ -425,6 +610,78 @@ impl Lexer {
         }
         self.buffered_tokens.pop_front().unwrap()
     }
+
+    /// Tokenize the whole source. The last token is EOF
+    pub fn tokenize_all(mut self) -> Vec<Token> {
+        let mut tokens = vec![];
+        while self.peek_token().kind != TokenKind::Eof {
+            tokens.push(self.skip_token());
+        }
+        tokens.push(self.skip_token());
+        tokens
+    }
+}
+
+/// Sequence of tokens fed to the parser
+pub struct TokenStream {
+    tokens: VecDeque<Token>,
+    eof: Token,
+    /// Number of tokens skipped so far
+    consumed: usize,
+    /// Span of the last token skipped
+    prev_span: Option<Span>,
+}
+
+impl TokenStream {
+    /// Create a token stream which keeps returning EOF with `eof_span` after the last token
+    pub fn new(tokens: Vec<Token>, eof_span: Span) -> Self {
+        let mut tokens: VecDeque<Token> = tokens.into();
+        // EOF is given by `self.eof`
+        if let Some(Token {
+            kind: TokenKind::Eof,
+            ..
+        }) = tokens.back()
+        {
+            tokens.pop_back();
+        }
+        TokenStream {
+            tokens,
+            eof: Token {
+                kind: TokenKind::Eof,
+                span: eof_span,
+            },
+            consumed: 0,
+            prev_span: None,
+        }
+    }
+
+    pub fn peek_token(&self) -> &Token {
+        self.tokens.front().unwrap_or(&self.eof)
+    }
+
+    /// Peek the `n`-th token after the current one
+    pub fn peek_nth_token(&self, n: usize) -> &Token {
+        self.tokens.get(n).unwrap_or(&self.eof)
+    }
+
+    /// Skip the current token. Keep returning EOF after the stream reached its end
+    pub fn skip_token(&mut self) -> Token {
+        if let Some(t) = self.tokens.pop_front() {
+            self.consumed += 1;
+            self.prev_span = Some(t.span.clone());
+            t
+        } else {
+            self.eof.clone()
+        }
+    }
+
+    pub fn num_consumed(&self) -> usize {
+        self.consumed
+    }
+
+    pub fn prev_span(&self) -> Option<&Span> {
+        self.prev_span.as_ref()
+    }
 }
 
 #[test]
@@ -481,3 +738,79 @@ fn test_empty() {
     assert_eq!(t.span.to_snippet(), "");
     assert_eq!(t.kind, TokenKind::Eof);
 }
+
+#[test]
+fn test_token_stream() {
+    let tokens = Lexer::new("$x => a?".to_string()).tokenize_all();
+    assert_eq!(tokens.len(), 6);
+    let eof_span = tokens.last().unwrap().span.clone();
+    let mut stream = TokenStream::new(tokens, eof_span);
+    assert_eq!(stream.skip_token().kind, TokenKind::Dollar);
+    assert_eq!(stream.skip_token().kind, TokenKind::Ident("x".to_string()));
+    assert_eq!(stream.skip_token().kind, TokenKind::FatArrow);
+    let _ = stream.skip_token();
+    assert_eq!(stream.peek_token().kind, TokenKind::Question);
+    let _ = stream.skip_token();
+    assert_eq!(stream.num_consumed(), 5);
+    assert_eq!(stream.skip_token().kind, TokenKind::Eof);
+}
+
+#[test]
+fn test_char_lit() {
+    let mut lexer = Lexer::new(r"'a' '\n' 'é' 'a".to_string());
+    assert_eq!(lexer.skip_token().kind, TokenKind::CharLit('a'));
+    assert_eq!(lexer.skip_token().kind, TokenKind::CharLit('\n'));
+    assert_eq!(lexer.skip_token().kind, TokenKind::CharLit('é'));
+    assert_eq!(
+        lexer.skip_token().kind,
+        TokenKind::Lifetime("a".to_string())
+    );
+    let mut lexer = Lexer::new(r#""a\tb\\""#.to_string());
+    assert_eq!(
+        lexer.skip_token().kind,
+        TokenKind::StrLit("a\tb\\".to_string())
+    );
+}
+
+#[test]
+fn test_float_lit() {
+    let mut lexer = Lexer::new("1.5 2e10 1f32 1..2 t.0".to_string());
+    assert_eq!(
+        lexer.skip_token().kind,
+        TokenKind::FloatLit("1.5".to_string(), None)
+    );
+    assert_eq!(
+        lexer.skip_token().kind,
+        TokenKind::FloatLit("2e10".to_string(), None)
+    );
+    assert_eq!(
+        lexer.skip_token().kind,
+        TokenKind::FloatLit("1".to_string(), Some("f32".to_string()))
+    );
+    assert_eq!(lexer.skip_token().kind, TokenKind::NumLit(1));
+    assert_eq!(lexer.skip_token().kind, TokenKind::DotDot);
+    assert_eq!(lexer.skip_token().kind, TokenKind::NumLit(2));
+    let _ = lexer.skip_token();
+    assert_eq!(lexer.skip_token().kind, TokenKind::Dot);
+    assert_eq!(lexer.skip_token().kind, TokenKind::NumLit(0));
+}
+
+#[test]
+fn test_int_types() {
+    let mut lexer = Lexer::new("u8 i64 usize u".to_string());
+    assert_eq!(lexer.skip_token().kind, TokenKind::Int(IntTy::U8));
+    assert_eq!(lexer.skip_token().kind, TokenKind::Int(IntTy::I64));
+    assert_eq!(lexer.skip_token().kind, TokenKind::Int(IntTy::Usize));
+    assert_eq!(lexer.skip_token().kind, TokenKind::Ident("u".to_string()));
+}
+
+#[test]
+fn test_div() {
+    let mut lexer = Lexer::new("a / b % c // d".to_string());
+    let _ = lexer.skip_token();
+    assert_eq!(lexer.skip_token().kind, TokenKind::BinOp(BinOp::Slash));
+    let _ = lexer.skip_token();
+    assert_eq!(lexer.skip_token().kind, TokenKind::BinOp(BinOp::Percent));
+    let _ = lexer.skip_token();
+    assert_eq!(lexer.skip_token().kind, TokenKind::Eof);
+}
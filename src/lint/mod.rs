@@ -0,0 +1,249 @@
+//! Lints
+//!
+//! Lints are warnings which can be allowed, warned or denied. Passes buffer them in
+//! `Ctxt`, and they are reported at the levels set by `-A`, `-W` and `-D` flags and
+//! `#[allow(..)]`, `#[warn(..)]` and `#[deny(..)]` attributes on the enclosing items.
+
+mod unused;
+
+pub use self::unused::check_unused;
+
+use crate::ast::{self, Crate, MetaItemKind};
+use crate::middle::Ctxt;
+use crate::span::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Level {
+    /// Level set by `-A`, `-W` or `-D`
+    pub fn from_flag(flag: &str) -> Option<Level> {
+        match flag {
+            "-A" => Some(Level::Allow),
+            "-W" => Some(Level::Warn),
+            "-D" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+
+    /// Level set by `#[allow(..)]`, `#[warn(..)]` or `#[deny(..)]`
+    fn from_attr_name(name: &str) -> Option<Level> {
+        match name {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Lint {
+    pub name: &'static str,
+    pub default_level: Level,
+}
+
+pub static UNUSED_VARIABLES: Lint = Lint {
+    name: "unused_variables",
+    default_level: Level::Warn,
+};
+
+pub static UNUSED_MUT: Lint = Lint {
+    name: "unused_mut",
+    default_level: Level::Warn,
+};
+
+pub static DEAD_CODE: Lint = Lint {
+    name: "dead_code",
+    default_level: Level::Warn,
+};
+
+pub static UNUSED_RESULTS: Lint = Lint {
+    name: "unused_results",
+    default_level: Level::Warn,
+};
+
+pub static UNREACHABLE_CODE: Lint = Lint {
+    name: "unreachable_code",
+    default_level: Level::Warn,
+};
+
+pub static UNUSED_UNSAFE: Lint = Lint {
+    name: "unused_unsafe",
+    default_level: Level::Warn,
+};
+
+pub static UNKNOWN_LINTS: Lint = Lint {
+    name: "unknown_lints",
+    default_level: Level::Warn,
+};
+
+pub static OVERFLOWING_LITERALS: Lint = Lint {
+    name: "overflowing_literals",
+    default_level: Level::Deny,
+};
+
+static LINTS: [&Lint; 8] = [
+    &UNUSED_VARIABLES,
+    &UNUSED_MUT,
+    &DEAD_CODE,
+    &UNUSED_RESULTS,
+    &UNREACHABLE_CODE,
+    &UNUSED_UNSAFE,
+    &UNKNOWN_LINTS,
+    &OVERFLOWING_LITERALS,
+];
+
+/// Lint group of all the lints which are warned otherwise
+const WARNINGS: &str = "warnings";
+
+fn is_known_lint(name: &str) -> bool {
+    name == WARNINGS || LINTS.iter().any(|lint| lint.name == name)
+}
+
+/// Lint emitted by a pass. The message contains the location like errors
+#[derive(Debug)]
+pub struct BufferedLint {
+    pub lint: &'static Lint,
+    pub span: Span,
+    pub msg: String,
+}
+
+/// Levels of lints set by command line flags and by attributes on items and statements
+pub struct LintLevels {
+    /// Levels set by `-A`, `-W` and `-D` flags in order
+    flags: Vec<(Level, String)>,
+    /// Spans of items and statements with lint attributes and the levels they set.
+    /// Outer ones come first
+    scopes: Vec<(Span, Vec<(Level, String)>)>,
+}
+
+impl LintLevels {
+    /// Check lint names given by `-A`, `-W` and `-D` flags
+    pub fn from_flags(flags: Vec<(Level, String)>) -> Option<LintLevels> {
+        for (_, name) in &flags {
+            if !is_known_lint(name) {
+                eprintln!("Unknown lint: `{}`", name);
+                return None;
+            }
+        }
+        Some(LintLevels {
+            flags,
+            scopes: vec![],
+        })
+    }
+
+    /// Collect lint attributes on items and statements. Malformed attributes are errors
+    pub fn collect_attrs(&mut self, ctx: &mut Ctxt, krate: &Crate) -> Vec<String> {
+        let mut collector = LintAttrCollector {
+            ctx,
+            scopes: vec![],
+            errors: vec![],
+        };
+        ast::visitor::go(&mut collector, krate);
+        self.scopes = collector.scopes;
+        collector.errors
+    }
+
+    /// Level of `lint` at `span`. Attributes on inner items and statements override the outer ones
+    fn level(&self, lint: &Lint, span: &Span) -> Level {
+        let mut level = lint.default_level;
+        let mut warnings = None;
+        let scopes = self
+            .scopes
+            .iter()
+            .filter(|(scope, _)| scope.contains(span))
+            .flat_map(|(_, levels)| levels);
+        for (lvl, name) in self.flags.iter().chain(scopes) {
+            if name == lint.name {
+                level = *lvl;
+            } else if name == WARNINGS {
+                warnings = Some(*lvl);
+            }
+        }
+        match (level, warnings) {
+            (Level::Warn, Some(warnings)) => warnings,
+            _ => level,
+        }
+    }
+
+    /// Report the lints which are not allowed. Returns true if any of them is denied
+    pub fn emit(&self, lints: Vec<BufferedLint>) -> bool {
+        let mut denied = false;
+        for BufferedLint { lint, span, msg } in lints {
+            match self.level(lint, &span) {
+                Level::Allow => (),
+                Level::Warn => eprintln!("Warning: {}", msg),
+                Level::Deny => {
+                    eprintln!("{}\n    the lint `{}` is denied", msg, lint.name);
+                    denied = true;
+                }
+            }
+        }
+        denied
+    }
+}
+
+struct LintAttrCollector<'ctx, 'chk> {
+    ctx: &'chk mut Ctxt<'ctx>,
+    scopes: Vec<(Span, Vec<(Level, String)>)>,
+    errors: Vec<String>,
+}
+
+impl LintAttrCollector<'_, '_> {
+    /// Collect the levels set by the attributes on an item or a statement at `span`
+    fn collect_levels(&mut self, attrs: &[ast::Attribute], span: &Span) {
+        let mut levels = vec![];
+        for attr in attrs {
+            let Some(level) = Level::from_attr_name(&attr.meta.name) else {
+                continue;
+            };
+            let MetaItemKind::List(names) = &attr.meta.kind else {
+                self.errors.push(format!(
+                    "Malformed `{}` attribute input at {}\n    must be of the form `#[{}(lint1, lint2, ...)]`",
+                    attr.meta.name,
+                    attr.span.to_location(),
+                    attr.meta.name
+                ));
+                continue;
+            };
+            for name in names {
+                if !matches!(name.kind, MetaItemKind::Word) {
+                    self.errors.push(format!(
+                        "Malformed lint attribute input at {}",
+                        name.span.to_location()
+                    ));
+                } else if !is_known_lint(&name.name) {
+                    self.ctx.buffer_lint(
+                        &UNKNOWN_LINTS,
+                        &name.span,
+                        format!(
+                            "Unknown lint: `{}` at {}",
+                            name.name,
+                            name.span.to_location()
+                        ),
+                    );
+                } else {
+                    levels.push((level, name.name.clone()));
+                }
+            }
+        }
+        if !levels.is_empty() {
+            self.scopes.push((span.clone(), levels));
+        }
+    }
+}
+
+impl<'chk> ast::visitor::Visitor<'chk> for LintAttrCollector<'_, '_> {
+    fn visit_item(&mut self, item: &'chk ast::Item) {
+        self.collect_levels(&item.attrs, &item.span);
+    }
+
+    fn visit_stmt(&mut self, stmt: &'chk ast::Stmt) {
+        self.collect_levels(&stmt.attrs, &stmt.span);
+    }
+}
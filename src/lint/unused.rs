@@ -0,0 +1,423 @@
+use super::{DEAD_CODE, UNUSED_MUT, UNUSED_RESULTS, UNUSED_VARIABLES};
+use crate::hir::{
+    self, Crate, DefId, DefKind, Expr, ExprKind, HirId, LetStmt, LocalId, Mutability, Res, Stmt,
+    StmtKind, UnOp,
+};
+use crate::middle::ty::TyKind;
+use crate::middle::Ctxt;
+use crate::resolve::CanonicalPath;
+use crate::span::Ident;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Lint unused variables, needless `mut`, items never used from `main` and unused results of calls.
+/// Names starting with `_` are never linted
+pub fn check_unused(ctx: &mut Ctxt, krate: &Crate) {
+    let mut collector = UseCollector {
+        ctx,
+        vars: vec![],
+        read_vars: HashSet::new(),
+        assigned_vars: HashSet::new(),
+        assign_lhs: HashSet::new(),
+        mut_vars: vec![],
+        mutated_vars: HashSet::new(),
+        items: vec![],
+        modules: vec![],
+        in_impl: false,
+        current_item: None,
+        refs: HashMap::new(),
+        type_refs: HashMap::new(),
+    };
+    hir::visitor::go(&mut collector, krate);
+
+    let UseCollector {
+        ctx,
+        vars,
+        read_vars,
+        assigned_vars,
+        mut_vars,
+        mutated_vars,
+        items,
+        refs,
+        type_refs,
+        ..
+    } = collector;
+
+    for (ident, binding) in vars {
+        if ident.symbol.starts_with('_') || read_vars.contains(&binding) {
+            continue;
+        }
+        let msg = if assigned_vars.contains(&binding) {
+            format!(
+                "Variable `{}` is assigned to, but never used at {}\n    consider using `_{}` instead",
+                ident.source_name(),
+                ident.span.to_location(),
+                ident.source_name()
+            )
+        } else {
+            format!(
+                "Unused variable: `{}` at {}\n    if this is intentional, prefix it with an underscore: `_{}`",
+                ident.source_name(),
+                ident.span.to_location(),
+                ident.source_name()
+            )
+        };
+        ctx.buffer_lint(&UNUSED_VARIABLES, &ident.span, msg);
+    }
+
+    for (ident, binding) in mut_vars {
+        if ident.symbol.starts_with('_') || mutated_vars.contains(&binding) {
+            continue;
+        }
+        let msg = format!(
+            "Variable `{}` does not need to be mutable at {}\n    remove this `mut`",
+            ident.source_name(),
+            ident.span.to_location()
+        );
+        ctx.buffer_lint(&UNUSED_MUT, &ident.span, msg);
+    }
+
+    // items used from `main`, and destructors of structs used
+    let mut live: HashSet<Rc<CanonicalPath>> = HashSet::new();
+    let mut worklist: Vec<Rc<CanonicalPath>> = items
+        .iter()
+        .filter(|item| item.is_main)
+        .map(|item| Rc::clone(&item.def_id.cpath))
+        .collect();
+    while let Some(cpath) = worklist.pop() {
+        if !live.insert(Rc::clone(&cpath)) {
+            continue;
+        }
+        if let Some(drop_fn) = ctx.lookup_drop_impl(&cpath) {
+            worklist.push(Rc::clone(&drop_fn.cpath));
+        }
+        if let Some(used) = refs.get(&cpath) {
+            worklist.extend(used.iter().cloned());
+        }
+    }
+
+    // modules containing no items used or named in the types of items used
+    let named: HashSet<_> = live
+        .iter()
+        .filter_map(|cpath| type_refs.get(cpath))
+        .flatten()
+        .collect();
+    let mut live_modules = HashSet::new();
+    for item in &items {
+        if live.contains(&item.def_id.cpath) || named.contains(&item.def_id.cpath) {
+            live_modules.extend(item.modules.iter().cloned());
+        }
+    }
+    for item in &items {
+        let is_live = match item.kind {
+            DeadKind::Module => live_modules.contains(&item.def_id.cpath),
+            DeadKind::Function
+            | DeadKind::Struct
+            | DeadKind::Const
+            | DeadKind::Static
+            | DeadKind::TyAlias => live.contains(&item.def_id.cpath),
+        };
+        // only the outermost unused module is reported
+        if is_live
+            || item.ident.symbol.starts_with('_')
+            || item
+                .modules
+                .iter()
+                .any(|module| !live_modules.contains(module))
+        {
+            continue;
+        }
+        let msg = format!(
+            "{} `{}` is never {} at {}",
+            item.kind.desc(),
+            item.ident.symbol,
+            item.kind.verb(),
+            item.ident.span.to_location()
+        );
+        ctx.buffer_lint(&DEAD_CODE, &item.ident.span, msg);
+    }
+}
+
+#[derive(Clone, Copy)]
+enum DeadKind {
+    Function,
+    Struct,
+    Module,
+    Const,
+    Static,
+    TyAlias,
+}
+
+impl DeadKind {
+    fn desc(self) -> &'static str {
+        match self {
+            DeadKind::Function => "Function",
+            DeadKind::Struct => "Struct",
+            DeadKind::Module => "Module",
+            DeadKind::Const => "Constant",
+            DeadKind::Static => "Static",
+            DeadKind::TyAlias => "Type alias",
+        }
+    }
+
+    fn verb(self) -> &'static str {
+        match self {
+            DeadKind::Function
+            | DeadKind::Module
+            | DeadKind::Const
+            | DeadKind::Static
+            | DeadKind::TyAlias => "used",
+            DeadKind::Struct => "constructed",
+        }
+    }
+}
+
+/// Item which may be dead code
+struct ItemDef {
+    ident: Ident,
+    def_id: DefId,
+    kind: DeadKind,
+    /// `main` at the crate root
+    is_main: bool,
+    /// Enclosing modules from the outermost
+    modules: Vec<Rc<CanonicalPath>>,
+}
+
+struct UseCollector<'ctx, 'chk> {
+    ctx: &'chk mut Ctxt<'ctx>,
+    /// Local variables and parameters declared
+    vars: Vec<(Ident, LocalId)>,
+    read_vars: HashSet<LocalId>,
+    assigned_vars: HashSet<LocalId>,
+    /// Paths assigned to by `a = expr`, which are not reads
+    assign_lhs: HashSet<HirId>,
+    /// Variables declared with `let mut`
+    mut_vars: Vec<(Ident, LocalId)>,
+    /// Variables assigned to or borrowed mutably, including their fields and elements
+    mutated_vars: HashSet<LocalId>,
+    items: Vec<ItemDef>,
+    /// Modules enclosing the current item
+    modules: Vec<Rc<CanonicalPath>>,
+    in_impl: bool,
+    /// Function, constant, struct or type alias whose body is being visited
+    current_item: Option<Rc<CanonicalPath>>,
+    /// Items to the items used in their bodies, including type aliases named
+    refs: HashMap<Rc<CanonicalPath>, HashSet<Rc<CanonicalPath>>>,
+    /// Items to the structs named in their types, which are not constructed by them
+    type_refs: HashMap<Rc<CanonicalPath>, HashSet<Rc<CanonicalPath>>>,
+}
+
+impl UseCollector<'_, '_> {
+    fn add_item(&mut self, ident: &Ident, def_id: &DefId, kind: DeadKind) {
+        self.items.push(ItemDef {
+            ident: ident.clone(),
+            def_id: Rc::clone(def_id),
+            kind,
+            is_main: self.modules.is_empty() && *ident.symbol == "main",
+            modules: self.modules.clone(),
+        });
+    }
+
+    fn add_ref(&mut self, cpath: &Rc<CanonicalPath>) {
+        if let Some(func) = &self.current_item {
+            self.refs
+                .entry(Rc::clone(func))
+                .or_default()
+                .insert(Rc::clone(cpath));
+        }
+    }
+
+    /// Variable whose value is changed by assigning to or mutably borrowing `place`.
+    /// Places behind references and raw pointers are not part of the variable
+    fn mutated_local<'e>(&self, place: &'e Expr) -> Option<&'e LocalId> {
+        match &place.kind {
+            ExprKind::Path(path) => match &path.res {
+                Res::Local(local_id) => Some(local_id),
+                _ => None,
+            },
+            // fields and indexing deref through boxes and references
+            ExprKind::Field(base, _) | ExprKind::Index(base, _) => {
+                match self.ctx.get_type(base.id).kind {
+                    TyKind::Ref(..) => None,
+                    _ => self.mutated_local(base),
+                }
+            }
+            ExprKind::Unary(UnOp::Deref, inner) => match self.ctx.get_type(inner.id).kind {
+                TyKind::Box(_) => self.mutated_local(inner),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn add_type_ref(&mut self, cpath: &Rc<CanonicalPath>) {
+        if let Some(item) = &self.current_item {
+            self.type_refs
+                .entry(Rc::clone(item))
+                .or_default()
+                .insert(Rc::clone(cpath));
+        }
+    }
+}
+
+impl<'chk> hir::visitor::Visitor<'chk> for UseCollector<'_, '_> {
+    fn visit_module_item(&mut self, module: &'chk hir::Module) {
+        self.add_item(&module.name, &module.def_id, DeadKind::Module);
+        self.modules.push(Rc::clone(&module.def_id.cpath));
+    }
+
+    fn visit_module_item_post(&mut self, _module: &'chk hir::Module) {
+        self.modules.pop();
+    }
+
+    fn visit_struct_item(&mut self, strct: &'chk hir::StructItem) {
+        self.add_item(&strct.ident, &strct.def_id, DeadKind::Struct);
+        // constants used in the lengths of array fields
+        self.current_item = Some(Rc::clone(&strct.def_id.cpath));
+    }
+
+    fn visit_struct_item_post(&mut self, _strct: &'chk hir::StructItem) {
+        self.current_item = None;
+    }
+
+    fn visit_ty_alias(&mut self, alias: &'chk hir::TyAlias) {
+        self.add_item(&alias.ident, &alias.def_id, DeadKind::TyAlias);
+        self.current_item = Some(Rc::clone(&alias.def_id.cpath));
+    }
+
+    fn visit_ty_alias_post(&mut self, _alias: &'chk hir::TyAlias) {
+        self.current_item = None;
+    }
+
+    fn visit_const_item(&mut self, const_item: &'chk hir::ConstItem) {
+        self.add_item(&const_item.ident, &const_item.def_id, DeadKind::Const);
+        self.current_item = Some(Rc::clone(&const_item.def_id.cpath));
+    }
+
+    fn visit_const_item_post(&mut self, _const_item: &'chk hir::ConstItem) {
+        self.current_item = None;
+    }
+
+    fn visit_static_item(&mut self, static_item: &'chk hir::StaticItem) {
+        self.add_item(&static_item.ident, &static_item.def_id, DeadKind::Static);
+        self.current_item = Some(Rc::clone(&static_item.def_id.cpath));
+    }
+
+    fn visit_static_item_post(&mut self, _static_item: &'chk hir::StaticItem) {
+        self.current_item = None;
+    }
+
+    fn visit_impl(&mut self, _imp: &'chk hir::Impl) {
+        self.in_impl = true;
+    }
+
+    fn visit_impl_post(&mut self, _imp: &'chk hir::Impl) {
+        self.in_impl = false;
+    }
+
+    fn visit_func(&mut self, func: &'chk hir::Func) {
+        // functions in extern blocks are only declarations
+        if func.body.is_none() {
+            return;
+        }
+        // functions in impls are used through their traits
+        if !self.in_impl {
+            self.add_item(&func.name, &func.def_id, DeadKind::Function);
+        }
+        self.current_item = Some(Rc::clone(&func.def_id.cpath));
+        for param in &func.params {
+            if *param.ident.symbol != "self" {
+                self.vars
+                    .push((param.ident.clone(), Rc::clone(&param.local_id)));
+            }
+        }
+    }
+
+    fn visit_func_post(&mut self, _func: &'chk hir::Func) {
+        self.current_item = None;
+    }
+
+    fn visit_stmt(&mut self, stmt: &'chk Stmt) {
+        match &stmt.kind {
+            StmtKind::Let(LetStmt {
+                ident,
+                local_id,
+                mutbl,
+                ..
+            }) => {
+                self.vars.push((ident.clone(), Rc::clone(local_id)));
+                if *mutbl == Mutability::Mut {
+                    self.mut_vars.push((ident.clone(), Rc::clone(local_id)));
+                }
+            }
+            StmtKind::Semi(expr)
+                if matches!(expr.kind, ExprKind::Call(..) | ExprKind::MethodCall(..)) =>
+            {
+                let ty = self.ctx.get_type(expr.id);
+                if !ty.is_unit() && !ty.is_never() {
+                    let msg = format!(
+                        "Unused result of type `{}` at {}",
+                        ty,
+                        expr.span.to_location()
+                    );
+                    self.ctx.buffer_lint(&UNUSED_RESULTS, &expr.span, msg);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &'chk Expr) {
+        if let ExprKind::Path(path) | ExprKind::Struct(path, ..) = &expr.kind
+            && let Some(alias) = &path.alias
+        {
+            self.add_ref(&alias.cpath);
+        }
+        if let ExprKind::Assign(place, _) | ExprKind::AddrOf(Mutability::Mut, place) = &expr.kind
+            && let Some(local_id) = self.mutated_local(place)
+        {
+            self.mutated_vars.insert(Rc::clone(local_id));
+        }
+        match &expr.kind {
+            ExprKind::Assign(lhs, _) if matches!(lhs.kind, ExprKind::Path(_)) => {
+                self.assign_lhs.insert(lhs.id);
+            }
+            ExprKind::Path(path) => match &path.res {
+                Res::Local(local_id) => {
+                    if self.assign_lhs.contains(&expr.id) {
+                        self.assigned_vars.insert(Rc::clone(local_id));
+                    } else {
+                        self.read_vars.insert(Rc::clone(local_id));
+                    }
+                }
+                Res::Def(_, def_id) => self.add_ref(&def_id.cpath),
+                Res::Err => (),
+            },
+            // the struct may be named through a type alias
+            ExprKind::Struct(..) => {
+                if let Some(cpath) = self.ctx.get_type(expr.id).get_adt_name() {
+                    let cpath = Rc::clone(cpath);
+                    self.add_ref(&cpath);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // types in signatures, fields and annotations
+    fn visit_type(&mut self, ty: &'chk hir::Ty) {
+        if let hir::TyKind::Path(path, _) = &ty.kind
+            && let Res::Def(kind, def_id) = &path.res
+        {
+            if *kind == DefKind::TyAlias {
+                self.add_ref(&def_id.cpath);
+            } else {
+                self.add_type_ref(&def_id.cpath);
+            }
+        }
+    }
+
+    fn visit_array_len(&mut self, len: &'chk Expr) {
+        hir::visitor::walk_expr(self, len);
+    }
+}
@@ -1,13 +1,20 @@
-#![feature(let_chains)]
 mod ast;
 mod backend_llvm;
+mod backend_x86;
+mod borrowck;
+mod expand;
+mod hir;
+mod interp;
 mod lexer;
+mod lint;
 //mod lvalue;
 mod middle;
+mod mir;
 mod parse;
 mod resolve;
 mod span;
 mod typeck;
+mod unsafety;
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
@@ -15,29 +22,75 @@ fn main() {
         eprintln!("Usage: mini-rustc file/source [options]");
         println!("Options:");
         println!("  --dump\tDump compiler states for debugging");
+        println!("  --emit=mir\tPrint MIR instead of LLVM IR");
+        println!("  --emit=asm\tPrint x86-64 assembly instead of LLVM IR");
+        println!("  --run\tInterpret the program instead of emitting LLVM IR");
+        println!("  -O\tOptimize the MIR before emitting it");
+        println!(
+            "  --cfg SPEC\tConfigure the compilation environment (e.g. `--cfg 'feature=\"x\"'`)"
+        );
+        println!("  -A LINT\tAllow a lint (e.g. `-A dead_code`)");
+        println!("  -W LINT\tWarn about a lint");
+        println!("  -D LINT\tDeny a lint (e.g. `-D warnings`)");
         eprintln!("Invalid number of arguments");
         std::process::exit(1);
     }
 
     // TODO: refine handling command line args
     let dump_enabled = args.contains(&"--dump".to_string());
+    let run_enabled = args.contains(&"--run".to_string());
+    let opt_enabled = args.contains(&"-O".to_string());
+    let mut emit_mir = false;
+    let mut emit_asm = false;
+    for arg in &args[2..] {
+        if let Some(kind) = arg.strip_prefix("--emit=") {
+            if kind == "mir" {
+                emit_mir = true;
+            } else if kind == "asm" {
+                emit_asm = true;
+            } else {
+                eprintln!("Unknown emit kind `{}`", kind);
+                std::process::exit(1);
+            }
+        }
+    }
+    let cfg_flags: Vec<String> = args
+        .windows(2)
+        .filter(|w| w[0] == "--cfg")
+        .map(|w| w[1].clone())
+        .collect();
+    let Some(cfg) = parse::CfgSet::from_flags(&cfg_flags) else {
+        std::process::exit(1);
+    };
+    let lint_flags = args
+        .windows(2)
+        .filter_map(|w| Some((lint::Level::from_flag(&w[0])?, w[1].clone())))
+        .collect();
+    let Some(mut lint_levels) = lint::LintLevels::from_flags(lint_flags) else {
+        std::process::exit(1);
+    };
 
     let path_or_src = args[1].clone();
-    let src = if args[1].ends_with(".rs") {
-        let res = std::fs::read_to_string(path_or_src);
+    let lexer = if args[1].ends_with(".rs") {
+        let res = std::fs::read_to_string(&path_or_src);
         if let Ok(src) = res {
-            src
+            lexer::Lexer::from_file(span::SourceFile::new(path_or_src, src))
         } else {
             eprintln!("Could not read file {}", args[1]);
             std::process::exit(1);
         }
     } else {
-        path_or_src
+        lexer::Lexer::new(path_or_src)
+    };
+
+    // Macro expansion stage
+    let Some(tokens) = expand::expand(lexer.tokenize_all()) else {
+        eprintln!("Failed to expand macros");
+        std::process::exit(1);
     };
 
     // Parse stage
-    let lexer = lexer::Lexer::new(src);
-    let mut parser = parse::Parser::new(lexer);
+    let mut parser = parse::Parser::new(tokens, std::rc::Rc::new(cfg));
     let parse_result = parser.parse_crate();
 
     let Some(krate) = parse_result else {
@@ -58,8 +111,35 @@ fn main() {
         ctx.dump_ribs();
     }
 
+    // Lint attributes
+    let errors = lint_levels.collect_attrs(&mut ctx, &krate);
+    if !errors.is_empty() {
+        for e in errors {
+            eprintln!("{}", e);
+        }
+        eprintln!("Failed to check lint attributes");
+        std::process::exit(1);
+    }
+
+    // HIR lowering stage
+    let lowering_result = hir::lower_crate(&mut ctx, &krate);
+    let Ok(krate) = lowering_result else {
+        if let Err(errors) = lowering_result {
+            for e in errors {
+                eprintln!("{}", e);
+            }
+        }
+        eprintln!("Failed to resolve names");
+        std::process::exit(1);
+    };
+
+    if ctx.dump_enabled {
+        dbg!(&krate);
+    }
+
     // Typecheck stage
     let typeck_result = typeck::typeck(&mut ctx, &krate);
+    emit_lints(&mut ctx, &lint_levels);
     let Ok(()) = typeck_result else {
         if let Err(errors) = typeck_result {
             for e in errors {
@@ -74,16 +154,77 @@ fn main() {
         ctx.dump_resolution();
     }
 
+    // Unsafety checking stage
+    let errors = unsafety::check_unsafety(&mut ctx, &krate);
+    emit_lints(&mut ctx, &lint_levels);
+    if !errors.is_empty() {
+        for e in errors {
+            eprintln!("{}", e);
+        }
+        eprintln!("Failed to check unsafety");
+        std::process::exit(1);
+    }
+
+    // Borrow checking stage
+    let borrowck_result = borrowck::borrowck(&mut ctx, &krate);
+    let Ok(()) = borrowck_result else {
+        if let Err(errors) = borrowck_result {
+            for e in errors {
+                eprintln!("{}", e);
+            }
+        }
+        eprintln!("Failed to borrow check crate");
+        std::process::exit(1);
+    };
+
+    // Lint stage
+    lint::check_unused(&mut ctx, &krate);
+    emit_lints(&mut ctx, &lint_levels);
+
     //dbg!(&ctx);
 
     // Lvalue analysis stage
     // lvalue::analyze(&mut ctx, &krate);
 
+    // MIR building stage
+    let build_result = mir::build(&mut ctx, &krate);
+    let Ok(mut program) = build_result else {
+        if let Err(errors) = build_result {
+            for e in errors {
+                eprintln!("{}", e);
+            }
+        }
+        eprintln!("Failed to check moves");
+        std::process::exit(1);
+    };
+    if opt_enabled {
+        mir::optimize(&ctx, &mut program);
+    }
+    if emit_mir {
+        print!("{}", program);
+        return;
+    }
+    if run_enabled {
+        std::process::exit(interp::run(&ctx, &program));
+    }
+
     // Codegen stage
-    let codegen_result = backend_llvm::compile(&mut ctx, &krate);
+    if emit_asm {
+        backend_x86::compile(&ctx, &program);
+        return;
+    }
+    let codegen_result = backend_llvm::compile(&mut ctx, &program);
 
     let Ok(()) = codegen_result else {
         eprintln!("ICE: Failed to generate assembly");
         std::process::exit(1);
     };
 }
+
+/// Report the lints emitted so far, and abort if any of them is denied
+fn emit_lints(ctx: &mut middle::Ctxt, lint_levels: &lint::LintLevels) {
+    if lint_levels.emit(ctx.take_lints()) {
+        eprintln!("Aborting due to denied lints");
+        std::process::exit(1);
+    }
+}
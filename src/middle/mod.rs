@@ -1,12 +1,11 @@
 pub mod ty;
 
 use crate::ast::{self, Crate, NodeId, Path};
-//use crate::hir::{self, HirId, LocalDefId};
-//use crate::hir::HirId;
-use crate::middle::ty::{AdtDef, Ty};
+use crate::lint::{BufferedLint, Lint};
+use crate::middle::ty::{AdtDef, ConstValue, RegionSig, Ty, TyKind};
 use crate::resolve::{Binding, CanonicalPath, Resolver};
-use crate::span::Ident;
-use std::collections::HashMap;
+use crate::span::{Ident, Span};
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
 #[derive(Debug)]
@@ -15,19 +14,27 @@ pub struct Ctxt<'ctx> {
     // Set during name resolution stage
     resolver: Resolver,
 
-    /// HIR root module
-    //hir_root_module: LocalDefId,
-    //hir_items: HashMap<LocalDefId, hir::Item<'ctx>>,
-    //hir_ty_mappings: HashMap<HirId, Rc<Ty>>,
     phantom: std::marker::PhantomData<&'ctx ()>,
 
     // Set during typecheck stage
-    /// Expr/Stmt/Block to type mappings
+    /// Expr/Stmt/Block to type mappings. HIR nodes share the ids of AST nodes
     ty_mappings: HashMap<NodeId, Rc<Ty>>,
     /// local variables, paramters, function-name to type mappings
     pub name_ty_mappings: HashMap<Rc<Binding>, Rc<Ty>>,
     // TODO: use NameBinding
     adt_defs: HashMap<Rc<CanonicalPath>, Rc<AdtDef>>,
+    /// ADTs to their `Drop::drop` functions
+    drop_impls: HashMap<Rc<CanonicalPath>, Rc<Binding>>,
+    /// Place expressions whose values are moved out
+    moves: HashSet<NodeId>,
+    /// Expressions, statements and blocks after which control never continues
+    diverging: HashSet<NodeId>,
+    /// Functions to the lifetimes in their signatures
+    region_sigs: HashMap<Rc<Binding>, Rc<RegionSig>>,
+    /// Values of `const` items
+    const_values: HashMap<Rc<Binding>, ConstValue>,
+    /// Lints emitted but not reported yet
+    lints: Vec<BufferedLint>,
     // Set during rvalue anlaysis stage
     // all node ids of place expressions
     // ref: https://doc.rust-lang.org/reference/expressions.html?highlight=rvalue#place-expressions-and-value-expressions
@@ -40,14 +47,17 @@ impl<'ctx> Ctxt<'ctx> {
             dump_enabled,
             resolver: Resolver::new(),
 
-            //hir_root_module: LocalDefId::dummy(),
-            //hir_items: HashMap::new(),
-            //hir_ty_mappings: HashMap::new(),
             phantom: std::marker::PhantomData::default(),
 
             ty_mappings: HashMap::new(),
             name_ty_mappings: HashMap::new(),
             adt_defs: HashMap::new(),
+            drop_impls: HashMap::new(),
+            moves: HashSet::new(),
+            diverging: HashSet::new(),
+            region_sigs: HashMap::new(),
+            const_values: HashMap::new(),
+            lints: vec![],
             // lvalues: HashSet::new(),
         }
     }
@@ -86,6 +96,16 @@ impl<'ctx> Ctxt<'ctx> {
         Rc::clone(self.ty_mappings.get(&node_id).unwrap())
     }
 
+    /// Rewrite all the types of nodes and names with `f`
+    pub fn map_types(&mut self, mut f: impl FnMut(&Rc<Ty>) -> Rc<Ty>) {
+        for ty in self.ty_mappings.values_mut() {
+            *ty = f(ty);
+        }
+        for ty in self.name_ty_mappings.values_mut() {
+            *ty = f(ty);
+        }
+    }
+
     pub fn lookup_name_type(&self, binding: &Binding) -> Option<Rc<Ty>> {
         self.name_ty_mappings.get(binding).map(Rc::clone)
     }
@@ -106,6 +126,77 @@ impl<'ctx> Ctxt<'ctx> {
         &self.adt_defs
     }
 
+    pub fn lookup_drop_impl(&self, cpath: &CanonicalPath) -> Option<Rc<Binding>> {
+        self.drop_impls.get(cpath).map(Rc::clone)
+    }
+
+    pub fn set_drop_impl(&mut self, cpath: Rc<CanonicalPath>, drop_fn: Rc<Binding>) {
+        self.drop_impls.insert(cpath, drop_fn);
+    }
+
+    pub fn lookup_region_sig(&self, binding: &Binding) -> Option<Rc<RegionSig>> {
+        self.region_sigs.get(binding).map(Rc::clone)
+    }
+
+    pub fn set_region_sig(&mut self, binding: Rc<Binding>, sig: RegionSig) {
+        self.region_sigs.insert(binding, Rc::new(sig));
+    }
+
+    pub fn lookup_const_value(&self, binding: &Binding) -> Option<ConstValue> {
+        self.const_values.get(binding).copied()
+    }
+
+    pub fn set_const_value(&mut self, binding: Rc<Binding>, value: ConstValue) {
+        self.const_values.insert(binding, value);
+    }
+
+    /// Whether values of `ty` own heap memory or have destructors to run
+    pub fn needs_drop(&self, ty: &Ty) -> bool {
+        match &ty.kind {
+            TyKind::Box(_) => true,
+            TyKind::Array(elem, n) => *n != 0 && self.needs_drop(elem),
+            // recursive structs always contain boxes, which stop the recursion
+            TyKind::Adt(name) => {
+                self.drop_impls.contains_key(name)
+                    || self
+                        .lookup_adt_def(name)
+                        .unwrap()
+                        .fields
+                        .iter()
+                        .any(|(_, fd_ty)| self.needs_drop(fd_ty))
+            }
+            _ => false,
+        }
+    }
+
+    pub fn register_move(&mut self, node_id: NodeId) {
+        self.moves.insert(node_id);
+    }
+
+    pub fn is_moved(&self, node_id: NodeId) -> bool {
+        self.moves.contains(&node_id)
+    }
+
+    pub fn register_diverging(&mut self, node_id: NodeId) {
+        self.diverging.insert(node_id);
+    }
+
+    pub fn diverges(&self, node_id: NodeId) -> bool {
+        self.diverging.contains(&node_id)
+    }
+
+    pub fn buffer_lint(&mut self, lint: &'static Lint, span: &Span, msg: String) {
+        self.lints.push(BufferedLint {
+            lint,
+            span: span.clone(),
+            msg,
+        });
+    }
+
+    pub fn take_lints(&mut self) -> Vec<BufferedLint> {
+        std::mem::take(&mut self.lints)
+    }
+
     // Rvalue analysis stage
     /*
     pub fn register_lvalue(&mut self, node_id: NodeId) {
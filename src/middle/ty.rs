@@ -1,5 +1,6 @@
 use std::rc::Rc;
 
+use crate::ast::{IntTy, Mutability, Region};
 use crate::resolve::CanonicalPath;
 
 #[derive(PartialEq, Eq)]
@@ -11,17 +12,34 @@ pub struct Ty {
 pub enum TyKind {
     Unit,
     Bool,
-    I32,
+    Int(IntTy),
+    F32,
+    F64,
     Str,
+    Char,
     Array(Rc<Ty>, usize),
     Fn(Rc<Vec<Rc<Ty>>>, Rc<Ty>),
     Adt(Rc<CanonicalPath>),
-    Ref(Rc<Ty>),
+    Ref(Rc<Ty>, Mutability),
     Never,
-    ConstPtr(Rc<Ty>),
+    RawPtr(Rc<Ty>, Mutability),
+    /// Owned pointer to a heap allocation
+    Box(Rc<Ty>),
+    /// Type variable created during type inference. Never remains after typeck
+    Infer(InferTy),
     Error,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum InferTy {
+    /// Any type
+    Ty(u32),
+    /// Type of an integer literal. Falls back to `i32`
+    Int(u32),
+    /// Type of a floating-point literal. Falls back to `f64`
+    Float(u32),
+}
+
 impl Ty {
     pub fn new(kind: TyKind) -> Self {
         Ty { kind }
@@ -35,6 +53,12 @@ impl Ty {
         }
     }
 
+    pub fn int(int_ty: IntTy) -> Self {
+        Ty {
+            kind: TyKind::Int(int_ty),
+        }
+    }
+
     pub fn unit() -> Self {
         Ty { kind: TyKind::Unit }
     }
@@ -65,9 +89,46 @@ impl Ty {
     }
     */
 
+    pub fn is_unit(&self) -> bool {
+        matches!(&self.kind, TyKind::Unit)
+    }
+
     pub fn is_never(&self) -> bool {
         matches!(&self.kind, TyKind::Never)
     }
+
+    pub fn is_error(&self) -> bool {
+        matches!(&self.kind, TyKind::Error)
+    }
+
+    /// Integer types including integer type variables
+    pub fn is_integral(&self) -> bool {
+        matches!(&self.kind, TyKind::Int(_) | TyKind::Infer(InferTy::Int(_)))
+    }
+
+    /// Floating-point types including float type variables
+    pub fn is_float(&self) -> bool {
+        matches!(
+            &self.kind,
+            TyKind::F32 | TyKind::F64 | TyKind::Infer(InferTy::Float(_))
+        )
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_integral() || self.is_float()
+    }
+
+    pub fn is_str_ref(&self) -> bool {
+        matches!(&self.kind, TyKind::Ref(inner, _) if inner.kind == TyKind::Str)
+    }
+
+    pub fn is_raw_ptr(&self) -> bool {
+        matches!(&self.kind, TyKind::RawPtr(..))
+    }
+
+    pub fn is_ty_var(&self) -> bool {
+        matches!(&self.kind, TyKind::Infer(InferTy::Ty(_)))
+    }
 }
 
 #[derive(Debug)]
@@ -75,8 +136,71 @@ pub struct AdtDef {
     pub fields: Vec<(Rc<String>, Rc<Ty>)>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CtorKind {
+    /// `S(a, b)`, which is called like functions
+    Fn,
+    /// `S`
+    Const,
+}
+
+/// Value of a constant computed at compile time. `i128` holds the values of all the
+/// integer types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstValue {
+    Int(i128),
+    Bool(bool),
+    Char(char),
+    Unit,
+}
+
+/// Lifetimes in the parameter types and the return type of a function.
+/// Elided lifetimes of parameters are given fresh names `1`, `2`, ...
+#[derive(Debug)]
+pub struct RegionSig {
+    pub inputs: Vec<Vec<Region>>,
+    pub output: Vec<Region>,
+}
+
 impl std::fmt::Debug for Ty {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", self.kind)
     }
 }
+
+/// Types in the source language notation, used in error messages
+impl std::fmt::Display for Ty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            TyKind::Unit => write!(f, "()"),
+            TyKind::Bool => write!(f, "bool"),
+            TyKind::Int(int_ty) => write!(f, "{}", int_ty.name()),
+            TyKind::F32 => write!(f, "f32"),
+            TyKind::F64 => write!(f, "f64"),
+            TyKind::Str => write!(f, "str"),
+            TyKind::Char => write!(f, "char"),
+            TyKind::Array(elem, n) => write!(f, "[{}; {}]", elem, n),
+            TyKind::Fn(params, ret) => {
+                write!(f, "fn(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", param)?;
+                }
+                write!(f, ") -> {}", ret)
+            }
+            TyKind::Adt(cpath) => write!(f, "{:?}", cpath),
+            TyKind::Ref(inner, Mutability::Not) => write!(f, "&{}", inner),
+            TyKind::Ref(inner, Mutability::Mut) => write!(f, "&mut {}", inner),
+            TyKind::Never => write!(f, "!"),
+            TyKind::RawPtr(inner, Mutability::Not) => write!(f, "*const {}", inner),
+            TyKind::RawPtr(inner, Mutability::Mut) => write!(f, "*mut {}", inner),
+            TyKind::Box(inner) => write!(f, "Box<{}>", inner),
+            TyKind::Infer(InferTy::Ty(_)) => write!(f, "_"),
+            TyKind::Infer(InferTy::Int(_)) => write!(f, "{{integer}}"),
+            TyKind::Infer(InferTy::Float(_)) => write!(f, "{{float}}"),
+            TyKind::Error => write!(f, "{{error}}"),
+        }
+    }
+}
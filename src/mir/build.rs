@@ -0,0 +1,715 @@
+//! Lowering of the typed HIR to MIR
+//!
+//! Expressions are lowered into a destination place, into an operand or into a place.
+//! Values which are not places but are used as places (e.g. `&f()` and `f().a`) are
+//! stored in temporaries, which are dropped at the end of the enclosing block.
+
+use super::*;
+use crate::hir::{
+    self, Block, DefKind, Expr, ExprKind, ItemKind, Path, Res, StmtKind as HirStmtKind,
+};
+use crate::middle::ty::{ConstValue, CtorKind};
+use crate::span::Ident;
+use std::collections::HashMap;
+
+/// Errors are uses of values after they are moved out, which drop elaboration finds
+pub fn build(ctx: &mut Ctxt, krate: &hir::Crate) -> Result<Program, Vec<String>> {
+    let mut program = Program {
+        bodies: vec![],
+        foreign_fns: vec![],
+        statics: vec![],
+    };
+    let mut errors = vec![];
+    for item in &krate.items {
+        build_item(ctx, item, &mut program, &mut errors);
+    }
+    if errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(errors)
+    }
+}
+
+fn build_item(ctx: &mut Ctxt, item: &hir::Item, program: &mut Program, errors: &mut Vec<String>) {
+    match &item.kind {
+        ItemKind::Func(func) => build_func(ctx, func, program, errors),
+        ItemKind::Impl(imp) => {
+            for func in &imp.items {
+                build_func(ctx, func, program, errors);
+            }
+        }
+        ItemKind::ExternBlock(ext_block) => {
+            for func in &ext_block.funcs {
+                build_func(ctx, func, program, errors);
+            }
+        }
+        ItemKind::Mod(module) => {
+            for item in &module.items {
+                build_item(ctx, item, program, errors);
+            }
+        }
+        ItemKind::Static(static_item) => program.statics.push(Static {
+            name: Rc::clone(&static_item.def_id.cpath),
+            mutbl: static_item.mutbl,
+            init: Constant {
+                kind: const_kind(ctx.lookup_const_value(&static_item.def_id).unwrap()),
+                ty: ctx.lookup_name_type(&static_item.def_id).unwrap(),
+            },
+        }),
+        // constants are inlined into their uses
+        ItemKind::Struct(_) | ItemKind::TyAlias(_) | ItemKind::Const(_) => (),
+    }
+}
+
+fn const_kind(value: ConstValue) -> ConstKind {
+    match value {
+        ConstValue::Int(n) => ConstKind::Int(n as i64),
+        ConstValue::Bool(b) => ConstKind::Bool(b),
+        ConstValue::Char(c) => ConstKind::Char(c),
+        ConstValue::Unit => ConstKind::Unit,
+    }
+}
+
+fn build_func(ctx: &mut Ctxt, func: &hir::Func, program: &mut Program, errors: &mut Vec<String>) {
+    let (param_tys, ret_ty) = ctx
+        .lookup_name_type(&func.def_id)
+        .unwrap()
+        .get_func_type()
+        .unwrap();
+    let Some(block) = &func.body else {
+        program.foreign_fns.push(ForeignFn {
+            name: Rc::clone(&func.def_id.cpath),
+            param_tys,
+            ret_ty,
+        });
+        return;
+    };
+
+    let mut builder = Builder {
+        ctx,
+        body: Body {
+            name: Rc::clone(&func.def_id.cpath),
+            arg_count: func.params.len(),
+            inline: func.inline,
+            locals: vec![],
+            blocks: vec![],
+        },
+        current: START_BLOCK,
+        body_span: block.span.clone(),
+        scopes: vec![],
+        var_locals: HashMap::new(),
+    };
+    builder.current = builder.new_block();
+    builder.new_local(None, ret_ty);
+
+    // parameters are dropped after the body
+    builder.scopes.push(vec![]);
+    for (param, param_ty) in func.params.iter().zip(param_tys.iter()) {
+        let local = builder.declare_var(&param.ident, &param.local_id, Rc::clone(param_ty));
+        builder.schedule_drop(local);
+    }
+    builder.block_into(&Place::local(RETURN_PLACE), block);
+    let end = block.span.end();
+    builder.pop_scope(&end);
+    builder.terminate(TerminatorKind::Return, &end);
+
+    let mut body = builder.body;
+    body.remove_unreachable_blocks();
+    errors.extend(super::elaborate_drops::elaborate_drops(ctx, &mut body));
+    program.bodies.push(body);
+}
+
+struct Builder<'a, 'ctx> {
+    ctx: &'a mut Ctxt<'ctx>,
+    body: Body,
+    /// Block to which statements are appended
+    current: BasicBlock,
+    /// Span of the function body, which blocks have until they are terminated
+    body_span: Span,
+    /// Locals to be dropped at the end of each of the enclosing blocks
+    scopes: Vec<Vec<Local>>,
+    /// Locals of variables and parameters
+    var_locals: HashMap<hir::LocalId, Local>,
+}
+
+impl Builder<'_, '_> {
+    fn new_block(&mut self) -> BasicBlock {
+        self.body.blocks.push(BasicBlockData {
+            stmts: vec![],
+            terminator: Terminator {
+                kind: TerminatorKind::Unreachable,
+                span: self.body_span.clone(),
+            },
+        });
+        self.body.blocks.len() - 1
+    }
+
+    fn new_local(&mut self, name: Option<Rc<String>>, ty: Rc<Ty>) -> Local {
+        self.body.locals.push(LocalDecl { name, ty });
+        self.body.locals.len() - 1
+    }
+
+    fn new_temp(&mut self, expr: &Expr) -> Local {
+        let ty = self.ctx.get_type(expr.id);
+        self.new_local(None, ty)
+    }
+
+    fn declare_var(&mut self, ident: &Ident, local_id: &hir::LocalId, ty: Rc<Ty>) -> Local {
+        let local = self.new_local(Some(Rc::new(ident.source_name().to_string())), ty);
+        self.var_locals.insert(Rc::clone(local_id), local);
+        local
+    }
+
+    fn push(&mut self, kind: StatementKind, span: &Span) {
+        self.body.blocks[self.current].stmts.push(Statement {
+            kind,
+            span: span.clone(),
+        });
+    }
+
+    fn push_assign(&mut self, place: Place, rvalue: Rvalue, span: &Span) {
+        self.push(StatementKind::Assign(place, rvalue), span);
+    }
+
+    fn terminate(&mut self, kind: TerminatorKind, span: &Span) {
+        self.body.blocks[self.current].terminator = Terminator {
+            kind,
+            span: span.clone(),
+        };
+    }
+
+    fn goto(&mut self, target: BasicBlock, span: &Span) {
+        self.terminate(TerminatorKind::Goto(target), span);
+        self.current = target;
+    }
+
+    /// Code following a terminator is unreachable. It is lowered into a new block,
+    /// which is removed after lowering
+    fn start_dead_block(&mut self) {
+        self.current = self.new_block();
+    }
+
+    /// Drop the local at the end of the current block unless it is moved out
+    fn schedule_drop(&mut self, local: Local) {
+        if self.ctx.needs_drop(&self.body.locals[local].ty) {
+            self.scopes.last_mut().unwrap().push(local);
+        }
+    }
+
+    /// Locals are dropped in the reverse order of declaration
+    fn pop_scope(&mut self, span: &Span) {
+        let scope = self.scopes.pop().unwrap();
+        for local in scope.into_iter().rev() {
+            self.push(StatementKind::Drop(Place::local(local)), span);
+        }
+    }
+
+    /// Drop everything in the function before returning
+    fn exit_all_scopes(&mut self, span: &Span) {
+        let locals: Vec<Local> = self
+            .scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .copied()
+            .collect();
+        for local in locals {
+            self.push(StatementKind::Drop(Place::local(local)), span);
+        }
+    }
+
+    fn block_into(&mut self, dest: &Place, block: &Block) {
+        self.scopes.push(vec![]);
+        let mut diverges = false;
+        for stmt in &block.stmts {
+            match &stmt.kind {
+                HirStmtKind::Let(let_stmt) if let_stmt.is_wildcard() => {
+                    // places are left as they are, and other values are dropped at the end
+                    // of the statement
+                    if let Some(init) = &let_stmt.init {
+                        if self.is_place_expr(init) {
+                            self.as_place(init);
+                        } else {
+                            self.expr_stmt(init, &stmt.span);
+                        }
+                    }
+                }
+                HirStmtKind::Let(let_stmt) => {
+                    let ty = self.ctx.lookup_name_type(&let_stmt.local_id).unwrap();
+                    let local = self.declare_var(&let_stmt.ident, &let_stmt.local_id, ty);
+                    if let Some(init) = &let_stmt.init {
+                        self.expr_into(&Place::local(local), init);
+                    }
+                    self.schedule_drop(local);
+                }
+                HirStmtKind::Expr(expr) | HirStmtKind::Semi(expr) => {
+                    self.expr_stmt(expr, &stmt.span);
+                }
+            }
+            if self.ctx.diverges(stmt.id) {
+                // the rest is unreachable
+                diverges = true;
+                break;
+            }
+        }
+        // the value of the block
+        if !diverges && let Some(expr) = &block.expr {
+            self.expr_into(dest, expr);
+        }
+        self.pop_scope(&block.span.end());
+    }
+
+    /// Lower `expr` whose value is dropped immediately
+    fn expr_stmt(&mut self, expr: &Expr, span: &Span) {
+        let temp = self.new_temp(expr);
+        self.expr_into(&Place::local(temp), expr);
+        if self.ctx.needs_drop(&self.body.locals[temp].ty) {
+            self.push(StatementKind::Drop(Place::local(temp)), span);
+        }
+    }
+
+    /// Lower `expr` writing its value to `dest`
+    fn expr_into(&mut self, dest: &Place, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Block(block) => self.block_into(dest, block),
+            ExprKind::If(cond, then, els) => {
+                let cond = self.as_operand(cond);
+                let then_bb = self.new_block();
+                let else_bb = els.as_ref().map(|_| self.new_block());
+                let join_bb = self.new_block();
+                self.terminate(
+                    TerminatorKind::If(cond, then_bb, else_bb.unwrap_or(join_bb)),
+                    &expr.span,
+                );
+                self.current = then_bb;
+                self.expr_into(dest, then);
+                self.goto(join_bb, &then.span.end());
+                if let (Some(els), Some(else_bb)) = (els, else_bb) {
+                    self.current = else_bb;
+                    self.expr_into(dest, els);
+                    self.goto(join_bb, &els.span.end());
+                }
+            }
+            ExprKind::Return(inner) => {
+                self.expr_into(&Place::local(RETURN_PLACE), inner);
+                self.exit_all_scopes(&expr.span);
+                self.terminate(TerminatorKind::Return, &expr.span);
+                self.start_dead_block();
+            }
+            ExprKind::Panic(fmt) => {
+                let msg = fmt.as_lit();
+                if msg.is_none() {
+                    // the same message as the one printed by the runtime
+                    let ops = fmt
+                        .args
+                        .iter()
+                        .map(|arg| self.as_format_operand(arg))
+                        .collect();
+                    let header =
+                        format!("thread 'main' panicked at {}:\n", expr.span.to_location());
+                    let pieces = std::iter::once(FormatPiece::Lit(header))
+                        .chain(fmt.pieces.iter().cloned())
+                        .chain(std::iter::once(FormatPiece::Lit("\n".to_string())))
+                        .collect();
+                    self.push(
+                        StatementKind::Print(PrintDest::Stderr, pieces, ops),
+                        &expr.span,
+                    );
+                }
+                self.terminate(TerminatorKind::Panic(msg), &expr.span);
+                self.start_dead_block();
+            }
+            ExprKind::Assign(lhs, rhs) => {
+                let op = self.as_operand(rhs);
+                let place = self.as_place(lhs);
+                // the old value is dropped before it is overwritten
+                if self.ctx.needs_drop(&self.ctx.get_type(lhs.id)) {
+                    self.push(StatementKind::Drop(place.clone()), &expr.span);
+                }
+                self.push_assign(place, Rvalue::Use(op), &expr.span);
+            }
+            ExprKind::Call(func, args) if self.ctor_kind(func) == Some(CtorKind::Fn) => {
+                // fields of tuple structs are named by their indices
+                let ops = args.iter().map(|arg| self.as_operand(arg)).collect();
+                self.push_adt(dest, expr, ops);
+            }
+            ExprKind::Call(func, args) => {
+                let ExprKind::Path(path) = &func.kind else {
+                    panic!("ICE: only functions can be called");
+                };
+                let def_id = path.res.def_id().unwrap();
+                let args = args.iter().map(|arg| self.as_call_operand(arg)).collect();
+                let target = if self.ctx.get_type(expr.id).is_never() {
+                    None
+                } else {
+                    Some(self.new_block())
+                };
+                self.terminate(
+                    TerminatorKind::Call {
+                        func: Rc::clone(&def_id.cpath),
+                        args,
+                        dest: dest.clone(),
+                        target,
+                    },
+                    &expr.span,
+                );
+                match target {
+                    Some(target) => self.current = target,
+                    None => self.start_dead_block(),
+                }
+            }
+            ExprKind::Path(_) if self.ctor_kind(expr) == Some(CtorKind::Const) => {
+                self.push_adt(dest, expr, vec![]);
+            }
+            ExprKind::Struct(_, fds, base) => {
+                // fields are evaluated in the order of the expression
+                let mut ops: HashMap<Rc<String>, Operand> = fds
+                    .iter()
+                    .map(|(fd, fd_expr)| (Rc::clone(&fd.symbol), self.as_operand(fd_expr)))
+                    .collect();
+                let base = base.as_ref().map(|base| (self.as_place(base), &base.span));
+                let ty = self.ctx.get_type(expr.id);
+                let adt = self.ctx.lookup_adt_def(ty.get_adt_name().unwrap()).unwrap();
+                let ops = adt
+                    .fields
+                    .iter()
+                    .map(|(name, fd_ty)| {
+                        ops.remove(name).unwrap_or_else(|| {
+                            // the rest of the fields are copied or moved out of the base
+                            let (base, base_span) = base.as_ref().unwrap();
+                            let place = base.project(Proj::Field(Rc::clone(name)));
+                            let span = Span::clone(base_span);
+                            if self.ctx.needs_drop(fd_ty) {
+                                Operand::Move(place, span)
+                            } else {
+                                Operand::Copy(place, span)
+                            }
+                        })
+                    })
+                    .collect();
+                self.push_adt(dest, expr, ops);
+            }
+            ExprKind::Array(elems) => {
+                let ops = elems.iter().map(|elem| self.as_operand(elem)).collect();
+                let rvalue = Rvalue::Aggregate(AggregateKind::Array, ops);
+                self.push_assign(dest.clone(), rvalue, &expr.span);
+            }
+            ExprKind::Print(print_dest, fmt) => {
+                let ops = fmt
+                    .args
+                    .iter()
+                    .map(|arg| self.as_format_operand(arg))
+                    .collect();
+                self.push(
+                    StatementKind::Print(*print_dest, fmt.pieces.clone(), ops),
+                    &expr.span,
+                );
+            }
+            ExprKind::Unit => (),
+            _ => {
+                let rvalue = self.as_rvalue(expr);
+                self.push_assign(dest.clone(), rvalue, &expr.span);
+            }
+        }
+    }
+
+    fn push_adt(&mut self, dest: &Place, expr: &Expr, ops: Vec<Operand>) {
+        let ty = self.ctx.get_type(expr.id);
+        let kind = AggregateKind::Adt(Rc::clone(ty.get_adt_name().unwrap()));
+        self.push_assign(dest.clone(), Rvalue::Aggregate(kind, ops), &expr.span);
+    }
+
+    fn as_rvalue(&mut self, expr: &Expr) -> Rvalue {
+        match &expr.kind {
+            ExprKind::Binary(binop @ (hir::BinOp::Div | hir::BinOp::Rem), lhs, rhs)
+                if self.ctx.get_type(expr.id).is_integral() =>
+            {
+                self.checked_div(expr, *binop, lhs, rhs)
+            }
+            ExprKind::Binary(binop, lhs, rhs) => {
+                let lhs = self.as_operand(lhs);
+                let rhs = self.as_operand(rhs);
+                let binop = match binop {
+                    hir::BinOp::Add => BinOp::Add,
+                    hir::BinOp::Sub => BinOp::Sub,
+                    hir::BinOp::Mul => BinOp::Mul,
+                    hir::BinOp::Div => BinOp::Div,
+                    hir::BinOp::Rem => BinOp::Rem,
+                    hir::BinOp::Eq => BinOp::Eq,
+                    hir::BinOp::Ne => BinOp::Ne,
+                    hir::BinOp::Gt => BinOp::Gt,
+                    hir::BinOp::Lt => BinOp::Lt,
+                };
+                Rvalue::BinaryOp(binop, lhs, rhs)
+            }
+            ExprKind::Unary(hir::UnOp::Minus, inner) => {
+                Rvalue::UnaryOp(UnOp::Neg, self.as_operand(inner))
+            }
+            ExprKind::Unary(hir::UnOp::Plus, inner) => Rvalue::Use(self.as_operand(inner)),
+            ExprKind::Cast(inner, _) => {
+                Rvalue::Cast(self.as_operand(inner), self.ctx.get_type(expr.id))
+            }
+            ExprKind::AddrOf(mutbl, inner) => Rvalue::Ref(*mutbl, self.as_place(inner)),
+            ExprKind::Box(inner) => Rvalue::Box(self.as_operand(inner)),
+            // the built-in methods of raw pointers
+            ExprKind::MethodCall(recv, method, args) => {
+                let ptr = self.as_operand(recv);
+                match method.symbol.as_str() {
+                    "add" | "offset" => {
+                        let count = self.as_operand(&args[0]);
+                        Rvalue::BinaryOp(BinOp::Offset, ptr, count)
+                    }
+                    "is_null" => {
+                        let null = Operand::Const(Constant {
+                            kind: ConstKind::Null,
+                            ty: self.ctx.get_type(recv.id),
+                        });
+                        Rvalue::BinaryOp(BinOp::Eq, ptr, null)
+                    }
+                    _ => panic!("ICE: unknown method `{}` of raw pointers", method.symbol),
+                }
+            }
+            _ => Rvalue::Use(self.as_operand(expr)),
+        }
+    }
+
+    /// Integer division and remainder panic if the divisor is zero, or if they overflow
+    fn checked_div(&mut self, expr: &Expr, binop: hir::BinOp, lhs: &Expr, rhs: &Expr) -> Rvalue {
+        let lhs = Operand::Copy(Place::local(self.as_local(lhs)), lhs.span.clone());
+        let rhs = Operand::Copy(Place::local(self.as_local(rhs)), rhs.span.clone());
+        let ty = self.ctx.get_type(expr.id);
+        let TyKind::Int(int_ty) = ty.kind else {
+            unreachable!();
+        };
+        let (binop, zero_msg, overflow_msg) = match binop {
+            hir::BinOp::Div => (
+                BinOp::Div,
+                "attempt to divide by zero",
+                "attempt to divide with overflow",
+            ),
+            _ => (
+                BinOp::Rem,
+                "attempt to calculate the remainder with a divisor of zero",
+                "attempt to calculate the remainder with overflow",
+            ),
+        };
+        let int_const = |n: i128| {
+            Operand::Const(Constant {
+                kind: ConstKind::Int(n as i64),
+                ty: Rc::clone(&ty),
+            })
+        };
+
+        let nonzero_bb = self.new_block();
+        self.panic_if(&rhs, int_const(0), zero_msg, nonzero_bb, &expr.span);
+        // `MIN / -1` overflows for signed integers
+        if int_ty.is_signed() {
+            let is_minus_one = self.eq_temp(&rhs, int_const(-1), &expr.span);
+            let minus_one_bb = self.new_block();
+            let ok_bb = self.new_block();
+            self.terminate(
+                TerminatorKind::If(is_minus_one, minus_one_bb, ok_bb),
+                &expr.span,
+            );
+            self.current = minus_one_bb;
+            let min = int_const(int_ty.min_value());
+            self.panic_if(&lhs, min, overflow_msg, ok_bb, &expr.span);
+        }
+        Rvalue::BinaryOp(binop, lhs, rhs)
+    }
+
+    /// Bool temporary holding whether `lhs == rhs`
+    fn eq_temp(&mut self, lhs: &Operand, rhs: Operand, span: &Span) -> Operand {
+        let temp = self.new_local(None, Rc::new(Ty::new(TyKind::Bool)));
+        self.push_assign(
+            Place::local(temp),
+            Rvalue::BinaryOp(BinOp::Eq, lhs.clone(), rhs),
+            span,
+        );
+        Operand::Copy(Place::local(temp), span.clone())
+    }
+
+    /// Panic with `msg` if `lhs == rhs`, and continue at `ok_bb` if not
+    fn panic_if(&mut self, lhs: &Operand, rhs: Operand, msg: &str, ok_bb: BasicBlock, span: &Span) {
+        let cond = self.eq_temp(lhs, rhs, span);
+        let panic_bb = self.new_block();
+        self.terminate(TerminatorKind::If(cond, panic_bb, ok_bb), span);
+        self.current = panic_bb;
+        self.terminate(TerminatorKind::Panic(Some(msg.to_string())), span);
+        self.current = ok_bb;
+    }
+
+    fn as_operand(&mut self, expr: &Expr) -> Operand {
+        let kind = match &expr.kind {
+            // literals are in the range of their types, and `u64` ones wrap around
+            ExprKind::NumLit(n) => ConstKind::Int(*n as i64),
+            ExprKind::FloatLit(f, _) => ConstKind::Float(*f),
+            ExprKind::BoolLit(b) => ConstKind::Bool(*b),
+            ExprKind::CharLit(c) => ConstKind::Char(*c),
+            ExprKind::StrLit(s) => ConstKind::Str(Rc::new(s.clone())),
+            ExprKind::Unit => ConstKind::Unit,
+            ExprKind::NullPtr(_) => ConstKind::Null,
+            ExprKind::Path(Path {
+                res: Res::Def(DefKind::Const, def_id),
+                ..
+            }) => const_kind(self.ctx.lookup_const_value(def_id).unwrap()),
+            _ if self.is_place_expr(expr) => {
+                let place = self.as_place(expr);
+                return if self.ctx.is_moved(expr.id) {
+                    Operand::Move(place, expr.span.clone())
+                } else {
+                    Operand::Copy(place, expr.span.clone())
+                };
+            }
+            _ => {
+                let temp = self.new_temp(expr);
+                self.expr_into(&Place::local(temp), expr);
+                return Operand::Move(Place::local(temp), expr.span.clone());
+            }
+        };
+        Operand::Const(Constant {
+            kind,
+            ty: self.ctx.get_type(expr.id),
+        })
+    }
+
+    /// Arrays and ADTs are passed by reference, so copies of them are passed as
+    /// temporaries owned by the callee
+    fn as_call_operand(&mut self, expr: &Expr) -> Operand {
+        match self.as_operand(expr) {
+            Operand::Copy(place, span)
+                if matches!(
+                    self.ctx.get_type(expr.id).kind,
+                    TyKind::Adt(_) | TyKind::Array(..)
+                ) =>
+            {
+                let temp = self.new_temp(expr);
+                self.push_assign(
+                    Place::local(temp),
+                    Rvalue::Use(Operand::Copy(place, span)),
+                    &expr.span,
+                );
+                Operand::Move(Place::local(temp), expr.span.clone())
+            }
+            op => op,
+        }
+    }
+
+    fn as_place(&mut self, expr: &Expr) -> Place {
+        match &expr.kind {
+            ExprKind::Path(Path {
+                res: Res::Local(local_id),
+                ..
+            }) => Place::local(self.var_locals[local_id]),
+            // statics are accessed through pointers to them
+            ExprKind::Path(Path {
+                res: Res::Def(DefKind::Static(_), def_id),
+                ..
+            }) => {
+                let ty = self.ctx.lookup_name_type(def_id).unwrap();
+                let ptr_ty = Rc::new(Ty::new(TyKind::RawPtr(ty, Mutability::Mut)));
+                let temp = self.new_local(None, Rc::clone(&ptr_ty));
+                let addr = Constant {
+                    kind: ConstKind::Static(Rc::clone(&def_id.cpath)),
+                    ty: ptr_ty,
+                };
+                self.push_assign(
+                    Place::local(temp),
+                    Rvalue::Use(Operand::Const(addr)),
+                    &expr.span,
+                );
+                Place::local(temp).project(Proj::Deref)
+            }
+            ExprKind::Field(base, field) => {
+                let mut place = self.as_place(base);
+                self.auto_deref(&mut place, &self.ctx.get_type(base.id));
+                place.projs.push(Proj::Field(Rc::clone(&field.symbol)));
+                place
+            }
+            ExprKind::Index(base, index) => {
+                let mut place = self.as_place(base);
+                let index = self.as_local(index);
+                self.auto_deref(&mut place, &self.ctx.get_type(base.id));
+                place.projs.push(Proj::Index(index));
+                place
+            }
+            ExprKind::Unary(hir::UnOp::Deref, inner) => {
+                let mut place = self.as_place(inner);
+                place.projs.push(Proj::Deref);
+                place
+            }
+            // temporaries live until the end of the enclosing block
+            _ => {
+                let temp = self.new_temp(expr);
+                self.expr_into(&Place::local(temp), expr);
+                self.schedule_drop(temp);
+                Place::local(temp)
+            }
+        }
+    }
+
+    /// Arguments of formatting macros are taken by reference, and references and boxes
+    /// are formatted as the values they point to
+    fn as_format_operand(&mut self, arg: &Expr) -> Operand {
+        let mut ty = self.ctx.get_type(arg.id);
+        if !matches!(&ty.kind, TyKind::Box(_) | TyKind::Ref(..)) || ty.is_str_ref() {
+            return self.as_operand(arg);
+        }
+        let mut place = self.as_place(arg);
+        while let TyKind::Box(inner) | TyKind::Ref(inner, _) = &ty.kind
+            && !ty.is_str_ref()
+        {
+            place.projs.push(Proj::Deref);
+            ty = Rc::clone(inner);
+        }
+        Operand::Copy(place, arg.span.clone())
+    }
+
+    /// Local holding the value of `expr`
+    fn as_local(&mut self, expr: &Expr) -> Local {
+        match self.as_operand(expr) {
+            Operand::Copy(place, _) | Operand::Move(place, _) if place.projs.is_empty() => {
+                place.local
+            }
+            op => {
+                let temp = self.new_temp(expr);
+                self.push_assign(Place::local(temp), Rvalue::Use(op), &expr.span);
+                temp
+            }
+        }
+    }
+
+    /// Fields and elements are accessed through boxes and references
+    fn auto_deref(&self, place: &mut Place, ty: &Rc<Ty>) {
+        let mut ty = Rc::clone(ty);
+        while let TyKind::Box(inner) | TyKind::Ref(inner, _) = &ty.kind {
+            place.projs.push(Proj::Deref);
+            ty = Rc::clone(inner);
+        }
+    }
+
+    /// Expressions denoting memory locations
+    /// ref: https://doc.rust-lang.org/reference/expressions.html#place-expressions-and-value-expressions
+    fn is_place_expr(&self, expr: &Expr) -> bool {
+        match &expr.kind {
+            ExprKind::Path(path) => {
+                matches!(path.res, Res::Local(_) | Res::Def(DefKind::Static(_), _))
+            }
+            ExprKind::Field(..) | ExprKind::Index(..) | ExprKind::Unary(hir::UnOp::Deref, _) => {
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Constructor which the path expression refers to
+    fn ctor_kind(&self, expr: &Expr) -> Option<CtorKind> {
+        match &expr.kind {
+            ExprKind::Path(Path {
+                res: Res::Def(DefKind::Ctor(ctor_kind), _),
+                ..
+            }) => Some(*ctor_kind),
+            _ => None,
+        }
+    }
+}
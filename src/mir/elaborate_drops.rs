@@ -0,0 +1,593 @@
+//! Drop elaboration
+//!
+//! `drop` statements inserted by lowering may refer to places which are uninitialized
+//! or partially moved out on some paths. Places which may be initialized and places
+//! which may be uninitialized are computed by a dataflow analysis over move paths,
+//! and each `drop` is replaced by drops of its initialized parts. Parts initialized on
+//! some paths only are guarded by drop flags, which are boolean locals updated whenever
+//! the parts are initialized or moved out.
+//!
+//! The same analysis remembers where each path may have been moved out, so that uses of
+//! values after they are moved out are reported.
+
+use super::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Returns errors of uses of moved values, in which case the body is left as it is
+pub fn elaborate_drops(ctx: &Ctxt, body: &mut Body) -> Vec<String> {
+    let move_paths = MovePaths::collect(ctx, body);
+    let entry_states = compute_entry_states(body, &move_paths);
+    let errors = check_uses(body, &move_paths, &entry_states);
+    if !errors.is_empty() {
+        return errors;
+    }
+
+    // drop flags are needed only for the parts which are dropped conditionally
+    let mut flagged = BTreeSet::new();
+    for (bb, block) in body.blocks.iter().enumerate() {
+        let mut state = entry_states[bb].clone();
+        for stmt in &block.stmts {
+            if let StatementKind::Drop(place) = &stmt.kind {
+                let ops = move_paths.elaborate(ctx, body, place, &state);
+                flagged.extend(ops.iter().filter_map(|op| op.flag));
+            }
+            for (place, init, span) in stmt_effects(stmt) {
+                state.apply(&move_paths, place, init, span);
+            }
+        }
+    }
+    // flags are numbered in the order of the paths
+    let mut flags = BTreeMap::new();
+    for path in flagged {
+        flags.insert(path, body.locals.len());
+        body.locals.push(LocalDecl {
+            name: None,
+            ty: Rc::new(Ty::new(TyKind::Bool)),
+        });
+    }
+
+    let mut elaborator = Elaborator {
+        ctx,
+        move_paths: &move_paths,
+        flags: &flags,
+    };
+    for (bb, state) in entry_states.into_iter().enumerate() {
+        elaborator.elaborate_block(body, bb, state);
+    }
+
+    // the arguments are initialized on entry
+    let inits: Vec<Statement> = flags
+        .iter()
+        .map(|(path, flag)| {
+            let init = body.args().contains(&move_paths.paths[*path].local);
+            flag_update(*flag, init, &body.blocks[START_BLOCK].terminator.span)
+        })
+        .collect();
+    body.blocks[START_BLOCK].stmts.splice(0..0, inits);
+    vec![]
+}
+
+/// Places rooted at locals through fields and boxes, whose initializedness is tracked
+struct MovePaths {
+    paths: Vec<Place>,
+    index: HashMap<Place, usize>,
+}
+
+impl MovePaths {
+    fn collect(ctx: &Ctxt, body: &Body) -> Self {
+        let mut move_paths = MovePaths {
+            paths: vec![],
+            index: HashMap::new(),
+        };
+        let mut dropped = vec![];
+        let mut moved = vec![];
+        for block in &body.blocks {
+            for stmt in &block.stmts {
+                if let StatementKind::Drop(place) = &stmt.kind
+                    && is_trackable(ctx, body, place)
+                {
+                    dropped.push(place.clone());
+                }
+            }
+            for (place, init, _) in block_effects(block) {
+                if !init && is_trackable(ctx, body, place) && ctx.needs_drop(&place.ty(body, ctx)) {
+                    moved.push(place.clone());
+                }
+            }
+        }
+        for place in dropped.iter().chain(&moved) {
+            move_paths.add(place.clone());
+        }
+
+        // places partially moved out are dropped part by part, so their parts are tracked
+        for dropped in &dropped {
+            for moved in &moved {
+                if !moved.starts_with(dropped) {
+                    continue;
+                }
+                for len in dropped.projs.len()..moved.projs.len() {
+                    let prefix = Place {
+                        local: moved.local,
+                        projs: moved.projs[..len].to_vec(),
+                    };
+                    for child in drop_children(ctx, body, &prefix) {
+                        move_paths.add(child);
+                    }
+                }
+            }
+        }
+        move_paths
+    }
+
+    fn add(&mut self, place: Place) {
+        if !self.index.contains_key(&place) {
+            self.index.insert(place.clone(), self.paths.len());
+            self.paths.push(place);
+        }
+    }
+
+    /// Whether some places inside the path are tracked separately
+    fn has_children(&self, path: usize) -> bool {
+        let place = &self.paths[path];
+        self.paths
+            .iter()
+            .any(|p| p.starts_with(place) && p.projs.len() > place.projs.len())
+    }
+
+    /// Drops and deallocations which a `drop` of the place is elaborated to
+    fn elaborate(&self, ctx: &Ctxt, body: &Body, place: &Place, state: &State) -> Vec<DropOp> {
+        let mut ops = vec![];
+        self.elaborate_into(ctx, body, place, state, &mut ops);
+        ops
+    }
+
+    fn elaborate_into(
+        &self,
+        ctx: &Ctxt,
+        body: &Body,
+        place: &Place,
+        state: &State,
+        ops: &mut Vec<DropOp>,
+    ) {
+        // places behind references and indices are always initialized
+        let Some(&path) = self.index.get(place) else {
+            ops.push(DropOp {
+                kind: DropOpKind::Drop,
+                place: place.clone(),
+                flag: None,
+            });
+            return;
+        };
+        if !self.has_children(path) {
+            state.push_op(DropOpKind::Drop, place, path, ops);
+            return;
+        }
+
+        // partially moved out
+        let ty = place.ty(body, ctx);
+        for child in drop_children(ctx, body, place) {
+            self.elaborate_into(ctx, body, &child, state, ops);
+        }
+        if let TyKind::Box(_) = ty.kind {
+            // the allocation is freed even if the contents are moved out
+            state.push_op(DropOpKind::Deallocate, place, path, ops);
+        }
+    }
+}
+
+/// Whether the place is rooted at a local through fields and boxes
+fn is_trackable(ctx: &Ctxt, body: &Body, place: &Place) -> bool {
+    let mut ty = Rc::clone(&body.locals[place.local].ty);
+    for proj in &place.projs {
+        match (&ty.kind, proj) {
+            (TyKind::Box(_), Proj::Deref) | (TyKind::Adt(_), Proj::Field(_)) => {
+                ty = project_ty(&ty, proj, ctx);
+            }
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Parts of the place which are dropped separately when it is partially moved out
+fn drop_children(ctx: &Ctxt, body: &Body, place: &Place) -> Vec<Place> {
+    let ty = place.ty(body, ctx);
+    match &ty.kind {
+        TyKind::Adt(name) => {
+            let adt = ctx.lookup_adt_def(name).unwrap();
+            adt.fields
+                .iter()
+                .filter(|(_, fd_ty)| ctx.needs_drop(fd_ty))
+                .map(|(fd, _)| place.project(Proj::Field(Rc::clone(fd))))
+                .collect()
+        }
+        TyKind::Box(_) => vec![place.project(Proj::Deref)],
+        _ => panic!("ICE: `{}` cannot be partially moved out", ty),
+    }
+}
+
+/// Places moved out (`false`) and initialized (`true`) by the statement in order, with
+/// the spans of the operands moved out
+fn stmt_effects(stmt: &Statement) -> Vec<(&Place, bool, &Span)> {
+    match &stmt.kind {
+        StatementKind::Assign(place, rvalue) => {
+            let mut effects = moves(rvalue.operands());
+            effects.push((place, true, &stmt.span));
+            effects
+        }
+        StatementKind::Print(_, _, args) => moves(args.iter().collect()),
+        StatementKind::Drop(_) | StatementKind::Deallocate(_) => vec![],
+    }
+}
+
+fn terminator_effects(terminator: &Terminator) -> Vec<(&Place, bool, &Span)> {
+    match &terminator.kind {
+        TerminatorKind::Call { args, dest, .. } => {
+            let mut effects = moves(args.iter().collect());
+            effects.push((dest, true, &terminator.span));
+            effects
+        }
+        TerminatorKind::If(cond, _, _) => moves(vec![cond]),
+        TerminatorKind::Goto(_)
+        | TerminatorKind::Return
+        | TerminatorKind::Panic(_)
+        | TerminatorKind::Unreachable => vec![],
+    }
+}
+
+fn block_effects(block: &BasicBlockData) -> Vec<(&Place, bool, &Span)> {
+    let mut effects: Vec<_> = block.stmts.iter().flat_map(stmt_effects).collect();
+    effects.extend(terminator_effects(&block.terminator));
+    effects
+}
+
+fn moves(ops: Vec<&Operand>) -> Vec<(&Place, bool, &Span)> {
+    ops.into_iter()
+        .filter_map(|op| match op {
+            Operand::Move(place, span) => Some((place, false, span)),
+            Operand::Copy(_, _) | Operand::Const(_) => None,
+        })
+        .collect()
+}
+
+/// Move paths which may be initialized and which may be uninitialized
+#[derive(Clone, PartialEq, Eq)]
+struct State {
+    maybe_init: Vec<bool>,
+    maybe_uninit: Vec<bool>,
+    /// Where the path may have been moved out, unless it is initialized again
+    moved_at: Vec<Option<Span>>,
+}
+
+impl State {
+    /// The place and places inside it are initialized or moved out at `span`
+    fn apply(&mut self, move_paths: &MovePaths, place: &Place, init: bool, span: &Span) {
+        for (i, path) in move_paths.paths.iter().enumerate() {
+            if path.starts_with(place) {
+                self.maybe_init[i] = init;
+                self.maybe_uninit[i] = !init;
+                self.moved_at[i] = (!init).then(|| span.clone());
+            }
+        }
+    }
+
+    fn join(&mut self, other: &State) {
+        for (a, b) in self.maybe_init.iter_mut().zip(&other.maybe_init) {
+            *a |= *b;
+        }
+        for (a, b) in self.maybe_uninit.iter_mut().zip(&other.maybe_uninit) {
+            *a |= *b;
+        }
+        // the first move found is reported
+        for (a, b) in self.moved_at.iter_mut().zip(&other.moved_at) {
+            if a.is_none() {
+                a.clone_from(b);
+            }
+        }
+    }
+
+    /// Places never initialized are skipped, and places maybe uninitialized are
+    /// guarded by their flags
+    fn push_op(&self, kind: DropOpKind, place: &Place, path: usize, ops: &mut Vec<DropOp>) {
+        if !self.maybe_init[path] {
+            return;
+        }
+        ops.push(DropOp {
+            kind,
+            place: place.clone(),
+            flag: self.maybe_uninit[path].then_some(path),
+        });
+    }
+}
+
+/// States on entry to each block. The arguments are initialized on entry to the function
+fn compute_entry_states(body: &Body, move_paths: &MovePaths) -> Vec<State> {
+    let n = move_paths.paths.len();
+    let bottom = State {
+        maybe_init: vec![false; n],
+        maybe_uninit: vec![false; n],
+        moved_at: vec![None; n],
+    };
+    let mut states = vec![bottom; body.blocks.len()];
+    for (i, path) in move_paths.paths.iter().enumerate() {
+        let is_arg = body.args().contains(&path.local);
+        states[START_BLOCK].maybe_init[i] = is_arg;
+        states[START_BLOCK].maybe_uninit[i] = !is_arg;
+    }
+
+    let mut worklist = vec![START_BLOCK];
+    while let Some(bb) = worklist.pop() {
+        let block = &body.blocks[bb];
+        let mut state = states[bb].clone();
+        for stmt in &block.stmts {
+            for (place, init, span) in stmt_effects(stmt) {
+                state.apply(move_paths, place, init, span);
+            }
+        }
+        for (place, init, span) in terminator_effects(&block.terminator) {
+            state.apply(move_paths, place, init, span);
+        }
+        for succ in block.terminator.kind.successors() {
+            let mut joined = states[succ].clone();
+            joined.join(&state);
+            if joined != states[succ] || succ == START_BLOCK {
+                states[succ] = joined;
+                worklist.push(succ);
+            }
+        }
+    }
+    states
+}
+
+/// Report uses of places which may have been moved out. Moves and initializations take
+/// effect in the same order as in `stmt_effects` and `terminator_effects`. Uses and moves
+/// are reported at the spans of their operands
+fn check_uses(body: &Body, move_paths: &MovePaths, entry_states: &[State]) -> Vec<String> {
+    let mut checker = UseChecker {
+        body,
+        move_paths,
+        errors: vec![],
+    };
+    for (bb, block) in body.blocks.iter().enumerate() {
+        let mut state = entry_states[bb].clone();
+        for stmt in &block.stmts {
+            match &stmt.kind {
+                StatementKind::Assign(place, rvalue) => {
+                    if let Rvalue::Ref(_, borrowed) = rvalue {
+                        checker.check_use(borrowed, "Borrow of", &state, &stmt.span);
+                    }
+                    checker.check_operands(rvalue.operands(), &mut state);
+                    checker.check_assign(place, &mut state, &stmt.span);
+                }
+                StatementKind::Print(_, _, args) => {
+                    checker.check_operands(args.iter().collect(), &mut state);
+                }
+                StatementKind::Drop(_) | StatementKind::Deallocate(_) => (),
+            }
+        }
+        let span = &block.terminator.span;
+        match &block.terminator.kind {
+            TerminatorKind::If(cond, _, _) => checker.check_operands(vec![cond], &mut state),
+            TerminatorKind::Call { args, dest, .. } => {
+                checker.check_operands(args.iter().collect(), &mut state);
+                checker.check_assign(dest, &mut state, span);
+            }
+            TerminatorKind::Goto(_)
+            | TerminatorKind::Return
+            | TerminatorKind::Panic(_)
+            | TerminatorKind::Unreachable => (),
+        }
+    }
+    checker.errors
+}
+
+struct UseChecker<'a> {
+    body: &'a Body,
+    move_paths: &'a MovePaths,
+    errors: Vec<String>,
+}
+
+impl UseChecker<'_> {
+    fn check_operands(&mut self, ops: Vec<&Operand>, state: &mut State) {
+        for op in ops {
+            match op {
+                Operand::Copy(place, span) => self.check_use(place, "Use of", state, span),
+                Operand::Move(place, span) => {
+                    self.check_use(place, "Use of", state, span);
+                    state.apply(self.move_paths, place, false, span);
+                }
+                Operand::Const(_) => (),
+            }
+        }
+    }
+
+    /// The value of the place is used, so no part of it may be moved out
+    fn check_use(&mut self, place: &Place, action: &str, state: &State, span: &Span) {
+        for (i, path) in self.move_paths.paths.iter().enumerate() {
+            let Some(moved_span) = &state.moved_at[i] else {
+                continue;
+            };
+            let value = if place.starts_with(path) {
+                format!("moved value {}", self.describe_place(path))
+            } else if path.starts_with(place) {
+                format!("partially moved value {}", self.describe_place(place))
+            } else {
+                continue;
+            };
+            self.errors.push(format!(
+                "{} {} at {}\n    value moved here at {}",
+                action,
+                value,
+                span.to_location(),
+                moved_span.to_location()
+            ));
+            return;
+        }
+    }
+
+    /// Places inside a value moved out cannot be assigned to, while the value itself can
+    fn check_assign(&mut self, place: &Place, state: &mut State, span: &Span) {
+        for (i, path) in self.move_paths.paths.iter().enumerate() {
+            if let Some(moved_span) = &state.moved_at[i]
+                && place.starts_with(path)
+                && place != path
+            {
+                self.errors.push(format!(
+                    "Assign to part of moved value {} at {}\n    value moved here at {}",
+                    self.describe_place(path),
+                    span.to_location(),
+                    moved_span.to_location()
+                ));
+                return;
+            }
+        }
+        state.apply(self.move_paths, place, true, span);
+    }
+
+    fn describe_place(&self, place: &Place) -> String {
+        let Some(name) = &self.body.locals[place.local].name else {
+            return "temporary value".to_string();
+        };
+        let mut desc = name.to_string();
+        for (i, proj) in place.projs.iter().enumerate() {
+            match proj {
+                // fields and elements are accessed through pointers implicitly
+                Proj::Deref
+                    if matches!(
+                        place.projs.get(i + 1),
+                        Some(Proj::Field(_) | Proj::Index(_))
+                    ) => {}
+                Proj::Deref => desc = format!("*{}", desc),
+                Proj::Field(field) => desc = format!("{}.{}", desc, field),
+                Proj::Index(_) => desc = format!("{}[_]", desc),
+            }
+        }
+        format!("`{}`", desc)
+    }
+}
+
+struct DropOp {
+    kind: DropOpKind,
+    place: Place,
+    /// Move path whose flag guards the operation
+    flag: Option<usize>,
+}
+
+#[derive(Clone, Copy)]
+enum DropOpKind {
+    Drop,
+    Deallocate,
+}
+
+struct Elaborator<'a, 'ctx> {
+    ctx: &'a Ctxt<'ctx>,
+    move_paths: &'a MovePaths,
+    /// Move paths to their drop flags
+    flags: &'a BTreeMap<usize, Local>,
+}
+
+impl Elaborator<'_, '_> {
+    /// Replace drops in the block and update drop flags. Guarded drops split the block
+    fn elaborate_block(&mut self, body: &mut Body, bb: BasicBlock, mut state: State) {
+        let stmts = std::mem::take(&mut body.blocks[bb].stmts);
+        let span = body.blocks[bb].terminator.span.clone();
+        let terminator = std::mem::replace(
+            &mut body.blocks[bb].terminator,
+            Terminator {
+                kind: TerminatorKind::Unreachable,
+                span,
+            },
+        );
+        let mut cursor = bb;
+        for stmt in stmts {
+            if let StatementKind::Drop(place) = &stmt.kind {
+                let ops = self.move_paths.elaborate(self.ctx, body, place, &state);
+                for op in ops {
+                    cursor = self.push_op(body, cursor, op, &stmt.span);
+                }
+                continue;
+            }
+            let updates = self.flag_updates(stmt_effects(&stmt), &mut state, &stmt.span);
+            body.blocks[cursor].stmts.push(stmt);
+            body.blocks[cursor].stmts.extend(updates);
+        }
+        let updates = self.flag_updates(
+            terminator_effects(&terminator),
+            &mut state,
+            &terminator.span,
+        );
+        body.blocks[cursor].stmts.extend(updates);
+        body.blocks[cursor].terminator = terminator;
+    }
+
+    /// Returns the block to which the following statements are appended
+    fn push_op(
+        &mut self,
+        body: &mut Body,
+        cursor: BasicBlock,
+        op: DropOp,
+        span: &Span,
+    ) -> BasicBlock {
+        let kind = match op.kind {
+            DropOpKind::Drop => StatementKind::Drop(op.place),
+            DropOpKind::Deallocate => StatementKind::Deallocate(op.place),
+        };
+        let stmt = Statement {
+            kind,
+            span: span.clone(),
+        };
+        let Some(path) = op.flag else {
+            body.blocks[cursor].stmts.push(stmt);
+            return cursor;
+        };
+
+        let drop_bb = body.blocks.len();
+        let next_bb = drop_bb + 1;
+        for (stmts, target) in [(vec![stmt], next_bb), (vec![], 0)] {
+            body.blocks.push(BasicBlockData {
+                stmts,
+                terminator: Terminator {
+                    kind: TerminatorKind::Goto(target),
+                    span: span.clone(),
+                },
+            });
+        }
+        let flag = Operand::Copy(Place::local(self.flags[&path]), span.clone());
+        body.blocks[cursor].terminator = Terminator {
+            kind: TerminatorKind::If(flag, drop_bb, next_bb),
+            span: span.clone(),
+        };
+        next_bb
+    }
+
+    fn flag_updates(
+        &self,
+        effects: Vec<(&Place, bool, &Span)>,
+        state: &mut State,
+        span: &Span,
+    ) -> Vec<Statement> {
+        let mut updates = vec![];
+        for (place, init, effect_span) in effects {
+            state.apply(self.move_paths, place, init, effect_span);
+            for (i, path) in self.move_paths.paths.iter().enumerate() {
+                if let Some(flag) = self.flags.get(&i)
+                    && path.starts_with(place)
+                {
+                    updates.push(flag_update(*flag, init, span));
+                }
+            }
+        }
+        updates
+    }
+}
+
+fn flag_update(flag: Local, value: bool, span: &Span) -> Statement {
+    let cons = Constant {
+        kind: ConstKind::Bool(value),
+        ty: Rc::new(Ty::new(TyKind::Bool)),
+    };
+    Statement {
+        kind: StatementKind::Assign(Place::local(flag), Rvalue::Use(Operand::Const(cons))),
+        span: span.clone(),
+    }
+}
@@ -0,0 +1,152 @@
+//! Inlining of calls, which runs with `-O` before the other optimizations
+//!
+//! A call is replaced by a copy of the callee's blocks, whose locals become new locals
+//! of the caller. The arguments are assigned to the copies of the parameters, and the
+//! return value is moved from the copy of the return place to the destination of the
+//! call once the callee returns.
+//!
+//! Functions marked `#[inline]` or `#[inline(always)]` are inlined regardless of their
+//! size, functions marked `#[inline(never)]` are never inlined, and the others are
+//! inlined if they are small. Recursive functions and foreign functions are never inlined.
+
+use super::*;
+use std::collections::{HashMap, HashSet};
+
+/// Functions with at most this many statements and terminators are inlined without hints
+const INLINE_THRESHOLD: usize = 10;
+
+pub fn inline(program: &mut Program) {
+    let recursive = recursive_fns(program);
+    // callees are copied from the bodies before inlining, so inlining terminates
+    let callees: HashMap<Rc<CanonicalPath>, Body> = program
+        .bodies
+        .iter()
+        .filter(|body| should_inline(body, &recursive))
+        .map(|body| (Rc::clone(&body.name), body.clone()))
+        .collect();
+    for body in &mut program.bodies {
+        inline_calls(body, &callees);
+    }
+}
+
+fn should_inline(body: &Body, recursive: &HashSet<Rc<CanonicalPath>>) -> bool {
+    if recursive.contains(&body.name) {
+        return false;
+    }
+    match body.inline {
+        InlineAttr::Hint | InlineAttr::Always => true,
+        InlineAttr::Never => false,
+        InlineAttr::None => {
+            let size: usize = body.blocks.iter().map(|block| block.stmts.len() + 1).sum();
+            size <= INLINE_THRESHOLD
+        }
+    }
+}
+
+fn callees_of(body: &Body) -> impl Iterator<Item = &Rc<CanonicalPath>> {
+    body.blocks
+        .iter()
+        .filter_map(|block| match &block.terminator.kind {
+            TerminatorKind::Call { func, .. } => Some(func),
+            _ => None,
+        })
+}
+
+/// Functions which may call themselves directly or through other functions
+fn recursive_fns(program: &Program) -> HashSet<Rc<CanonicalPath>> {
+    let calls: HashMap<&Rc<CanonicalPath>, Vec<&Rc<CanonicalPath>>> = program
+        .bodies
+        .iter()
+        .map(|body| (&body.name, callees_of(body).collect()))
+        .collect();
+    let mut recursive = HashSet::new();
+    for body in &program.bodies {
+        let mut visited = HashSet::new();
+        let mut worklist = calls[&body.name].clone();
+        while let Some(func) = worklist.pop() {
+            if func == &body.name {
+                recursive.insert(Rc::clone(func));
+                break;
+            }
+            // foreign functions have no bodies
+            if visited.insert(func)
+                && let Some(callees) = calls.get(func)
+            {
+                worklist.extend(callees);
+            }
+        }
+    }
+    recursive
+}
+
+/// Inline calls until none of the calls in the body is to be inlined
+fn inline_calls(body: &mut Body, callees: &HashMap<Rc<CanonicalPath>, Body>) {
+    let mut bb = START_BLOCK;
+    // inlined blocks are appended, so the calls in them are also inlined
+    while bb < body.blocks.len() {
+        if let TerminatorKind::Call { func, .. } = &body.blocks[bb].terminator.kind
+            && let Some(callee) = callees.get(func)
+        {
+            inline_call(body, bb, callee);
+        }
+        bb += 1;
+    }
+}
+
+/// Replace the call terminating `bb` by the blocks of `callee`
+fn inline_call(body: &mut Body, bb: BasicBlock, callee: &Body) {
+    let TerminatorKind::Call {
+        args, dest, target, ..
+    } = std::mem::replace(
+        &mut body.blocks[bb].terminator.kind,
+        TerminatorKind::Unreachable,
+    )
+    else {
+        panic!("ICE: call expected");
+    };
+    let span = body.blocks[bb].terminator.span.clone();
+
+    // the locals and the blocks of the callee are numbered after those of the caller
+    let local_offset = body.locals.len();
+    let block_offset = body.blocks.len();
+    let mut callee = callee.clone();
+    for local in callee.locals_mut() {
+        *local += local_offset;
+    }
+    // the return value is moved to the destination in a new block after the callee
+    let ret_bb = block_offset + callee.blocks.len();
+    for block in &mut callee.blocks {
+        for succ in block.terminator.kind.successors_mut() {
+            *succ += block_offset;
+        }
+        if let TerminatorKind::Return = block.terminator.kind {
+            block.terminator.kind = TerminatorKind::Goto(ret_bb);
+        }
+    }
+
+    for (arg, op) in callee.args().zip(args) {
+        body.blocks[bb].stmts.push(Statement {
+            kind: StatementKind::Assign(Place::local(local_offset + arg), Rvalue::Use(op)),
+            span: span.clone(),
+        });
+    }
+    body.blocks[bb].terminator.kind = TerminatorKind::Goto(block_offset + START_BLOCK);
+    body.locals.extend(callee.locals);
+    body.blocks.extend(callee.blocks);
+    let ret = Place::local(local_offset + RETURN_PLACE);
+    // callees which diverge never return
+    let (stmts, kind) = match target {
+        Some(target) => (
+            vec![Statement {
+                kind: StatementKind::Assign(dest, Rvalue::Use(Operand::Move(ret, span.clone()))),
+                span: span.clone(),
+            }],
+            TerminatorKind::Goto(target),
+        ),
+        None => (vec![], TerminatorKind::Unreachable),
+    };
+    body.blocks.push(BasicBlockData {
+        stmts,
+        terminator: Terminator { kind, span },
+    });
+}
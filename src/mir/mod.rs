@@ -0,0 +1,390 @@
+//! Mid-level IR: control-flow graphs of function bodies
+//!
+//! Each function body is lowered from the typed HIR to basic blocks of statements
+//! and terminators over places. Every value lives in a local: the return place `_0`
+//! comes first, then the arguments, then user variables and temporaries.
+//! Drops are elaborated with drop flags after lowering, so every `drop` runs unconditionally.
+
+mod build;
+mod elaborate_drops;
+mod inline;
+mod opt;
+mod pretty;
+
+pub use self::build::build;
+pub use self::opt::optimize;
+
+use crate::ast::{FormatPiece, InlineAttr, Mutability, PrintDest};
+use crate::middle::ty::{Ty, TyKind};
+use crate::middle::Ctxt;
+use crate::resolve::CanonicalPath;
+use crate::span::Span;
+use std::rc::Rc;
+
+pub type Local = usize;
+pub type BasicBlock = usize;
+
+/// Local holding the return value
+pub const RETURN_PLACE: Local = 0;
+
+/// The first block of a body
+pub const START_BLOCK: BasicBlock = 0;
+
+pub struct Program {
+    pub bodies: Vec<Body>,
+    /// Functions declared in `extern` blocks
+    pub foreign_fns: Vec<ForeignFn>,
+    pub statics: Vec<Static>,
+}
+
+/// Statics live in memory for the whole run of the program and are accessed through
+/// their addresses
+pub struct Static {
+    pub name: Rc<CanonicalPath>,
+    pub mutbl: Mutability,
+    pub init: Constant,
+}
+
+pub struct ForeignFn {
+    pub name: Rc<CanonicalPath>,
+    pub param_tys: Rc<Vec<Rc<Ty>>>,
+    pub ret_ty: Rc<Ty>,
+}
+
+#[derive(Clone)]
+pub struct Body {
+    pub name: Rc<CanonicalPath>,
+    /// Arguments are the locals `_1` to `_{arg_count}`
+    pub arg_count: usize,
+    pub inline: InlineAttr,
+    pub locals: Vec<LocalDecl>,
+    pub blocks: Vec<BasicBlockData>,
+}
+
+impl Body {
+    pub fn args(&self) -> std::ops::Range<Local> {
+        1..self.arg_count + 1
+    }
+
+    pub fn return_ty(&self) -> Rc<Ty> {
+        Rc::clone(&self.locals[RETURN_PLACE].ty)
+    }
+
+    /// Every mention of a local, including the indices of places
+    fn locals_mut(&mut self) -> Vec<&mut Local> {
+        fn place_locals(place: &mut Place) -> impl Iterator<Item = &mut Local> {
+            std::iter::once(&mut place.local).chain(place.projs.iter_mut().filter_map(|proj| {
+                match proj {
+                    Proj::Index(index) => Some(index),
+                    Proj::Deref | Proj::Field(_) => None,
+                }
+            }))
+        }
+        fn operand_locals(op: &mut Operand) -> Vec<&mut Local> {
+            match op {
+                Operand::Copy(place, _) | Operand::Move(place, _) => place_locals(place).collect(),
+                Operand::Const(_) => vec![],
+            }
+        }
+
+        let mut locals = vec![];
+        for block in &mut self.blocks {
+            for stmt in &mut block.stmts {
+                match &mut stmt.kind {
+                    StatementKind::Assign(place, rvalue) => {
+                        locals.extend(place_locals(place));
+                        match rvalue {
+                            Rvalue::Use(op)
+                            | Rvalue::UnaryOp(_, op)
+                            | Rvalue::Cast(op, _)
+                            | Rvalue::Box(op) => locals.extend(operand_locals(op)),
+                            Rvalue::BinaryOp(_, lhs, rhs) => {
+                                locals.extend(operand_locals(lhs));
+                                locals.extend(operand_locals(rhs));
+                            }
+                            Rvalue::Aggregate(_, args) => {
+                                locals.extend(args.iter_mut().flat_map(operand_locals));
+                            }
+                            Rvalue::Ref(_, borrowed) => locals.extend(place_locals(borrowed)),
+                        }
+                    }
+                    StatementKind::Drop(place) | StatementKind::Deallocate(place) => {
+                        locals.extend(place_locals(place));
+                    }
+                    StatementKind::Print(_, _, args) => {
+                        locals.extend(args.iter_mut().flat_map(operand_locals));
+                    }
+                }
+            }
+            match &mut block.terminator.kind {
+                TerminatorKind::If(cond, _, _) => locals.extend(operand_locals(cond)),
+                TerminatorKind::Call { args, dest, .. } => {
+                    locals.extend(args.iter_mut().flat_map(operand_locals));
+                    locals.extend(place_locals(dest));
+                }
+                _ => (),
+            }
+        }
+        locals
+    }
+
+    /// Remove blocks which are never reached from the start block (e.g. code after `return`)
+    fn remove_unreachable_blocks(&mut self) {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut worklist = vec![START_BLOCK];
+        while let Some(bb) = worklist.pop() {
+            if !reachable[bb] {
+                reachable[bb] = true;
+                worklist.extend(self.blocks[bb].terminator.kind.successors());
+            }
+        }
+        let mut new_index = vec![None; self.blocks.len()];
+        let mut num_reachable = 0;
+        for (bb, is_reachable) in reachable.iter().enumerate() {
+            if *is_reachable {
+                new_index[bb] = Some(num_reachable);
+                num_reachable += 1;
+            }
+        }
+        let blocks = std::mem::take(&mut self.blocks);
+        for (mut block, is_reachable) in blocks.into_iter().zip(reachable) {
+            if is_reachable {
+                for succ in block.terminator.kind.successors_mut() {
+                    *succ = new_index[*succ].unwrap();
+                }
+                self.blocks.push(block);
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct LocalDecl {
+    /// `None` for temporaries
+    pub name: Option<Rc<String>>,
+    pub ty: Rc<Ty>,
+}
+
+#[derive(Clone)]
+pub struct BasicBlockData {
+    pub stmts: Vec<Statement>,
+    pub terminator: Terminator,
+}
+
+#[derive(Clone)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+#[derive(Clone)]
+pub enum StatementKind {
+    Assign(Place, Rvalue),
+    /// Run the drop glue of the value in the place
+    Drop(Place),
+    /// Free the heap allocation of a box whose contents are moved out
+    Deallocate(Place),
+    /// `print!` and friends
+    Print(PrintDest, Vec<FormatPiece>, Vec<Operand>),
+}
+
+#[derive(Clone)]
+pub struct Terminator {
+    pub kind: TerminatorKind,
+    pub span: Span,
+}
+
+#[derive(Clone)]
+pub enum TerminatorKind {
+    Goto(BasicBlock),
+    /// cond, then, else
+    If(Operand, BasicBlock, BasicBlock),
+    /// The result is written to `dest`. Calls to diverging functions have no target
+    Call {
+        func: Rc<CanonicalPath>,
+        args: Vec<Operand>,
+        dest: Place,
+        target: Option<BasicBlock>,
+    },
+    /// The value of the return place is returned
+    Return,
+    /// Print the message with the location of the terminator and exit with code 101.
+    /// Formatted messages are printed by a preceding `Print` instead, and `None` only exits
+    Panic(Option<String>),
+    Unreachable,
+}
+
+impl TerminatorKind {
+    pub fn successors(&self) -> Vec<BasicBlock> {
+        match self {
+            TerminatorKind::Goto(bb) => vec![*bb],
+            TerminatorKind::If(_, then_bb, else_bb) => vec![*then_bb, *else_bb],
+            TerminatorKind::Call { target, .. } => target.iter().copied().collect(),
+            TerminatorKind::Return | TerminatorKind::Panic(_) | TerminatorKind::Unreachable => {
+                vec![]
+            }
+        }
+    }
+
+    pub fn successors_mut(&mut self) -> Vec<&mut BasicBlock> {
+        match self {
+            TerminatorKind::Goto(bb) => vec![bb],
+            TerminatorKind::If(_, then_bb, else_bb) => vec![then_bb, else_bb],
+            TerminatorKind::Call { target, .. } => target.iter_mut().collect(),
+            TerminatorKind::Return | TerminatorKind::Panic(_) | TerminatorKind::Unreachable => {
+                vec![]
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Place {
+    pub local: Local,
+    pub projs: Vec<Proj>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Proj {
+    /// Dereference of a box, a reference or a raw pointer
+    Deref,
+    Field(Rc<String>),
+    /// Element at the index held by the local
+    Index(Local),
+}
+
+impl Place {
+    pub fn local(local: Local) -> Self {
+        Place {
+            local,
+            projs: vec![],
+        }
+    }
+
+    pub fn project(&self, proj: Proj) -> Self {
+        let mut ret = self.clone();
+        ret.projs.push(proj);
+        ret
+    }
+
+    /// Whether `self` is `other` or a place inside `other`
+    pub fn starts_with(&self, other: &Place) -> bool {
+        self.local == other.local && self.projs.starts_with(&other.projs)
+    }
+
+    pub fn ty(&self, body: &Body, ctx: &Ctxt) -> Rc<Ty> {
+        let mut ty = Rc::clone(&body.locals[self.local].ty);
+        for proj in &self.projs {
+            ty = project_ty(&ty, proj, ctx);
+        }
+        ty
+    }
+}
+
+/// Type of the place projected from a place of type `ty`
+pub fn project_ty(ty: &Ty, proj: &Proj, ctx: &Ctxt) -> Rc<Ty> {
+    match (&ty.kind, proj) {
+        (TyKind::Box(inner) | TyKind::Ref(inner, _) | TyKind::RawPtr(inner, _), Proj::Deref) => {
+            Rc::clone(inner)
+        }
+        (TyKind::Adt(name), Proj::Field(field)) => {
+            let adt = ctx.lookup_adt_def(name).unwrap();
+            let (_, fd_ty) = adt.fields.iter().find(|(fd, _)| fd == field).unwrap();
+            Rc::clone(fd_ty)
+        }
+        (TyKind::Array(elem, _), Proj::Index(_)) => Rc::clone(elem),
+        _ => panic!("ICE: cannot project `{}` with {:?}", ty, proj),
+    }
+}
+
+/// Places are used at the span of the expression they are evaluated from, which
+/// diagnostics of moves point at
+#[derive(Clone)]
+pub enum Operand {
+    Copy(Place, Span),
+    /// The value is moved out and the place is left uninitialized
+    Move(Place, Span),
+    Const(Constant),
+}
+
+impl Operand {
+    pub fn ty(&self, body: &Body, ctx: &Ctxt) -> Rc<Ty> {
+        match self {
+            Operand::Copy(place, _) | Operand::Move(place, _) => place.ty(body, ctx),
+            Operand::Const(cons) => Rc::clone(&cons.ty),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Constant {
+    pub kind: ConstKind,
+    pub ty: Rc<Ty>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstKind {
+    /// Bits of the value, which are truncated to the width of the type
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Char(char),
+    Str(Rc<String>),
+    Unit,
+    /// Null pointer
+    Null,
+    /// Address of a static
+    Static(Rc<CanonicalPath>),
+}
+
+#[derive(Clone)]
+pub enum Rvalue {
+    Use(Operand),
+    BinaryOp(BinOp, Operand, Operand),
+    UnaryOp(UnOp, Operand),
+    Cast(Operand, Rc<Ty>),
+    Ref(Mutability, Place),
+    /// `Box::new(operand)`, which moves the value to a new heap allocation
+    Box(Operand),
+    /// Operands of ADTs are in the order of the field definitions
+    Aggregate(AggregateKind, Vec<Operand>),
+}
+
+impl Rvalue {
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Rvalue::Use(op) | Rvalue::UnaryOp(_, op) | Rvalue::Cast(op, _) | Rvalue::Box(op) => {
+                vec![op]
+            }
+            Rvalue::BinaryOp(_, lhs, rhs) => vec![lhs, rhs],
+            Rvalue::Aggregate(_, ops) => ops.iter().collect(),
+            Rvalue::Ref(..) => vec![],
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum AggregateKind {
+    Array,
+    Adt(Rc<CanonicalPath>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Gt,
+    Lt,
+    /// `ptr.add(count)` and `ptr.offset(count)`
+    Offset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Neg,
+}
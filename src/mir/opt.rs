@@ -0,0 +1,485 @@
+//! Optimizations of MIR bodies, which run with `-O`
+//!
+//! Calls are inlined first (see `inline`), so the passes also work across them.
+//! Lowering creates a temporary for every expression and a block for every branch, and
+//! the LLVM backend gives every local its own stack slot. The passes here fold constant
+//! arithmetic and branches, propagate constants and copies into their uses, remove
+//! assignments which are never read with the locals they leave unused, and merge
+//! blocks which always run one after another.
+//!
+//! Since there are no loops, every local assigned exactly once is defined before all
+//! of its uses, which makes propagation a matter of counting uses.
+
+use super::*;
+use crate::ast::IntTy;
+
+pub fn optimize(ctx: &Ctxt, program: &mut Program) {
+    super::inline::inline(program);
+    for body in &mut program.bodies {
+        optimize_body(ctx, body);
+    }
+}
+
+fn optimize_body(ctx: &Ctxt, body: &mut Body) {
+    loop {
+        let mut changed = fold_constants(ctx, body);
+        changed |= propagate(ctx, body);
+        changed |= remove_dead_assignments(ctx, body);
+        if !changed {
+            break;
+        }
+    }
+    simplify_cfg(body);
+    remove_unused_locals(body);
+}
+
+/// How a local is used throughout the body
+#[derive(Default, Clone)]
+struct LocalUses {
+    /// Assignments to the whole local, including the destinations of calls
+    assigns: usize,
+    /// `copy _n` and `move _n`
+    operands: usize,
+    /// Any other mention: projections, borrows, indices, drops and partial assignments
+    others: usize,
+}
+
+impl LocalUses {
+    fn is_read(&self) -> bool {
+        self.operands + self.others > 0
+    }
+}
+
+fn count_uses(body: &Body) -> Vec<LocalUses> {
+    fn count_place(place: &Place, uses: &mut [LocalUses]) {
+        uses[place.local].others += 1;
+        for proj in &place.projs {
+            if let Proj::Index(index) = proj {
+                uses[*index].others += 1;
+            }
+        }
+    }
+    fn count_operand(op: &Operand, uses: &mut [LocalUses]) {
+        match op {
+            Operand::Copy(place, _) | Operand::Move(place, _) if place.projs.is_empty() => {
+                uses[place.local].operands += 1;
+            }
+            Operand::Copy(place, _) | Operand::Move(place, _) => count_place(place, uses),
+            Operand::Const(_) => (),
+        }
+    }
+    fn count_dest(place: &Place, uses: &mut [LocalUses]) {
+        if place.projs.is_empty() {
+            uses[place.local].assigns += 1;
+        } else {
+            count_place(place, uses);
+        }
+    }
+
+    let mut uses = vec![LocalUses::default(); body.locals.len()];
+    for block in &body.blocks {
+        for stmt in &block.stmts {
+            match &stmt.kind {
+                StatementKind::Assign(place, rvalue) => {
+                    count_dest(place, &mut uses);
+                    if let Rvalue::Ref(_, borrowed) = rvalue {
+                        count_place(borrowed, &mut uses);
+                    }
+                    for op in rvalue.operands() {
+                        count_operand(op, &mut uses);
+                    }
+                }
+                StatementKind::Drop(place) | StatementKind::Deallocate(place) => {
+                    count_place(place, &mut uses);
+                }
+                StatementKind::Print(_, _, args) => {
+                    for arg in args {
+                        count_operand(arg, &mut uses);
+                    }
+                }
+            }
+        }
+        match &block.terminator.kind {
+            TerminatorKind::If(cond, _, _) => count_operand(cond, &mut uses),
+            TerminatorKind::Call { args, dest, .. } => {
+                for arg in args {
+                    count_operand(arg, &mut uses);
+                }
+                count_dest(dest, &mut uses);
+            }
+            TerminatorKind::Goto(_)
+            | TerminatorKind::Return
+            | TerminatorKind::Panic(_)
+            | TerminatorKind::Unreachable => (),
+        }
+    }
+    // the arguments are assigned by the caller, and the return place is read by it
+    for arg in body.args() {
+        uses[arg].assigns += 1;
+    }
+    uses[RETURN_PLACE].others += 1;
+    uses
+}
+
+fn operands_mut(body: &mut Body) -> Vec<&mut Operand> {
+    let mut ops = vec![];
+    for block in &mut body.blocks {
+        for stmt in &mut block.stmts {
+            match &mut stmt.kind {
+                StatementKind::Assign(_, rvalue) => match rvalue {
+                    Rvalue::Use(op)
+                    | Rvalue::UnaryOp(_, op)
+                    | Rvalue::Cast(op, _)
+                    | Rvalue::Box(op) => ops.push(op),
+                    Rvalue::BinaryOp(_, lhs, rhs) => ops.extend([lhs, rhs]),
+                    Rvalue::Aggregate(_, args) => ops.extend(args),
+                    Rvalue::Ref(..) => (),
+                },
+                StatementKind::Print(_, _, args) => ops.extend(args),
+                StatementKind::Drop(_) | StatementKind::Deallocate(_) => (),
+            }
+        }
+        match &mut block.terminator.kind {
+            TerminatorKind::If(cond, _, _) => ops.push(cond),
+            TerminatorKind::Call { args, .. } => ops.extend(args),
+            _ => (),
+        }
+    }
+    ops
+}
+
+/// Replace arithmetic, comparisons and casts of constants by their results, and
+/// branches on constants by jumps
+fn fold_constants(ctx: &Ctxt, body: &mut Body) -> bool {
+    let mut changed = false;
+    for bb in 0..body.blocks.len() {
+        for i in 0..body.blocks[bb].stmts.len() {
+            let StatementKind::Assign(place, rvalue) = &body.blocks[bb].stmts[i].kind else {
+                continue;
+            };
+            let ty = place.ty(body, ctx);
+            let folded = match rvalue {
+                Rvalue::BinaryOp(binop, Operand::Const(lhs), Operand::Const(rhs)) => {
+                    fold_binary(*binop, lhs, rhs)
+                }
+                Rvalue::UnaryOp(UnOp::Neg, Operand::Const(inner)) => fold_neg(inner),
+                Rvalue::Cast(Operand::Const(inner), to_ty) => fold_cast(inner, to_ty),
+                _ => None,
+            };
+            if let Some(kind) = folded {
+                let cons = Operand::Const(Constant { kind, ty });
+                if let StatementKind::Assign(_, rvalue) = &mut body.blocks[bb].stmts[i].kind {
+                    *rvalue = Rvalue::Use(cons);
+                }
+                changed = true;
+            }
+        }
+        let terminator = &mut body.blocks[bb].terminator.kind;
+        if let TerminatorKind::If(Operand::Const(cond), then_bb, else_bb) = terminator {
+            let target = if cond.kind == ConstKind::Bool(true) {
+                *then_bb
+            } else {
+                *else_bb
+            };
+            *terminator = TerminatorKind::Goto(target);
+            changed = true;
+        }
+    }
+    // uses in the branches not taken no longer count
+    body.remove_unreachable_blocks();
+    changed
+}
+
+/// Value of an integer constant of type `int_ty`
+fn int_value(cons: &Constant, int_ty: IntTy) -> i128 {
+    let ConstKind::Int(n) = cons.kind else {
+        panic!("ICE: integer constant expected");
+    };
+    int_ty.truncate(i128::from(n))
+}
+
+fn fold_binary(binop: BinOp, lhs: &Constant, rhs: &Constant) -> Option<ConstKind> {
+    let compare = |ordering: std::cmp::Ordering| match binop {
+        BinOp::Eq => Some(ConstKind::Bool(ordering.is_eq())),
+        BinOp::Ne => Some(ConstKind::Bool(ordering.is_ne())),
+        BinOp::Gt => Some(ConstKind::Bool(ordering.is_gt())),
+        BinOp::Lt => Some(ConstKind::Bool(ordering.is_lt())),
+        _ => None,
+    };
+    match (&lhs.kind, &rhs.kind, &lhs.ty.kind) {
+        (ConstKind::Int(_), ConstKind::Int(_), TyKind::Int(int_ty)) => {
+            let (a, b) = (int_value(lhs, *int_ty), int_value(rhs, *int_ty));
+            let n = match binop {
+                BinOp::Add => a + b,
+                BinOp::Sub => a - b,
+                BinOp::Mul => a.wrapping_mul(b),
+                // division by zero and overflow are left to the checks before them
+                BinOp::Div | BinOp::Rem if b == 0 || (a == int_ty.min_value() && b == -1) => {
+                    return None;
+                }
+                BinOp::Div => a / b,
+                BinOp::Rem => a % b,
+                BinOp::Offset => return None,
+                _ => return compare(a.cmp(&b)),
+            };
+            Some(ConstKind::Int(int_ty.truncate(n) as i64))
+        }
+        (ConstKind::Float(a), ConstKind::Float(b), float_ty) => {
+            let f = match binop {
+                BinOp::Add => a + b,
+                BinOp::Sub => a - b,
+                BinOp::Mul => a * b,
+                BinOp::Div => a / b,
+                BinOp::Rem => a % b,
+                // comparisons with NaN are unordered
+                BinOp::Eq => return Some(ConstKind::Bool(a == b)),
+                BinOp::Ne => return Some(ConstKind::Bool(a != b)),
+                BinOp::Gt => return Some(ConstKind::Bool(a > b)),
+                BinOp::Lt => return Some(ConstKind::Bool(a < b)),
+                BinOp::Offset => return None,
+            };
+            // f32 arithmetic rounds each result to f32
+            if *float_ty == TyKind::F32 {
+                Some(ConstKind::Float(f64::from(f as f32)))
+            } else {
+                Some(ConstKind::Float(f))
+            }
+        }
+        (ConstKind::Bool(a), ConstKind::Bool(b), _) => compare(a.cmp(b)),
+        (ConstKind::Char(a), ConstKind::Char(b), _) => compare(a.cmp(b)),
+        _ => None,
+    }
+}
+
+fn fold_neg(inner: &Constant) -> Option<ConstKind> {
+    match (&inner.kind, &inner.ty.kind) {
+        (ConstKind::Int(_), TyKind::Int(int_ty)) => Some(ConstKind::Int(
+            int_ty.truncate(-int_value(inner, *int_ty)) as i64,
+        )),
+        (ConstKind::Float(f), _) => Some(ConstKind::Float(-f)),
+        _ => None,
+    }
+}
+
+/// Casts between numbers, bools and chars. Casts of pointers are kept
+fn fold_cast(inner: &Constant, to_ty: &Ty) -> Option<ConstKind> {
+    let n = match (&inner.kind, &inner.ty.kind) {
+        (ConstKind::Int(_), TyKind::Int(int_ty)) => int_value(inner, *int_ty),
+        (ConstKind::Bool(b), _) => i128::from(*b),
+        (ConstKind::Char(c), _) => i128::from(u32::from(*c)),
+        // float-to-int casts saturate, and NaN is cast to 0
+        (ConstKind::Float(f), _) => {
+            return match &to_ty.kind {
+                TyKind::Int(int_ty) if f.is_nan() => {
+                    Some(ConstKind::Int(int_ty.truncate(0) as i64))
+                }
+                TyKind::Int(int_ty) => Some(ConstKind::Int(
+                    (*f as i128).clamp(int_ty.min_value(), int_ty.max_value()) as i64,
+                )),
+                TyKind::F32 => Some(ConstKind::Float(f64::from(*f as f32))),
+                TyKind::F64 => Some(ConstKind::Float(*f)),
+                _ => None,
+            };
+        }
+        _ => return None,
+    };
+    match &to_ty.kind {
+        TyKind::Int(int_ty) => Some(ConstKind::Int(int_ty.truncate(n) as i64)),
+        TyKind::Char => Some(ConstKind::Char(char::from(n as u8))),
+        TyKind::F32 => Some(ConstKind::Float(f64::from(n as f32))),
+        TyKind::F64 => Some(ConstKind::Float(n as f64)),
+        _ => None,
+    }
+}
+
+/// Replace uses of locals which are assigned a constant once by the constant, and
+/// merge a local which is only moved to another local once into the other
+fn propagate(ctx: &Ctxt, body: &mut Body) -> bool {
+    let uses = count_uses(body);
+    let mut consts = vec![None; body.locals.len()];
+    // (from, to) of the merged locals
+    let mut copies = vec![];
+    for block in &body.blocks {
+        for stmt in &block.stmts {
+            let StatementKind::Assign(place, Rvalue::Use(op)) = &stmt.kind else {
+                continue;
+            };
+            if !place.projs.is_empty() || uses[place.local].assigns != 1 {
+                continue;
+            }
+            match op {
+                Operand::Const(cons) if uses[place.local].others == 0 => {
+                    consts[place.local] = Some(cons.clone());
+                }
+                Operand::Copy(src, _) | Operand::Move(src, _)
+                    if src.projs.is_empty()
+                        && src.local != place.local
+                        && src.local != RETURN_PLACE
+                        && !body.args().contains(&src.local)
+                        && uses[src.local].assigns == 1
+                        && uses[src.local].operands == 1
+                        && uses[src.local].others == 0
+                        && body.locals[src.local].ty == body.locals[place.local].ty
+                        && !ctx.needs_drop(&body.locals[src.local].ty) =>
+                {
+                    copies.push((src.local, place.local));
+                }
+                _ => (),
+            }
+        }
+    }
+    // constants are propagated into the copies instead
+    copies.retain(|(from, _)| consts[*from].is_none());
+
+    let mut changed = false;
+    for op in operands_mut(body) {
+        if let Operand::Copy(place, _) | Operand::Move(place, _) = op
+            && place.projs.is_empty()
+            && let Some(cons) = &consts[place.local]
+        {
+            *op = Operand::Const(cons.clone());
+            changed = true;
+        }
+    }
+    // locals merged into the others may be merged further (e.g. `_2 = move _3; _1 = move _2`)
+    let mut renames: Vec<Local> = (0..body.locals.len()).collect();
+    for (from, to) in copies {
+        renames[from] = to;
+    }
+    for local in 0..renames.len() {
+        let mut to = renames[local];
+        while renames[to] != to {
+            to = renames[to];
+        }
+        renames[local] = to;
+    }
+    for local in body.locals_mut() {
+        if renames[*local] != *local {
+            *local = renames[*local];
+            changed = true;
+        }
+    }
+    // the copies themselves have become `_n = move _n`
+    for block in &mut body.blocks {
+        block.stmts.retain(|stmt| {
+            !matches!(&stmt.kind, StatementKind::Assign(place, Rvalue::Use(Operand::Copy(src, _) | Operand::Move(src, _)))
+                if place.projs.is_empty() && src == place)
+        });
+    }
+    changed
+}
+
+/// Remove assignments to locals which are never read. Values which need drop are kept
+/// as removing their moves would leak them
+fn remove_dead_assignments(ctx: &Ctxt, body: &mut Body) -> bool {
+    let uses = count_uses(body);
+    let is_dead = |place: &Place| {
+        place.projs.is_empty()
+            && !uses[place.local].is_read()
+            && !ctx.needs_drop(&body.locals[place.local].ty)
+    };
+    let mut dead = vec![];
+    for (bb, block) in body.blocks.iter().enumerate() {
+        for (i, stmt) in block.stmts.iter().enumerate() {
+            if let StatementKind::Assign(place, _) = &stmt.kind
+                && is_dead(place)
+            {
+                dead.push((bb, i));
+            }
+        }
+    }
+    for (bb, i) in dead.iter().rev() {
+        body.blocks[*bb].stmts.remove(*i);
+    }
+    !dead.is_empty()
+}
+
+/// Skip blocks which only jump to another block, and merge blocks into their only
+/// predecessor when it jumps to them unconditionally
+fn simplify_cfg(body: &mut Body) {
+    let forward = |body: &Body, mut bb: BasicBlock| {
+        // bounded in case of a cycle of empty blocks
+        for _ in 0..body.blocks.len() {
+            match body.blocks[bb].terminator.kind {
+                TerminatorKind::Goto(target) if body.blocks[bb].stmts.is_empty() => bb = target,
+                _ => break,
+            }
+        }
+        bb
+    };
+    for bb in 0..body.blocks.len() {
+        let targets: Vec<_> = body.blocks[bb]
+            .terminator
+            .kind
+            .successors()
+            .into_iter()
+            .map(|succ| forward(body, succ))
+            .collect();
+        for (succ, target) in body.blocks[bb]
+            .terminator
+            .kind
+            .successors_mut()
+            .into_iter()
+            .zip(targets)
+        {
+            *succ = target;
+        }
+    }
+    body.remove_unreachable_blocks();
+
+    loop {
+        let mut preds = vec![0; body.blocks.len()];
+        for block in &body.blocks {
+            for succ in block.terminator.kind.successors() {
+                preds[succ] += 1;
+            }
+        }
+        let mergeable =
+            (0..body.blocks.len()).find_map(|bb| match body.blocks[bb].terminator.kind {
+                TerminatorKind::Goto(succ)
+                    if succ != bb && succ != START_BLOCK && preds[succ] == 1 =>
+                {
+                    Some((bb, succ))
+                }
+                _ => None,
+            });
+        let Some((bb, succ)) = mergeable else {
+            break;
+        };
+        // the successor is left unreachable and removed
+        let stmts = std::mem::take(&mut body.blocks[succ].stmts);
+        let kind = std::mem::replace(
+            &mut body.blocks[succ].terminator.kind,
+            TerminatorKind::Unreachable,
+        );
+        let span = body.blocks[succ].terminator.span.clone();
+        body.blocks[bb].stmts.extend(stmts);
+        body.blocks[bb].terminator = Terminator { kind, span };
+        body.remove_unreachable_blocks();
+    }
+}
+
+/// Remove locals which are no longer mentioned, and renumber the rest
+fn remove_unused_locals(body: &mut Body) {
+    let mut used = vec![false; body.locals.len()];
+    // the return place and the arguments are part of the signature
+    used[..=body.arg_count].fill(true);
+    for local in body.locals_mut() {
+        used[*local] = true;
+    }
+    let mut new_index = vec![0; body.locals.len()];
+    let mut num_used = 0;
+    for (local, is_used) in used.iter().enumerate() {
+        new_index[local] = num_used;
+        num_used += usize::from(*is_used);
+    }
+    for local in body.locals_mut() {
+        *local = new_index[*local];
+    }
+    let locals = std::mem::take(&mut body.locals);
+    body.locals = locals
+        .into_iter()
+        .zip(used)
+        .filter_map(|(decl, is_used)| is_used.then_some(decl))
+        .collect();
+}
@@ -0,0 +1,237 @@
+//! Textual form of MIR printed by `--emit=mir`, which resembles the output of rustc
+
+use super::*;
+use crate::ast::FormatAlign;
+use std::fmt::{self, Display, Formatter};
+
+impl Display for Program {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for foreign_fn in &self.foreign_fns {
+            let params: Vec<String> = foreign_fn
+                .param_tys
+                .iter()
+                .map(|ty| ty.to_string())
+                .collect();
+            writeln!(
+                f,
+                "extern fn {:?}({}) -> {};",
+                foreign_fn.name,
+                params.join(", "),
+                foreign_fn.ret_ty
+            )?;
+        }
+        if !self.foreign_fns.is_empty() {
+            writeln!(f)?;
+        }
+        for static_item in &self.statics {
+            let mutbl = match static_item.mutbl {
+                Mutability::Not => "",
+                Mutability::Mut => "mut ",
+            };
+            writeln!(
+                f,
+                "static {}{:?}: {} = {};",
+                mutbl, static_item.name, static_item.init.ty, static_item.init
+            )?;
+        }
+        if !self.statics.is_empty() {
+            writeln!(f)?;
+        }
+        for (i, body) in self.bodies.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", body)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Body {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let params: Vec<String> = self
+            .args()
+            .map(|arg| format!("_{}: {}", arg, self.locals[arg].ty))
+            .collect();
+        writeln!(
+            f,
+            "fn {:?}({}) -> {} {{",
+            self.name,
+            params.join(", "),
+            self.return_ty()
+        )?;
+        for (local, decl) in self.locals.iter().enumerate().skip(self.arg_count + 1) {
+            write!(f, "    let _{}: {};", local, decl.ty)?;
+            match &decl.name {
+                Some(name) => writeln!(f, " // {}", name)?,
+                None => writeln!(f)?,
+            }
+        }
+        for (bb, block) in self.blocks.iter().enumerate() {
+            // blocks are separated from the declarations and each other
+            if bb != START_BLOCK || self.locals.len() > self.arg_count + 1 {
+                writeln!(f)?;
+            }
+            writeln!(f, "    bb{}: {{", bb)?;
+            for stmt in &block.stmts {
+                writeln!(f, "        {};", stmt.kind)?;
+            }
+            writeln!(f, "        {};", block.terminator.kind)?;
+            writeln!(f, "    }}")?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl Display for StatementKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StatementKind::Assign(place, rvalue) => write!(f, "{} = {}", place, rvalue),
+            StatementKind::Drop(place) => write!(f, "drop({})", place),
+            StatementKind::Deallocate(place) => write!(f, "deallocate({})", place),
+            StatementKind::Print(dest, pieces, args) => {
+                let name = match dest {
+                    PrintDest::Stdout => "print",
+                    PrintDest::Stderr => "eprint",
+                };
+                write!(f, "{}(\"{}\"", name, format_string(pieces))?;
+                for arg in args {
+                    write!(f, ", {}", arg)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// Format string of `print!` with the arguments numbered explicitly
+fn format_string(pieces: &[FormatPiece]) -> String {
+    let mut s = String::new();
+    for piece in pieces {
+        match piece {
+            FormatPiece::Lit(lit) => {
+                let escaped = format!("{:?}", lit);
+                s.push_str(
+                    &escaped[1..escaped.len() - 1]
+                        .replace('{', "{{")
+                        .replace('}', "}}"),
+                );
+            }
+            FormatPiece::Placeholder(spec) => {
+                s.push_str(&format!("{{{}:", spec.arg));
+                match spec.align {
+                    Some(FormatAlign::Left) => s.push('<'),
+                    Some(FormatAlign::Right) => s.push('>'),
+                    None => (),
+                }
+                if spec.zero_pad {
+                    s.push('0');
+                }
+                if let Some(width) = spec.width {
+                    s.push_str(&width.to_string());
+                }
+                if let Some(precision) = spec.precision {
+                    s.push_str(&format!(".{}", precision));
+                }
+                if spec.debug {
+                    s.push('?');
+                }
+                if s.ends_with(':') {
+                    s.pop();
+                }
+                s.push('}');
+            }
+        }
+    }
+    s
+}
+
+impl Display for TerminatorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TerminatorKind::Goto(bb) => write!(f, "goto -> bb{}", bb),
+            TerminatorKind::If(cond, then_bb, else_bb) => write!(
+                f,
+                "if({}) -> [true: bb{}, false: bb{}]",
+                cond, then_bb, else_bb
+            ),
+            TerminatorKind::Call {
+                func,
+                args,
+                dest,
+                target,
+            } => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{} = {:?}({}) -> ", dest, func, args.join(", "))?;
+                match target {
+                    Some(bb) => write!(f, "bb{}", bb),
+                    None => write!(f, "!"),
+                }
+            }
+            TerminatorKind::Return => write!(f, "return"),
+            TerminatorKind::Panic(Some(msg)) => write!(f, "panic({:?})", msg),
+            TerminatorKind::Panic(None) => write!(f, "panic"),
+            TerminatorKind::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+impl Display for Place {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut s = format!("_{}", self.local);
+        for proj in &self.projs {
+            s = match proj {
+                Proj::Deref => format!("(*{})", s),
+                Proj::Field(field) => format!("{}.{}", s, field),
+                Proj::Index(index) => format!("{}[_{}]", s, index),
+            };
+        }
+        write!(f, "{}", s)
+    }
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Copy(place, _) => write!(f, "copy {}", place),
+            Operand::Move(place, _) => write!(f, "move {}", place),
+            Operand::Const(cons) => write!(f, "const {}", cons),
+        }
+    }
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ConstKind::Int(n) => write!(f, "{}_{}", n, self.ty),
+            ConstKind::Float(x) => write!(f, "{:?}_{}", x, self.ty),
+            ConstKind::Bool(b) => write!(f, "{}", b),
+            ConstKind::Char(c) => write!(f, "{:?}", c),
+            ConstKind::Str(s) => write!(f, "{:?}", s),
+            ConstKind::Unit => write!(f, "()"),
+            ConstKind::Null => write!(f, "null::<{}>", self.ty),
+            ConstKind::Static(name) => write!(f, "&raw mut {:?}", name),
+        }
+    }
+}
+
+impl Display for Rvalue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Rvalue::Use(op) => write!(f, "{}", op),
+            Rvalue::BinaryOp(binop, lhs, rhs) => write!(f, "{:?}({}, {})", binop, lhs, rhs),
+            Rvalue::UnaryOp(unop, op) => write!(f, "{:?}({})", unop, op),
+            Rvalue::Cast(op, ty) => write!(f, "{} as {}", op, ty),
+            Rvalue::Ref(Mutability::Not, place) => write!(f, "&{}", place),
+            Rvalue::Ref(Mutability::Mut, place) => write!(f, "&mut {}", place),
+            Rvalue::Box(op) => write!(f, "Box::new({})", op),
+            Rvalue::Aggregate(kind, ops) => {
+                let ops: Vec<String> = ops.iter().map(|op| op.to_string()).collect();
+                match kind {
+                    AggregateKind::Array => write!(f, "[{}]", ops.join(", ")),
+                    AggregateKind::Adt(name) => write!(f, "{:?} {{ {} }}", name, ops.join(", ")),
+                }
+            }
+        }
+    }
+}
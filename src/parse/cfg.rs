@@ -0,0 +1,93 @@
+use super::Parser;
+use crate::ast::{Attribute, MetaItem, MetaItemKind};
+use std::collections::HashSet;
+
+/// Configuration options set by `--cfg` flags
+#[derive(Debug, Default)]
+pub struct CfgSet {
+    /// `name` or `name="value"`
+    options: HashSet<(String, Option<String>)>,
+}
+
+impl CfgSet {
+    /// Parse `--cfg` flags like `debug` or `feature="x"`
+    pub fn from_flags(flags: &[String]) -> Option<CfgSet> {
+        let mut options = HashSet::new();
+        for flag in flags {
+            let option = match flag.split_once('=') {
+                Some((name, value)) => {
+                    let Some(value) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"'))
+                    else {
+                        eprintln!(
+                            "Invalid `--cfg` argument: `{}` (expected `name=\"value\"`)",
+                            flag
+                        );
+                        return None;
+                    };
+                    (name.to_string(), Some(value.to_string()))
+                }
+                None => (flag.to_string(), None),
+            };
+            options.insert(option);
+        }
+        Some(CfgSet { options })
+    }
+
+    /// Evaluate a configuration predicate
+    /// https://doc.rust-lang.org/reference/conditional-compilation.html
+    pub fn eval(&self, pred: &MetaItem) -> Option<bool> {
+        match (pred.name.as_str(), &pred.kind) {
+            (_, MetaItemKind::Word) => Some(self.options.contains(&(pred.name.clone(), None))),
+            (_, MetaItemKind::NameValue(value)) => Some(
+                self.options
+                    .contains(&(pred.name.clone(), Some(value.clone()))),
+            ),
+            ("not", MetaItemKind::List(preds)) if preds.len() == 1 => Some(!self.eval(&preds[0])?),
+            ("all", MetaItemKind::List(preds)) => {
+                let mut res = true;
+                for pred in preds {
+                    res &= self.eval(pred)?;
+                }
+                Some(res)
+            }
+            ("any", MetaItemKind::List(preds)) => {
+                let mut res = false;
+                for pred in preds {
+                    res |= self.eval(pred)?;
+                }
+                Some(res)
+            }
+            _ => {
+                eprintln!(
+                    "Invalid cfg predicate `{}` at {}",
+                    pred.span.to_snippet(),
+                    pred.span.to_location()
+                );
+                None
+            }
+        }
+    }
+}
+
+impl Parser {
+    /// Returns false if `#[cfg]` attributes in `attrs` configure out the node
+    pub fn is_cfg_enabled(&self, attrs: &[Attribute]) -> Option<bool> {
+        let mut enabled = true;
+        for attr in attrs.iter().filter(|attr| attr.meta.name == "cfg") {
+            let MetaItemKind::List(preds) = &attr.meta.kind else {
+                eprintln!("`cfg` takes a predicate at {}", attr.span.to_location());
+                return None;
+            };
+            let [pred] = &preds[..] else {
+                eprintln!(
+                    "`cfg` takes exactly one predicate at {}",
+                    attr.span.to_location()
+                );
+                return None;
+            };
+            // evaluate all predicates to report errors
+            enabled &= self.cfg.eval(pred)?;
+        }
+        Some(enabled)
+    }
+}
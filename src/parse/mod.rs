@@ -1,26 +1,42 @@
+mod cfg;
+mod parse_attr;
 mod parse_expr;
+mod parse_format;
 mod parse_item;
+mod parse_macro;
 mod parse_stmt;
 
+pub use self::cfg::CfgSet;
+pub use self::parse_expr::is_expr_start;
+pub use self::parse_format::conversion_spec;
 use self::parse_item::is_item_start;
+pub use self::parse_item::is_type_start;
 use crate::ast::{Crate, Item, NodeId, Path};
-use crate::lexer::{Lexer, Token, TokenKind};
-use crate::span::Ident;
+use crate::lexer::{Token, TokenKind, TokenStream};
+use crate::span::{Ident, Span};
 use std::rc::Rc;
 
 pub struct Parser {
-    lexer: Lexer,
+    tokens: TokenStream,
     next_node_id: u32,
+    /// Items, statements and fields configured out by `#[cfg]` are dropped while parsing
+    cfg: Rc<CfgSet>,
 }
 
 impl Parser {
-    pub fn new(lexer: Lexer) -> Self {
+    pub fn new(tokens: TokenStream, cfg: Rc<CfgSet>) -> Self {
         Parser {
-            lexer,
+            tokens,
             next_node_id: 0,
+            cfg,
         }
     }
 
+    /// Number of tokens consumed so far
+    pub fn num_consumed_tokens(&self) -> usize {
+        self.tokens.num_consumed()
+    }
+
     pub fn get_next_id(&mut self) -> NodeId {
         let id = self.next_node_id;
         self.next_node_id += 1;
@@ -28,18 +44,29 @@ impl Parser {
     }
 
     fn peek_token(&mut self) -> &Token {
-        self.lexer.peek_token()
+        self.tokens.peek_token()
+    }
+
+    fn peek_nth_token(&self, n: usize) -> &Token {
+        self.tokens.peek_nth_token(n)
     }
 
     fn skip_token(&mut self) -> Token {
-        self.lexer.skip_token()
+        self.tokens.skip_token()
+    }
+
+    /// Span from `lo` to the end of the last token skipped
+    fn span_from(&self, lo: &Span) -> Span {
+        self.tokens
+            .prev_span()
+            .map_or(lo.clone(), |hi| lo.concat(hi))
     }
 
     /// Skip token only when bumping into the expected token.
     fn skip_expected_token(&mut self, kind: TokenKind) -> bool {
         let t = self.peek_token();
         if t.kind == kind {
-            self.lexer.skip_token();
+            self.tokens.skip_token();
             true
         } else {
             false
@@ -73,8 +100,18 @@ impl Parser {
     fn parse_items(&mut self) -> Option<Vec<Item>> {
         let mut items = vec![];
 
-        while is_item_start(self.peek_token()) {
-            items.push(self.parse_item()?);
+        loop {
+            if is_item_start(self.peek_token()) {
+                let item = self.parse_item()?;
+                if self.is_cfg_enabled(&item.attrs)? {
+                    items.push(item);
+                }
+            } else if self.peek_token().kind == TokenKind::Semi {
+                // skip `;` left after expanding macros into items
+                self.skip_token();
+            } else {
+                break;
+            }
         }
         Some(items)
     }
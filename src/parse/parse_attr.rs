@@ -0,0 +1,108 @@
+use super::Parser;
+use crate::ast::{Attribute, InlineAttr, MetaItem, MetaItemKind};
+use crate::lexer::TokenKind;
+
+impl Parser {
+    /// outerAttributes ::= ("#" "[" meta "]")*
+    /// https://doc.rust-lang.org/reference/attributes.html
+    pub fn parse_outer_attributes(&mut self) -> Option<Vec<Attribute>> {
+        let mut attrs = vec![];
+        while self.peek_token().kind == TokenKind::Pound {
+            let span = self.skip_token().span;
+            if !self.skip_expected_token(TokenKind::OpenBracket) {
+                eprintln!(
+                    "Expected '[' for attribute, but found `{}`",
+                    self.peek_token().span.to_snippet()
+                );
+                return None;
+            }
+            let meta = self.parse_meta_item()?;
+            let span = span.concat(&self.peek_token().span);
+            if !self.skip_expected_token(TokenKind::CloseBracket) {
+                eprintln!(
+                    "Expected ']' for attribute, but found `{}`",
+                    self.peek_token().span.to_snippet()
+                );
+                return None;
+            }
+            attrs.push(Attribute { meta, span });
+        }
+        Some(attrs)
+    }
+
+    /// meta ::= ident
+    ///     | ident "=" strLit
+    ///     | ident "(" (meta ("," meta)* ","?)? ")"
+    pub fn parse_meta_item(&mut self) -> Option<MetaItem> {
+        let name = self.parse_ident()?;
+        let mut span = name.span.clone();
+        let kind = match self.peek_token().kind {
+            TokenKind::Eq => {
+                self.skip_token();
+                let t = self.skip_token();
+                let TokenKind::StrLit(value) = t.kind else {
+                    eprintln!(
+                        "Expected string literal, but found `{}`",
+                        t.span.to_snippet()
+                    );
+                    return None;
+                };
+                span = span.concat(&t.span);
+                MetaItemKind::NameValue(value)
+            }
+            TokenKind::OpenParen => {
+                self.skip_token();
+                let mut items = vec![];
+                while self.peek_token().kind != TokenKind::CloseParen {
+                    items.push(self.parse_meta_item()?);
+                    if !self.skip_expected_token(TokenKind::Comma) {
+                        break;
+                    }
+                }
+                span = span.concat(&self.peek_token().span);
+                if !self.skip_expected_token(TokenKind::CloseParen) {
+                    eprintln!(
+                        "Expected ')', but found `{}`",
+                        self.peek_token().span.to_snippet()
+                    );
+                    return None;
+                }
+                MetaItemKind::List(items)
+            }
+            _ => MetaItemKind::Word,
+        };
+        Some(MetaItem {
+            name: name.symbol.to_string(),
+            kind,
+            span,
+        })
+    }
+
+    /// Interpret `#[inline]`, `#[inline(always)]` and `#[inline(never)]` in `attrs`
+    pub fn inline_attr(&self, attrs: &[Attribute]) -> Option<InlineAttr> {
+        let mut inline = InlineAttr::None;
+        for attr in attrs.iter().filter(|attr| attr.meta.name == "inline") {
+            let kind = match &attr.meta.kind {
+                MetaItemKind::Word => Some(InlineAttr::Hint),
+                MetaItemKind::List(args) => match &args[..] {
+                    [arg] if matches!(arg.kind, MetaItemKind::Word) => match arg.name.as_str() {
+                        "always" => Some(InlineAttr::Always),
+                        "never" => Some(InlineAttr::Never),
+                        _ => None,
+                    },
+                    _ => None,
+                },
+                MetaItemKind::NameValue(_) => None,
+            };
+            let Some(kind) = kind else {
+                eprintln!(
+                    "Malformed `inline` attribute input at {}\n    must be of the form `#[inline]`, `#[inline(always)]` or `#[inline(never)]`",
+                    attr.span.to_location()
+                );
+                return None;
+            };
+            inline = kind;
+        }
+        Some(inline)
+    }
+}
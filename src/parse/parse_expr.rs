@@ -1,18 +1,23 @@
 use super::Parser;
-use crate::ast::{self, Expr, ExprKind, Path, UnOp};
+use crate::ast::{self, Expr, ExprKind, FloatTy, Mutability, Path, UnOp, Unsafety};
 use crate::lexer::{self, Token, TokenKind};
-use crate::span::Ident;
+use crate::span::{Ident, Span};
+use std::rc::Rc;
 
 pub fn is_expr_start(token: &Token) -> bool {
     matches!(
         token.kind,
         TokenKind::NumLit(_)
+            | TokenKind::FloatLit(..)
             | TokenKind::StrLit(_)
+            | TokenKind::CharLit(_)
             | TokenKind::Ident(_)
             | TokenKind::OpenParen
             | TokenKind::OpenBrace
             | TokenKind::OpenBracket
-            | TokenKind::BinOp(lexer::BinOp::Plus | lexer::BinOp::Minus)
+            | TokenKind::BinOp(
+                lexer::BinOp::Plus | lexer::BinOp::Minus | lexer::BinOp::Star | lexer::BinOp::And
+            )
             | TokenKind::Return
             | TokenKind::True
             | TokenKind::False
@@ -21,6 +26,29 @@ pub fn is_expr_start(token: &Token) -> bool {
     )
 }
 
+/// `Box::new`, which is built in since there are no associated functions
+fn is_box_new(path: &Path) -> bool {
+    path.segments.len() == 2
+        && path.segments[0].symbol.as_str() == "Box"
+        && path.segments[1].symbol.as_str() == "new"
+}
+
+/// `core::ptr::null` and `core::ptr::null_mut`, which are built in since there are no
+/// generic functions. They can also be referred to from `std`
+fn null_ptr_mutability(path: &Path) -> Option<Mutability> {
+    let [krate, module, name] = &path.segments[..] else {
+        return None;
+    };
+    if !matches!(krate.symbol.as_str(), "core" | "std") || module.symbol.as_str() != "ptr" {
+        return None;
+    }
+    match name.symbol.as_str() {
+        "null" => Some(Mutability::Not),
+        "null_mut" => Some(Mutability::Mut),
+        _ => None,
+    }
+}
+
 impl Parser {
     /// expr ::= assign
     pub fn parse_expr(&mut self) -> Option<Expr> {
@@ -80,7 +108,7 @@ impl Parser {
     /// assign ::= equality ("=" assign)?
     fn parse_assign(&mut self) -> Option<Expr> {
         let lhs = self.parse_binary_equality()?;
-        let t = self.lexer.peek_token();
+        let t = self.peek_token();
         if t.kind != TokenKind::Eq {
             return Some(lhs);
         }
@@ -96,7 +124,7 @@ impl Parser {
     /// equality ::= relational (("=="|"!=") equality)?
     fn parse_binary_equality(&mut self) -> Option<Expr> {
         let lhs = self.parse_binary_relational()?;
-        let t = self.lexer.peek_token();
+        let t = self.peek_token();
         let binop = match t.kind {
             TokenKind::BinOp(lexer::BinOp::Eq) => ast::BinOp::Eq,
             TokenKind::BinOp(lexer::BinOp::Ne) => ast::BinOp::Ne,
@@ -104,7 +132,7 @@ impl Parser {
                 return Some(lhs);
             }
         };
-        self.lexer.skip_token();
+        self.skip_token();
 
         let rhs = self.parse_binary_equality()?;
 
@@ -118,7 +146,7 @@ impl Parser {
     /// relational ::= add (("=="|"!=") relational)?
     fn parse_binary_relational(&mut self) -> Option<Expr> {
         let lhs = self.parse_binary_add()?;
-        let t = self.lexer.peek_token();
+        let t = self.peek_token();
         let binop = match t.kind {
             TokenKind::BinOp(lexer::BinOp::Lt) => ast::BinOp::Lt,
             TokenKind::BinOp(lexer::BinOp::Gt) => ast::BinOp::Gt,
@@ -126,7 +154,7 @@ impl Parser {
                 return Some(lhs);
             }
         };
-        self.lexer.skip_token();
+        self.skip_token();
 
         let rhs = self.parse_binary_relational()?;
 
@@ -137,47 +165,52 @@ impl Parser {
         })
     }
 
-    /// add ::= mul (("+"|"-") add)?
+    /// add ::= mul (("+"|"-") mul)*
+    /// Left-associative: `a - b - c` is `(a - b) - c`
     fn parse_binary_add(&mut self) -> Option<Expr> {
-        let lhs = self.parse_binary_mul()?;
-        let t = self.lexer.peek_token();
-        let binop = match t.kind {
-            TokenKind::BinOp(lexer::BinOp::Plus) => ast::BinOp::Add,
-            TokenKind::BinOp(lexer::BinOp::Minus) => ast::BinOp::Sub,
-            _ => {
-                return Some(lhs);
-            }
-        };
-        self.lexer.skip_token();
+        let mut lhs = self.parse_binary_mul()?;
+        loop {
+            let binop = match self.peek_token().kind {
+                TokenKind::BinOp(lexer::BinOp::Plus) => ast::BinOp::Add,
+                TokenKind::BinOp(lexer::BinOp::Minus) => ast::BinOp::Sub,
+                _ => {
+                    return Some(lhs);
+                }
+            };
+            self.skip_token();
 
-        let rhs = self.parse_binary_add()?;
+            let rhs = self.parse_binary_mul()?;
 
-        Some(Expr {
-            span: lhs.span.concat(&rhs.span),
-            kind: ExprKind::Binary(binop, Box::new(lhs), Box::new(rhs)),
-            id: self.get_next_id(),
-        })
+            lhs = Expr {
+                span: lhs.span.concat(&rhs.span),
+                kind: ExprKind::Binary(binop, Box::new(lhs), Box::new(rhs)),
+                id: self.get_next_id(),
+            };
+        }
     }
 
-    /// mul ::= typeCastExpr ("*" mul)?
+    /// mul ::= typeCastExpr (("*"|"/"|"%") typeCastExpr)*
     fn parse_binary_mul(&mut self) -> Option<Expr> {
-        let lhs = self.parse_binary_cast()?;
-        let t = self.lexer.peek_token();
-        let binop = match t.kind {
-            TokenKind::BinOp(lexer::BinOp::Star) => ast::BinOp::Mul,
-            _ => {
-                return Some(lhs);
-            }
-        };
-        self.lexer.skip_token();
+        let mut lhs = self.parse_binary_cast()?;
+        loop {
+            let binop = match self.peek_token().kind {
+                TokenKind::BinOp(lexer::BinOp::Star) => ast::BinOp::Mul,
+                TokenKind::BinOp(lexer::BinOp::Slash) => ast::BinOp::Div,
+                TokenKind::BinOp(lexer::BinOp::Percent) => ast::BinOp::Rem,
+                _ => {
+                    return Some(lhs);
+                }
+            };
+            self.skip_token();
 
-        let rhs = self.parse_binary_mul()?;
+            let rhs = self.parse_binary_cast()?;
 
-        Some(Expr {
-            span: lhs.span.concat(&rhs.span),
-            kind: ExprKind::Binary(binop, Box::new(lhs), Box::new(rhs)),
-            id: self.get_next_id(),
-        })
+            lhs = Expr {
+                span: lhs.span.concat(&rhs.span),
+                kind: ExprKind::Binary(binop, Box::new(lhs), Box::new(rhs)),
+                id: self.get_next_id(),
+            };
+        }
     }
 
     /// typeCastExpr ::= unary ("as" type)*
@@ -198,13 +231,27 @@ impl Parser {
         Some(ret)
     }
 
-    /// unary ::= ("+"|"-")? primary
+    /// unary ::= ("+"|"-")? primary | "*" unary | "&" "mut"? unary
     fn parse_binary_unary(&mut self) -> Option<Expr> {
         let span = self.peek_token().span.clone();
-        let t = self.lexer.peek_token();
+        if self.skip_expected_token(TokenKind::BinOp(lexer::BinOp::And)) {
+            let mutbl = if self.skip_expected_token(TokenKind::Mut) {
+                Mutability::Mut
+            } else {
+                Mutability::Not
+            };
+            let operand = self.parse_binary_unary()?;
+            return Some(Expr {
+                span: span.concat(&operand.span),
+                kind: ExprKind::AddrOf(mutbl, Box::new(operand)),
+                id: self.get_next_id(),
+            });
+        }
+        let t = self.peek_token();
         let unup = match &t.kind {
             TokenKind::BinOp(lexer::BinOp::Plus) => UnOp::Plus,
             TokenKind::BinOp(lexer::BinOp::Minus) => UnOp::Minus,
+            TokenKind::BinOp(lexer::BinOp::Star) => UnOp::Deref,
             _ => {
                 return self.parse_binary_primary();
             }
@@ -212,7 +259,12 @@ impl Parser {
         // skip unary op token
         self.skip_token();
         // parse primary
-        let primary = self.parse_binary_primary()?;
+        // dereferences can be nested (e.g. `**b`)
+        let primary = if matches!(unup, UnOp::Deref) {
+            self.parse_binary_unary()?
+        } else {
+            self.parse_binary_primary()?
+        };
 
         Some(Expr {
             span: span.concat(&primary.span),
@@ -231,7 +283,7 @@ impl Parser {
     /// returnExpr ::= "return" expr
     /// ref: https://doc.rust-lang.org/reference/expressions.html#expression-precedence
     fn parse_binary_primary(&mut self) -> Option<Expr> {
-        let t = &self.lexer.peek_token();
+        let t = &self.peek_token();
         let mut expr = match t.kind {
             TokenKind::NumLit(n) => {
                 let span = self.skip_token().span;
@@ -241,6 +293,20 @@ impl Parser {
                     span,
                 }
             }
+            TokenKind::FloatLit(ref lit, ref suffix) => {
+                let value: f64 = lit.parse().unwrap();
+                let suffix = match suffix.as_deref() {
+                    Some("f32") => Some(FloatTy::F32),
+                    Some("f64") => Some(FloatTy::F64),
+                    _ => None,
+                };
+                let span = self.skip_token().span;
+                Expr {
+                    kind: ExprKind::FloatLit(value, suffix),
+                    id: self.get_next_id(),
+                    span,
+                }
+            }
             TokenKind::True => {
                 let span = self.skip_token().span;
                 Expr {
@@ -259,13 +325,23 @@ impl Parser {
             }
             TokenKind::StrLit(_) => {
                 let t = self.skip_token();
-                let TokenKind::StrLit(s) = t.kind else { unreachable!() };
+                let TokenKind::StrLit(s) = t.kind else {
+                    unreachable!()
+                };
                 Expr {
                     kind: ExprKind::StrLit(s),
                     id: self.get_next_id(),
                     span: t.span,
                 }
             }
+            TokenKind::CharLit(c) => {
+                let span = self.skip_token().span;
+                Expr {
+                    kind: ExprKind::CharLit(c),
+                    id: self.get_next_id(),
+                    span,
+                }
+            }
             TokenKind::If => self.parse_if_expr()?,
             TokenKind::Return => {
                 // TODO: parse `return;`
@@ -315,11 +391,11 @@ impl Parser {
                 }
             }
             // unsafe block expression
-            // TODO: Should AST node have `unsafe` info?
             TokenKind::Unsafe => {
                 // skip "unsafe"
                 let unsafe_span = self.skip_token().span;
-                let block = self.parse_block()?;
+                let mut block = self.parse_block()?;
+                block.unsafety = Unsafety::Unsafe;
                 Expr {
                     span: unsafe_span.concat(&block.span),
                     kind: ExprKind::Block(block),
@@ -361,12 +437,20 @@ impl Parser {
         Some(expr)
     }
 
-    /// ident | structExpr
+    /// ident | structExpr | macroInvocation
     fn parse_ident_or_struct_expr(&mut self) -> Option<Expr> {
         let path = self.parse_path()?;
         let t = self.peek_token();
         if let TokenKind::OpenBrace = t.kind {
             self.parse_struct_expr(path)
+        } else if let TokenKind::Bang = t.kind {
+            self.parse_builtin_macro(path)
+        } else if is_box_new(&path) && t.kind == TokenKind::OpenParen {
+            self.parse_box_expr(path)
+        } else if let Some(mutbl) = null_ptr_mutability(&path)
+            && t.kind == TokenKind::OpenParen
+        {
+            self.parse_null_ptr_expr(path, mutbl)
         } else {
             Some(Expr {
                 span: path.span.clone(),
@@ -376,7 +460,53 @@ impl Parser {
         }
     }
 
-    /// structExpr ::= ident "{" structExprFields? "}"
+    /// boxExpr ::= "Box" "::" "new" "(" expr ")"
+    /// NOTE: path is already parsed
+    fn parse_box_expr(&mut self, path: Path) -> Option<Expr> {
+        let fn_expr = Expr {
+            span: path.span.clone(),
+            kind: ExprKind::Path(path),
+            id: self.get_next_id(),
+        };
+        let call = self.parse_call_expr(fn_expr)?;
+        let ExprKind::Call(_, mut args) = call.kind else {
+            unreachable!()
+        };
+        if args.len() != 1 {
+            eprintln!(
+                "`Box::new` takes 1 argument, but {} arguments were supplied",
+                args.len()
+            );
+            return None;
+        }
+        Some(Expr {
+            kind: ExprKind::Box(Box::new(args.pop().unwrap())),
+            id: call.id,
+            span: call.span,
+        })
+    }
+
+    /// nullPtrExpr ::= ("core" | "std") "::" "ptr" "::" ("null" | "null_mut") "(" ")"
+    /// NOTE: path is already parsed
+    fn parse_null_ptr_expr(&mut self, path: Path, mutbl: Mutability) -> Option<Expr> {
+        let (args, close_span) = self.parse_call_args()?;
+        if !args.is_empty() {
+            eprintln!(
+                "`{}` takes 0 arguments, but {} arguments were supplied",
+                path.span.to_snippet(),
+                args.len()
+            );
+            return None;
+        }
+        Some(Expr {
+            span: path.span.concat(&close_span),
+            kind: ExprKind::NullPtr(mutbl),
+            id: self.get_next_id(),
+        })
+    }
+
+    /// structExpr ::= ident "{" (structExprFields ("," structBase)? | structBase)? "}"
+    /// structBase ::= ".." expr
     /// NOTE: first ident is already parsed
     fn parse_struct_expr(&mut self, path: Path) -> Option<Expr> {
         let mut span = self.peek_token().span.clone();
@@ -395,6 +525,13 @@ impl Parser {
             vec![]
         };
 
+        let base = if self.peek_token().kind == TokenKind::DotDot {
+            self.skip_token();
+            Some(Box::new(self.parse_expr()?))
+        } else {
+            None
+        };
+
         span = span.concat(&self.peek_token().span);
         if !self.skip_expected_token(TokenKind::CloseBrace) {
             eprintln!(
@@ -404,7 +541,7 @@ impl Parser {
             return None;
         }
         Some(Expr {
-            kind: ExprKind::Struct(path, fields),
+            kind: ExprKind::Struct(path, fields, base),
             id: self.get_next_id(),
             span,
         })
@@ -424,16 +561,21 @@ impl Parser {
         Some(fds)
     }
 
-    /// structExprField ::= ident ":" expr
+    /// structExprField ::= ident (":" expr)?
     /// https://doc.rust-lang.org/reference/expressions/struct-expr.html
     fn parse_struct_expr_field(&mut self) -> Option<(Ident, Box<Expr>)> {
         let ident = self.parse_ident()?;
         if !self.skip_expected_token(TokenKind::Colon) {
-            eprintln!(
-                "Expected ':', but found `{}`",
-                self.peek_token().span.to_snippet()
-            );
-            return None;
+            // shorthand `S { a }` is `S { a: a }`
+            let expr = Expr {
+                span: ident.span.clone(),
+                kind: ExprKind::Path(Path {
+                    span: ident.span.clone(),
+                    segments: vec![ident.clone()],
+                }),
+                id: self.get_next_id(),
+            };
+            return Some((ident, Box::new(expr)));
         }
         let expr = self.parse_expr()?;
         Some((ident, Box::new(expr)))
@@ -487,8 +629,17 @@ impl Parser {
     /// callExpr ::= primary "(" callParams? ")"
     /// NOTE: first primary is already parsed
     fn parse_call_expr(&mut self, fn_expr: Expr) -> Option<Expr> {
-        let mut span = fn_expr.span.clone();
+        let (args, close_span) = self.parse_call_args()?;
+        Some(Expr {
+            span: fn_expr.span.concat(&close_span),
+            kind: ExprKind::Call(Box::new(fn_expr), args),
+            id: self.get_next_id(),
+        })
+    }
 
+    /// "(" callParams? ")"
+    /// Returns the arguments and the span of ')'
+    fn parse_call_args(&mut self) -> Option<(Vec<Expr>, Span)> {
         // skip '('
         self.skip_token();
         let args = if self.peek_token().kind == TokenKind::CloseParen {
@@ -497,7 +648,7 @@ impl Parser {
             self.parse_call_params()?
         };
 
-        span = span.concat(&self.peek_token().span);
+        let close_span = self.peek_token().span.clone();
         if !self.skip_expected_token(TokenKind::CloseParen) {
             eprintln!(
                 "Expected ')', but found `{}`",
@@ -505,16 +656,12 @@ impl Parser {
             );
             return None;
         }
-        Some(Expr {
-            kind: ExprKind::Call(Box::new(fn_expr), args),
-            id: self.get_next_id(),
-            span,
-        })
+        Some((args, close_span))
     }
 
     /// callParams ::= callParam ("," callParam)* ","?
     /// callParam = expr
-    fn parse_call_params(&mut self) -> Option<Vec<Expr>> {
+    pub fn parse_call_params(&mut self) -> Option<Vec<Expr>> {
         let mut args = vec![];
         args.push(self.parse_expr()?);
 
@@ -565,7 +712,51 @@ impl Parser {
 
         // skip '.'
         self.skip_token();
-        let fd = self.parse_ident()?;
+
+        // `t.0.1` is lexed as `t`, `.` and `0.1`
+        if let TokenKind::FloatLit(lit, None) = &self.peek_token().kind
+            && let Some((outer, inner)) = lit.split_once('.')
+            && !outer.is_empty()
+            && !inner.is_empty()
+            && inner.chars().all(|c| c.is_ascii_digit())
+        {
+            let (outer, inner) = (outer.to_string(), inner.to_string());
+            let t = self.skip_token();
+            let mut expr = recv;
+            for index in [outer, inner] {
+                let fd = Ident {
+                    symbol: Rc::new(index),
+                    span: t.span.clone(),
+                };
+                expr = Expr {
+                    span: span.concat(&t.span),
+                    kind: ExprKind::Field(Box::new(expr), fd),
+                    id: self.get_next_id(),
+                };
+            }
+            return Some(expr);
+        }
+
+        // `.0` for tuple structs
+        let fd = if let TokenKind::NumLit(n) = self.peek_token().kind {
+            let t = self.skip_token();
+            Ident {
+                symbol: Rc::new(n.to_string()),
+                span: t.span,
+            }
+        } else {
+            self.parse_ident()?
+        };
+
+        // method call `recv.method(args)`
+        if self.peek_token().kind == TokenKind::OpenParen {
+            let (args, close_span) = self.parse_call_args()?;
+            return Some(Expr {
+                span: span.concat(&close_span),
+                kind: ExprKind::MethodCall(Box::new(recv), fd, args),
+                id: self.get_next_id(),
+            });
+        }
 
         span = span.concat(&fd.span);
         Some(Expr {
@@ -0,0 +1,146 @@
+use crate::ast::{FormatAlign, FormatPiece, FormatSpec};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Parse the format string of `print!` and friends
+/// format ::= (text | "{{" | "}}" | "{" argument? (":" formatSpec)? "}")*
+/// argument ::= integer
+/// formatSpec ::= ("<" | ">")? "0"? width? ("." precision)? "?"?
+/// Checks that the arguments referenced exist and that all arguments are used.
+/// Types of the arguments are checked by typeck
+pub fn parse_format_string(fmt: &str, num_args: usize) -> Result<Vec<FormatPiece>, String> {
+    let mut pieces = vec![];
+    let mut lit = String::new();
+    let mut next_arg = 0;
+    let mut used = vec![false; num_args];
+
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                lit.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                lit.push('}');
+            }
+            '}' => return Err("unmatched `}` found".to_string()),
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err("expected `}`, but string ended".to_string()),
+                    }
+                }
+                let spec = parse_placeholder(&placeholder, &mut next_arg)?;
+                if spec.arg >= num_args {
+                    return Err(format!(
+                        "invalid reference to positional argument {} ({} given)",
+                        spec.arg, num_args
+                    ));
+                }
+                used[spec.arg] = true;
+
+                if !lit.is_empty() {
+                    pieces.push(FormatPiece::Lit(std::mem::take(&mut lit)));
+                }
+                pieces.push(FormatPiece::Placeholder(spec));
+            }
+            _ => lit.push(c),
+        }
+    }
+    if !lit.is_empty() {
+        pieces.push(FormatPiece::Lit(lit));
+    }
+
+    if let Some(i) = used.iter().position(|used| !used) {
+        return Err(format!("argument {} never used", i));
+    }
+    Ok(pieces)
+}
+
+/// Parse the inside of `{...}`
+fn parse_placeholder(s: &str, next_arg: &mut usize) -> Result<FormatSpec, String> {
+    let (arg, spec) = s.split_once(':').unwrap_or((s, ""));
+
+    // implicit positions are counted independently of explicit ones
+    let arg = if arg.is_empty() {
+        let i = *next_arg;
+        *next_arg += 1;
+        i
+    } else if let Ok(i) = arg.parse::<usize>() {
+        i
+    } else {
+        return Err(format!("named argument `{}` is not supported", arg));
+    };
+
+    let mut chars = spec.chars().peekable();
+    let align = match chars.peek() {
+        Some('<') => Some(FormatAlign::Left),
+        Some('>') => Some(FormatAlign::Right),
+        Some('^') => return Err("center alignment is not supported".to_string()),
+        _ => None,
+    };
+    if align.is_some() {
+        chars.next();
+    }
+    let zero_pad = chars.next_if_eq(&'0').is_some();
+    let width = parse_count(&mut chars);
+    let precision = if chars.next_if_eq(&'.').is_some() {
+        let Some(precision) = parse_count(&mut chars) else {
+            return Err("expected precision after `.`".to_string());
+        };
+        Some(precision)
+    } else {
+        None
+    };
+    let debug = chars.next_if_eq(&'?').is_some();
+    if chars.next().is_some() {
+        return Err(format!("unsupported format spec `{}`", spec));
+    }
+
+    Ok(FormatSpec {
+        arg,
+        align,
+        zero_pad,
+        width,
+        precision,
+        debug,
+    })
+}
+
+fn parse_count(chars: &mut Peekable<Chars>) -> Option<usize> {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        digits.push(c);
+    }
+    digits.parse().ok()
+}
+
+/// printf conversion specification like `%-5.2s` for a placeholder, which the backends and
+/// the interpreter print arguments with
+pub fn conversion_spec(
+    spec: &FormatSpec,
+    default_align: FormatAlign,
+    use_precision: bool,
+    conversion: &str,
+) -> String {
+    let mut s = "%".to_string();
+    // padding with zeros is only for numbers, and it overrides the alignment
+    if spec.zero_pad && conversion != "s" {
+        s.push('0');
+    } else if spec.align.unwrap_or(default_align) == FormatAlign::Left {
+        s.push('-');
+    }
+    if let Some(width) = spec.width {
+        s.push_str(&width.to_string());
+    }
+    if use_precision && let Some(precision) = spec.precision {
+        s.push_str(&format!(".{precision}"));
+    }
+    s.push_str(conversion);
+    s
+}
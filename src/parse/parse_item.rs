@@ -1,40 +1,94 @@
 use super::Parser;
-use crate::ast::{ExternBlock, Func, Item, ItemKind, Module, StructItem, Ty, TyKind};
+use crate::ast::{
+    ConstItem, Constness, ExternBlock, Func, Generics, Impl, InlineAttr, Item, ItemKind, Lifetime,
+    Module, Mutability, Path, StaticItem, StructItem, StructKind, Ty, TyAlias, TyKind, Unsafety,
+};
 use crate::lexer::{self, Token, TokenKind};
 use crate::span::Ident;
+use std::rc::Rc;
 
 pub fn is_item_start(token: &Token) -> bool {
     matches!(
         token.kind,
-        TokenKind::Fn | TokenKind::Extern | TokenKind::Struct | TokenKind::Mod
+        TokenKind::Fn
+            | TokenKind::Unsafe
+            | TokenKind::Extern
+            | TokenKind::Struct
+            | TokenKind::Mod
+            | TokenKind::Type
+            | TokenKind::Impl
+            | TokenKind::Const
+            | TokenKind::Static
+            | TokenKind::Pound
+    )
+}
+
+pub fn is_type_start(token: &Token) -> bool {
+    matches!(
+        token.kind,
+        TokenKind::Ident(_)
+            | TokenKind::OpenParen
+            | TokenKind::Bang
+            | TokenKind::Int(_)
+            | TokenKind::F32
+            | TokenKind::F64
+            | TokenKind::Str
+            | TokenKind::Char
+            | TokenKind::Bool
+            | TokenKind::OpenBracket
+            | TokenKind::BinOp(lexer::BinOp::And | lexer::BinOp::Star)
     )
 }
 
 impl Parser {
-    /// item ::= func | structItem | externBlock | module
+    /// item ::= outerAttributes (func | structItem | externBlock | module | tyAlias | impl
+    ///     | constItem | staticItem)
     pub fn parse_item(&mut self) -> Option<Item> {
-        let t = self.peek_token();
-        match &t.kind {
-            TokenKind::Fn => Some(Item {
-                kind: ItemKind::Func(self.parse_func(None)?),
-            }),
-            TokenKind::Struct => Some(Item {
-                kind: ItemKind::Struct(self.parse_struct_item()?),
-            }),
-            TokenKind::Extern => Some(Item {
-                kind: ItemKind::ExternBlock(self.parse_extern_block()?),
-            }),
-            TokenKind::Mod => Some(Item {
-                kind: ItemKind::Mod(self.parse_module()?),
-            }),
+        let lo = self.peek_token().span.clone();
+        let attrs = self.parse_outer_attributes()?;
+        let t = self.peek_token().clone();
+        let inline = self.inline_attr(&attrs)?;
+        let kind = match &t.kind {
+            TokenKind::Fn | TokenKind::Unsafe => ItemKind::Func(self.parse_func(None)?),
+            TokenKind::Struct => ItemKind::Struct(self.parse_struct_item()?),
+            TokenKind::Extern => ItemKind::ExternBlock(self.parse_extern_block()?),
+            TokenKind::Mod => ItemKind::Mod(self.parse_module()?),
+            TokenKind::Type => ItemKind::TyAlias(self.parse_ty_alias()?),
+            TokenKind::Impl => ItemKind::Impl(self.parse_impl()?),
+            TokenKind::Const
+                if matches!(
+                    self.peek_nth_token(1).kind,
+                    TokenKind::Fn | TokenKind::Unsafe
+                ) =>
+            {
+                ItemKind::Func(self.parse_func(None)?)
+            }
+            TokenKind::Const => ItemKind::Const(self.parse_const_item()?),
+            TokenKind::Static => ItemKind::Static(self.parse_static_item()?),
             _ => {
                 eprintln!(
                     "Expected item, but found `{}`",
                     self.peek_token().span.to_snippet()
                 );
-                None
+                return None;
             }
-        }
+        };
+        let kind = match (kind, inline) {
+            (ItemKind::Func(func), inline) => ItemKind::Func(Func { inline, ..func }),
+            (kind, InlineAttr::None) => kind,
+            (_, _) => {
+                eprintln!(
+                    "`inline` attribute should be applied to a function at {}",
+                    lo.to_location()
+                );
+                return None;
+            }
+        };
+        Some(Item {
+            kind,
+            attrs,
+            span: self.span_from(&lo),
+        })
     }
 
     /// module ::= "mod" ident "{" item* "}"
@@ -72,6 +126,166 @@ impl Parser {
         })
     }
 
+    /// tyAlias ::= "type" ident "=" type ";"
+    /// https://doc.rust-lang.org/reference/items/type-aliases.html
+    fn parse_ty_alias(&mut self) -> Option<TyAlias> {
+        // skip `type`
+        self.skip_token();
+
+        let ident = self.parse_ident()?;
+        if !self.skip_expected_token(TokenKind::Eq) {
+            eprintln!(
+                "Expected '=' for type alias, but found `{}`",
+                self.peek_token().span.to_snippet()
+            );
+            return None;
+        }
+        let ty = self.parse_type()?;
+        if !self.skip_expected_token(TokenKind::Semi) {
+            eprintln!(
+                "Expected ';' for type alias, but found `{}`",
+                self.peek_token().span.to_snippet()
+            );
+            return None;
+        }
+
+        Some(TyAlias { ident, ty })
+    }
+
+    /// constItem ::= "const" ident ":" type "=" expr ";"
+    /// https://doc.rust-lang.org/reference/items/constant-items.html
+    fn parse_const_item(&mut self) -> Option<ConstItem> {
+        // skip `const`
+        self.skip_token();
+
+        let ident = self.parse_ident()?;
+        if !self.skip_expected_token(TokenKind::Colon) {
+            eprintln!(
+                "Expected ':' for constant item, but found `{}`",
+                self.peek_token().span.to_snippet()
+            );
+            return None;
+        }
+        let ty = self.parse_type()?;
+        if !self.skip_expected_token(TokenKind::Eq) {
+            eprintln!(
+                "Expected '=' for constant item, but found `{}`",
+                self.peek_token().span.to_snippet()
+            );
+            return None;
+        }
+        let expr = self.parse_expr()?;
+        if !self.skip_expected_token(TokenKind::Semi) {
+            eprintln!(
+                "Expected ';' for constant item, but found `{}`",
+                self.peek_token().span.to_snippet()
+            );
+            return None;
+        }
+
+        Some(ConstItem { ident, ty, expr })
+    }
+
+    /// staticItem ::= "static" "mut"? ident ":" type "=" expr ";"
+    /// https://doc.rust-lang.org/reference/items/static-items.html
+    fn parse_static_item(&mut self) -> Option<StaticItem> {
+        // skip `static`
+        self.skip_token();
+
+        let mutbl = if self.skip_expected_token(TokenKind::Mut) {
+            Mutability::Mut
+        } else {
+            Mutability::Not
+        };
+        let ident = self.parse_ident()?;
+        if !self.skip_expected_token(TokenKind::Colon) {
+            eprintln!(
+                "Expected ':' for static item, but found `{}`",
+                self.peek_token().span.to_snippet()
+            );
+            return None;
+        }
+        let ty = self.parse_type()?;
+        if !self.skip_expected_token(TokenKind::Eq) {
+            eprintln!(
+                "Expected '=' for static item, but found `{}`",
+                self.peek_token().span.to_snippet()
+            );
+            return None;
+        }
+        let expr = self.parse_expr()?;
+        if !self.skip_expected_token(TokenKind::Semi) {
+            eprintln!(
+                "Expected ';' for static item, but found `{}`",
+                self.peek_token().span.to_snippet()
+            );
+            return None;
+        }
+
+        Some(StaticItem {
+            ident,
+            mutbl,
+            ty,
+            expr,
+        })
+    }
+
+    /// impl ::= "impl" generics? path "for" type "{" func* "}"
+    /// Only trait impls are supported
+    /// https://doc.rust-lang.org/reference/items/implementations.html
+    fn parse_impl(&mut self) -> Option<Impl> {
+        // skip `impl`
+        self.skip_token();
+
+        let generics = self.parse_generics()?;
+
+        let of_trait = self.parse_path()?;
+        if !self.skip_expected_token(TokenKind::For) {
+            eprintln!(
+                "Expected 'for' for trait implementation, but found `{}`",
+                self.peek_token().span.to_snippet()
+            );
+            return None;
+        }
+        let self_ty = self.parse_type()?;
+
+        if !self.skip_expected_token(TokenKind::OpenBrace) {
+            eprintln!(
+                "Expected '{{' for implementation, but found `{}`",
+                self.peek_token().span.to_snippet()
+            );
+            return None;
+        }
+
+        let mut items = vec![];
+        while matches!(
+            self.peek_token().kind,
+            TokenKind::Fn | TokenKind::Unsafe | TokenKind::Pound
+        ) {
+            let attrs = self.parse_outer_attributes()?;
+            let inline = self.inline_attr(&attrs)?;
+            let func = self.parse_func(None)?;
+            if self.is_cfg_enabled(&attrs)? {
+                items.push(Func { inline, ..func });
+            }
+        }
+
+        if !self.skip_expected_token(TokenKind::CloseBrace) {
+            eprintln!(
+                "Expected '}}' or associated item, but found `{}`",
+                self.peek_token().span.to_snippet()
+            );
+            return None;
+        }
+
+        Some(Impl {
+            generics,
+            of_trait,
+            self_ty,
+            items,
+        })
+    }
+
     /// externBlock ::= "extern" abi "{" externalItem* "}"
     /// abi ::= "\"C\""
     /// https://doc.rust-lang.org/reference/items/external-blocks.html
@@ -119,9 +333,20 @@ impl Parser {
         Some(ExternBlock { funcs })
     }
 
-    /// func ::= "fn" ident "(" funcParams? ")" "->" "i32" (block | ";")
+    /// func ::= "const"? "unsafe"? "fn" ident generics? "(" funcParams? ")" ("->" type)?
+    ///     (block | ";")
     /// https://doc.rust-lang.org/reference/items/functions.html
     pub fn parse_func(&mut self, ext: Option<String>) -> Option<Func> {
+        let constness = if self.skip_expected_token(TokenKind::Const) {
+            Constness::Const
+        } else {
+            Constness::NotConst
+        };
+        let unsafety = if self.skip_expected_token(TokenKind::Unsafe) {
+            Unsafety::Unsafe
+        } else {
+            Unsafety::Safe
+        };
         if !self.skip_expected_token(TokenKind::Fn) {
             eprintln!(
                 "Expected \"fn\", but found `{}`",
@@ -130,6 +355,7 @@ impl Parser {
             return None;
         }
         let name = self.parse_ident()?;
+        let generics = self.parse_generics()?;
         if !self.skip_expected_token(TokenKind::OpenParen) {
             eprintln!(
                 "Expected '(', but found `{}`",
@@ -151,14 +377,15 @@ impl Parser {
             return None;
         }
 
-        if !self.skip_expected_token(TokenKind::Arrow) {
-            eprintln!(
-                "Expected '->', but found `{}`",
-                self.peek_token().span.to_snippet()
-            );
-            return None;
-        }
-        let ret_ty = self.parse_type()?;
+        // the return type defaults to `()`
+        let ret_ty = if self.skip_expected_token(TokenKind::Arrow) {
+            self.parse_type()?
+        } else {
+            Ty {
+                kind: TyKind::Unit,
+                span: self.peek_token().span.clone(),
+            }
+        };
 
         let t = self.peek_token();
         let body = if t.kind == TokenKind::OpenBrace {
@@ -176,6 +403,10 @@ impl Parser {
 
         Some(Func {
             name,
+            generics,
+            unsafety,
+            constness,
+            inline: InlineAttr::None,
             params,
             ret_ty: ret_ty,
             ext,
@@ -184,8 +415,48 @@ impl Parser {
         })
     }
 
+    /// generics ::= "<" lifetimes? ">"
+    /// https://doc.rust-lang.org/reference/items/generics.html
+    fn parse_generics(&mut self) -> Option<Generics> {
+        if !self.skip_expected_token(TokenKind::BinOp(lexer::BinOp::Lt)) {
+            return Some(Generics::default());
+        }
+        let lifetimes = self.parse_lifetimes()?;
+        if !self.skip_expected_token(TokenKind::BinOp(lexer::BinOp::Gt)) {
+            eprintln!(
+                "Expected lifetime parameter or '>', but found `{}`",
+                self.peek_token().span.to_snippet()
+            );
+            return None;
+        }
+        Some(Generics { lifetimes })
+    }
+
+    /// lifetimes ::= lifetime ("," lifetime)* ","?
+    fn parse_lifetimes(&mut self) -> Option<Vec<Lifetime>> {
+        let mut lifetimes = vec![];
+        while let Some(lifetime) = self.parse_lifetime() {
+            lifetimes.push(lifetime);
+            if !self.skip_expected_token(TokenKind::Comma) {
+                break;
+            }
+        }
+        Some(lifetimes)
+    }
+
+    fn parse_lifetime(&mut self) -> Option<Lifetime> {
+        if !matches!(self.peek_token().kind, TokenKind::Lifetime(_)) {
+            return None;
+        }
+        let t = self.skip_token();
+        let TokenKind::Lifetime(name) = t.kind else {
+            unreachable!()
+        };
+        Some(Lifetime { name, span: t.span })
+    }
+
     /// funcParams ::= funcParam ("," funcParam)* ","?
-    /// funcParam ::= ident ":" type
+    /// funcParam ::= selfParam | ident ":" type
     fn parse_func_params(&mut self) -> Option<Vec<(Ident, Ty)>> {
         let mut params = vec![];
         params.push(self.parse_func_param()?);
@@ -200,6 +471,11 @@ impl Parser {
     }
 
     fn parse_func_param(&mut self) -> Option<(Ident, Ty)> {
+        if self.peek_token().kind == TokenKind::BinOp(lexer::BinOp::And)
+            || matches!(&self.peek_token().kind, TokenKind::Ident(s) if s == "self")
+        {
+            return self.parse_self_param();
+        }
         let ident = self.parse_ident()?;
         if !self.skip_expected_token(TokenKind::Colon) {
             eprintln!(
@@ -212,6 +488,40 @@ impl Parser {
         Some((ident, ty))
     }
 
+    /// selfParam ::= ("&" lifetime?)? "mut"? "self"
+    /// The type of `self` is `Self`, `&Self` or `&mut Self`
+    fn parse_self_param(&mut self) -> Option<(Ident, Ty)> {
+        let start = self.peek_token().span.clone();
+        let is_ref = self.skip_expected_token(TokenKind::BinOp(lexer::BinOp::And));
+        let region = if is_ref { self.parse_lifetime() } else { None };
+        let mutbl = if self.skip_expected_token(TokenKind::Mut) {
+            Mutability::Mut
+        } else {
+            Mutability::Not
+        };
+        let ident = self.parse_ident()?;
+        if *ident.symbol != "self" {
+            eprintln!("Expected `self`, but found `{}`", ident.span.to_snippet());
+            return None;
+        }
+        let span = start.concat(&ident.span);
+        let self_ty = Ty {
+            kind: TyKind::SelfTy,
+            span: ident.span.clone(),
+        };
+        let ty = if is_ref {
+            Ty {
+                kind: TyKind::Ref(region, mutbl, Box::new(self_ty)),
+                span,
+            }
+        } else {
+            self_ty
+        };
+        Some((ident, ty))
+    }
+
+    /// structItem ::= "struct" ident generics? ("{" structFields? "}" | "(" tupleFields? ")" ";" | ";")
+    /// https://doc.rust-lang.org/reference/items/structs.html
     fn parse_struct_item(&mut self) -> Option<StructItem> {
         if !self.skip_expected_token(TokenKind::Struct) {
             eprintln!(
@@ -221,15 +531,52 @@ impl Parser {
             return None;
         }
         let ident = self.parse_ident()?;
-        if !self.skip_expected_token(TokenKind::OpenBrace) {
-            eprintln!(
-                "Expected '{{' for struct definiton, but found `{}`",
-                self.peek_token().span.to_snippet()
-            );
-            return None;
-        }
+        let generics = self.parse_generics()?;
 
-        let fields = if matches!(self.peek_token().kind, TokenKind::Ident(_)) {
+        let (fields, kind) = match self.peek_token().kind {
+            TokenKind::OpenBrace => (self.parse_named_struct_body()?, StructKind::Named),
+            TokenKind::OpenParen => {
+                let fields = self.parse_tuple_fields()?;
+                if !self.skip_expected_token(TokenKind::Semi) {
+                    eprintln!(
+                        "Expected ';' for tuple struct definition, but found `{}`",
+                        self.peek_token().span.to_snippet()
+                    );
+                    return None;
+                }
+                (fields, StructKind::Tuple)
+            }
+            TokenKind::Semi => {
+                self.skip_token();
+                (vec![], StructKind::Unit)
+            }
+            _ => {
+                eprintln!(
+                    "Expected '{{', '(' or ';' for struct definiton, but found `{}`",
+                    self.peek_token().span.to_snippet()
+                );
+                return None;
+            }
+        };
+
+        Some(StructItem {
+            ident,
+            generics,
+            fields,
+            kind,
+            id: self.get_next_id(),
+        })
+    }
+
+    /// "{" structFields? "}"
+    fn parse_named_struct_body(&mut self) -> Option<Vec<(Ident, Ty)>> {
+        // skip '{'
+        self.skip_token();
+
+        let fields = if matches!(
+            self.peek_token().kind,
+            TokenKind::Ident(_) | TokenKind::Pound
+        ) {
             self.parse_struct_fields()?
         } else {
             vec![]
@@ -241,28 +588,64 @@ impl Parser {
             );
             return None;
         }
+        Some(fields)
+    }
 
-        Some(StructItem {
-            ident,
-            fields,
-            id: self.get_next_id(),
-        })
+    /// tupleFields ::= "(" (outerAttributes type ("," outerAttributes type)* ","?)? ")"
+    /// Fields are named by their indices
+    fn parse_tuple_fields(&mut self) -> Option<Vec<(Ident, Ty)>> {
+        // skip '('
+        self.skip_token();
+
+        let mut fields = vec![];
+        while self.peek_token().kind != TokenKind::CloseParen {
+            let attrs = self.parse_outer_attributes()?;
+            let ty = self.parse_type()?;
+            if self.is_cfg_enabled(&attrs)? {
+                // fields configured out are not counted
+                let name = Ident {
+                    symbol: Rc::new(fields.len().to_string()),
+                    span: ty.span.clone(),
+                };
+                fields.push((name, ty));
+            }
+            if !self.skip_expected_token(TokenKind::Comma) {
+                break;
+            }
+        }
+        if !self.skip_expected_token(TokenKind::CloseParen) {
+            eprintln!(
+                "Expected ')' for tuple struct definition, but found `{}`",
+                self.peek_token().span.to_snippet()
+            );
+            return None;
+        }
+        Some(fields)
     }
 
     fn parse_struct_fields(&mut self) -> Option<Vec<(Ident, Ty)>> {
         let mut fields = vec![];
-        fields.push(self.parse_struct_field()?);
+        loop {
+            let attrs = self.parse_outer_attributes()?;
+            let field = self.parse_struct_field()?;
+            if self.is_cfg_enabled(&attrs)? {
+                fields.push(field);
+            }
 
-        while matches!(self.peek_token().kind, TokenKind::Comma) {
-            self.skip_token();
-            if matches!(self.peek_token().kind, TokenKind::Ident(_)) {
-                fields.push(self.parse_struct_field()?);
+            if !self.skip_expected_token(TokenKind::Comma)
+                || !matches!(
+                    self.peek_token().kind,
+                    TokenKind::Ident(_) | TokenKind::Pound
+                )
+            {
+                break;
             }
         }
         Some(fields)
     }
 
-    /// structField ::= ident ":" type
+    /// structField ::= outerAttributes ident ":" type
+    /// NOTE: attributes are already parsed
     fn parse_struct_field(&mut self) -> Option<(Ident, Ty)> {
         let name = self.parse_ident()?;
         if !self.skip_expected_token(TokenKind::Colon) {
@@ -279,9 +662,37 @@ impl Parser {
     pub fn parse_type(&mut self) -> Option<Ty> {
         if matches!(self.peek_token().kind, TokenKind::Ident(_)) {
             let path = self.parse_path()?;
+            if path.segments.len() == 1
+                && path.segments[0].symbol.as_str() == "Box"
+                && self.peek_token().kind == TokenKind::BinOp(lexer::BinOp::Lt)
+            {
+                return self.parse_box_type(path);
+            }
+            if path.segments.len() == 1 && path.segments[0].symbol.as_str() == "Self" {
+                return Some(Ty {
+                    span: path.span.clone(),
+                    kind: TyKind::SelfTy,
+                });
+            }
+            // lifetime arguments `S<'a>`
+            if self.skip_expected_token(TokenKind::BinOp(lexer::BinOp::Lt)) {
+                let lifetimes = self.parse_lifetimes()?;
+                let span = path.span.concat(&self.peek_token().span);
+                if !self.skip_expected_token(TokenKind::BinOp(lexer::BinOp::Gt)) {
+                    eprintln!(
+                        "Expected lifetime argument or '>', but found `{}`",
+                        self.peek_token().span.to_snippet()
+                    );
+                    return None;
+                }
+                return Some(Ty {
+                    span,
+                    kind: TyKind::Adt(path, lifetimes),
+                });
+            }
             return Some(Ty {
                 span: path.span.clone(),
-                kind: TyKind::Adt(path),
+                kind: TyKind::Adt(path, vec![]),
             });
         }
 
@@ -289,8 +700,19 @@ impl Parser {
         let mut span = t.span;
         match t.kind {
             // Unit type: ()
+            // Parenthesized type: "(" type ")"
             TokenKind::OpenParen => {
-                if !self.skip_expected_token(TokenKind::CloseParen) {
+                if self.peek_token().kind != TokenKind::CloseParen {
+                    let ty = self.parse_type()?;
+                    if !self.skip_expected_token(TokenKind::CloseParen) {
+                        eprintln!(
+                            "Expected ')', but found `{}`",
+                            self.peek_token().span.to_snippet()
+                        );
+                        return None;
+                    }
+                    Some(ty)
+                } else if !self.skip_expected_token(TokenKind::CloseParen) {
                     eprintln!(
                         "Expected ')', but found `{}`",
                         self.peek_token().span.to_snippet()
@@ -308,9 +730,19 @@ impl Parser {
                 kind: TyKind::Never,
                 span,
             }),
-            // i32
-            TokenKind::I32 => Some(Ty {
-                kind: TyKind::I32,
+            // i32, u8, usize, ...
+            TokenKind::Int(int_ty) => Some(Ty {
+                kind: TyKind::Int(int_ty),
+                span,
+            }),
+            // f32
+            TokenKind::F32 => Some(Ty {
+                kind: TyKind::F32,
+                span,
+            }),
+            // f64
+            TokenKind::F64 => Some(Ty {
+                kind: TyKind::F64,
                 span,
             }),
             // str
@@ -323,6 +755,11 @@ impl Parser {
                 kind: TyKind::Bool,
                 span,
             }),
+            // char
+            TokenKind::Char => Some(Ty {
+                kind: TyKind::Char,
+                span,
+            }),
             // [type; n]
             TokenKind::OpenBracket => {
                 let elem_ty = self.parse_type()?;
@@ -333,10 +770,7 @@ impl Parser {
                     );
                     return None;
                 }
-                let t = self.skip_token();
-                let TokenKind::NumLit(n) = t.kind else {
-                    return None;
-                };
+                let len = self.parse_expr()?;
                 let span = span.concat(&self.peek_token().span);
                 if !self.skip_expected_token(TokenKind::CloseBracket) {
                     eprintln!(
@@ -345,9 +779,8 @@ impl Parser {
                     );
                     return None;
                 }
-                // u32 is safely converted to usize
                 Some(Ty {
-                    kind: TyKind::Array(Box::new(elem_ty), n.try_into().unwrap()),
+                    kind: TyKind::Array(Box::new(elem_ty), Box::new(len)),
                     span,
                 })
             }
@@ -355,32 +788,37 @@ impl Parser {
                 unreachable!()
             }
             TokenKind::BinOp(lexer::BinOp::And) => {
-                let t = self.peek_token();
-                let region = if let TokenKind::Lifetime(_) = t.kind {
-                    let TokenKind::Lifetime(r) = self.skip_token().kind else { unreachable!() };
-                    Some(r)
+                let region = self.parse_lifetime();
+                let mutbl = if self.skip_expected_token(TokenKind::Mut) {
+                    Mutability::Mut
                 } else {
-                    None
+                    Mutability::Not
                 };
                 let referent = self.parse_type()?;
                 let span = span.concat(&referent.span);
                 Some(Ty {
-                    kind: TyKind::Ref(region, Box::new(referent)),
+                    kind: TyKind::Ref(region, mutbl, Box::new(referent)),
                     span,
                 })
             }
-            // *const T
+            // *const T or *mut T
             TokenKind::BinOp(lexer::BinOp::Star) => {
-                // TODO: *mut T
                 let t = self.skip_token();
-                if t.kind != TokenKind::Const {
-                    eprintln!("Expected 'const', but found `{}`", t.span.to_snippet());
-                    return None;
-                }
+                let mutbl = match t.kind {
+                    TokenKind::Const => Mutability::Not,
+                    TokenKind::Mut => Mutability::Mut,
+                    _ => {
+                        eprintln!(
+                            "Expected 'const' or 'mut', but found `{}`",
+                            t.span.to_snippet()
+                        );
+                        return None;
+                    }
+                };
                 let referent = self.parse_type()?;
                 span = span.concat(&referent.span);
                 Some(Ty {
-                    kind: TyKind::ConstPtr(Box::new(referent)),
+                    kind: TyKind::RawPtr(mutbl, Box::new(referent)),
                     span,
                 })
             }
@@ -390,4 +828,24 @@ impl Parser {
             }
         }
     }
+
+    /// boxType ::= "Box" "<" type ">"
+    /// NOTE: `Box` is already parsed
+    fn parse_box_type(&mut self, path: Path) -> Option<Ty> {
+        // skip '<'
+        self.skip_token();
+        let inner = self.parse_type()?;
+        let span = path.span.concat(&self.peek_token().span);
+        if !self.skip_expected_token(TokenKind::BinOp(lexer::BinOp::Gt)) {
+            eprintln!(
+                "Expected '>', but found `{}`",
+                self.peek_token().span.to_snippet()
+            );
+            return None;
+        }
+        Some(Ty {
+            kind: TyKind::Box(Box::new(inner)),
+            span,
+        })
+    }
 }
@@ -0,0 +1,299 @@
+use super::parse_format::parse_format_string;
+use super::Parser;
+use crate::ast::{
+    self, Block, Expr, ExprKind, FormatArgs, FormatPiece, FormatSpec, LetStmt, Mutability, Path,
+    PrintDest, Stmt, StmtKind, Unsafety,
+};
+use crate::lexer::TokenKind;
+use crate::span::{Ident, Span};
+use std::rc::Rc;
+
+impl Parser {
+    /// macroInvocation ::= ident "!" "(" macroArgs? ")"
+    /// Built-in macros are expanded here.
+    /// Formatting macros (`print!` and friends) take a string literal followed by arguments
+    /// NOTE: path is already parsed
+    pub fn parse_builtin_macro(&mut self, path: Path) -> Option<Expr> {
+        // skip '!'
+        self.skip_token();
+
+        if path.segments.len() != 1 {
+            eprintln!("Cannot find macro `{}`", path.span.to_snippet());
+            return None;
+        }
+        let name = path.segments[0].symbol.as_str();
+        match name {
+            "panic" => {
+                let (mut args, span) = self.parse_macro_args(&path.span)?;
+                let fmt = if args.is_empty() {
+                    FormatArgs::lit("explicit panic".to_string())
+                } else {
+                    let msg = self.expect_str_lit(&args.remove(0))?;
+                    self.new_format_args(&msg, args, &span)?
+                };
+                Some(self.new_panic_expr(fmt, span))
+            }
+            "unreachable" => {
+                let (mut args, span) = self.parse_macro_args(&path.span)?;
+                let fmt = if args.is_empty() {
+                    FormatArgs::lit("internal error: entered unreachable code".to_string())
+                } else {
+                    let msg = self.expect_str_lit(&args.remove(0))?;
+                    let msg = format!("internal error: entered unreachable code: {msg}");
+                    self.new_format_args(&msg, args, &span)?
+                };
+                Some(self.new_panic_expr(fmt, span))
+            }
+            "assert" => {
+                let (mut args, span) = self.parse_macro_args(&path.span)?;
+                if args.is_empty() {
+                    eprintln!("`assert!` takes at least 1 argument");
+                    return None;
+                }
+                let cond = args.remove(0);
+                let fmt = if args.is_empty() {
+                    // the condition is not a format string even if it contains braces
+                    FormatArgs::lit(format!("assertion failed: {}", cond.span.to_snippet()))
+                } else {
+                    let msg = self.expect_str_lit(&args.remove(0))?;
+                    self.new_format_args(&msg, args, &span)?
+                };
+                Some(self.new_assert_expr(cond, fmt, span))
+            }
+            "assert_eq" => {
+                let (mut args, span) = self.parse_macro_args(&path.span)?;
+                if args.len() < 2 {
+                    eprintln!("`assert_eq!` takes at least 2 arguments");
+                    return None;
+                }
+                let lhs = args.remove(0);
+                let rhs = args.remove(0);
+                let fmt = if args.is_empty() {
+                    FormatArgs::lit("assertion `left == right` failed".to_string())
+                } else {
+                    let msg = self.expect_str_lit(&args.remove(0))?;
+                    let msg = format!("assertion `left == right` failed: {msg}");
+                    self.new_format_args(&msg, args, &span)?
+                };
+                Some(self.new_assert_eq_expr(lhs, rhs, fmt, span))
+            }
+            "cfg" => {
+                // `cfg!(pred)` takes a configuration predicate instead of expressions
+                if !self.skip_expected_token(TokenKind::OpenParen) {
+                    eprintln!(
+                        "Expected '(', but found `{}`",
+                        self.peek_token().span.to_snippet()
+                    );
+                    return None;
+                }
+                let pred = self.parse_meta_item()?;
+                let span = path.span.concat(&self.peek_token().span);
+                if !self.skip_expected_token(TokenKind::CloseParen) {
+                    eprintln!(
+                        "Expected ')', but found `{}`",
+                        self.peek_token().span.to_snippet()
+                    );
+                    return None;
+                }
+                Some(Expr {
+                    kind: ExprKind::BoolLit(self.cfg.eval(&pred)?),
+                    id: self.get_next_id(),
+                    span,
+                })
+            }
+            "print" | "println" | "eprint" | "eprintln" => {
+                let (mut args, span) = self.parse_macro_args(&path.span)?;
+                let dest = if name.starts_with('e') {
+                    PrintDest::Stderr
+                } else {
+                    PrintDest::Stdout
+                };
+                let mut fmt = if args.is_empty() {
+                    if !name.ends_with("ln") {
+                        eprintln!("`{}!` requires at least a format string argument", name);
+                        return None;
+                    }
+                    String::new()
+                } else {
+                    self.expect_str_lit(&args.remove(0))?
+                };
+                if name.ends_with("ln") {
+                    fmt.push('\n');
+                }
+                let fmt = self.new_format_args(&fmt, args, &span)?;
+                Some(Expr {
+                    kind: ExprKind::Print(dest, fmt),
+                    id: self.get_next_id(),
+                    span,
+                })
+            }
+            _ => {
+                eprintln!("Cannot find macro `{}`", name);
+                None
+            }
+        }
+    }
+
+    /// macroArgs ::= expr ("," expr)* ","?
+    /// Returns arguments and the span of the whole invocation
+    fn parse_macro_args(&mut self, macro_span: &Span) -> Option<(Vec<Expr>, Span)> {
+        if !self.skip_expected_token(TokenKind::OpenParen) {
+            eprintln!(
+                "Expected '(', but found `{}`",
+                self.peek_token().span.to_snippet()
+            );
+            return None;
+        }
+        let args = if self.peek_token().kind == TokenKind::CloseParen {
+            vec![]
+        } else {
+            self.parse_call_params()?
+        };
+
+        let span = macro_span.concat(&self.peek_token().span);
+        if !self.skip_expected_token(TokenKind::CloseParen) {
+            eprintln!(
+                "Expected ')', but found `{}`",
+                self.peek_token().span.to_snippet()
+            );
+            return None;
+        }
+        Some((args, span))
+    }
+
+    fn expect_str_lit(&self, expr: &Expr) -> Option<String> {
+        if let ExprKind::StrLit(s) = &expr.kind {
+            Some(s.clone())
+        } else {
+            eprintln!(
+                "Expected string literal, but found `{}`",
+                expr.span.to_snippet()
+            );
+            None
+        }
+    }
+
+    /// Parse the format string of a formatting macro invoked at `span`
+    fn new_format_args(&self, fmt: &str, args: Vec<Expr>, span: &Span) -> Option<FormatArgs> {
+        match parse_format_string(fmt, args.len()) {
+            Ok(pieces) => Some(FormatArgs { pieces, args }),
+            Err(msg) => {
+                eprintln!("Invalid format string: {} at {}", msg, span.to_location());
+                None
+            }
+        }
+    }
+
+    fn new_panic_expr(&mut self, fmt: FormatArgs, span: Span) -> Expr {
+        Expr {
+            kind: ExprKind::Panic(fmt),
+            id: self.get_next_id(),
+            span,
+        }
+    }
+
+    /// `assert!(cond, ..)` => `if cond { } else { panic!(..) }`
+    fn new_assert_expr(&mut self, cond: Expr, fmt: FormatArgs, span: Span) -> Expr {
+        let then = self.new_block_expr(vec![], span.clone());
+        let panic = self.new_panic_expr(fmt, span.clone());
+        let panic_stmt = Stmt {
+            kind: StmtKind::Expr(Box::new(panic)),
+            attrs: vec![],
+            id: self.get_next_id(),
+            span: span.clone(),
+        };
+        let els = self.new_block_expr(vec![panic_stmt], span.clone());
+        Expr {
+            kind: ExprKind::If(Box::new(cond), Box::new(then), Some(Box::new(els))),
+            id: self.get_next_id(),
+            span,
+        }
+    }
+
+    /// `assert_eq!(lhs, rhs, ..)` =>
+    /// `{ let left_val = lhs; let right_val = rhs; assert!(left_val == right_val, ..) }`
+    /// where the message is followed by the values of both operands
+    fn new_assert_eq_expr(
+        &mut self,
+        lhs: Expr,
+        rhs: Expr,
+        mut fmt: FormatArgs,
+        span: Span,
+    ) -> Expr {
+        let mut stmts = vec![];
+        let mut operands = vec![];
+        for (name, label, init) in [
+            ("left_val", "\n  left: ", lhs),
+            ("right_val", "\n right: ", rhs),
+        ] {
+            let ident = Ident {
+                symbol: Rc::new(name.to_string()),
+                // errors about the operand point to it
+                span: init.span.clone(),
+            };
+            stmts.push(Stmt {
+                kind: StmtKind::Let(LetStmt {
+                    ident: ident.clone(),
+                    mutbl: Mutability::Not,
+                    ty: None,
+                    init: Some(init),
+                }),
+                attrs: vec![],
+                id: self.get_next_id(),
+                span: span.clone(),
+            });
+            fmt.pieces.push(FormatPiece::Lit(label.to_string()));
+            fmt.pieces.push(FormatPiece::Placeholder(FormatSpec {
+                arg: fmt.args.len(),
+                align: None,
+                zero_pad: false,
+                width: None,
+                precision: None,
+                debug: true,
+            }));
+            fmt.args.push(self.new_path_expr(ident.clone()));
+            operands.push(self.new_path_expr(ident));
+        }
+        let rhs = operands.pop().unwrap();
+        let lhs = operands.pop().unwrap();
+        let cond = Expr {
+            kind: ExprKind::Binary(ast::BinOp::Eq, Box::new(lhs), Box::new(rhs)),
+            id: self.get_next_id(),
+            span: span.clone(),
+        };
+        let assert = self.new_assert_expr(cond, fmt, span.clone());
+        stmts.push(Stmt {
+            kind: StmtKind::Expr(Box::new(assert)),
+            attrs: vec![],
+            id: self.get_next_id(),
+            span: span.clone(),
+        });
+        self.new_block_expr(stmts, span)
+    }
+
+    fn new_path_expr(&mut self, ident: Ident) -> Expr {
+        let span = ident.span.clone();
+        Expr {
+            kind: ExprKind::Path(Path {
+                segments: vec![ident],
+                span: span.clone(),
+            }),
+            id: self.get_next_id(),
+            span,
+        }
+    }
+
+    fn new_block_expr(&mut self, stmts: Vec<Stmt>, span: Span) -> Expr {
+        let block = Block {
+            stmts,
+            unsafety: Unsafety::Safe,
+            span: span.clone(),
+            id: self.get_next_id(),
+        };
+        Expr {
+            kind: ExprKind::Block(block),
+            id: self.get_next_id(),
+            span,
+        }
+    }
+}
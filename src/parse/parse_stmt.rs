@@ -1,19 +1,19 @@
 use super::parse_expr::is_expr_start;
 use super::Parser;
-use crate::ast::{Block, LetStmt, Stmt, StmtKind};
+use crate::ast::{Attribute, Block, LetStmt, Mutability, Stmt, StmtKind, Unsafety};
 use crate::lexer::{Token, TokenKind};
 
 pub fn is_stmt_start(t: &Token) -> bool {
-    is_expr_start(t) || matches!(t.kind, TokenKind::Let)
+    is_expr_start(t) || matches!(t.kind, TokenKind::Let | TokenKind::Pound)
 }
 
 impl Parser {
-    pub fn parse_stmt(&mut self) -> Option<Stmt> {
+    pub fn parse_stmt(&mut self, attrs: Vec<Attribute>) -> Option<Stmt> {
         let t = self.peek_token();
         let mut span = t.span.clone();
 
         match &t.kind {
-            TokenKind::Let => self.parse_let_stmt(),
+            TokenKind::Let => self.parse_let_stmt(attrs),
             _ if is_expr_start(t) => {
                 let expr = self.parse_expr()?;
                 span = span.concat(&expr.span);
@@ -24,12 +24,14 @@ impl Parser {
                     span = span.concat(&self.skip_token().span);
                     Some(Stmt {
                         kind: StmtKind::Semi(Box::new(expr)),
+                        attrs,
                         id: self.get_next_id(),
                         span,
                     })
                 } else {
                     Some(Stmt {
                         kind: StmtKind::Expr(Box::new(expr)),
+                        attrs,
                         id: self.get_next_id(),
                         span,
                     })
@@ -45,23 +47,24 @@ impl Parser {
         }
     }
 
-    /// letStmt ::= "let" ident (: type)? ("=" expr)? ";"
+    /// letStmt ::= "let" "mut"? ident (: type)? ("=" expr)? ";"
     /// https://doc.rust-lang.org/reference/statements.html#let-statements
-    fn parse_let_stmt(&mut self) -> Option<Stmt> {
+    fn parse_let_stmt(&mut self, attrs: Vec<Attribute>) -> Option<Stmt> {
         // skip "let"
         let mut span = self.skip_token().span;
 
+        let mutbl = if self.skip_expected_token(TokenKind::Mut) {
+            Mutability::Mut
+        } else {
+            Mutability::Not
+        };
         let ident = self.parse_ident()?;
-        // skip colon
-        if !self.skip_expected_token(TokenKind::Colon) {
-            eprintln!(
-                "Expected ':', but found `{}`",
-                self.peek_token().span.to_snippet()
-            );
-            return None;
-        }
-        // parse type
-        let ty = self.parse_type()?;
+        // parse (":" type)?
+        let ty = if self.skip_expected_token(TokenKind::Colon) {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
 
         // parse ("=" expr)?
         let t = self.peek_token();
@@ -85,9 +88,11 @@ impl Parser {
         Some(Stmt {
             kind: StmtKind::Let(LetStmt {
                 ident,
-                ty: Some(ty),
+                mutbl,
+                ty,
                 init,
             }),
+            attrs,
             id: self.get_next_id(),
             span,
         })
@@ -108,14 +113,23 @@ impl Parser {
         loop {
             let t = self.peek_token();
             if is_stmt_start(t) {
-                let stmt = self.parse_stmt()?;
+                // stmt ::= outerAttributes (letStmt | exprStmt)
+                let attrs = self.parse_outer_attributes()?;
+                let enabled = self.is_cfg_enabled(&attrs)?;
+                let stmt = self.parse_stmt(attrs)?;
                 span = span.concat(&stmt.span);
-                stmts.push(stmt);
+                if enabled {
+                    stmts.push(stmt);
+                }
+            } else if t.kind == TokenKind::Semi {
+                // empty statement
+                self.skip_token();
             } else if t.kind == TokenKind::CloseBrace {
                 // skip '}'
                 span = span.concat(&self.skip_token().span);
                 return Some(Block {
                     stmts,
+                    unsafety: Unsafety::Safe,
                     span,
                     id: self.get_next_id(),
                 });
@@ -19,16 +19,6 @@ pub enum BindingKind {
 
 pub type ShadowingIndex = u32;
 
-impl BindingKind {
-    pub fn is_param(&self) -> bool {
-        matches!(self, BindingKind::Param)
-    }
-
-    pub fn is_let(&self) -> bool {
-        matches!(self, BindingKind::Let(_))
-    }
-}
-
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct CanonicalPath {
     segments: Vec<Rc<String>>,
@@ -68,7 +58,7 @@ impl CanonicalPath {
             if i == 0 {
                 continue;
             }
-            s.push_str(&seg);
+            s.push_str(&escape_seg(seg));
             if i != self.segments.len() - 1 {
                 s.push_str("..");
             }
@@ -77,6 +67,15 @@ impl CanonicalPath {
     }
 }
 
+/// Escape characters invalid in symbols like the legacy mangling of rustc
+/// e.g. `<S as Drop>` => `$LT$S$u20$as$u20$Drop$GT$`
+fn escape_seg(seg: &str) -> String {
+    seg.replace("::", "..")
+        .replace('<', "$LT$")
+        .replace('>', "$GT$")
+        .replace(' ', "$u20$")
+}
+
 impl std::fmt::Debug for CanonicalPath {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for (i, seg) in self.segments.iter().enumerate() {
@@ -112,6 +111,8 @@ const DUMMY_RIB_ID: u32 = u32::MAX;
 #[derive(Debug, PartialEq, Eq)]
 pub enum RibKind {
     Mod,
+    /// Items in an impl are not visible from the enclosing module
+    Impl,
     Func,
     Block,
 }
@@ -278,6 +279,9 @@ impl Resolver {
             // search from this module (if this rib is not module, starts from its parent module)
             if rib.kind != RibKind::Mod {
                 let parent_module_rib = self.get_parent_module(rib_id).unwrap();
+                // items in the module are relative to the module, not to the function
+                let mut prefixes = prefixes;
+                prefixes.push(&parent_module_rib.cpath);
                 self.resolve_to_item_with_dfs(&prefixes, path, parent_module_rib.id, &mut result);
             } else {
                 self.resolve_to_item_with_dfs(&prefixes, path, rib_id, &mut result);
@@ -166,6 +166,22 @@ impl<'ctx> ast::visitor::Visitor<'ctx> for Resolver {
         self.pop_rib();
     }
 
+    fn visit_impl(&mut self, imp: &'ctx ast::Impl) {
+        // items are named like `<S as Drop>::drop`
+        let seg = format!(
+            "<{} as {}>",
+            imp.self_ty.span.to_snippet(),
+            imp.of_trait.span.to_snippet()
+        );
+        self.push_segment_to_current_cpath(Rc::new(seg));
+        self.push_rib(RibKind::Impl);
+    }
+
+    fn visit_impl_post(&mut self, _imp: &'ctx ast::Impl) {
+        self.pop_segment_from_current_cpath().unwrap();
+        self.pop_rib();
+    }
+
     fn visit_func(&mut self, func: &'ctx ast::Func) {
         // register func name
         self.insert_item_def(&func.name, BindingKind::Item);
@@ -201,6 +217,18 @@ impl<'ctx> ast::visitor::Visitor<'ctx> for Resolver {
         self.insert_item_def(&strct.ident, BindingKind::Item);
     }
 
+    fn visit_ty_alias(&mut self, alias: &'ctx ast::TyAlias) {
+        self.insert_item_def(&alias.ident, BindingKind::Item);
+    }
+
+    fn visit_const_item(&mut self, const_item: &'ctx ast::ConstItem) {
+        self.insert_item_def(&const_item.ident, BindingKind::Item);
+    }
+
+    fn visit_static_item(&mut self, static_item: &'ctx ast::StaticItem) {
+        self.insert_item_def(&static_item.ident, BindingKind::Item);
+    }
+
     fn visit_block(&mut self, _block: &'ctx ast::Block) {
         // push new rib
         self.push_rib(RibKind::Block);
@@ -1,22 +1,50 @@
 use std::cmp::{max, min};
 use std::rc::Rc;
 
+/// Source code and the name of the file it was read from
+#[derive(Eq, PartialEq, Hash)]
+pub struct SourceFile {
+    pub name: String,
+    pub src: String,
+}
+
+impl SourceFile {
+    pub fn new(name: String, src: String) -> Self {
+        SourceFile { name, src }
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Hash)]
 pub struct Span {
     lo: usize,
     hi: usize,
-    src: Rc<String>,
+    file: Rc<SourceFile>,
+    /// Tokens produced by macro expansion have the span of the macro invocation.
+    /// Each of them is given a unique mark so that they are still distinguishable.
+    mark: u32,
 }
 
 impl Span {
-    pub fn new(lo: usize, hi: usize, src: Rc<String>) -> Self {
-        Span { lo, hi, src }
+    pub fn new(lo: usize, hi: usize, file: Rc<SourceFile>) -> Self {
+        Span {
+            lo,
+            hi,
+            file,
+            mark: 0,
+        }
+    }
+
+    pub fn with_mark(&self, mark: u32) -> Span {
+        Span {
+            mark,
+            ..self.clone()
+        }
     }
 
     pub fn to_snippet(&self) -> String {
         assert!(self.lo <= self.hi);
-        assert!(self.hi <= self.src.len());
-        let src = &*self.src;
+        assert!(self.hi <= self.file.src.len());
+        let src = &self.file.src;
         let s = &src[self.lo()..self.hi()];
         // replace tabs and newlines
         s.replace("\r\n", "").replace('\n', "").replace("    ", " ")
@@ -26,10 +54,39 @@ impl Span {
         Span {
             lo: min(self.lo, span.lo),
             hi: max(self.hi, span.hi),
-            src: Rc::clone(&self.src),
+            file: Rc::clone(&self.file),
+            mark: self.mark,
         }
     }
 
+    /// The last character of the span (e.g. the closing brace of a block)
+    pub fn end(&self) -> Span {
+        Span {
+            lo: max(self.lo, self.hi.saturating_sub(1)),
+            ..self.clone()
+        }
+    }
+
+    /// `file:line:col` of the start of the span (1-origin), as printed by rustc
+    pub fn to_location(&self) -> String {
+        let mut line = 1;
+        let mut col = 1;
+        for c in self.file.src.chars().take(self.lo) {
+            if c == '\n' {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
+        }
+        format!("{}:{}:{}", self.file.name, line, col)
+    }
+
+    /// Whether `span` lies within this span of the same file
+    pub fn contains(&self, span: &Span) -> bool {
+        Rc::ptr_eq(&self.file, &span.file) && self.lo <= span.lo && span.hi <= self.hi
+    }
+
     pub fn lo(&self) -> usize {
         self.lo
     }
@@ -53,6 +110,16 @@ pub struct Ident {
     pub span: Span,
 }
 
+impl Ident {
+    /// The name as written in the source. Variables declared in macro definitions are
+    /// renamed to `name.N` by hygiene, and the suffix is not shown in diagnostics
+    pub fn source_name(&self) -> &str {
+        self.symbol
+            .split_once('.')
+            .map_or(&self.symbol, |(name, _)| name)
+    }
+}
+
 impl std::fmt::Debug for Ident {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "\"{}\" ({:?})", self.symbol, self.span)
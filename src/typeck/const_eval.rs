@@ -0,0 +1,560 @@
+//! Evaluation of constants at compile time
+//!
+//! `const` items and array lengths are evaluated on the HIR, calling `const fn`s on the
+//! way. Array lengths are needed to build types, so evaluation cannot wait for type
+//! inference: integer types are taken from the declared types of constants, parameters
+//! and variables, and from casts, and unsuffixed literals get the type the context
+//! expects. Overflow, division by zero and panics are errors.
+
+use super::TypeChecker;
+use crate::hir::{
+    self, BinOp, DefId, DefKind, ExprKind, HirId, IntTy, LocalId, Path, Res, StmtKind, UnOp,
+};
+use crate::middle::ty::{ConstValue, Ty, TyKind};
+use crate::span::Span;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Calls to `const fn`s nested deeper than this are considered infinite recursion
+const RECURSION_LIMIT: usize = 128;
+
+#[derive(Default)]
+pub(super) struct ConstEvalState<'chk> {
+    pub(super) consts: HashMap<DefId, &'chk hir::ConstItem>,
+    pub(super) const_fns: HashMap<DefId, &'chk hir::Func>,
+    /// `None` for constants whose evaluation failed, which is reported only once
+    evaluated: HashMap<DefId, Option<ConstValue>>,
+    /// Stack of constants being evaluated to detect cycles
+    evaluating: Vec<DefId>,
+    /// Array lengths by the ids of their expressions, as types are often converted twice
+    array_lens: HashMap<HirId, Option<usize>>,
+    /// Number of the `const fn` calls being evaluated
+    depth: usize,
+}
+
+/// Why evaluation stopped without a value
+enum Interrupt {
+    /// `return` in a `const fn`
+    Return(ConstValue),
+    /// The error is already reported
+    Error,
+}
+
+type EvalResult<T = ConstValue> = Result<T, Interrupt>;
+
+/// Variables of the `const fn` being evaluated
+#[derive(Default)]
+struct Frame {
+    /// Values with their integer types if known
+    locals: HashMap<LocalId, (ConstValue, Option<IntTy>)>,
+    ret_ty: Option<IntTy>,
+}
+
+fn int_ty_of(ty: &Ty) -> Option<IntTy> {
+    match ty.kind {
+        TyKind::Int(int_ty) => Some(int_ty),
+        _ => None,
+    }
+}
+
+fn value_desc(value: ConstValue) -> &'static str {
+    match value {
+        ConstValue::Int(_) => "{integer}",
+        ConstValue::Bool(_) => "bool",
+        ConstValue::Char(_) => "char",
+        ConstValue::Unit => "()",
+    }
+}
+
+impl<'chk> TypeChecker<'_, 'chk> {
+    /// Type of a constant. Constants can be used before their definitions
+    pub(super) fn const_ty(&mut self, def_id: &DefId) -> Rc<Ty> {
+        if let Some(ty) = self.ctx.lookup_name_type(def_id) {
+            return ty;
+        }
+        let const_item = self.const_eval.consts[def_id];
+        let ty = self.hir_ty_to_ty(&const_item.ty);
+        self.ctx.set_name_type(Rc::clone(def_id), Rc::clone(&ty));
+        ty
+    }
+
+    /// Value of a `const` item, which is evaluated on first use
+    pub(super) fn eval_const(&mut self, def_id: &DefId, span: &Span) -> Option<ConstValue> {
+        if let Some(value) = self.const_eval.evaluated.get(def_id) {
+            return *value;
+        }
+        if self.const_eval.evaluating.contains(def_id) {
+            self.error(format!(
+                "Cycle detected when evaluating constant `{:?}` at {}",
+                def_id.cpath,
+                span.to_location()
+            ));
+            return None;
+        }
+
+        let const_item = self.const_eval.consts[def_id];
+        self.const_eval.evaluating.push(Rc::clone(def_id));
+        let ty = self.const_ty(def_id);
+        let value = self.eval_root(&const_item.expr, &ty);
+        self.const_eval.evaluating.pop();
+        self.const_eval.evaluated.insert(Rc::clone(def_id), value);
+        if let Some(value) = value {
+            self.ctx.set_const_value(Rc::clone(def_id), value);
+        }
+        value
+    }
+
+    /// Initial value of a static, which is placed in memory by the backends
+    pub(super) fn eval_static(&mut self, static_item: &hir::StaticItem, ty: &Ty) {
+        if !matches!(
+            ty.kind,
+            TyKind::Int(_) | TyKind::Bool | TyKind::Char | TyKind::Error
+        ) {
+            self.error(format!(
+                "Statics of type `{}` are not supported at {}",
+                ty,
+                static_item.ty.span.to_location()
+            ));
+            return;
+        }
+        if let Some(value) = self.eval_root(&static_item.expr, ty) {
+            self.ctx
+                .set_const_value(Rc::clone(&static_item.def_id), value);
+        }
+    }
+
+    /// Length of an array type, which must be a `usize` constant
+    pub(super) fn eval_array_len(&mut self, len: &hir::Expr) -> Option<usize> {
+        if let Some(n) = self.const_eval.array_lens.get(&len.id) {
+            return *n;
+        }
+        let n = self.eval_usize(len);
+        self.const_eval.array_lens.insert(len.id, n);
+        n
+    }
+
+    fn eval_usize(&mut self, len: &hir::Expr) -> Option<usize> {
+        let usize_ty = Ty::int(IntTy::Usize);
+        if let Some(int_ty) = self.int_ty_of_expr(len, &Frame::default())
+            && int_ty != IntTy::Usize
+        {
+            self.error(format!(
+                "Mismatched types: expected `usize`, found `{}` at {}",
+                int_ty.name(),
+                len.span.to_location()
+            ));
+            return None;
+        }
+        match self.eval_root(len, &usize_ty)? {
+            ConstValue::Int(n) => Some(n as usize),
+            _ => None,
+        }
+    }
+
+    /// Evaluate an expression of type `ty` outside of functions
+    fn eval_root(&mut self, expr: &hir::Expr, ty: &Ty) -> Option<ConstValue> {
+        if ty.is_error() {
+            return None;
+        }
+        if !matches!(
+            ty.kind,
+            TyKind::Int(_) | TyKind::Bool | TyKind::Char | TyKind::Unit
+        ) {
+            self.error(format!(
+                "Constants of type `{}` are not supported at {}",
+                ty,
+                expr.span.to_location()
+            ));
+            return None;
+        }
+        let value = match self.eval_expr(expr, &mut Frame::default(), int_ty_of(ty)) {
+            Ok(value) => value,
+            Err(Interrupt::Return(_)) => {
+                self.error(format!(
+                    "`return` outside of a function at {}",
+                    expr.span.to_location()
+                ));
+                return None;
+            }
+            Err(Interrupt::Error) => return None,
+        };
+        if matches!(
+            (value, &ty.kind),
+            (ConstValue::Int(_), TyKind::Int(_))
+                | (ConstValue::Bool(_), TyKind::Bool)
+                | (ConstValue::Char(_), TyKind::Char)
+                | (ConstValue::Unit, TyKind::Unit)
+        ) {
+            Some(value)
+        } else {
+            self.error(format!(
+                "Mismatched types: expected `{}`, found `{}` at {}",
+                ty,
+                value_desc(value),
+                expr.span.to_location()
+            ));
+            None
+        }
+    }
+
+    fn const_eval_error(&mut self, msg: &str, span: &Span) -> Interrupt {
+        self.error(format!(
+            "Evaluation of constant failed: {} at {}",
+            msg,
+            span.to_location()
+        ));
+        Interrupt::Error
+    }
+
+    fn not_const(&mut self, span: &Span) -> Interrupt {
+        self.error(format!(
+            "`{}` cannot be evaluated at compile time at {}",
+            span.to_snippet(),
+            span.to_location()
+        ));
+        Interrupt::Error
+    }
+
+    /// Check that `n` fits in `int_ty`
+    fn check_int(&mut self, n: Option<i128>, int_ty: IntTy, what: &str, span: &Span) -> EvalResult {
+        match n {
+            Some(n) if (int_ty.min_value()..=int_ty.max_value()).contains(&n) => {
+                Ok(ConstValue::Int(n))
+            }
+            _ => Err(self.const_eval_error(&format!("attempt to {} with overflow", what), span)),
+        }
+    }
+
+    /// Integer type of `expr` decided without the context
+    fn int_ty_of_expr(&mut self, expr: &hir::Expr, frame: &Frame) -> Option<IntTy> {
+        match &expr.kind {
+            ExprKind::Path(path) => match &path.res {
+                Res::Local(local_id) => frame.locals.get(local_id).and_then(|(_, ty)| *ty),
+                Res::Def(DefKind::Const, def_id) => int_ty_of(&self.const_ty(def_id)),
+                _ => None,
+            },
+            ExprKind::Call(func, _) => {
+                let callee = self.const_fn_of(func)?;
+                int_ty_of(&self.hir_ty_to_ty(&callee.ret_ty))
+            }
+            ExprKind::Cast(_, ty) => int_ty_of(&self.hir_ty_to_ty(ty)),
+            ExprKind::Unary(UnOp::Minus | UnOp::Plus, inner) => self.int_ty_of_expr(inner, frame),
+            ExprKind::Binary(
+                BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Rem,
+                l,
+                r,
+            ) => self
+                .int_ty_of_expr(l, frame)
+                .or_else(|| self.int_ty_of_expr(r, frame)),
+            ExprKind::Block(block) => self.int_ty_of_expr(block.expr.as_ref()?, frame),
+            ExprKind::If(_, then, els) => self
+                .int_ty_of_expr(then, frame)
+                .or_else(|| self.int_ty_of_expr(els.as_ref()?, frame)),
+            _ => None,
+        }
+    }
+
+    fn const_fn_of(&self, func: &hir::Expr) -> Option<&'chk hir::Func> {
+        match &func.kind {
+            ExprKind::Path(Path {
+                res: Res::Def(DefKind::Fn, def_id),
+                ..
+            }) => self.const_eval.const_fns.get(def_id).copied(),
+            _ => None,
+        }
+    }
+
+    /// Evaluate `expr`, whose type is `expected` if it is an integer of known type
+    fn eval_expr(
+        &mut self,
+        expr: &hir::Expr,
+        frame: &mut Frame,
+        expected: Option<IntTy>,
+    ) -> EvalResult {
+        match &expr.kind {
+            ExprKind::NumLit(n) => {
+                let int_ty = expected.unwrap_or(IntTy::I32);
+                if *n > int_ty.max_value() as u128 {
+                    return Err(self.const_eval_error(
+                        &format!("literal out of range for `{}`", int_ty.name()),
+                        &expr.span,
+                    ));
+                }
+                Ok(ConstValue::Int(*n as i128))
+            }
+            ExprKind::BoolLit(b) => Ok(ConstValue::Bool(*b)),
+            ExprKind::CharLit(c) => Ok(ConstValue::Char(*c)),
+            ExprKind::Unit => Ok(ConstValue::Unit),
+            ExprKind::Path(path) => match &path.res {
+                Res::Local(local_id) => match frame.locals.get(local_id) {
+                    Some((value, _)) => Ok(*value),
+                    None => {
+                        self.error(format!(
+                            "Attempt to use a non-constant value `{}` in a constant at {}",
+                            path.span.to_snippet(),
+                            path.span.to_location()
+                        ));
+                        Err(Interrupt::Error)
+                    }
+                },
+                Res::Def(DefKind::Const, def_id) => {
+                    self.eval_const(def_id, &path.span).ok_or(Interrupt::Error)
+                }
+                _ => Err(self.not_const(&expr.span)),
+            },
+            ExprKind::Unary(UnOp::Plus, inner) => self.eval_expr(inner, frame, expected),
+            ExprKind::Unary(UnOp::Minus, inner) => {
+                // `-128` is in the range of `i8` though `128` is not
+                if let ExprKind::NumLit(n) = inner.kind {
+                    let int_ty = expected.unwrap_or(IntTy::I32);
+                    if n > int_ty.min_value().unsigned_abs() {
+                        return Err(self.const_eval_error(
+                            &format!("literal out of range for `{}`", int_ty.name()),
+                            &expr.span,
+                        ));
+                    }
+                    return Ok(ConstValue::Int(-(n as i128)));
+                }
+                let int_ty = self
+                    .int_ty_of_expr(inner, frame)
+                    .or(expected)
+                    .unwrap_or(IntTy::I32);
+                let n = self.eval_int(inner, frame, int_ty)?;
+                self.check_int(n.checked_neg(), int_ty, "negate", &expr.span)
+            }
+            ExprKind::Binary(op, l, r) => self.eval_binary(expr, *op, l, r, frame, expected),
+            ExprKind::Cast(inner, ty) => {
+                let target = self.hir_ty_to_ty(ty);
+                let inner_ty = self.int_ty_of_expr(inner, frame);
+                let value = self.eval_expr(inner, frame, inner_ty)?;
+                match (value, &target.kind) {
+                    (ConstValue::Int(n), TyKind::Int(int_ty)) => {
+                        Ok(ConstValue::Int(int_ty.truncate(n)))
+                    }
+                    (ConstValue::Bool(b), TyKind::Int(_)) => Ok(ConstValue::Int(i128::from(b))),
+                    (ConstValue::Char(c), TyKind::Int(int_ty)) => {
+                        Ok(ConstValue::Int(int_ty.truncate(i128::from(u32::from(c)))))
+                    }
+                    // only `u8` can be cast to `char`
+                    (ConstValue::Int(n), TyKind::Char) => {
+                        Ok(ConstValue::Char(char::from(IntTy::U8.truncate(n) as u8)))
+                    }
+                    (ConstValue::Bool(_), TyKind::Bool) | (ConstValue::Char(_), TyKind::Char) => {
+                        Ok(value)
+                    }
+                    _ => Err(self.not_const(&expr.span)),
+                }
+            }
+            ExprKind::Block(block) => self.eval_block(block, frame, expected),
+            ExprKind::If(cond, then, els) => match self.eval_expr(cond, frame, None)? {
+                ConstValue::Bool(true) => self.eval_expr(then, frame, expected),
+                ConstValue::Bool(false) => match els {
+                    Some(els) => self.eval_expr(els, frame, expected),
+                    None => Ok(ConstValue::Unit),
+                },
+                value => Err(self.mismatched_value("bool", value, &cond.span)),
+            },
+            ExprKind::Return(inner) => {
+                let value = self.eval_expr(inner, frame, frame.ret_ty)?;
+                Err(Interrupt::Return(value))
+            }
+            ExprKind::Assign(lhs, rhs) => {
+                let ExprKind::Path(Path {
+                    res: Res::Local(local_id),
+                    ..
+                }) = &lhs.kind
+                else {
+                    return Err(self.not_const(&expr.span));
+                };
+                let Some((_, int_ty)) = frame.locals.get(local_id).copied() else {
+                    return Err(self.not_const(&expr.span));
+                };
+                let value = self.eval_expr(rhs, frame, int_ty)?;
+                frame.locals.insert(Rc::clone(local_id), (value, int_ty));
+                Ok(ConstValue::Unit)
+            }
+            ExprKind::Call(func, args) => self.eval_call(expr, func, args, frame),
+            ExprKind::Panic(fmt) => {
+                let msg = match fmt.as_lit() {
+                    Some(msg) => format!("the evaluated program panicked at '{}'", msg),
+                    None => "the evaluated program panicked".to_string(),
+                };
+                Err(self.const_eval_error(&msg, &expr.span))
+            }
+            _ => Err(self.not_const(&expr.span)),
+        }
+    }
+
+    fn mismatched_value(&mut self, expected: &str, found: ConstValue, span: &Span) -> Interrupt {
+        self.error(format!(
+            "Mismatched types: expected `{}`, found `{}` at {}",
+            expected,
+            value_desc(found),
+            span.to_location()
+        ));
+        Interrupt::Error
+    }
+
+    fn eval_int(&mut self, expr: &hir::Expr, frame: &mut Frame, int_ty: IntTy) -> EvalResult<i128> {
+        match self.eval_expr(expr, frame, Some(int_ty))? {
+            ConstValue::Int(n) => Ok(n),
+            value => Err(self.mismatched_value(int_ty.name(), value, &expr.span)),
+        }
+    }
+
+    fn eval_binary(
+        &mut self,
+        expr: &hir::Expr,
+        op: BinOp,
+        l: &hir::Expr,
+        r: &hir::Expr,
+        frame: &mut Frame,
+        expected: Option<IntTy>,
+    ) -> EvalResult {
+        // the operands have the same type
+        let operand_ty = self
+            .int_ty_of_expr(l, frame)
+            .or_else(|| self.int_ty_of_expr(r, frame));
+        if matches!(op, BinOp::Eq | BinOp::Ne | BinOp::Gt | BinOp::Lt) {
+            let l = self.eval_expr(l, frame, operand_ty)?;
+            let r = self.eval_expr(r, frame, operand_ty)?;
+            let ordering = match (l, r) {
+                (ConstValue::Int(a), ConstValue::Int(b)) => a.cmp(&b),
+                (ConstValue::Char(a), ConstValue::Char(b)) => a.cmp(&b),
+                (ConstValue::Bool(a), ConstValue::Bool(b)) => a.cmp(&b),
+                (ConstValue::Unit, ConstValue::Unit) => std::cmp::Ordering::Equal,
+                _ => return Err(self.mismatched_value(value_desc(l), r, &expr.span)),
+            };
+            let result = match op {
+                BinOp::Eq => ordering.is_eq(),
+                BinOp::Ne => ordering.is_ne(),
+                BinOp::Gt => ordering.is_gt(),
+                _ => ordering.is_lt(),
+            };
+            return Ok(ConstValue::Bool(result));
+        }
+
+        let int_ty = operand_ty.or(expected).unwrap_or(IntTy::I32);
+        let a = self.eval_int(l, frame, int_ty)?;
+        let b = self.eval_int(r, frame, int_ty)?;
+        let (result, what) = match op {
+            BinOp::Add => (a.checked_add(b), "add"),
+            BinOp::Sub => (a.checked_sub(b), "subtract"),
+            BinOp::Mul => (a.checked_mul(b), "multiply"),
+            BinOp::Div if b == 0 => {
+                return Err(self.const_eval_error("attempt to divide by zero", &expr.span));
+            }
+            BinOp::Rem if b == 0 => {
+                return Err(self.const_eval_error(
+                    "attempt to calculate the remainder with a divisor of zero",
+                    &expr.span,
+                ));
+            }
+            // `MIN / -1` overflows, and so does `MIN % -1`
+            BinOp::Div | BinOp::Rem if int_ty.is_signed() && a == int_ty.min_value() && b == -1 => {
+                let what = if matches!(op, BinOp::Div) {
+                    "divide"
+                } else {
+                    "calculate the remainder"
+                };
+                (None, what)
+            }
+            BinOp::Div => (Some(a / b), "divide"),
+            _ => (Some(a % b), "calculate the remainder"),
+        };
+        self.check_int(result, int_ty, what, &expr.span)
+    }
+
+    fn eval_block(
+        &mut self,
+        block: &hir::Block,
+        frame: &mut Frame,
+        expected: Option<IntTy>,
+    ) -> EvalResult {
+        for stmt in &block.stmts {
+            match &stmt.kind {
+                StmtKind::Let(let_stmt) => {
+                    let int_ty = match &let_stmt.ty {
+                        Some(ty) => int_ty_of(&self.hir_ty_to_ty(ty)),
+                        None => match &let_stmt.init {
+                            Some(init) => self.int_ty_of_expr(init, frame),
+                            None => None,
+                        },
+                    };
+                    let Some(init) = &let_stmt.init else {
+                        return Err(self.not_const(&stmt.span));
+                    };
+                    let value = self.eval_expr(init, frame, int_ty)?;
+                    frame
+                        .locals
+                        .insert(Rc::clone(&let_stmt.local_id), (value, int_ty));
+                }
+                StmtKind::Expr(expr) | StmtKind::Semi(expr) => {
+                    self.eval_expr(expr, frame, None)?;
+                }
+            }
+        }
+        match &block.expr {
+            Some(expr) => self.eval_expr(expr, frame, expected),
+            None => Ok(ConstValue::Unit),
+        }
+    }
+
+    fn eval_call(
+        &mut self,
+        expr: &hir::Expr,
+        func: &hir::Expr,
+        args: &[hir::Expr],
+        frame: &mut Frame,
+    ) -> EvalResult {
+        let Some(callee) = self.const_fn_of(func) else {
+            self.error(format!(
+                "Cannot call non-const fn `{}` in constants at {}",
+                func.span.to_snippet(),
+                expr.span.to_location()
+            ));
+            return Err(Interrupt::Error);
+        };
+        let Some(body) = &callee.body else {
+            return Err(self.not_const(&expr.span));
+        };
+        if callee.params.len() != args.len() {
+            self.error(format!(
+                "Expected {} arguments, but found {} at {}",
+                callee.params.len(),
+                args.len(),
+                expr.span.to_location()
+            ));
+            return Err(Interrupt::Error);
+        }
+        if self.const_eval.depth >= RECURSION_LIMIT {
+            return Err(self.const_eval_error(
+                &format!(
+                    "reached the recursion limit while calling `{}`",
+                    callee.name.symbol
+                ),
+                &expr.span,
+            ));
+        }
+
+        let mut callee_frame = Frame {
+            locals: HashMap::new(),
+            ret_ty: int_ty_of(&self.hir_ty_to_ty(&callee.ret_ty)),
+        };
+        for (param, arg) in callee.params.iter().zip(args) {
+            let int_ty = int_ty_of(&self.hir_ty_to_ty(&param.ty));
+            let value = self.eval_expr(arg, frame, int_ty)?;
+            callee_frame
+                .locals
+                .insert(Rc::clone(&param.local_id), (value, int_ty));
+        }
+        let ret_ty = callee_frame.ret_ty;
+        self.const_eval.depth += 1;
+        let result = self.eval_block(body, &mut callee_frame, ret_ty);
+        self.const_eval.depth -= 1;
+        match result {
+            Ok(value) | Err(Interrupt::Return(value)) => Ok(value),
+            Err(Interrupt::Error) => Err(Interrupt::Error),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use crate::ast::IntTy;
+use crate::middle::ty::{InferTy, Ty, TyKind};
+use crate::span::Span;
+use std::rc::Rc;
+
+/// Unification table of type variables
+/// ref: https://rustc-dev-guide.rust-lang.org/type-inference.html
+pub struct InferCtxt {
+    vars: Vec<VarInfo>,
+}
+
+struct VarInfo {
+    /// Type the variable is bound to
+    value: Option<Rc<Ty>>,
+    /// Where the variable was created. Used for "type annotations needed" errors
+    origin: Span,
+    kind: InferTy,
+}
+
+impl InferCtxt {
+    pub fn new() -> Self {
+        InferCtxt { vars: vec![] }
+    }
+
+    fn new_var(&mut self, origin: &Span, make: fn(u32) -> InferTy) -> Rc<Ty> {
+        let kind = make(self.vars.len() as u32);
+        self.vars.push(VarInfo {
+            value: None,
+            origin: origin.clone(),
+            kind,
+        });
+        Rc::new(Ty::new(TyKind::Infer(kind)))
+    }
+
+    pub fn new_ty_var(&mut self, origin: &Span) -> Rc<Ty> {
+        self.new_var(origin, InferTy::Ty)
+    }
+
+    pub fn new_int_var(&mut self, origin: &Span) -> Rc<Ty> {
+        self.new_var(origin, InferTy::Int)
+    }
+
+    pub fn new_float_var(&mut self, origin: &Span) -> Rc<Ty> {
+        self.new_var(origin, InferTy::Float)
+    }
+
+    fn get_var(&self, infer: InferTy) -> &VarInfo {
+        let (InferTy::Ty(vid) | InferTy::Int(vid) | InferTy::Float(vid)) = infer;
+        &self.vars[vid as usize]
+    }
+
+    pub fn get_origin(&self, infer: InferTy) -> &Span {
+        &self.get_var(infer).origin
+    }
+
+    fn probe(&self, infer: InferTy) -> Option<Rc<Ty>> {
+        self.get_var(infer).value.as_ref().map(Rc::clone)
+    }
+
+    fn bind(&mut self, infer: InferTy, ty: Rc<Ty>) {
+        let (InferTy::Ty(vid) | InferTy::Int(vid) | InferTy::Float(vid)) = infer;
+        self.vars[vid as usize].value = Some(ty);
+    }
+
+    /// Follow bound variables until reaching a type which is not a bound variable
+    pub fn shallow_resolve(&self, ty: &Rc<Ty>) -> Rc<Ty> {
+        let mut ty = Rc::clone(ty);
+        while let TyKind::Infer(infer) = &ty.kind
+            && let Some(bound) = self.probe(*infer)
+        {
+            ty = bound;
+        }
+        ty
+    }
+
+    /// Substitute all bound variables in `ty`. Unbound variables remain
+    pub fn resolve_fully(&self, ty: &Rc<Ty>) -> Rc<Ty> {
+        let ty = self.shallow_resolve(ty);
+        let kind = match &ty.kind {
+            TyKind::Array(elem, n) => TyKind::Array(self.resolve_fully(elem), *n),
+            TyKind::Ref(inner, mutbl) => TyKind::Ref(self.resolve_fully(inner), *mutbl),
+            TyKind::RawPtr(inner, mutbl) => TyKind::RawPtr(self.resolve_fully(inner), *mutbl),
+            TyKind::Box(inner) => TyKind::Box(self.resolve_fully(inner)),
+            TyKind::Fn(params, ret) => TyKind::Fn(
+                Rc::new(params.iter().map(|p| self.resolve_fully(p)).collect()),
+                self.resolve_fully(ret),
+            ),
+            _ => return ty,
+        };
+        Rc::new(Ty::new(kind))
+    }
+
+    /// Bind integer and float variables which are still unbound to `i32` and `f64`
+    pub fn fallback_numeric_vars(&mut self) {
+        for var in &mut self.vars {
+            if var.value.is_some() {
+                continue;
+            }
+            match var.kind {
+                InferTy::Int(_) => var.value = Some(Rc::new(Ty::int(IntTy::I32))),
+                InferTy::Float(_) => var.value = Some(Rc::new(Ty::new(TyKind::F64))),
+                InferTy::Ty(_) => (),
+            }
+        }
+    }
+
+    /// Make `a` and `b` the same type
+    pub fn unify(&mut self, a: &Rc<Ty>, b: &Rc<Ty>) -> Result<(), ()> {
+        let a = self.shallow_resolve(a);
+        let b = self.shallow_resolve(b);
+        match (&a.kind, &b.kind) {
+            // errors are already reported
+            (TyKind::Error, _) | (_, TyKind::Error) => Ok(()),
+            (TyKind::Infer(x), TyKind::Infer(y)) if x == y => Ok(()),
+            (TyKind::Infer(InferTy::Ty(_)), _) => self.bind_checked(&a, b),
+            (_, TyKind::Infer(InferTy::Ty(_))) => self.bind_checked(&b, a),
+            (TyKind::Infer(InferTy::Int(_)), _) if b.is_integral() => self.bind_checked(&a, b),
+            (_, TyKind::Infer(InferTy::Int(_))) if a.is_integral() => self.bind_checked(&b, a),
+            (TyKind::Infer(InferTy::Float(_)), _) if b.is_float() => self.bind_checked(&a, b),
+            (_, TyKind::Infer(InferTy::Float(_))) if a.is_float() => self.bind_checked(&b, a),
+            (TyKind::Array(elem_a, n_a), TyKind::Array(elem_b, n_b)) if n_a == n_b => {
+                self.unify(elem_a, elem_b)
+            }
+            (TyKind::Ref(inner_a, mutbl_a), TyKind::Ref(inner_b, mutbl_b))
+            | (TyKind::RawPtr(inner_a, mutbl_a), TyKind::RawPtr(inner_b, mutbl_b))
+                if mutbl_a == mutbl_b =>
+            {
+                self.unify(inner_a, inner_b)
+            }
+            (TyKind::Box(inner_a), TyKind::Box(inner_b)) => self.unify(inner_a, inner_b),
+            (TyKind::Fn(params_a, ret_a), TyKind::Fn(params_b, ret_b))
+                if params_a.len() == params_b.len() =>
+            {
+                for (param_a, param_b) in params_a.iter().zip(params_b.iter()) {
+                    self.unify(param_a, param_b)?;
+                }
+                self.unify(ret_a, ret_b)
+            }
+            (TyKind::Adt(name_a), TyKind::Adt(name_b)) if name_a == name_b => Ok(()),
+            (
+                TyKind::Unit
+                | TyKind::Bool
+                | TyKind::Int(_)
+                | TyKind::F32
+                | TyKind::F64
+                | TyKind::Str
+                | TyKind::Char
+                | TyKind::Never,
+                _,
+            ) if a.kind == b.kind => Ok(()),
+            _ => Err(()),
+        }
+    }
+
+    /// Bind the variable `var` to `ty` unless `ty` contains `var`
+    fn bind_checked(&mut self, var: &Rc<Ty>, ty: Rc<Ty>) -> Result<(), ()> {
+        let TyKind::Infer(infer) = var.kind else {
+            panic!("ICE: {:?} is not a type variable", var);
+        };
+        if self.occurs(infer, &ty) {
+            return Err(());
+        }
+        self.bind(infer, ty);
+        Ok(())
+    }
+
+    fn occurs(&self, infer: InferTy, ty: &Rc<Ty>) -> bool {
+        let ty = self.shallow_resolve(ty);
+        match &ty.kind {
+            TyKind::Infer(other) => *other == infer,
+            TyKind::Array(inner, _)
+            | TyKind::Ref(inner, _)
+            | TyKind::RawPtr(inner, _)
+            | TyKind::Box(inner) => self.occurs(infer, inner),
+            TyKind::Fn(params, ret) => {
+                params.iter().any(|p| self.occurs(infer, p)) || self.occurs(infer, ret)
+            }
+            _ => false,
+        }
+    }
+}
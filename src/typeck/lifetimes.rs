@@ -0,0 +1,293 @@
+use crate::hir::{self, Crate, Generics, Lifetime, Path, Region, Ty, TyKind};
+use crate::middle::ty::RegionSig;
+use crate::middle::Ctxt;
+use crate::resolve::CanonicalPath;
+use crate::span::Span;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Check that all the lifetimes used are declared, and record the lifetimes in the
+/// signatures of functions with the elided ones filled in
+pub fn check_lifetimes(ctx: &mut Ctxt, krate: &Crate) -> Vec<String> {
+    let mut collector = StructGenericsCollector {
+        num_lifetimes: HashMap::new(),
+    };
+    hir::visitor::go(&mut collector, krate);
+    let num_lifetimes = collector.num_lifetimes;
+
+    let mut checker = LifetimeChecker {
+        ctx,
+        num_lifetimes,
+        scopes: vec![],
+        allow_elided: true,
+        self_regions: vec![],
+        errors: vec![],
+    };
+    hir::visitor::go(&mut checker, krate);
+    checker.errors
+}
+
+struct StructGenericsCollector {
+    num_lifetimes: HashMap<Rc<CanonicalPath>, usize>,
+}
+
+impl<'chk> hir::visitor::Visitor<'chk> for StructGenericsCollector {
+    fn visit_struct_item(&mut self, strct: &'chk hir::StructItem) {
+        self.num_lifetimes.insert(
+            Rc::clone(&strct.def_id.cpath),
+            strct.generics.lifetimes.len(),
+        );
+    }
+}
+
+struct LifetimeChecker<'ctx, 'chk> {
+    ctx: &'chk mut Ctxt<'ctx>,
+    /// Number of lifetime parameters of each struct
+    num_lifetimes: HashMap<Rc<CanonicalPath>, usize>,
+    /// Lifetime parameters of the enclosing items
+    scopes: Vec<&'chk [Lifetime]>,
+    /// Lifetimes can be elided everywhere but in struct fields and type aliases
+    allow_elided: bool,
+    /// Lifetimes in the type which `Self` stands for in the current impl
+    self_regions: Vec<Region>,
+    errors: Vec<String>,
+}
+
+impl<'chk> LifetimeChecker<'_, 'chk> {
+    fn push_scope(&mut self, generics: &'chk Generics) {
+        for (i, lifetime) in generics.lifetimes.iter().enumerate() {
+            if lifetime.name == "static" || lifetime.name == "_" {
+                self.errors.push(format!(
+                    "Invalid lifetime parameter name: `'{}` at {}",
+                    lifetime.name,
+                    lifetime.span.to_location()
+                ));
+            } else if generics.lifetimes[..i]
+                .iter()
+                .any(|declared| declared.name == lifetime.name)
+            {
+                self.errors.push(format!(
+                    "Lifetime name `'{}` declared twice in the same scope at {}",
+                    lifetime.name,
+                    lifetime.span.to_location()
+                ));
+            }
+        }
+        self.scopes.push(&generics.lifetimes);
+    }
+
+    fn check_declared(&mut self, lifetime: &Lifetime) {
+        let declared = lifetime.name == "static"
+            || self
+                .scopes
+                .iter()
+                .any(|scope| scope.iter().any(|param| param.name == lifetime.name));
+        if !declared {
+            self.errors.push(format!(
+                "Use of undeclared lifetime name `'{}` at {}",
+                lifetime.name,
+                lifetime.span.to_location()
+            ));
+        }
+    }
+
+    fn check_elided(&mut self, span: &Span) {
+        if !self.allow_elided {
+            self.errors.push(format!(
+                "Missing lifetime specifier at {}",
+                span.to_location()
+            ));
+        }
+    }
+
+    /// Number of lifetime parameters of the type `path` refers to
+    fn num_lifetimes(&self, path: &Path) -> usize {
+        path.res
+            .def_id()
+            .and_then(|def_id| self.num_lifetimes.get(&def_id.cpath).copied())
+            .unwrap_or(0)
+    }
+
+    /// Lifetimes appearing in `ty` from left to right, where elided ones are `None`
+    fn collect_regions(&mut self, ty: &Ty, regions: &mut Vec<Option<Region>>) {
+        match &ty.kind {
+            TyKind::Ref(lifetime, _, referent) => {
+                regions.push(
+                    lifetime
+                        .as_ref()
+                        .filter(|lifetime| lifetime.name != "_")
+                        .map(|lifetime| lifetime.name.clone()),
+                );
+                self.collect_regions(referent, regions);
+            }
+            TyKind::Path(path, lifetimes) => {
+                if lifetimes.is_empty() {
+                    let n = self.num_lifetimes(path);
+                    regions.extend(std::iter::repeat_n(None, n));
+                } else {
+                    regions.extend(
+                        lifetimes
+                            .iter()
+                            .map(|lifetime| Some(lifetime.name.clone()).filter(|name| name != "_")),
+                    );
+                }
+            }
+            TyKind::SelfTy => regions.extend(self.self_regions.iter().cloned().map(Some)),
+            TyKind::Array(inner, _) | TyKind::RawPtr(_, inner) | TyKind::Box(inner) => {
+                self.collect_regions(inner, regions)
+            }
+            TyKind::Unit
+            | TyKind::Bool
+            | TyKind::Int(_)
+            | TyKind::F32
+            | TyKind::F64
+            | TyKind::Str
+            | TyKind::Char
+            | TyKind::Never => (),
+        }
+    }
+
+    /// Each elided lifetime in parameters is a fresh lifetime. An elided lifetime in the
+    /// return type is the lifetime of `&self`, or the only lifetime in parameters
+    fn region_sig(&mut self, func: &hir::Func) -> RegionSig {
+        let mut num_fresh = self.self_regions.len();
+        let mut inputs = vec![];
+        for param in &func.params {
+            let mut regions = vec![];
+            self.collect_regions(&param.ty, &mut regions);
+            inputs.push(fill_elided(regions, &mut num_fresh));
+        }
+
+        let mut output = vec![];
+        self.collect_regions(&func.ret_ty, &mut output);
+        if output.iter().any(Option::is_none) {
+            let mut input_regions: Vec<&Region> = inputs.iter().flatten().collect();
+            input_regions.sort();
+            input_regions.dedup();
+            let elided = match func.params.first() {
+                Some(hir::Param {
+                    ident,
+                    ty:
+                        Ty {
+                            kind: TyKind::Ref(..),
+                            ..
+                        },
+                    ..
+                }) if *ident.symbol == "self" => Some(inputs[0][0].clone()),
+                _ if input_regions.len() == 1 => Some(input_regions[0].clone()),
+                _ => None,
+            };
+            match elided {
+                Some(elided) => {
+                    for region in &mut output {
+                        region.get_or_insert_with(|| elided.clone());
+                    }
+                }
+                None => {
+                    let reason = if input_regions.is_empty() {
+                        "there is no value for it to be borrowed from"
+                    } else {
+                        "the signature does not say which one of the parameters it is borrowed from"
+                    };
+                    self.errors.push(format!(
+                        "Missing lifetime specifier at {}\n    this function's return type contains a borrowed value, but {}",
+                        func.ret_ty.span.to_location(),
+                        reason
+                    ));
+                }
+            }
+        }
+
+        RegionSig {
+            inputs,
+            output: output.into_iter().flatten().collect(),
+        }
+    }
+}
+
+impl<'chk> hir::visitor::Visitor<'chk> for LifetimeChecker<'_, 'chk> {
+    fn visit_struct_item(&mut self, strct: &'chk hir::StructItem) {
+        self.push_scope(&strct.generics);
+        self.allow_elided = false;
+    }
+
+    fn visit_struct_item_post(&mut self, _strct: &'chk hir::StructItem) {
+        self.scopes.pop();
+        self.allow_elided = true;
+    }
+
+    // type aliases have no lifetime parameters
+    fn visit_ty_alias(&mut self, _alias: &'chk hir::TyAlias) {
+        self.allow_elided = false;
+    }
+
+    fn visit_ty_alias_post(&mut self, _alias: &'chk hir::TyAlias) {
+        self.allow_elided = true;
+    }
+
+    fn visit_impl(&mut self, imp: &'chk hir::Impl) {
+        self.push_scope(&imp.generics);
+        let mut regions = vec![];
+        self.collect_regions(&imp.self_ty, &mut regions);
+        self.self_regions = fill_elided(regions, &mut 0);
+    }
+
+    fn visit_impl_post(&mut self, _imp: &'chk hir::Impl) {
+        self.scopes.pop();
+        self.self_regions.clear();
+    }
+
+    fn visit_func(&mut self, func: &'chk hir::Func) {
+        self.push_scope(&func.generics);
+        let sig = self.region_sig(func);
+        self.ctx.set_region_sig(Rc::clone(&func.def_id), sig);
+    }
+
+    fn visit_func_post(&mut self, _func: &'chk hir::Func) {
+        self.scopes.pop();
+    }
+
+    fn visit_type(&mut self, ty: &'chk Ty) {
+        match &ty.kind {
+            TyKind::Ref(Some(lifetime), _, _) if lifetime.name != "_" => {
+                self.check_declared(lifetime)
+            }
+            TyKind::Ref(..) => self.check_elided(&ty.span),
+            TyKind::Path(path, lifetimes) => {
+                for lifetime in lifetimes {
+                    if lifetime.name == "_" {
+                        self.check_elided(&lifetime.span);
+                    } else {
+                        self.check_declared(lifetime);
+                    }
+                }
+                let n = self.num_lifetimes(path);
+                if lifetimes.is_empty() && n > 0 {
+                    self.check_elided(&ty.span);
+                } else if !lifetimes.is_empty() && lifetimes.len() != n {
+                    self.errors.push(format!(
+                        "Type `{}` takes {} lifetime arguments but {} were supplied at {}",
+                        path.span.to_snippet(),
+                        n,
+                        lifetimes.len(),
+                        ty.span.to_location()
+                    ));
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Give fresh names to elided lifetimes
+fn fill_elided(regions: Vec<Option<Region>>, num_fresh: &mut usize) -> Vec<Region> {
+    regions
+        .into_iter()
+        .map(|region| {
+            region.unwrap_or_else(|| {
+                *num_fresh += 1;
+                num_fresh.to_string()
+            })
+        })
+        .collect()
+}
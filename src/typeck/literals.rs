@@ -0,0 +1,61 @@
+use crate::hir::{self, Crate, Expr, ExprKind, HirId, UnOp};
+use crate::lint::OVERFLOWING_LITERALS;
+use crate::middle::ty::TyKind;
+use crate::middle::Ctxt;
+use crate::span::Span;
+use std::collections::HashMap;
+
+/// Lint integer literals out of the range of their inferred types
+pub fn check_literals(ctx: &mut Ctxt, krate: &Crate) {
+    let mut checker = LiteralChecker {
+        ctx,
+        negated: HashMap::new(),
+    };
+    hir::visitor::go(&mut checker, krate);
+}
+
+struct LiteralChecker<'ctx, 'chk> {
+    ctx: &'chk mut Ctxt<'ctx>,
+    /// Literals negated by unary minus to the spans of the negations.
+    /// `-128` is in the range of `i8` though `128` is not
+    negated: HashMap<HirId, Span>,
+}
+
+impl<'chk> hir::visitor::Visitor<'chk> for LiteralChecker<'_, '_> {
+    fn visit_expr(&mut self, expr: &'chk Expr) {
+        match &expr.kind {
+            ExprKind::Unary(UnOp::Minus, inner) if matches!(inner.kind, ExprKind::NumLit(_)) => {
+                self.negated.insert(inner.id, expr.span.clone());
+            }
+            ExprKind::NumLit(n) => {
+                let TyKind::Int(int_ty) = self.ctx.get_type(expr.id).kind else {
+                    return;
+                };
+                let (lit, span, fits) = match self.negated.get(&expr.id) {
+                    Some(span) => (
+                        format!("-{n}"),
+                        span.clone(),
+                        *n <= int_ty.min_value().unsigned_abs(),
+                    ),
+                    None => (
+                        n.to_string(),
+                        expr.span.clone(),
+                        *n <= int_ty.max_value() as u128,
+                    ),
+                };
+                if fits {
+                    return;
+                }
+                let msg = format!(
+                    "Literal out of range for `{ty}` at {}\n    the literal `{lit}` does not fit into the type `{ty}` whose range is `{}..={}`",
+                    span.to_location(),
+                    int_ty.min_value(),
+                    int_ty.max_value(),
+                    ty = int_ty.name(),
+                );
+                self.ctx.buffer_lint(&OVERFLOWING_LITERALS, &span, msg);
+            }
+            _ => (),
+        }
+    }
+}
@@ -1,24 +1,109 @@
-use crate::ast::{self, BinOp, Crate, ExprKind, LetStmt, Stmt, StmtKind};
-use crate::middle::ty::{self, AdtDef, Ty, TyKind};
+mod const_eval;
+mod infer;
+mod lifetimes;
+mod literals;
+mod moves;
+mod unreachable;
+
+use self::const_eval::ConstEvalState;
+use self::infer::InferCtxt;
+use crate::hir::{
+    self, BinOp, Crate, DefKind, ExprKind, FormatPiece, HirId, IntTy, LetStmt, Res, Stmt, StmtKind,
+};
+use crate::middle::ty::{self, AdtDef, CtorKind, Ty, TyKind};
 use crate::middle::Ctxt;
+use crate::resolve::CanonicalPath;
+use crate::span::Span;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
 pub fn typeck<'ctx, 'chk>(
     ctx: &'chk mut Ctxt<'ctx>,
     krate: &'chk Crate,
 ) -> Result<(), Vec<String>> {
+    let mut collector = ItemCollector::default();
+    hir::visitor::go(&mut collector, krate);
+
     let mut checker = TypeChecker::new(ctx);
-    ast::visitor::go(&mut checker, krate);
-    if checker.errors.is_empty() {
+    for alias in collector.aliases {
+        checker
+            .ty_aliases
+            .insert(Rc::clone(&alias.def_id.cpath), &alias.ty);
+    }
+    // `const fn`s can be called from constants before their definitions
+    for func in collector.const_fns {
+        checker.declare_func(func);
+        checker
+            .const_eval
+            .const_fns
+            .insert(Rc::clone(&func.def_id), func);
+    }
+    for const_item in &collector.consts {
+        checker
+            .const_eval
+            .consts
+            .insert(Rc::clone(&const_item.def_id), const_item);
+    }
+    // constants and statics can be used before their definitions
+    for const_item in collector.consts {
+        checker.const_ty(&const_item.def_id);
+    }
+    for static_item in collector.statics {
+        let ty = checker.hir_ty_to_ty(&static_item.ty);
+        checker
+            .ctx
+            .set_name_type(Rc::clone(&static_item.def_id), ty);
+    }
+    hir::visitor::go(&mut checker, krate);
+    checker.write_back_types();
+    if !checker.errors.is_empty() {
+        return Err(checker.errors);
+    }
+    unreachable::check_unreachable(ctx, krate);
+    literals::check_literals(ctx, krate);
+
+    // lifetimes and moves are checked after all the types are inferred
+    let mut errors = lifetimes::check_lifetimes(ctx, krate);
+    errors.append(&mut moves::check_moves(ctx, krate));
+    if errors.is_empty() {
         Ok(())
     } else {
-        Err(checker.errors)
+        Err(errors)
+    }
+}
+
+/// Immutable statics cannot be assigned to or borrowed mutably
+fn immutable_static(place: &hir::Expr) -> Option<&hir::Path> {
+    match &place.kind {
+        ExprKind::Path(
+            path @ hir::Path {
+                res: Res::Def(DefKind::Static(hir::Mutability::Not), _),
+                ..
+            },
+        ) => Some(path),
+        _ => None,
     }
 }
 
 struct TypeChecker<'ctx, 'chk> {
     ctx: &'chk mut Ctxt<'ctx>,
-    current_return_type: Option<Ty>,
+    infcx: InferCtxt,
+    /// Type aliases to the types they stand for
+    ty_aliases: HashMap<Rc<CanonicalPath>, &'chk hir::Ty>,
+    expanded_ty_aliases: HashMap<Rc<CanonicalPath>, Rc<Ty>>,
+    /// Stack of type aliases being expanded to detect cycles
+    expanding_ty_aliases: Vec<Rc<CanonicalPath>>,
+    /// Structs checked to have finite size after all of them are collected
+    structs: Vec<&'chk hir::StructItem>,
+    /// Type which `Self` stands for in the current impl
+    current_self_ty: Option<Rc<Ty>>,
+    current_return_type: Option<Rc<Ty>>,
+    const_eval: ConstEvalState<'chk>,
+    /// Negated `{integer}`s with the spans of the negations, which are checked not to be
+    /// unsigned after inference
+    negated_int_vars: Vec<(Rc<Ty>, Span)>,
+    /// Callees of `f(args)`, where functions and tuple struct constructors can be named
+    callees: HashSet<HirId>,
     errors: Vec<String>,
 }
 
@@ -26,7 +111,16 @@ impl<'ctx, 'chk> TypeChecker<'ctx, 'chk> {
     fn new(ctx: &'chk mut Ctxt<'ctx>) -> Self {
         TypeChecker {
             ctx,
+            infcx: InferCtxt::new(),
+            ty_aliases: HashMap::new(),
+            expanded_ty_aliases: HashMap::new(),
+            expanding_ty_aliases: vec![],
+            structs: vec![],
+            current_self_ty: None,
             current_return_type: None,
+            const_eval: ConstEvalState::default(),
+            negated_int_vars: vec![],
+            callees: HashSet::new(),
             errors: vec![],
         }
     }
@@ -35,11 +129,11 @@ impl<'ctx, 'chk> TypeChecker<'ctx, 'chk> {
         self.errors.push(e);
     }
 
-    fn peek_return_type(&self) -> &Ty {
-        self.current_return_type.as_ref().unwrap()
+    fn peek_return_type(&self) -> Rc<Ty> {
+        Rc::clone(self.current_return_type.as_ref().unwrap())
     }
 
-    fn push_return_type(&mut self, ty: Ty) {
+    fn push_return_type(&mut self, ty: Rc<Ty>) {
         self.current_return_type = Some(ty);
     }
 
@@ -47,328 +141,842 @@ impl<'ctx, 'chk> TypeChecker<'ctx, 'chk> {
         self.current_return_type = None;
     }
 
-    fn get_block_type(&self, block: &ast::Block) -> Rc<Ty> {
-        if let Some(stmt) = block.stmts.last() {
-            let last_stmt_ty = &self.ctx.get_type(stmt.id);
-            Rc::clone(last_stmt_ty)
-        } else {
-            // no statement. Unit type
-            Rc::new(Ty::unit())
+    /// The type of the tail expression if any. Otherwise a block has the never type if
+    /// it diverges, and the unit type if not
+    fn get_block_type(&self, block: &hir::Block) -> Rc<Ty> {
+        match &block.expr {
+            Some(expr) => self.ctx.get_type(expr.id),
+            None if self.ctx.diverges(block.id) => Rc::new(Ty::never()),
+            None => Rc::new(Ty::unit()),
         }
     }
-    fn ast_ty_to_ty(&mut self, ast_ty: &ast::Ty) -> self::Ty {
-        let kind = match &ast_ty.kind {
-            ast::TyKind::I32 => ty::TyKind::I32,
-            ast::TyKind::Never => ty::TyKind::Never,
-            ast::TyKind::Bool => ty::TyKind::Bool,
-            ast::TyKind::Unit => ty::TyKind::Unit,
-            ast::TyKind::Str => ty::TyKind::Str,
-            ast::TyKind::Ref(_region, referent) => {
-                ty::TyKind::Ref(Rc::new(self.ast_ty_to_ty(&referent)))
-            }
-            ast::TyKind::Array(elem_ty, n) => {
-                ty::TyKind::Array(Rc::new(self.ast_ty_to_ty(elem_ty)), *n)
-            }
-            ast::TyKind::Adt(path) => {
-                if let Some(binding) = self.ctx.resolve_path(path) {
-                    ty::TyKind::Adt(Rc::clone(&binding.cpath))
-                } else {
-                    self.error(format!("{:?}", path));
-                    ty::TyKind::Error
-                }
+
+    /// Whether control never continues after `expr`, that is, it has the never type or
+    /// one of its operands which are always evaluated diverges
+    fn expr_diverges(&self, expr: &hir::Expr, ty: &Rc<Ty>) -> bool {
+        if self.infcx.shallow_resolve(ty).is_never() {
+            return true;
+        }
+        let diverges = |expr: &hir::Expr| self.ctx.diverges(expr.id);
+        match &expr.kind {
+            ExprKind::If(cond, then, els) => {
+                diverges(cond) || (diverges(then) && els.as_ref().is_some_and(|els| diverges(els)))
             }
-            ast::TyKind::ConstPtr(referent) => {
-                ty::TyKind::ConstPtr(Rc::new(self.ast_ty_to_ty(referent)))
+            ExprKind::Block(block) => self.ctx.diverges(block.id),
+            ExprKind::Binary(_, l, r) | ExprKind::Assign(l, r) | ExprKind::Index(l, r) => {
+                diverges(l) || diverges(r)
             }
-        };
-        Ty::new(kind)
+            ExprKind::Unary(_, inner)
+            | ExprKind::AddrOf(_, inner)
+            | ExprKind::Return(inner)
+            | ExprKind::Field(inner, _)
+            | ExprKind::Cast(inner, _)
+            | ExprKind::Box(inner) => diverges(inner),
+            ExprKind::Call(func, args) => diverges(func) || args.iter().any(diverges),
+            ExprKind::MethodCall(recv, _, args) => diverges(recv) || args.iter().any(diverges),
+            ExprKind::Struct(_, fields, base) => {
+                fields.iter().any(|(_, field)| diverges(field))
+                    || base.as_ref().is_some_and(|base| diverges(base))
+            }
+            ExprKind::Array(elems) => elems.iter().any(diverges),
+            ExprKind::Print(_, fmt) | ExprKind::Panic(fmt) => fmt.args.iter().any(diverges),
+            ExprKind::NumLit(_)
+            | ExprKind::FloatLit(..)
+            | ExprKind::BoolLit(_)
+            | ExprKind::StrLit(_)
+            | ExprKind::CharLit(_)
+            | ExprKind::Unit
+            | ExprKind::Path(_)
+            | ExprKind::NullPtr(_) => false,
+        }
     }
-}
 
-impl<'chk> ast::visitor::Visitor<'chk> for TypeChecker<'_, 'chk> {
-    fn visit_crate(&mut self, _krate: &'chk Crate) {}
+    /// Type of the node with type variables bound so far substituted
+    fn get_resolved_type(&self, node_id: hir::HirId) -> Rc<Ty> {
+        self.infcx.shallow_resolve(&self.ctx.get_type(node_id))
+    }
+
+    /// Check that `found` can be used where `expected` is expected.
+    /// The never type is coerced to any type
+    fn coerce(&mut self, found: &Rc<Ty>, expected: &Rc<Ty>, span: &Span) {
+        if self.infcx.shallow_resolve(found).is_never() {
+            return;
+        }
+        if self.infcx.unify(found, expected).is_err() {
+            self.mismatched_types(expected, found, span);
+        }
+    }
 
-    fn visit_crate_post(&mut self, _krate: &'chk Crate) {}
+    fn mismatched_types(&mut self, expected: &Rc<Ty>, found: &Rc<Ty>, span: &Span) {
+        let expected = self.infcx.resolve_fully(expected);
+        let found = self.infcx.resolve_fully(found);
+        self.error(format!(
+            "Mismatched types: expected `{}`, found `{}` at {}",
+            expected,
+            found,
+            span.to_location()
+        ));
+    }
 
-    // TODO: allow func call before finding declaration of the func
-    // TODO: what if typechecker does not find a body of non-external func?
-    // TODO: external func must not have its body (correct?)
-    fn visit_func(&mut self, func: &'chk ast::Func) {
-        // TODO: typecheck main func
+    /// Check that the arguments of a formatting macro can be formatted by their placeholders
+    fn check_format_args(&mut self, fmt: &hir::FormatArgs) {
+        for piece in &fmt.pieces {
+            let FormatPiece::Placeholder(spec) = piece else {
+                continue;
+            };
+            let arg = &fmt.args[spec.arg];
+            let arg_ty = self.infcx.resolve_fully(&self.ctx.get_type(arg.id));
+            let mut pointee = &arg_ty;
+            while let TyKind::Ref(inner, _) | TyKind::Box(inner) = &pointee.kind {
+                pointee = inner;
+            }
+            if pointee.is_ty_var() {
+                self.annotations_needed(&arg.span);
+            } else if !is_formattable(&arg_ty) {
+                self.error(format!(
+                    "`{}` of type `{}` cannot be formatted with `{}` at {}",
+                    arg.span.to_snippet(),
+                    arg_ty,
+                    if spec.debug { "{:?}" } else { "{}" },
+                    arg.span.to_location()
+                ));
+            }
+        }
+    }
+
+    /// Report that the type of the expression must be known at this point
+    fn annotations_needed(&mut self, span: &Span) {
+        self.error(format!(
+            "Type annotations needed for `{}` at {}",
+            span.to_snippet(),
+            span.to_location()
+        ));
+    }
+
+    /// Replace type variables in the types recorded in `Ctxt` with the inferred types
+    fn write_back_types(&mut self) {
+        self.infcx.fallback_numeric_vars();
+
+        for (ty, span) in std::mem::take(&mut self.negated_int_vars) {
+            let ty = self.infcx.resolve_fully(&ty);
+            if matches!(ty.kind, TyKind::Int(int_ty) if !int_ty.is_signed()) {
+                self.error(format!(
+                    "Cannot apply unary operator to type `{}` at {}",
+                    ty,
+                    span.to_location()
+                ));
+            }
+        }
+
+        let mut unresolved = vec![];
+        let infcx = &self.infcx;
+        self.ctx.map_types(|ty| {
+            let ty = infcx.resolve_fully(ty);
+            if let Some(var) = find_ty_var(&ty) {
+                unresolved.push(var);
+            }
+            ty
+        });
+
+        // report only the earliest one since the rest is likely to be caused by it
+        if let Some(var) = unresolved.iter().min() {
+            let span = self.infcx.get_origin(*var).clone();
+            self.annotations_needed(&span);
+        }
+    }
+
+    fn hir_ty_to_ty(&mut self, hir_ty: &hir::Ty) -> Rc<Ty> {
+        let kind = match &hir_ty.kind {
+            hir::TyKind::Int(int_ty) => ty::TyKind::Int(*int_ty),
+            hir::TyKind::F32 => ty::TyKind::F32,
+            hir::TyKind::F64 => ty::TyKind::F64,
+            hir::TyKind::Never => ty::TyKind::Never,
+            hir::TyKind::Bool => ty::TyKind::Bool,
+            hir::TyKind::Unit => ty::TyKind::Unit,
+            hir::TyKind::Str => ty::TyKind::Str,
+            hir::TyKind::Char => ty::TyKind::Char,
+            hir::TyKind::Ref(_region, mutbl, referent) => {
+                ty::TyKind::Ref(self.hir_ty_to_ty(referent), *mutbl)
+            }
+            hir::TyKind::Array(elem_ty, len) => {
+                let elem_ty = self.hir_ty_to_ty(elem_ty);
+                match self.eval_array_len(len) {
+                    Some(n) => ty::TyKind::Array(elem_ty, n),
+                    None => return Rc::new(Ty::error()),
+                }
+            }
+            hir::TyKind::Path(path, _lifetimes) => match &path.res {
+                Res::Def(DefKind::TyAlias, def_id) => {
+                    return self.expand_ty_alias(&def_id.cpath, &path.span);
+                }
+                Res::Def(DefKind::Struct, def_id) => ty::TyKind::Adt(Rc::clone(&def_id.cpath)),
+                res => panic!("ICE: type path resolved to {:?}", res),
+            },
+            hir::TyKind::RawPtr(mutbl, referent) => {
+                ty::TyKind::RawPtr(self.hir_ty_to_ty(referent), *mutbl)
+            }
+            hir::TyKind::Box(inner) => ty::TyKind::Box(self.hir_ty_to_ty(inner)),
+            // `Self` outside of impls is rejected by lowering
+            hir::TyKind::SelfTy => return Rc::clone(self.current_self_ty.as_ref().unwrap()),
+        };
+        Rc::new(Ty::new(kind))
+    }
+
+    fn declare_func(&mut self, func: &hir::Func) {
         let param_tys = func
             .params
             .iter()
-            .map(|(_ident, ty)| Rc::new(self.ast_ty_to_ty(ty)))
+            .map(|param| self.hir_ty_to_ty(&param.ty))
             .collect();
         let func_ty = Rc::new(Ty::new(TyKind::Fn(
             Rc::new(param_tys),
-            Rc::new(self.ast_ty_to_ty(&func.ret_ty)),
+            self.hir_ty_to_ty(&func.ret_ty),
         )));
+        self.ctx.set_name_type(Rc::clone(&func.def_id), func_ty);
+    }
 
-        let binding = self.ctx.get_binding(&func.name).unwrap();
-        self.ctx.set_name_type(Rc::clone(&binding), func_ty);
+    /// Whether the value of `ty` contains a value of ADT `target` inline
+    fn contains_adt(
+        &self,
+        ty: &Ty,
+        target: &Rc<CanonicalPath>,
+        visited: &mut Vec<Rc<CanonicalPath>>,
+    ) -> bool {
+        match &ty.kind {
+            TyKind::Array(elem, _) => self.contains_adt(elem, target, visited),
+            TyKind::Adt(name) if name == target => true,
+            TyKind::Adt(name) if !visited.contains(name) => {
+                visited.push(Rc::clone(name));
+                let Some(adt) = self.ctx.lookup_adt_def(name) else {
+                    return false;
+                };
+                adt.fields
+                    .iter()
+                    .any(|(_, fd_ty)| self.contains_adt(fd_ty, target, visited))
+            }
+            _ => false,
+        }
+    }
+
+    /// Type which the type alias `cpath` stands for
+    fn expand_ty_alias(&mut self, cpath: &Rc<CanonicalPath>, span: &Span) -> Rc<Ty> {
+        if let Some(ty) = self.expanded_ty_aliases.get(cpath) {
+            return Rc::clone(ty);
+        }
+        if self.expanding_ty_aliases.contains(cpath) {
+            self.error(format!(
+                "Cycle detected when expanding type alias `{:?}` at {}",
+                cpath,
+                span.to_location()
+            ));
+            return Rc::new(Ty::error());
+        }
+
+        let hir_ty = self.ty_aliases[cpath];
+        self.expanding_ty_aliases.push(Rc::clone(cpath));
+        let ty = self.hir_ty_to_ty(hir_ty);
+        self.expanding_ty_aliases.pop();
+        self.expanded_ty_aliases
+            .insert(Rc::clone(cpath), Rc::clone(&ty));
+        ty
+    }
+}
+
+/// Collect all type aliases, constants, statics and `const fn`s in advance since they can
+/// be used before their definitions
+#[derive(Default)]
+struct ItemCollector<'chk> {
+    aliases: Vec<&'chk hir::TyAlias>,
+    consts: Vec<&'chk hir::ConstItem>,
+    statics: Vec<&'chk hir::StaticItem>,
+    const_fns: Vec<&'chk hir::Func>,
+    /// Methods cannot be called by paths
+    in_impl: bool,
+}
+
+impl<'chk> hir::visitor::Visitor<'chk> for ItemCollector<'chk> {
+    fn visit_ty_alias(&mut self, alias: &'chk hir::TyAlias) {
+        self.aliases.push(alias);
+    }
+
+    fn visit_const_item(&mut self, const_item: &'chk hir::ConstItem) {
+        self.consts.push(const_item);
+    }
+
+    fn visit_static_item(&mut self, static_item: &'chk hir::StaticItem) {
+        self.statics.push(static_item);
+    }
+
+    fn visit_impl(&mut self, _imp: &'chk hir::Impl) {
+        self.in_impl = true;
+    }
+
+    fn visit_impl_post(&mut self, _imp: &'chk hir::Impl) {
+        self.in_impl = false;
+    }
+
+    fn visit_func(&mut self, func: &'chk hir::Func) {
+        if func.constness == hir::Constness::Const && !self.in_impl {
+            self.const_fns.push(func);
+        }
+    }
+}
+
+impl<'chk> hir::visitor::Visitor<'chk> for TypeChecker<'_, 'chk> {
+    fn visit_crate(&mut self, _krate: &'chk Crate) {}
+
+    fn visit_crate_post(&mut self, _krate: &'chk Crate) {
+        // a struct cannot contain itself without indirection such as `Box`
+        for strct in std::mem::take(&mut self.structs) {
+            let cpath = &strct.def_id.cpath;
+            let adt = self.ctx.lookup_adt_def(cpath).unwrap();
+            if adt
+                .fields
+                .iter()
+                .any(|(_, fd_ty)| self.contains_adt(fd_ty, cpath, &mut vec![]))
+            {
+                self.error(format!(
+                    "Recursive type `{:?}` has infinite size at {}",
+                    cpath,
+                    strct.ident.span.to_location()
+                ));
+            }
+        }
+    }
+
+    // TODO: allow func call before finding declaration of the func
+    // TODO: what if typechecker does not find a body of non-external func?
+    // TODO: external func must not have its body (correct?)
+    fn visit_func(&mut self, func: &'chk hir::Func) {
+        // TODO: typecheck main func
+        self.declare_func(func);
 
         // push scope
-        for (param, param_ty) in &func.params {
-            let binding = self.ctx.get_binding(param).unwrap();
-            let param_ty = self.ast_ty_to_ty(param_ty);
-            self.ctx
-                .set_name_type(Rc::clone(&binding), Rc::new(param_ty));
+        for param in &func.params {
+            let param_ty = self.hir_ty_to_ty(&param.ty);
+            self.ctx.set_name_type(Rc::clone(&param.local_id), param_ty);
         }
         // push return type
-        let ret_ty = self.ast_ty_to_ty(&func.ret_ty);
+        let ret_ty = self.hir_ty_to_ty(&func.ret_ty);
         self.push_return_type(ret_ty);
     }
 
-    fn visit_func_post(&mut self, func: &'chk ast::Func) {
+    fn visit_func_post(&mut self, func: &'chk hir::Func) {
         let Some(body) = &func.body else {
             return;
         };
 
         let body_ty = self.ctx.get_type(body.id);
-
         let expected = self.peek_return_type();
-        if !body_ty.is_never() && *body_ty != *expected {
-            self.error(format!(
-                "Expected type {:?} for func body, but found {:?}",
-                expected, body_ty
-            ));
+        match &body.expr {
+            // the value of the tail expression is returned
+            Some(expr) => self.coerce(&body_ty, &expected, &expr.span),
+            // control may fall off the end of the body
+            _ if !self.ctx.diverges(body.id) && self.infcx.unify(&body_ty, &expected).is_err() => {
+                self.error(format!(
+                    "Mismatched types: expected `{}`, found `()` at {}\n    implicitly returns `()` as its body has no tail or `return` expression",
+                    self.infcx.resolve_fully(&expected),
+                    func.ret_ty.span.to_location()
+                ));
+            }
+            _ => (),
         }
         // pop return type
         self.pop_return_type();
     }
 
-    fn visit_struct_item(&mut self, strct: &'chk ast::StructItem) {
+    fn visit_impl(&mut self, imp: &'chk hir::Impl) {
+        let self_ty = self.hir_ty_to_ty(&imp.self_ty);
+        self.current_self_ty = Some(Rc::clone(&self_ty));
+
+        // the trait is always `Drop`
+        let Some(adt_name) = self_ty.get_adt_name() else {
+            if !self_ty.is_error() {
+                self.error(format!(
+                    "The `Drop` trait may only be implemented for structs, but found `{}` at {}",
+                    self_ty,
+                    imp.self_ty.span.to_location()
+                ));
+            }
+            return;
+        };
+
+        let mut drop_fn = None;
+        for func in &imp.items {
+            if *func.name.symbol != "drop" {
+                self.error(format!(
+                    "Method `{}` is not a member of trait `Drop` at {}",
+                    func.name.symbol,
+                    func.name.span.to_location()
+                ));
+            } else if drop_fn.is_some() {
+                self.error(format!(
+                    "Duplicate definitions with name `drop` at {}",
+                    func.name.span.to_location()
+                ));
+            } else {
+                drop_fn = Some(func);
+            }
+        }
+        let Some(drop_fn) = drop_fn else {
+            self.error(format!(
+                "Missing `drop` in implementation of `Drop` at {}",
+                imp.self_ty.span.to_location()
+            ));
+            return;
+        };
+
+        // `fn drop(&mut self)`
+        let expected = Ty::new(TyKind::Fn(
+            Rc::new(vec![Rc::new(Ty::new(TyKind::Ref(
+                Rc::clone(&self_ty),
+                hir::Mutability::Mut,
+            )))]),
+            Rc::new(Ty::unit()),
+        ));
+        let found = Ty::new(TyKind::Fn(
+            Rc::new(
+                drop_fn
+                    .params
+                    .iter()
+                    .map(|param| self.hir_ty_to_ty(&param.ty))
+                    .collect(),
+            ),
+            self.hir_ty_to_ty(&drop_fn.ret_ty),
+        ));
+        if found != expected {
+            self.error(format!(
+                "Method `drop` has an incompatible type for trait: expected `{}`, found `{}` at {}",
+                expected,
+                found,
+                drop_fn.name.span.to_location()
+            ));
+        }
+
+        if self.ctx.lookup_drop_impl(adt_name).is_some() {
+            self.error(format!(
+                "Conflicting implementations of trait `Drop` for type `{}` at {}",
+                self_ty,
+                imp.self_ty.span.to_location()
+            ));
+        } else {
+            self.ctx
+                .set_drop_impl(Rc::clone(adt_name), Rc::clone(&drop_fn.def_id));
+        }
+    }
+
+    fn visit_impl_post(&mut self, _imp: &'chk hir::Impl) {
+        self.current_self_ty = None;
+    }
+
+    fn visit_ty_alias(&mut self, alias: &'chk hir::TyAlias) {
+        // report errors in aliases which are never used
+        self.expand_ty_alias(&alias.def_id.cpath, &alias.ident.span);
+    }
+
+    fn visit_const_item_post(&mut self, const_item: &'chk hir::ConstItem) {
+        let ty = self.const_ty(&const_item.def_id);
+        let init_ty = self.ctx.get_type(const_item.expr.id);
+        self.coerce(&init_ty, &ty, &const_item.expr.span);
+        // report errors in constants which are never used
+        self.eval_const(&const_item.def_id, &const_item.ident.span);
+    }
+
+    fn visit_static_item_post(&mut self, static_item: &'chk hir::StaticItem) {
+        let ty = self.ctx.lookup_name_type(&static_item.def_id).unwrap();
+        let init_ty = self.ctx.get_type(static_item.expr.id);
+        self.coerce(&init_ty, &ty, &static_item.expr.span);
+        self.eval_static(static_item, &ty);
+    }
+
+    fn visit_struct_item(&mut self, strct: &'chk hir::StructItem) {
         let field_tys: Vec<(Rc<String>, Rc<Ty>)> = strct
             .fields
             .iter()
-            .map(|(name, ty)| (Rc::clone(&name.symbol), Rc::new(self.ast_ty_to_ty(ty))))
+            .map(|(name, ty)| (Rc::clone(&name.symbol), self.hir_ty_to_ty(ty)))
             .collect();
+        let def_id = &strct.def_id;
+        let adt_ty = Rc::new(Ty::new(TyKind::Adt(Rc::clone(&def_id.cpath))));
+
+        // constructors of tuple structs and unit structs are values
+        match strct.kind {
+            hir::StructKind::Named => (),
+            hir::StructKind::Tuple => {
+                let param_tys = field_tys.iter().map(|(_, ty)| Rc::clone(ty)).collect();
+                let ctor_ty = Ty::new(TyKind::Fn(Rc::new(param_tys), adt_ty));
+                self.ctx.set_name_type(Rc::clone(def_id), Rc::new(ctor_ty));
+            }
+            hir::StructKind::Unit => {
+                self.ctx.set_name_type(Rc::clone(def_id), adt_ty);
+            }
+        }
         let adt = AdtDef { fields: field_tys };
-        let binding = self.ctx.get_binding(&strct.ident).unwrap();
-        self.ctx.set_adt_def(Rc::clone(&binding.cpath), adt);
+        self.ctx.set_adt_def(Rc::clone(&def_id.cpath), adt);
+        self.structs.push(strct);
     }
 
-    fn visit_stmt_post(&mut self, stmt: &'chk ast::Stmt) {
+    fn visit_stmt_post(&mut self, stmt: &'chk hir::Stmt) {
         let ty: Rc<Ty> = match &stmt.kind {
             StmtKind::Semi(expr) => {
-                let expr_ty = self.ctx.get_type(expr.id);
-                if expr_ty.is_never() {
+                if self.ctx.diverges(expr.id) {
+                    self.ctx.register_diverging(stmt.id);
                     Rc::new(Ty::never())
                 } else {
                     Rc::new(Ty::unit())
                 }
             }
-            StmtKind::Let(LetStmt { init, ty, ident: _ }) => {
+            StmtKind::Let(LetStmt { init, local_id, .. }) => {
                 if let Some(init) = init {
+                    let var_ty = self.ctx.lookup_name_type(local_id).unwrap();
                     let init_ty = self.ctx.get_type(init.id);
-                    let annotated_ty = self.ast_ty_to_ty(ty.as_ref().unwrap());
-                    if init_ty.is_never() {
+                    self.coerce(&init_ty, &var_ty, &init.span);
+                    if self.ctx.diverges(init.id) {
+                        self.ctx.register_diverging(stmt.id);
                         Rc::new(Ty::never())
                     } else {
-                        if annotated_ty != *init_ty {
-                            self.error(format!(
-                                "Expected `{:?}` type, but found `{:?}`",
-                                annotated_ty, init_ty
-                            ));
-                            Rc::new(Ty::error())
-                        } else {
-                            Rc::new(Ty::unit())
-                        }
+                        Rc::new(Ty::unit())
                     }
                 } else {
                     Rc::new(Ty::unit())
                 }
             }
-            StmtKind::Expr(expr) => self.ctx.get_type(expr.id),
+            StmtKind::Expr(expr) => {
+                if self.ctx.diverges(expr.id) {
+                    self.ctx.register_diverging(stmt.id);
+                }
+                self.ctx.get_type(expr.id)
+            }
         };
         self.ctx.insert_type(stmt.id, ty);
     }
 
-    // TODO: handling local variables properly
-    // TODO: shadowing
     fn visit_stmt(&mut self, stmt: &'chk Stmt) {
-        match &stmt.kind {
-            StmtKind::Let(let_stmt) => {
-                // set local variable type
-                let binding = self.ctx.get_binding(&let_stmt.ident).unwrap();
-                // set type of local variable
-                // TODO: unwrap
-                let annotated_ty = self.ast_ty_to_ty(let_stmt.ty.as_ref().unwrap());
-                self.ctx
-                    .set_name_type(Rc::clone(&binding), Rc::new(annotated_ty));
-                // set type of statement
-                let stmt_ty = self.ast_ty_to_ty(let_stmt.ty.as_ref().unwrap());
-                // TODO: unwrap
-                self.ctx.insert_type(stmt.id, Rc::new(stmt_ty));
-            }
-            _ => {}
+        if let StmtKind::Let(let_stmt) = &stmt.kind {
+            // set type of local variable
+            // type of variables without annotations are inferred from their uses
+            let var_ty = if let Some(ty) = &let_stmt.ty {
+                self.hir_ty_to_ty(ty)
+            } else {
+                self.infcx.new_ty_var(&let_stmt.ident.span)
+            };
+            self.ctx
+                .set_name_type(Rc::clone(&let_stmt.local_id), var_ty);
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &'chk hir::Expr) {
+        if let ExprKind::Call(func, _) = &expr.kind {
+            self.callees.insert(func.id);
         }
     }
 
     // use post order
-    fn visit_expr_post(&mut self, expr: &'chk ast::Expr) {
+    fn visit_expr_post(&mut self, expr: &'chk hir::Expr) {
         let ty: Rc<Ty> = match &expr.kind {
-            ExprKind::NumLit(_) => Rc::new(Ty::new(TyKind::I32)),
+            ExprKind::NumLit(_) => self.infcx.new_int_var(&expr.span),
+            ExprKind::FloatLit(_, suffix) => match suffix {
+                Some(hir::FloatTy::F32) => Rc::new(Ty::new(TyKind::F32)),
+                Some(hir::FloatTy::F64) => Rc::new(Ty::new(TyKind::F64)),
+                None => self.infcx.new_float_var(&expr.span),
+            },
             ExprKind::BoolLit(_) => Rc::new(Ty::new(TyKind::Bool)),
-            ExprKind::StrLit(_) => Rc::new(Ty::new(TyKind::Ref(Rc::new(Ty::new(TyKind::Str))))),
+            ExprKind::StrLit(_) => Rc::new(Ty::new(TyKind::Ref(
+                Rc::new(Ty::new(TyKind::Str)),
+                hir::Mutability::Not,
+            ))),
+            ExprKind::CharLit(_) => Rc::new(Ty::new(TyKind::Char)),
             ExprKind::Unit => Rc::new(Ty::unit()),
-            ExprKind::Assign(l, r) => {
-                let lhs_ty = &self.ctx.get_type(l.id);
-                let rhs_ty = &self.ctx.get_type(r.id);
-                if rhs_ty.is_never() || **lhs_ty == **rhs_ty {
-                    Rc::new(Ty::unit())
+            ExprKind::Panic(fmt) => {
+                self.check_format_args(fmt);
+                Rc::new(Ty::never())
+            }
+            ExprKind::Print(_, fmt) => {
+                self.check_format_args(fmt);
+                if fmt
+                    .args
+                    .iter()
+                    .any(|arg| self.get_resolved_type(arg.id).is_never())
+                {
+                    Rc::new(Ty::never())
                 } else {
-                    self.error(format!("Cannot assign {:?} to {:?}", rhs_ty, lhs_ty));
-                    Rc::new(Ty::error())
+                    Rc::new(Ty::unit())
                 }
             }
-            // TODO: deal with never type
+            ExprKind::Assign(l, r) => {
+                if let Some(path) = immutable_static(l) {
+                    self.error(format!(
+                        "Cannot assign to immutable static item `{}` at {}",
+                        path.span.to_snippet(),
+                        l.span.to_location()
+                    ));
+                }
+                let lhs_ty = self.ctx.get_type(l.id);
+                let rhs_ty = self.ctx.get_type(r.id);
+                self.coerce(&rhs_ty, &lhs_ty, &r.span);
+                Rc::new(Ty::unit())
+            }
             ExprKind::Binary(op, l, r) => {
-                let lhs_ty = &self.ctx.get_type(l.id);
-                let rhs_ty = &self.ctx.get_type(r.id);
-                match op {
-                    BinOp::Add | BinOp::Sub | BinOp::Mul => {
-                        if lhs_ty.kind == TyKind::I32 && rhs_ty.kind == TyKind::I32 {
-                            Rc::new(Ty::new(TyKind::I32))
-                        } else {
-                            self.error("Both lhs and rhs must be type of i32".to_string());
-                            Rc::new(Ty::error())
+                let lhs_ty = self.get_resolved_type(l.id);
+                let rhs_ty = self.get_resolved_type(r.id);
+                // operands of the never type are coerced to the type of the other operand
+                let operand_ty = match (lhs_ty.is_never(), rhs_ty.is_never()) {
+                    (true, true) => None,
+                    (true, false) => Some(rhs_ty),
+                    (false, true) => Some(lhs_ty),
+                    (false, false) => {
+                        if self.infcx.unify(&lhs_ty, &rhs_ty).is_err() {
+                            self.mismatched_types(&lhs_ty, &rhs_ty, &r.span);
                         }
+                        Some(lhs_ty)
                     }
-                    BinOp::Gt | BinOp::Lt => {
-                        if lhs_ty.kind == TyKind::I32 && rhs_ty.kind == TyKind::I32 {
-                            Rc::new(Ty::new(TyKind::I32))
-                        } else {
-                            self.error("Both lhs and rhs must be type of i32".to_string());
-                            Rc::new(Ty::error())
+                };
+                match operand_ty {
+                    // both operands diverge
+                    None => Rc::new(Ty::never()),
+                    Some(operand_ty) => {
+                        let operand_ty = self.infcx.shallow_resolve(&operand_ty);
+                        let is_supported = match op {
+                            BinOp::Add
+                            | BinOp::Sub
+                            | BinOp::Mul
+                            | BinOp::Div
+                            | BinOp::Rem
+                            | BinOp::Gt
+                            | BinOp::Lt => operand_ty.is_numeric(),
+                            BinOp::Eq | BinOp::Ne => {
+                                operand_ty.is_numeric()
+                                    || matches!(operand_ty.kind, TyKind::Bool | TyKind::Char)
+                            }
+                        };
+                        if operand_ty.is_ty_var() {
+                            self.annotations_needed(&l.span);
+                        } else if !is_supported && !operand_ty.is_error() {
+                            self.error(format!(
+                                "Cannot apply binary operator {:?} to type `{}` at {}",
+                                op,
+                                self.infcx.resolve_fully(&operand_ty),
+                                expr.span.to_location()
+                            ));
                         }
-                    }
-                    BinOp::Eq | BinOp::Ne => {
-                        // TODO: other types?
-                        if (lhs_ty.kind == TyKind::I32 && rhs_ty.kind == TyKind::I32)
-                            || (lhs_ty.kind == TyKind::Bool && rhs_ty.kind == TyKind::Bool)
-                        {
-                            Rc::new(Ty::new(TyKind::Bool))
-                        } else {
-                            self.error("Both lhs and rhs must have the same type".to_string());
-                            Rc::new(Ty::error())
+                        match op {
+                            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Rem => {
+                                operand_ty
+                            }
+                            BinOp::Gt | BinOp::Lt | BinOp::Eq | BinOp::Ne => {
+                                Rc::new(Ty::new(TyKind::Bool))
+                            }
                         }
                     }
                 }
             }
-            // TODO: deal with never type
-            ExprKind::Unary(_op, inner) => {
-                let inner_ty = &self.ctx.get_type(inner.id);
-                if inner_ty.kind == TyKind::I32 {
-                    Rc::new(Ty::new(TyKind::I32))
+            ExprKind::Unary(hir::UnOp::Deref, inner) => {
+                let inner_ty = self.get_resolved_type(inner.id);
+                match &inner_ty.kind {
+                    // dereferences of raw pointers are checked to be in unsafe blocks later
+                    TyKind::Box(pointee) | TyKind::Ref(pointee, _) | TyKind::RawPtr(pointee, _) => {
+                        Rc::clone(pointee)
+                    }
+                    TyKind::Infer(ty::InferTy::Ty(_)) => {
+                        self.annotations_needed(&inner.span);
+                        Rc::new(Ty::error())
+                    }
+                    TyKind::Never | TyKind::Error => inner_ty,
+                    _ => {
+                        self.error(format!(
+                            "Type `{}` cannot be dereferenced at {}",
+                            self.infcx.resolve_fully(&inner_ty),
+                            expr.span.to_location()
+                        ));
+                        Rc::new(Ty::error())
+                    }
+                }
+            }
+            ExprKind::Unary(op, inner) => {
+                let inner_ty = self.get_resolved_type(inner.id);
+                // unsigned integers cannot be negated
+                let is_negated_unsigned = matches!(op, hir::UnOp::Minus)
+                    && matches!(inner_ty.kind, TyKind::Int(int_ty) if !int_ty.is_signed());
+                if matches!(op, hir::UnOp::Minus)
+                    && matches!(inner_ty.kind, TyKind::Infer(ty::InferTy::Int(_)))
+                {
+                    self.negated_int_vars
+                        .push((Rc::clone(&inner_ty), expr.span.clone()));
+                }
+                if inner_ty.is_ty_var() {
+                    self.annotations_needed(&inner.span);
+                    Rc::new(Ty::error())
+                } else if (inner_ty.is_numeric() && !is_negated_unsigned)
+                    || inner_ty.is_never()
+                    || inner_ty.is_error()
+                {
+                    inner_ty
                 } else {
-                    self.error("inner expr of unary must be type of i32".to_string());
+                    self.error(format!(
+                        "Cannot apply unary operator to type `{}` at {}",
+                        self.infcx.resolve_fully(&inner_ty),
+                        expr.span.to_location()
+                    ));
                     Rc::new(Ty::error())
                 }
             }
             ExprKind::Path(path) => {
-                // find symbols in local variables, parameters, and in functions
-                if let Some(binding) = self.ctx.resolve_path(path) {
-                    if let Some(ty) = self.ctx.lookup_name_type(&binding) {
-                        ty
-                    } else {
-                        self.error(format!("Cannot use `{:?}` before declaration", path));
-                        Rc::new(Ty::error())
-                    }
+                // local variables, parameters, functions and constructors
+                let binding = match &path.res {
+                    Res::Def(_, binding) | Res::Local(binding) => binding,
+                    Res::Err => panic!("ICE: unresolved path in HIR"),
+                };
+                // function pointers are not supported, so they can only be called
+                if let Res::Def(kind @ (DefKind::Fn | DefKind::Ctor(CtorKind::Fn)), _) = &path.res
+                    && !self.callees.contains(&expr.id)
+                {
+                    self.error(format!(
+                        "Cannot use {} `{}` as a value, only call it at {}",
+                        kind.desc(),
+                        path.span.to_snippet(),
+                        path.span.to_location()
+                    ));
+                    Rc::new(Ty::error())
+                } else if let Some(ty) = self.ctx.lookup_name_type(binding) {
+                    ty
                 } else {
-                    self.error(format!("Could not resolve ident `{:?}`", path));
+                    self.error(format!(
+                        "Cannot use `{}` before declaration at {}",
+                        path.span.to_snippet(),
+                        path.span.to_location()
+                    ));
                     Rc::new(Ty::error())
                 }
             }
-
-            ExprKind::Return(expr) => {
-                let actual_ret_ty = self.ctx.get_type(expr.id);
-                let expected_ret_ty = self.peek_return_type();
-                if *actual_ret_ty == *expected_ret_ty {
-                    Rc::new(Ty::never())
+            ExprKind::Return(inner) => {
+                // `return` in constants is reported by the evaluation
+                if self.current_return_type.is_some() {
+                    let inner_ty = self.ctx.get_type(inner.id);
+                    let ret_ty = self.peek_return_type();
+                    self.coerce(&inner_ty, &ret_ty, &inner.span);
+                }
+                Rc::new(Ty::never())
+            }
+            ExprKind::Call(func, args) => {
+                let maybe_func_ty = self.get_resolved_type(func.id);
+                if let TyKind::Fn(param_tys, ret_ty) = &maybe_func_ty.kind {
+                    if param_tys.len() == args.len() {
+                        for (arg, param_ty) in args.iter().zip(param_tys.iter()) {
+                            let arg_ty = self.ctx.get_type(arg.id);
+                            self.coerce(&arg_ty, param_ty, &arg.span);
+                        }
+                    } else {
+                        self.error(format!(
+                            "Expected {} arguments, but found {} at {}",
+                            param_tys.len(),
+                            args.len(),
+                            expr.span.to_location()
+                        ));
+                    }
+                    Rc::clone(ret_ty)
+                } else if maybe_func_ty.is_error() {
+                    maybe_func_ty
                 } else {
                     self.error(format!(
-                        "Expected {:?} type, but {:?} returned",
-                        expected_ret_ty, actual_ret_ty
+                        "Expected fn type, but found `{}` at {}",
+                        self.infcx.resolve_fully(&maybe_func_ty),
+                        func.span.to_location()
                     ));
                     Rc::new(Ty::error())
                 }
             }
-            // TODO: deal with never type params
-            ExprKind::Call(expr, args) => {
-                let maybe_func_ty = self.ctx.get_type(expr.id);
-                if let TyKind::Fn(param_ty, ret_ty) = &maybe_func_ty.kind {
-                    if param_ty.len() == args.len() {
-                        let mut ok = true;
-                        for (arg, param_ty) in args.iter().zip(param_ty.iter()) {
-                            let arg_ty = &self.ctx.get_type(arg.id);
-                            if arg_ty != param_ty {
-                                self.error(format!(
-                                    "Expected {:?} type argument, but found {:?} type",
-                                    param_ty, arg_ty
-                                ));
-                                ok = false;
-                            }
-                        }
-                        if ok {
-                            Rc::clone(ret_ty)
-                        } else {
-                            Rc::new(Ty::error())
+            ExprKind::MethodCall(recv, method, args) => {
+                let recv_ty = self.get_resolved_type(recv.id);
+                // built-in methods of raw pointers
+                let sig = match method.symbol.as_str() {
+                    _ if !recv_ty.is_raw_ptr() => None,
+                    "add" => Some((vec![IntTy::Usize], Rc::clone(&recv_ty))),
+                    "offset" => Some((vec![IntTy::Isize], Rc::clone(&recv_ty))),
+                    "is_null" => Some((vec![], Rc::new(Ty::new(TyKind::Bool)))),
+                    _ => None,
+                };
+                if let Some((param_tys, ret_ty)) = sig {
+                    if param_tys.len() == args.len() {
+                        for (arg, param_ty) in args.iter().zip(param_tys) {
+                            let arg_ty = self.ctx.get_type(arg.id);
+                            self.coerce(&arg_ty, &Rc::new(Ty::int(param_ty)), &arg.span);
                         }
                     } else {
                         self.error(format!(
-                            "Expected {} arguments, but found {}",
-                            param_ty.len(),
-                            args.len()
+                            "Expected {} arguments, but found {} at {}",
+                            param_tys.len(),
+                            args.len(),
+                            expr.span.to_location()
                         ));
-                        Rc::new(Ty::error())
                     }
+                    ret_ty
+                } else if recv_ty.is_ty_var() {
+                    self.annotations_needed(&recv.span);
+                    Rc::new(Ty::error())
+                } else if recv_ty.is_error() || recv_ty.is_never() {
+                    Rc::new(Ty::error())
                 } else {
-                    self.error(format!("Expected fn type, but found {:?}", maybe_func_ty));
+                    self.error(format!(
+                        "No method named `{}` found for type `{}` at {}",
+                        method.symbol,
+                        self.infcx.resolve_fully(&recv_ty),
+                        method.span.to_location()
+                    ));
                     Rc::new(Ty::error())
                 }
             }
             ExprKind::Block(block) => self.ctx.get_type(block.id),
             ExprKind::If(cond, then, els) => {
                 let cond_ty = self.ctx.get_type(cond.id);
-                let then_ty = self.ctx.get_type(then.id);
-                if cond_ty.is_never() || cond_ty.kind == TyKind::Bool {
-                    let els_ty = if let Some(els) = els {
-                        self.ctx.get_type(els.id)
-                    } else {
-                        Rc::new(Ty::unit())
-                    };
+                self.coerce(&cond_ty, &Rc::new(Ty::new(TyKind::Bool)), &cond.span);
 
-                    if then_ty.is_never() || then_ty.kind == els_ty.kind {
-                        then_ty
+                let then_ty = self.get_resolved_type(then.id);
+                if let Some(els) = els {
+                    let els_ty = self.get_resolved_type(els.id);
+                    if then_ty.is_never() {
+                        els_ty
                     } else {
-                        self.error(format!(
-                            "Type mismatch then block has `{:?}`, but else block has `{:?}`",
-                            then_ty, els_ty
-                        ));
-                        Rc::new(Ty::error())
+                        // `if` and `else` must have the same type
+                        self.coerce(&els_ty, &then_ty, &els.span);
+                        then_ty
                     }
                 } else {
-                    self.error(format!(
-                        "Expected bool for conditional, but found {:?}",
-                        cond_ty
-                    ));
-                    Rc::new(Ty::error())
+                    // `if` without `else` has unit type
+                    self.coerce(&then_ty, &Rc::new(Ty::unit()), &then.span);
+                    Rc::new(Ty::unit())
                 }
             }
-            ExprKind::Index(array, _index) => {
-                let maybe_array_ty = self.ctx.get_type(array.id);
-                // TODO: typecheck index
-                if let TyKind::Array(elem_ty, _) = &maybe_array_ty.kind {
-                    Rc::clone(elem_ty)
-                } else {
-                    self.error(format!("type {:?} cannot be indexed", maybe_array_ty));
-                    Rc::new(Ty::error())
+            ExprKind::Index(array, index) => {
+                let index_ty = self.ctx.get_type(index.id);
+                self.coerce(&index_ty, &Rc::new(Ty::int(IntTy::I32)), &index.span);
+
+                let mut maybe_array_ty = self.get_resolved_type(array.id);
+                // arrays are indexed through boxes and references
+                while let TyKind::Box(inner) | TyKind::Ref(inner, _) = &maybe_array_ty.kind {
+                    maybe_array_ty = self.infcx.shallow_resolve(inner);
+                }
+                match &maybe_array_ty.kind {
+                    TyKind::Array(elem_ty, _) => Rc::clone(elem_ty),
+                    TyKind::Infer(ty::InferTy::Ty(_)) => {
+                        self.annotations_needed(&array.span);
+                        Rc::new(Ty::error())
+                    }
+                    TyKind::Error => maybe_array_ty,
+                    _ => {
+                        self.error(format!(
+                            "Type `{}` cannot be indexed at {}",
+                            self.infcx.resolve_fully(&maybe_array_ty),
+                            array.span.to_location()
+                        ));
+                        Rc::new(Ty::error())
+                    }
                 }
             }
             ExprKind::Field(receiver, field) => {
-                let maybe_adt = self.ctx.get_type(receiver.id);
+                let mut maybe_adt = self.get_resolved_type(receiver.id);
+                // fields are accessed through boxes and references (e.g. `node.next.val`)
+                while let TyKind::Box(inner) | TyKind::Ref(inner, _) = &maybe_adt.kind {
+                    maybe_adt = self.infcx.shallow_resolve(inner);
+                }
                 if let Some(cpath) = maybe_adt.get_adt_name() {
                     if let Some(adt) = self.ctx.lookup_adt_def(cpath) {
                         let r = adt.fields.iter().find(|(f, _)| field.symbol == *f);
@@ -376,8 +984,10 @@ impl<'chk> ast::visitor::Visitor<'chk> for TypeChecker<'_, 'chk> {
                             Rc::clone(ty)
                         } else {
                             self.error(format!(
-                                "Type {:?} does not have field `{}`",
-                                cpath, field.symbol
+                                "Type `{}` does not have field `{}` at {}",
+                                maybe_adt,
+                                field.symbol,
+                                field.span.to_location()
                             ));
                             Rc::new(Ty::error())
                         }
@@ -385,87 +995,195 @@ impl<'chk> ast::visitor::Visitor<'chk> for TypeChecker<'_, 'chk> {
                         self.error(format!("receiver is not struct, but {:?}", maybe_adt));
                         Rc::new(Ty::error())
                     }
+                } else if maybe_adt.is_ty_var() {
+                    self.annotations_needed(&receiver.span);
+                    Rc::new(Ty::error())
+                } else if maybe_adt.is_error() {
+                    maybe_adt
                 } else {
-                    self.error("field access can used only for ADT".to_string());
+                    self.error(format!(
+                        "Field access can be used only for ADT, but found `{}` at {}",
+                        self.infcx.resolve_fully(&maybe_adt),
+                        receiver.span.to_location()
+                    ));
                     Rc::new(Ty::error())
                 }
             }
-            ExprKind::Struct(path, _fds) => {
-                if let Some(binding) = self.ctx.resolve_path(path) {
-                    if let Some(_adt) = self.ctx.lookup_adt_def(&binding.cpath) {
-                        // TODO: typecheck fields
-                        Rc::new(Ty::new(TyKind::Adt(Rc::clone(&binding.cpath))))
+            ExprKind::Struct(path, fds, base) => {
+                // lowering ensures that the path names a struct
+                let binding = path.res.def_id().unwrap();
+                if let Some(adt) = self.ctx.lookup_adt_def(&binding.cpath) {
+                    let adt_ty = Rc::new(Ty::new(TyKind::Adt(Rc::clone(&binding.cpath))));
+                    for (i, (fd, _)) in fds.iter().enumerate() {
+                        if fds[..i].iter().any(|(prev, _)| prev.symbol == fd.symbol) {
+                            self.error(format!(
+                                "Field `{}` is specified more than once at {}",
+                                fd.symbol,
+                                fd.span.to_location()
+                            ));
+                        }
+                    }
+                    if let Some(base) = base {
+                        // fields not specified are moved from the base
+                        let base_ty = self.ctx.get_type(base.id);
+                        self.coerce(&base_ty, &adt_ty, &base.span);
                     } else {
-                        self.error(format!("{:?} does not have struct type", binding.cpath));
-                        Rc::new(Ty::error())
+                        let missing: Vec<&str> = adt
+                            .fields
+                            .iter()
+                            .filter(|(name, _)| fds.iter().all(|(fd, _)| fd.symbol != *name))
+                            .map(|(name, _)| name.as_str())
+                            .collect();
+                        if !missing.is_empty() {
+                            self.error(format!(
+                                "Missing fields `{}` in initializer of `{:?}` at {}",
+                                missing.join("`, `"),
+                                binding.cpath,
+                                expr.span.to_location()
+                            ));
+                        }
                     }
+                    for (fd, fd_expr) in fds {
+                        let Some((_, fd_ty)) =
+                            adt.fields.iter().find(|(name, _)| fd.symbol == *name)
+                        else {
+                            self.error(format!(
+                                "Struct {:?} does not have field `{}` at {}",
+                                binding.cpath,
+                                fd.symbol,
+                                fd.span.to_location()
+                            ));
+                            continue;
+                        };
+                        let fd_expr_ty = self.ctx.get_type(fd_expr.id);
+                        self.coerce(&fd_expr_ty, fd_ty, &fd_expr.span);
+                    }
+                    adt_ty
                 } else {
-                    self.error(format!("Could not resolve {}", path.span.to_snippet()));
+                    self.error(format!("{:?} does not have struct type", binding.cpath));
                     Rc::new(Ty::error())
                 }
             }
             ExprKind::Array(elems) => {
-                if elems.is_empty() {
-                    // TODO: type inference: typecheck arary with zero element
-                    self.error("Array with zero element is not supported".to_string());
-                    Rc::new(Ty::error())
-                } else {
-                    let first_elem = elems.first().unwrap();
-                    let first_elem_ty = self.ctx.get_type(first_elem.id);
-
-                    if first_elem_ty.is_never() {
-                        self.error(format!(
-                            "First element `{}` has never type. Could not infer type of array `{}`.",
-                            first_elem.span.to_snippet(),
-                            expr.span.to_snippet(),
-                        ));
-                        Rc::new(Ty::error())
-                    } else {
-                        let mut saw_error = false;
-                        for elem in elems {
-                            let elem_ty = self.ctx.get_type(elem.id);
-                            if !elem_ty.is_never() && elem_ty != first_elem_ty {
-                                self.error(format!(
-                                    "Expected type `{:?}`, but `{}` has type `{:?}`",
-                                    first_elem_ty,
-                                    elem.span.to_snippet(),
-                                    elem_ty,
-                                ));
-                                saw_error = true;
-                            }
-                        }
-                        if saw_error {
-                            Rc::new(Ty::error())
-                        } else {
-                            Rc::new(Ty::new(TyKind::Array(first_elem_ty, elems.len())))
-                        }
-                    }
+                // the element type is inferred from the elements or the uses of the array
+                let elem_ty = self.infcx.new_ty_var(&expr.span);
+                for elem in elems {
+                    let ty = self.ctx.get_type(elem.id);
+                    self.coerce(&ty, &elem_ty, &elem.span);
                 }
+                Rc::new(Ty::new(TyKind::Array(elem_ty, elems.len())))
+            }
+            ExprKind::Box(inner) => Rc::new(Ty::new(TyKind::Box(self.ctx.get_type(inner.id)))),
+            ExprKind::NullPtr(mutbl) => {
+                let pointee = self.infcx.new_ty_var(&expr.span);
+                Rc::new(Ty::new(TyKind::RawPtr(pointee, *mutbl)))
+            }
+            ExprKind::AddrOf(mutbl, inner) => {
+                if *mutbl == hir::Mutability::Mut
+                    && let Some(path) = immutable_static(inner)
+                {
+                    self.error(format!(
+                        "Cannot borrow immutable static item `{}` as mutable at {}",
+                        path.span.to_snippet(),
+                        inner.span.to_location()
+                    ));
+                }
+                Rc::new(Ty::new(TyKind::Ref(self.ctx.get_type(inner.id), *mutbl)))
             }
             ExprKind::Cast(expr, ty) => {
                 // ref: https://doc.rust-lang.org/reference/expressions/operator-expr.html#type-cast-expressions
-                let expr_ty = self.ctx.get_type(expr.id);
-                let cast_ty = self.ast_ty_to_ty(ty);
+                let expr_ty = self.infcx.resolve_fully(&self.ctx.get_type(expr.id));
+                let cast_ty = self.hir_ty_to_ty(ty);
+                let u8_ty = Rc::new(Ty::int(IntTy::U8));
 
                 match (&expr_ty.kind, &cast_ty.kind) {
-                    (TyKind::Ref(_), TyKind::ConstPtr(_))
-                    | (TyKind::ConstPtr(_), TyKind::ConstPtr(_)) => Rc::new(cast_ty),
+                    (TyKind::Error | TyKind::Never, _) | (_, TyKind::Error) => cast_ty,
+                    _ if expr_ty == cast_ty => cast_ty,
+                    // `&T` can only be cast to `*const T`
+                    (TyKind::Ref(_, from), TyKind::RawPtr(_, to))
+                        if *from == hir::Mutability::Mut || *to == hir::Mutability::Not =>
+                    {
+                        cast_ty
+                    }
+                    (TyKind::RawPtr(..), TyKind::RawPtr(..)) => cast_ty,
+                    // pointer-integer casts
+                    (TyKind::RawPtr(..), _) if cast_ty.is_integral() => cast_ty,
+                    (_, TyKind::RawPtr(..)) if expr_ty.is_integral() => cast_ty,
+                    // numeric casts
+                    _ if expr_ty.is_numeric() && cast_ty.is_numeric() => cast_ty,
+                    (TyKind::Bool | TyKind::Char, TyKind::Int(_)) => cast_ty,
+                    (TyKind::Infer(ty::InferTy::Ty(_)), _) => {
+                        self.annotations_needed(&expr.span);
+                        Rc::new(Ty::error())
+                    }
+                    // integer literals are inferred to be `u8`
+                    (_, TyKind::Char)
+                        if expr_ty.is_integral() && self.infcx.unify(&expr_ty, &u8_ty).is_ok() =>
+                    {
+                        cast_ty
+                    }
                     _ => {
+                        let note = if cast_ty.kind == TyKind::Char && expr_ty.is_integral() {
+                            format!("\n    only `u8` can be cast as `char`, not `{}`", expr_ty)
+                        } else {
+                            String::new()
+                        };
                         self.error(format!(
-                            "Cannot cast {:?} to {}",
+                            "Cannot cast `{}` to `{}` at {}{}",
                             expr_ty,
-                            ty.span.to_snippet()
+                            ty.span.to_snippet(),
+                            expr.span.to_location(),
+                            note
                         ));
                         Rc::new(Ty::error())
                     }
                 }
             }
         };
+        if self.expr_diverges(expr, &ty) {
+            self.ctx.register_diverging(expr.id);
+        }
         self.ctx.insert_type(expr.id, ty);
     }
 
-    fn visit_block_post(&mut self, block: &'chk ast::Block) {
+    fn visit_block_post(&mut self, block: &'chk hir::Block) {
+        if block.stmts.iter().any(|stmt| self.ctx.diverges(stmt.id))
+            || block
+                .expr
+                .as_ref()
+                .is_some_and(|expr| self.ctx.diverges(expr.id))
+        {
+            self.ctx.register_diverging(block.id);
+        }
         let block_ty = self.get_block_type(block);
         self.ctx.insert_type(block.id, block_ty);
     }
 }
+
+/// Types which `print!` and friends can format with both `{}` and `{:?}`
+fn is_formattable(ty: &Ty) -> bool {
+    match &ty.kind {
+        TyKind::Bool | TyKind::Char | TyKind::Never | TyKind::Error => true,
+        _ if ty.is_numeric() || ty.is_str_ref() => true,
+        // references and boxes are formatted as the values they point to
+        TyKind::Ref(inner, _) => is_formattable(inner),
+        TyKind::Box(inner) => is_formattable(inner),
+        _ => false,
+    }
+}
+
+/// Find a type variable which is not inferred
+fn find_ty_var(ty: &Ty) -> Option<ty::InferTy> {
+    match &ty.kind {
+        TyKind::Infer(var) => Some(*var),
+        TyKind::Array(inner, _)
+        | TyKind::Ref(inner, _)
+        | TyKind::RawPtr(inner, _)
+        | TyKind::Box(inner) => find_ty_var(inner),
+        TyKind::Fn(params, ret) => params
+            .iter()
+            .find_map(|param| find_ty_var(param))
+            .or_else(|| find_ty_var(ret)),
+        _ => None,
+    }
+}
@@ -0,0 +1,161 @@
+use crate::hir::{self, Crate, Expr, ExprKind, HirId, Res, UnOp};
+use crate::middle::ty::TyKind;
+use crate::middle::Ctxt;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+/// Record place expressions whose values are moved out, and report moves out of
+/// places which cannot be left uninitialized
+pub fn check_moves(ctx: &mut Ctxt, krate: &Crate) -> Vec<String> {
+    let mut checker = MoveChecker {
+        ctx,
+        non_moving: HashSet::new(),
+        errors: vec![],
+    };
+    hir::visitor::go(&mut checker, krate);
+    checker.errors
+}
+
+struct MoveChecker<'ctx, 'chk> {
+    ctx: &'chk mut Ctxt<'ctx>,
+    /// Place expressions whose values are used without being moved out
+    /// (e.g. `a` in `a.b`, `*a` and `a = b`)
+    non_moving: HashSet<HirId>,
+    errors: Vec<String>,
+}
+
+impl MoveChecker<'_, '_> {
+    /// Place expressions whose values can be moved out
+    fn is_place_expr(&self, expr: &Expr) -> bool {
+        match &expr.kind {
+            ExprKind::Path(path) => matches!(path.res, Res::Local(_)),
+            ExprKind::Field(..) | ExprKind::Index(..) | ExprKind::Unary(UnOp::Deref, _) => true,
+            _ => false,
+        }
+    }
+
+    /// Check the place `expr` and places containing it can be partially moved out
+    fn check_move(&mut self, expr: &Expr) {
+        let mut place = expr;
+        loop {
+            match &place.kind {
+                ExprKind::Index(array, _) => {
+                    self.errors.push(format!(
+                        "Cannot move out of index of `{}` at {}",
+                        self.ctx.get_type(array.id),
+                        expr.span.to_location()
+                    ));
+                    return;
+                }
+                ExprKind::Field(base, _) => {
+                    let mut base_ty = self.ctx.get_type(base.id);
+                    loop {
+                        match &base_ty.kind {
+                            TyKind::Box(inner) => base_ty = Rc::clone(inner),
+                            TyKind::Ref(..) => {
+                                self.behind_pointer(expr, "a reference");
+                                return;
+                            }
+                            _ => break,
+                        }
+                    }
+                    if let Some(name) = base_ty.get_adt_name()
+                        && self.ctx.lookup_drop_impl(name).is_some()
+                    {
+                        self.implements_drop(&base_ty.to_string(), expr);
+                        return;
+                    }
+                    place = base;
+                }
+                ExprKind::Unary(UnOp::Deref, base) => match self.ctx.get_type(base.id).kind {
+                    TyKind::Ref(..) => {
+                        self.behind_pointer(expr, "a reference");
+                        return;
+                    }
+                    TyKind::RawPtr(..) => {
+                        self.behind_pointer(expr, "a raw pointer");
+                        return;
+                    }
+                    _ => place = base,
+                },
+                _ => return,
+            }
+        }
+    }
+
+    fn behind_pointer(&mut self, expr: &Expr, pointer: &str) {
+        self.errors.push(format!(
+            "Cannot move out of `{}`, which is behind {} at {}",
+            expr.span.to_snippet(),
+            pointer,
+            expr.span.to_location()
+        ));
+    }
+
+    fn implements_drop(&mut self, ty: &str, expr: &Expr) {
+        self.errors.push(format!(
+            "Cannot move out of type `{}`, which implements the `Drop` trait at {}",
+            ty,
+            expr.span.to_location()
+        ));
+    }
+}
+
+impl<'chk> hir::visitor::Visitor<'chk> for MoveChecker<'_, '_> {
+    fn visit_stmt(&mut self, stmt: &'chk hir::Stmt) {
+        if let hir::StmtKind::Let(let_stmt) = &stmt.kind
+            && let_stmt.is_wildcard()
+            && let Some(init) = &let_stmt.init
+        {
+            self.non_moving.insert(init.id);
+        }
+    }
+
+    // use pre order so that uses of places are known before visiting them
+    fn visit_expr(&mut self, expr: &'chk Expr) {
+        match &expr.kind {
+            ExprKind::Assign(lhs, _) => {
+                self.non_moving.insert(lhs.id);
+            }
+            ExprKind::Field(base, _)
+            | ExprKind::Index(base, _)
+            | ExprKind::Unary(UnOp::Deref, base)
+            | ExprKind::AddrOf(_, base) => {
+                self.non_moving.insert(base.id);
+            }
+            ExprKind::Print(_, fmt) | ExprKind::Panic(fmt) => {
+                // formatting macros take their arguments by reference
+                for arg in &fmt.args {
+                    self.non_moving.insert(arg.id);
+                }
+            }
+            ExprKind::Struct(_, fds, Some(base)) => {
+                // fields which are not specified are moved out of the base
+                self.non_moving.insert(base.id);
+                let ty = self.ctx.get_type(expr.id);
+                let adt_name = ty.get_adt_name().unwrap();
+                let adt = self.ctx.lookup_adt_def(adt_name).unwrap();
+                let moves_fields = adt.fields.iter().any(|(name, fd_ty)| {
+                    fds.iter().all(|(fd, _)| fd.symbol != *name) && self.ctx.needs_drop(fd_ty)
+                });
+                if moves_fields {
+                    if self.ctx.lookup_drop_impl(adt_name).is_some() {
+                        self.implements_drop(&ty.to_string(), base);
+                    } else {
+                        self.check_move(base);
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        if self.non_moving.contains(&expr.id) || !self.is_place_expr(expr) {
+            return;
+        }
+        let ty = self.ctx.get_type(expr.id);
+        if self.ctx.needs_drop(&ty) {
+            self.check_move(expr);
+            self.ctx.register_move(expr.id);
+        }
+    }
+}
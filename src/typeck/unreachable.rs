@@ -0,0 +1,87 @@
+use crate::hir::{self, Block, Crate, Expr, HirId, Stmt, StmtKind};
+use crate::lint::UNREACHABLE_CODE;
+use crate::middle::Ctxt;
+use std::collections::HashSet;
+
+/// Lint statements following a statement after which control never continues
+pub fn check_unreachable(ctx: &mut Ctxt, krate: &Crate) {
+    let mut checker = UnreachableChecker {
+        ctx,
+        unreachable: HashSet::new(),
+        num_enclosing: 0,
+    };
+    hir::visitor::go(&mut checker, krate);
+}
+
+struct UnreachableChecker<'ctx, 'chk> {
+    ctx: &'chk mut Ctxt<'ctx>,
+    /// Statements and tail expressions following a diverging statement
+    unreachable: HashSet<HirId>,
+    /// Number of the enclosing unreachable statements. Nothing inside them is reported
+    /// again
+    num_enclosing: usize,
+}
+
+impl UnreachableChecker<'_, '_> {
+    fn enter(&mut self, id: HirId) {
+        if self.unreachable.contains(&id) {
+            self.num_enclosing += 1;
+        }
+    }
+
+    fn exit(&mut self, id: HirId) {
+        if self.unreachable.contains(&id) {
+            self.num_enclosing -= 1;
+        }
+    }
+}
+
+impl<'chk> hir::visitor::Visitor<'chk> for UnreachableChecker<'_, '_> {
+    fn visit_block(&mut self, block: &'chk Block) {
+        if self.num_enclosing > 0 {
+            return;
+        }
+        let Some(i) = block
+            .stmts
+            .iter()
+            .position(|stmt| self.ctx.diverges(stmt.id))
+        else {
+            return;
+        };
+        let (desc, span) = match (block.stmts.get(i + 1), &block.expr) {
+            (Some(next), _) => match next.kind {
+                StmtKind::Expr(_) => ("expression", &next.span),
+                StmtKind::Semi(_) | StmtKind::Let(_) => ("statement", &next.span),
+            },
+            (None, Some(tail)) => ("expression", &tail.span),
+            (None, None) => return,
+        };
+        let msg = format!(
+            "Unreachable {} at {}\n    any code following the statement at {} is unreachable",
+            desc,
+            span.to_location(),
+            block.stmts[i].span.to_location()
+        );
+        self.ctx.buffer_lint(&UNREACHABLE_CODE, span, msg);
+        self.unreachable
+            .extend(block.stmts[i + 1..].iter().map(|stmt| stmt.id));
+        self.unreachable
+            .extend(block.expr.iter().map(|tail| tail.id));
+    }
+
+    fn visit_stmt(&mut self, stmt: &'chk Stmt) {
+        self.enter(stmt.id);
+    }
+
+    fn visit_stmt_post(&mut self, stmt: &'chk Stmt) {
+        self.exit(stmt.id);
+    }
+
+    fn visit_expr(&mut self, expr: &'chk Expr) {
+        self.enter(expr.id);
+    }
+
+    fn visit_expr_post(&mut self, expr: &'chk Expr) {
+        self.exit(expr.id);
+    }
+}
@@ -0,0 +1,166 @@
+//! Unsafety checking
+//!
+//! Calls to extern functions and `unsafe fn`, dereferences of raw pointers, pointer
+//! arithmetic and uses of `static mut` are only allowed in `unsafe` blocks and the bodies
+//! of `unsafe fn`.
+
+use crate::hir::{
+    self, Block, Crate, DefId, DefKind, Expr, ExprKind, HirId, Mutability, Res, UnOp, Unsafety,
+};
+use crate::lint::UNUSED_UNSAFE;
+use crate::middle::Ctxt;
+use crate::span::Span;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+/// Unnecessary `unsafe` blocks are linted
+pub fn check_unsafety(ctx: &mut Ctxt, krate: &Crate) -> Vec<String> {
+    let mut collector = UnsafeFnCollector {
+        unsafe_fns: HashSet::new(),
+    };
+    hir::visitor::go(&mut collector, krate);
+    let unsafe_fns = collector.unsafe_fns;
+
+    let mut checker = UnsafetyChecker {
+        ctx,
+        unsafe_fns,
+        unsafe_fn: None,
+        unsafe_blocks: vec![],
+        errors: vec![],
+    };
+    hir::visitor::go(&mut checker, krate);
+    checker.errors
+}
+
+struct UnsafeFnCollector {
+    unsafe_fns: HashSet<DefId>,
+}
+
+impl<'chk> hir::visitor::Visitor<'chk> for UnsafeFnCollector {
+    // functions in extern blocks are lowered to `unsafe fn`s
+    fn visit_func(&mut self, func: &'chk hir::Func) {
+        if func.unsafety == Unsafety::Unsafe {
+            self.unsafe_fns.insert(Rc::clone(&func.def_id));
+        }
+    }
+}
+
+struct UnsafeBlock {
+    id: HirId,
+    span: Span,
+    /// Whether any unsafe operation is performed in the block
+    used: bool,
+}
+
+struct UnsafetyChecker<'ctx, 'chk> {
+    ctx: &'chk mut Ctxt<'ctx>,
+    unsafe_fns: HashSet<DefId>,
+    /// Span of the name of the enclosing `unsafe fn`
+    unsafe_fn: Option<Span>,
+    /// Enclosing `unsafe` blocks. Nested ones are not pushed as they are never needed
+    unsafe_blocks: Vec<UnsafeBlock>,
+    errors: Vec<String>,
+}
+
+impl UnsafetyChecker<'_, '_> {
+    fn require_unsafe(&mut self, desc: &str, span: &Span) {
+        if let Some(block) = self.unsafe_blocks.last_mut() {
+            block.used = true;
+        } else if self.unsafe_fn.is_none() {
+            self.errors.push(format!(
+                "{} is unsafe and requires unsafe function or block at {}",
+                desc,
+                span.to_location()
+            ));
+        }
+    }
+}
+
+impl<'chk> hir::visitor::Visitor<'chk> for UnsafetyChecker<'_, '_> {
+    fn visit_func(&mut self, func: &'chk hir::Func) {
+        if func.unsafety == Unsafety::Unsafe {
+            self.unsafe_fn = Some(func.name.span.clone());
+        }
+    }
+
+    fn visit_func_post(&mut self, _func: &'chk hir::Func) {
+        self.unsafe_fn = None;
+    }
+
+    fn visit_block(&mut self, block: &'chk Block) {
+        if block.unsafety == Unsafety::Safe {
+            return;
+        }
+        let enclosing = match (self.unsafe_blocks.last(), &self.unsafe_fn) {
+            (Some(outer), _) => Some(format!("`unsafe` block at {}", outer.span.to_location())),
+            (None, Some(span)) => Some(format!("`unsafe` fn at {}", span.to_location())),
+            (None, None) => None,
+        };
+        match enclosing {
+            Some(enclosing) => {
+                let msg = format!(
+                    "Unnecessary `unsafe` block at {}\n    because it's nested under this {}",
+                    block.span.to_location(),
+                    enclosing
+                );
+                self.ctx.buffer_lint(&UNUSED_UNSAFE, &block.span, msg);
+            }
+            None => self.unsafe_blocks.push(UnsafeBlock {
+                id: block.id,
+                span: block.span.clone(),
+                used: false,
+            }),
+        }
+    }
+
+    fn visit_block_post(&mut self, block: &'chk Block) {
+        if self
+            .unsafe_blocks
+            .last()
+            .is_none_or(|outer| outer.id != block.id)
+        {
+            return;
+        }
+        let unsafe_block = self.unsafe_blocks.pop().unwrap();
+        if !unsafe_block.used {
+            let msg = format!(
+                "Unnecessary `unsafe` block at {}",
+                unsafe_block.span.to_location()
+            );
+            self.ctx
+                .buffer_lint(&UNUSED_UNSAFE, &unsafe_block.span, msg);
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &'chk Expr) {
+        match &expr.kind {
+            ExprKind::Call(func, _) => {
+                if let ExprKind::Path(path) = &func.kind
+                    && let Some(def_id) = path.res.def_id()
+                    && self.unsafe_fns.contains(def_id)
+                {
+                    let desc = format!("Call to unsafe function `{}`", path.span.to_snippet());
+                    self.require_unsafe(&desc, &expr.span);
+                }
+            }
+            // `is_null` is the only safe method of raw pointers
+            ExprKind::MethodCall(recv, method, _)
+                if self.ctx.get_type(recv.id).is_raw_ptr()
+                    && method.symbol.as_str() != "is_null" =>
+            {
+                let desc = format!("Call to unsafe method `{}`", method.symbol);
+                self.require_unsafe(&desc, &expr.span);
+            }
+            ExprKind::Unary(UnOp::Deref, inner) if self.ctx.get_type(inner.id).is_raw_ptr() => {
+                self.require_unsafe("Dereference of raw pointer", &expr.span);
+            }
+            // reads, writes and borrows alike
+            ExprKind::Path(path)
+                if matches!(path.res, Res::Def(DefKind::Static(Mutability::Mut), _)) =>
+            {
+                self.require_unsafe("Use of mutable static", &expr.span);
+            }
+            _ => (),
+        }
+    }
+}
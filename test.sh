@@ -19,3 +19,6 @@ success_or_stop "$?"
 
 ./tests/execute.sh
 success_or_stop "$?"
+
+./tests/run.sh
+success_or_stop "$?"
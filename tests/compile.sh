@@ -12,7 +12,8 @@ compile() {
     input="$1"
 
     rm $TMP
-    $RUSTC "$input" >$TMP
+    # remaining arguments are passed to the compiler
+    $RUSTC "$input" "${@:2}" >$TMP
     res="$?"
 
     if [ "$res" = "0" ]; then
@@ -23,6 +24,56 @@ compile() {
     fi
 }
 
+# the MIR of the input contains the expected line
+assert_mir() {
+    expected="$1"
+    input="$2"
+
+    compile "$input" --emit=mir "${@:3}"
+    if ! grep -qF -- "$expected" $TMP; then
+        echo -e "[${RED}ERROR${NC}] $input ${GRAY}=> \`$expected\` not found in MIR${NC}"
+        exit 1
+    fi
+}
+
+# the LLVM IR of the input contains the expected line
+assert_llvm() {
+    expected="$1"
+    input="$2"
+
+    compile "$input" "${@:3}"
+    if ! grep -qF -- "$expected" $TMP; then
+        echo -e "[${RED}ERROR${NC}] $input ${GRAY}=> \`$expected\` not found in LLVM IR${NC}"
+        exit 1
+    fi
+}
+
+# the LLVM IR of the input does not contain the line
+assert_not_llvm() {
+    unexpected="$1"
+    input="$2"
+
+    compile "$input" "${@:3}"
+    if grep -qF -- "$unexpected" $TMP; then
+        echo -e "[${RED}ERROR${NC}] $input ${GRAY}=> \`$unexpected\` found in LLVM IR${NC}"
+        exit 1
+    fi
+}
+
+# the LLVM IR of the input is shorter with -O
+assert_shrinks() {
+    input="$1"
+
+    compile "$input"
+    lines=$(wc -l <$TMP)
+    compile "$input" -O
+    optimized_lines=$(wc -l <$TMP)
+    if [ "$optimized_lines" -ge "$lines" ]; then
+        echo -e "[${RED}ERROR${NC}] $input ${GRAY}=> $lines lines of LLVM IR, but $optimized_lines with -O${NC}"
+        exit 1
+    fi
+}
+
 QT="'"
 NL=$'\n'
 
@@ -45,6 +96,12 @@ compile 'fn main() -> () { { let never: ! = (return ()); } }'
 compile 'fn main() -> () { { let unit: () = (return ()); } }'
 compile 'fn main() -> () { let never: ! = (return ()); }'
 compile 'fn main() -> () { let unit: () = (return ()); }'
+# divergence
+compile 'fn main() -> i32 { let u: (); u = (return 0); }'
+compile 'fn main() -> i32 { return 0; let a = 1; }'
+compile 'fn main() -> i32 { 1 + return 0; }'
+compile 'fn main() -> i32 { [1, return 0]; }'
+compile 'fn main() -> i32 { if true == true { return 0; } else { panic!("no"); }; }'
 # typeck block expr
 compile 'fn main() -> () { { let u: () = { }; } }'
 compile 'fn main() -> () { { let u: () = { () }; } }'
@@ -112,3 +169,49 @@ compile 'fn main() -> () { let a: i32 = 0; let b: i32 = { { a } }; }'
 # shadowing
 compile 'fn main() -> () { let a: i32 = 0; let a: i32 = 1; }'
 compile 'struct S { a: i32 } fn main() -> () { let a: i32 = 4; let a: S; }'
+# panic
+compile 'fn main() -> i32 { panic!("never returns") }'
+compile 'fn main() -> () { let a: i32 = panic!(); }'
+compile 'fn main() -> () { assert!(true); assert!(1 == 1, "msg"); assert_eq!(1, 1,); }'
+# macro_rules
+compile 'macro_rules! unit { () => { () }; } fn main() -> () { unit!() }'
+compile 'macro_rules! m { [$($x:expr);*] => { $($x;)* }; } fn main() -> () { m![1; 2; 3]; }'
+compile 'macro_rules! ty { ($t:ty) => { $t }; } fn main() -> () { let a: ty!(i32) = 0; }'
+# print
+compile 'fn main() -> () { print!("a"); println!(); println!("{}", 1,); eprint!("b"); eprintln!("{:?}", "c"); }'
+compile 'fn main() -> i32 { println!("{}", return 0); }'
+compile 'extern "C" { fn printf(s: &str) -> i32; } fn main() -> () { println!("{}", 1); }'
+# attributes
+compile '#[inline] #[allow(dead_code)] fn f() -> () { } fn main() -> () { }'
+compile 'fn main() -> () { #[cfg(debug)] println!("debug"); let a: bool = cfg!(feature = "x"); }'
+compile 'fn main() -> () { let a: [bool; 0] = []; let b = []; let c: [[i32; 0]; 1] = [b]; }'
+# MIR
+compile 'fn main() -> i32 { let a = [1, 2]; if a[0] == 1 { 0 } else { 1 } }' --emit=mir
+assert_mir 'if(move _1) -> [true: bb1, false: bb2];' 'fn main() -> i32 { if 1 == 1 { 0 } else { 1 } }'
+assert_mir '_0 = crate::f(const 1_i32) -> bb1;' 'fn f(a: i32) -> i32 { a } fn main() -> i32 { f(1) }'
+assert_mir 'drop(_1);' 'fn main() -> () { let b = Box::new(1); }'
+assert_mir 'eprint("thread '"'"'main'"'"' panicked at <anon>:1:36:\na = {0}\n", copy _1);' 'fn main() -> i32 { let a: i32 = 1; panic!("a = {}", a) }'
+assert_mir 'panic("assertion failed: a == 1");' 'fn main() -> () { let a: i32 = 1; assert!(a == 1); }'
+assert_mir 'deallocate(_1);' 'fn main() -> () { let b = Box::new(Box::new(1)); let c = *b; }'
+assert_mir 'if(copy _5) -> [true:' 'fn f(c: bool) -> () { let b = Box::new(1); if c == true { let d = b; } } fn main() -> () { }'
+# optimizations
+assert_mir '_0 = const 9_i32;' 'fn main() -> i32 { 11 + 8 * 2 - 3 * (1 + 5) }' -O
+assert_mir '_0 = const 8_i32;' 'fn main() -> i32 { let x = if 1 == 1 { 2 } else { 3 }; x * 4 }' -O
+assert_mir '_0 = Add(copy _1, const 1_i32);' 'fn f(a: i32) -> i32 { let b = a + 1; b } fn main() -> i32 { f(1) }' -O
+assert_shrinks 'fn main() -> i32 { let a = 4; let b = a * 2; if b > 5 { b + 1 } else { 0 } }'
+assert_shrinks 'struct P { x: i32, y: i32 } fn get(p: &P) -> i32 { p.x } fn main() -> i32 { let p = P { x: 1 + 2, y: 4 }; get(&p) + p.y }'
+# inlining
+assert_mir '_0 = copy (*_2).x;' 'struct P { x: i32, y: i32 } fn get(p: &P) -> i32 { p.x } fn main() -> i32 { let p = P { x: 1, y: 2 }; get(&p) }' -O
+assert_mir '_0 = const 1_i32;' '#[inline] fn big(a: i32) -> i32 { let b = a + 1; let c = b * 2; let d = c - 3; let e = d * d; let f = e + a; let g = f * b; let h = g - c; let i = h + d; let j = i * e; j } fn main() -> i32 { big(1) }' -O
+assert_mir '_0 = crate::big(const 1_i32) -> bb1;' 'fn big(a: i32) -> i32 { let b = a + 1; let c = b * 2; let d = c - 3; let e = d * d; let f = e + a; let g = f * b; let h = g - c; let i = h + d; let j = i * e; j } fn main() -> i32 { big(1) }' -O
+assert_mir '_0 = crate::f(const 1_i32) -> bb1;' '#[inline(never)] fn f(a: i32) -> i32 { a } fn main() -> i32 { f(1) }' -O
+assert_mir '_1 = crate::fact(const 3_i32) -> bb1;' 'fn fact(n: i32) -> i32 { if n == 0 { 1 } else { n * fact(n - 1) } } fn main() -> i32 { fact(3) + 1 }' -O
+assert_mir '_0 = crate::abs(const -3_i32) -> bb1;' 'extern "C" { fn abs(x: i32) -> i32; } #[inline(always)] fn f(x: i32) -> i32 { unsafe { abs(x) } } fn main() -> i32 { f(-3) }' -O
+assert_shrinks 'struct P { x: i32, y: i32 } impl Drop for P { #[inline] fn drop(&mut self) -> () { } } fn x(p: &P) -> i32 { p.x } fn y(p: &P) -> i32 { p.y } fn main() -> i32 { let p = P { x: 1, y: 2 }; x(&p) + y(&p) }'
+# SSA values
+assert_llvm '%_3.bb3 = phi i32 [%1, %bb1], [%2, %bb2]' 'fn f(c: bool, x: i32) -> i32 { let y = if c == true { x + 1 } else { x * 2 }; y + 3 } fn main() -> i32 { f(true, 3) }'
+assert_llvm 'ret i32 %_1' 'fn id(a: i32) -> i32 { let b = a; b } fn main() -> i32 { id(1) }'
+assert_not_llvm 'alloca' 'fn f(a: i64, b: bool) -> i64 { let c = a * 2; let d; if b == true { d = c; } else { d = a; }; d + c } fn main() -> i32 { f(3, true) as i32 }'
+assert_not_llvm 'phi' 'fn f(a: i32, c: bool) -> i32 { let b = a * 2; if c == true { println!("yes"); }; b } fn main() -> i32 { f(1, true) }'
+assert_llvm '%_1 = alloca i32' 'fn main() -> i32 { let a = 1; let r = &a; *r }'
+assert_llvm '%_1 = alloca i32*' 'fn main() -> i32 { let b = Box::new(1); *b }'
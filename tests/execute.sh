@@ -17,17 +17,51 @@ assert() {
     input="$2"
 
     rm $TMP $EXE
-    $RUSTC "$input" >$TMP
+    # remaining arguments are passed to the compiler
+    $RUSTC "$input" "${@:3}" >$TMP
     $LLC -o $ASM $TMP -opaque-pointers
     $CC -o $EXE $ASM
     chmod +x $EXE
     $EXE
     actual="$?"
+    # the interpreter must agree with the compiled program
+    $RUSTC "$input" "${@:3}" --run >/dev/null
+    interpreted="$?"
+    # and so must the program built from the x86-64 backend
+    rm $EXE
+    $RUSTC "$input" "${@:3}" --emit=asm >$ASM
+    $CC -o $EXE $ASM
+    $EXE
+    native="$?"
+
+    if [ "$actual" = "$expected" ] && [ "$interpreted" = "$expected" ] && [ "$native" = "$expected" ]; then
+        echo -e "[${GREEN}OK${NC}] $input ${GRAY}=> $actual${NC}"
+    else
+        echo -e "[${RED}ERROR${NC}] $input ${GRAY}=> $expected expected, but got $actual (--run: $interpreted, --emit=asm: $native)${NC}"
+        exit 1
+    fi
+}
+
+assert_stdout() {
+    expected="$1"
+    input="$2"
+
+    rm $TMP $EXE
+    $RUSTC "$input" "${@:3}" >$TMP
+    $LLC -o $ASM $TMP -opaque-pointers
+    $CC -o $EXE $ASM
+    chmod +x $EXE
+    actual=$($EXE)
+    interpreted=$($RUSTC "$input" "${@:3}" --run)
+    rm $EXE
+    $RUSTC "$input" "${@:3}" --emit=asm >$ASM
+    $CC -o $EXE $ASM
+    native=$($EXE)
 
-    if [ "$actual" = "$expected" ]; then
+    if [ "$actual" = "$expected" ] && [ "$interpreted" = "$expected" ] && [ "$native" = "$expected" ]; then
         echo -e "[${GREEN}OK${NC}] $input ${GRAY}=> $actual${NC}"
     else
-        echo -e "[${RED}ERROR${NC}] $input ${GRAY}=> $expected expected, but got $actual${NC}"
+        echo -e "[${RED}ERROR${NC}] $input ${GRAY}=> $expected expected, but got $actual (--run: $interpreted, --emit=asm: $native)${NC}"
         exit 1
     fi
 }
@@ -122,9 +156,215 @@ assert 1 'struct S { a: i32 }
 fn main() -> i32 { let s: S; s = if false { S { a: 0 } } else { S { a: 1 } }; s.a }'
 assert 30 'fn main() -> i32 { let a: i32 = 3; if a == 1 { 10 } else if a == 2 { 20 } else { 30 } }'
 assert 20 'fn main() -> i32 { let a: i32 = 2; if a == 1 { 10 } else if a == 2 { 20 } else { 30 } }'
+assert 1 'fn main() -> i32 { let b: bool = 1 == 1; if (b) { 1 } else { 0 } }'
+assert 3 'fn main() -> i32 { let a: i32 = 1; if a == 1 { a = 3; }; a }'
+assert 7 'fn main() -> i32 { let x: i32 = if true { return 7; } else { 2 }; x }'
 # return struct
 assert 0 'struct S { a: i32 } fn f() -> S { S { a: 0 } } fn main() -> i32 { f().a }'
 assert 42 'struct S { a: i32 } fn f() -> S { S { a: 42 } } fn main() -> i32 { f().a }'
 assert 100 'struct S { a: i32 } fn f(s: S) -> S { s } fn main() -> i32 { f(S { a: 100 }).a }'
 assert 5 'struct S { a: i32, b: i32 } fn f() -> S { S { a: 3, b: 5 } } fn main() -> i32 { f().b }'
 assert 7 'struct S { a: i32 } fn f(b: i32, c: i32) -> S { S { a: b + c } } fn main() -> i32 { f(3, 4).a }'
+# panic
+assert 101 'fn main() -> i32 { panic!("explicit panic") }'
+assert 101 'fn main() -> () { panic!(); }'
+assert 101 'fn f() -> ! { unreachable!() } fn main() -> i32 { f() }'
+assert 2 'fn f(a: i32) -> i32 { if a == 1 { unreachable!("a is one") } else { a } } fn main() -> i32 { f(2) }'
+assert 0 'fn main() -> i32 { let a: i32 = 3; assert!(a == 3); assert_eq!(a + 1, 4); 0 }'
+assert 101 'fn main() -> i32 { let b: bool = false; assert!(b, "b must be true"); 0 }'
+assert 101 'fn main() -> i32 { assert_eq!(1, 2); 0 }'
+assert 101 'fn main() -> i32 { let a: u8 = 3; panic!("a = {}, {:?}", a, "s") }'
+assert 101 'fn f(c: char) -> i32 { unreachable!("{:?} {}", c, true) } fn main() -> i32 { f('"'"'x'"'"') }'
+assert 0 'fn main() -> i32 { let a: i32 = 2; assert!(a == 2, "a is {}", a); assert_eq!(a, 2, "{}", a); 0 }'
+assert 101 'fn main() -> i32 { let a: i64 = 2; assert_eq!(a * 2, 5, "{} doubled", a); 0 }'
+# macro_rules
+assert 10 'macro_rules! double { ($e:expr) => { $e * 2 }; } fn main() -> i32 { double!(5) }'
+assert 8 'macro_rules! double { ($e:expr) => { $e * 2 }; } fn main() -> i32 { double!(1 + 3) }'
+assert 5 'macro_rules! one { () => { 1 }; } fn main() -> i32 { one!() + one!() * 4 }'
+assert 6 'macro_rules! sum { () => { 0 }; ($x:expr $(, $rest:expr)*) => { $x + sum!($($rest),*) }; } fn main() -> i32 { sum!(1, 2, 3) }'
+assert 3 'macro_rules! set { ($v:ident, $e:expr) => { $v = $e; }; } fn main() -> i32 { let a: i32 = 0; set!(a, 3); a }'
+assert 4 'macro_rules! max { ($a:expr, $b:expr) => { if $a > $b { $a } else { $b } }; } fn main() -> i32 { max!(1 + 1, 4) }'
+assert 10 'macro_rules! double { ($e:expr) => {{ let t: i32 = $e; t + t }}; } fn main() -> i32 { let t: i32 = 5; double!(t) }'
+assert 7 'macro_rules! make_fn { ($name:ident, $ty:ty, $body:block) => { fn $name() -> $ty $body }; } make_fn!(seven, i32, { 7 }); fn main() -> i32 { seven() }'
+assert 3 'macro_rules! first { ($a:tt $($rest:tt)*) => { $a }; } fn main() -> i32 { first!(3 4 5) }'
+assert 2 'macro_rules! opt { ($a:expr $(, $b:expr)?) => { $a $(- $b)? }; } fn main() -> i32 { opt!(5, 3) }'
+assert 1 'fn main() -> i32 { macro_rules! local { () => { 1 } } local!() }'
+# print
+assert_stdout 'Hello, world!' 'fn main() -> () { println!("Hello, world!"); }'
+assert_stdout '1 + 2 = 3' 'fn main() -> () { let a: i32 = 1; println!("{} + {} = {}", a, 2, a + 2); }'
+assert_stdout 'true false' 'fn main() -> () { print!("{} {}", true, 1 == 2); }'
+assert_stdout 'b a a' 'fn main() -> () { println!("{1} {0} {}", "a", "b"); }'
+assert_stdout '"str" '"'"'c'"'"' -1' 'fn main() -> () { println!("{:?} {:?} {:?}", "str", '"'"'c'"'"', -1); }'
+assert_stdout '"q\"x" '"'"'\n'"'"' "a\\b\t" '"'"'\'"'"''"'"' '"'"'"'"'"' "it'"'"'s" {0: "\"\""}' 'fn main() -> () { let s: &str = "it'"'"'s"; println!("{:?} {:?} {:?} {:?} {:?} {:?} {{0: {:?}}}", "q\"x", '"'"'\n'"'"', "a\\b\t", '"'"'\'"'"''"'"', '"'"'"'"'"', s, "\"\""); }'
+assert_stdout '[   42][42   ][0042][ab   ][  ab][hel]' 'fn main() -> () { println!("[{:5}][{:<5}][{:04}][{:5}][{:>4}][{:.3}]", 42, 42, 42, "ab", "ab", "hello"); }'
+assert_stdout 'x é 😀' 'fn main() -> () { let c: char = '"'"'é'"'"'; println!("{} {} {}", '"'"'x'"'"', c, '"'"'😀'"'"'); }'
+assert_stdout '{} 100%' 'fn main() -> () { println!("{{}} 100%"); }'
+assert_stdout 'a	b' 'fn main() -> () { println!("a\tb"); }'
+assert_stdout '' 'fn main() -> () { eprintln!("to stderr {}", 1); }'
+assert 3 'fn main() -> i32 { println!("{}", 1); 3 }'
+assert 1 'fn main() -> i32 { let c: char = '"'"'a'"'"'; if c == '"'"'a'"'"' { 1 } else { 0 } }'
+# cfg
+assert 1 '#[cfg(debug)] fn f() -> i32 { 2 } #[cfg(not(debug))] fn f() -> i32 { 1 } fn main() -> i32 { f() }'
+assert 2 '#[cfg(debug)] fn f() -> i32 { 2 } #[cfg(not(debug))] fn f() -> i32 { 1 } fn main() -> i32 { f() }' --cfg debug
+assert 3 'fn main() -> i32 { let a: i32 = 3; #[cfg(feature = "x")] let a: i32 = 4; a }'
+assert 4 'fn main() -> i32 { let a: i32 = 3; #[cfg(feature = "x")] let a: i32 = 4; a }' --cfg 'feature="x"'
+assert 5 '#[cfg(all(a, any(b, c)))] fn f() -> i32 { 5 } fn main() -> i32 { f() }' --cfg a --cfg c
+assert 0 'fn main() -> i32 { if cfg!(debug) { 1 } else { 0 } }'
+assert 1 'fn main() -> i32 { if cfg!(any(debug, test)) { 1 } else { 0 } }' --cfg test
+assert 8 'struct S { a: i32, #[cfg(big)] b: [i32; 100], c: i32 } fn main() -> i32 { let s: S = S { a: 3, c: 5 }; s.a + s.c }'
+assert 9 '#[cfg(a)] mod m { fn f() -> i32 { 1 } } fn main() -> i32 { 9 }'
+# type inference
+assert 5 'fn main() -> i32 { let x; x = 5; x }'
+assert 7 'fn main() -> i32 { let x = 3; let y = x + 4; y }'
+assert 3 'fn main() -> i32 { let a: [i32; 0] = []; let b = [1, 2, 3]; b[2] }'
+assert 6 'fn main() -> i32 { let a = [5, 6]; let b = a; b[1] }'
+assert 3 'fn main() -> i32 { let m = [[1, 2], [3, 4]]; m[1][0] }'
+assert 4 'struct S { a: i32 } fn main() -> i32 { let s = S { a: 4 }; let t = s; t.a }'
+assert 1 'fn main() -> i32 { let b; let c = true; b = c; if b == true { 1 } else { 0 } }'
+assert 10 'fn main() -> i32 { let x = 3; let c = if x == 3 { 10 } else { 20 }; c }'
+# type aliases
+assert 7 'type Board = [[i32; 8]; 8]; fn main() -> i32 { let b: Board; b[2][3] = 7; b[2][3] }'
+assert 2 'type P = Point; struct Point { x: i32, y: i32 } fn main() -> i32 { let p: P = Point { x: 1, y: 2 }; p.y }'
+assert 2 'type S = &'"'"'static str; fn len(s: S) -> i32 { 2 } fn main() -> i32 { let s: S = "hi"; len(s) }'
+assert 3 'mod m { type T = i32; fn f() -> T { 3 } } type U = crate::m::T; fn main() -> U { crate::m::f() }'
+assert 4 'type A = B; type B = i32; fn main() -> A { let b: B = 4; b }'
+assert 2 'type P = Point; struct Point { x: i32, y: i32 } fn main() -> i32 { let p = P { x: 1, y: 2 }; p.y }' -D warnings
+assert 3 'type Q = P; type P = M; struct M(i32); fn main() -> i32 { let m = Q(3); m.0 }' -D warnings
+assert 5 'mod m { struct S(i32); struct U; } type A = m::S; type V = m::U; fn main() -> i32 { let _u: m::U = V; let s = A(5); s.0 }' -D warnings
+assert 3 'mod m { struct S { a: i32 } fn f() -> i32 { let s: S = S { a: 3 }; s.a } } fn main() -> i32 { crate::m::f() }'
+# tuple structs, unit structs and struct update syntax
+assert 5 'struct Meters(i32); fn main() -> i32 { let m = Meters(5); m.0 }'
+assert 4 'struct P(i32, bool, i32,); fn main() -> i32 { let p: P = P(1, true, 3); if p.1 == true { p.0 + p.2 } else { 0 } }'
+assert 6 'struct W(i32); struct V(W); fn main() -> i32 { let v = V(W(6)); v.0.0 }'
+assert 9 'struct W(i32); fn get(w: W) -> i32 { w.0 } fn main() -> i32 { get(W(9)) }'
+assert 3 'struct Marker; fn main() -> i32 { let m = Marker; let n: Marker = Marker {}; 3 }'
+assert 12 'struct Point { x: i32, y: i32 } fn main() -> i32 { let x = 3; let y = 4; let p = Point { x, y }; p.x * p.y }'
+assert 15 'struct Point { x: i32, y: i32, z: i32 } fn main() -> i32 { let base = Point { x: 1, y: 2, z: 3 }; let p = Point { x: 10, ..base }; p.x + p.y + p.z }'
+assert 6 'struct In(i32, i32); struct Out { a: In, b: i32 } fn main() -> i32 { let o = Out { a: In(1, 2), b: 3 }; let o2 = Out { b: 4, ..o }; o2.a.1 + o2.b }'
+assert 1 'struct S(#[cfg(a)] bool, i32); fn main() -> i32 { let s = S(1); s.0 }'
+# floating-point numbers
+assert 22 'fn main() -> i32 { let x = 1.5; let y: f32 = 2e1; let z = 1f32 + y; let t = (x * 2.0 + -x) as i32 + z as i32; if z > 20.5 { t } else { 100 } }'
+assert 122 'struct B(i32, f32); struct A(i32, B); fn half(x: f32) -> f32 { x * 0.5 } fn main() -> i32 { let a = A(1, B(2, 3.0)); let f = 7 as f64 * 2.0; let g = if 1.5 as f32 as f64 == 1.5 { 100 } else { 0 }; (half(a.1.1) * 4.0) as i32 + a.1.0 + f as i32 + g }'
+assert 1 'fn main() -> i32 { let x = 0.1 + 0.2; if x != 0.3 { 1 } else { 0 } }'
+assert_stdout '2.5 2.5 0.1 0.30000000000000004 1.0 0.1 -3.75 [  -3.750] [1     ] [   2.5] 2 1e20' 'fn main() -> () { let f: f64 = 2.5; let g: f32 = 0.1; let z = 0.1 + 0.2; let one: f64 = 1.0; let n: f64 = -3.75; println!("{} {:.1} {} {} {:?} {:?} {} [{:8.3}] [{:<6}] [{:>6}] {:.0} {:?}", f, f, g, z, one, g, n, n, one, f, 2.5, 1e20); }'
+assert_stdout '1e-5 1.5e-7 0.00001 0.0001 1e16 9999999999999998.0 -0.0' 'fn main() -> () { let a: f64 = 0.00001; let b: f32 = 1.5e-7; println!("{:?} {:?} {} {:?} {:?} {:?} {:?}", a, b, a, 0.0001, 1e16, 9999999999999998.0, -0.0); }'
+assert_stdout 'inf -inf NaN 3.00 -02.2 4.5' 'fn main() -> () { let z: f64 = 0.0; let x: f32 = 3.0; println!("{} {} {:?} {:.2} {:05.1} {}", 1.0 / z, -1.0 / z, z / z, x, -2.25, x * 1.5); }'
+assert 101 'fn main() -> i32 { let x = 1.5; assert_eq!(x * 2.0, 2.5); 0 }'
+# casts
+assert_stdout '255 -1 4294967295 44 -56 65408' 'fn main() -> () { let a: i32 = -1; let b: i8 = -128; println!("{} {} {} {} {} {}", a as u8, a as i64, a as u32, 300 as u8, 200 as u8 as i8, b as u16); }'
+assert_stdout '1 65 a A 98' 'fn main() -> () { let b: u8 = 66; println!("{} {} {} {} {}", true as i32 + false as i32, '$QT'A'$QT' as u32, 97 as char, 321 as u8 as char, (b as char) as u64 + 32); }'
+assert_stdout '2147483647 0 -2147483648 3 -1' 'fn main() -> () { println!("{} {} {} {} {}", 1e10 as i32, -5.5 as u8, (1e300 * -1e300) as i32, 3.99 as i64, -1.5f32 as i8); }'
+assert_stdout '255 true 4000000000 true -294967296' 'fn main() -> () { let b: u8 = 255; let big: u64 = 4000000000; println!("{} {} {} {} {}", (b as f64) as i32, (-3 as f32 * 0.5) as f64 as f32 == -1.5, big, big > 3, big as i32); }'
+assert_stdout '18446744073709551615 -9223372036854775808 -128' 'fn main() -> () { let a: u64 = 18446744073709551615; let b: i64 = -9223372036854775808; let c: i8 = -128; println!("{} {} {}", a, b, c); }'
+assert 5 'fn main() -> i32 { let x = 5; let p = &x as *const i32 as u64; let q = p as *const i32; unsafe { *q } }'
+assert 12 'extern "C" { fn ldexp(x: f64, e: i32) -> f64; } fn main() -> i32 { unsafe { ldexp(1.5, 3) as i32 } }'
+assert 3 'extern "C" { fn abs(x: i32) -> i32; } unsafe fn f(x: i32) -> i32 { abs(x) } fn main() -> i32 { unsafe { f(-3) } }'
+assert 7 'struct P { a: i32, b: i32 } fn main() -> i32 { let x = P { a: 1, b: 7 }; let p = &x as *const P; unsafe { (*p).b } }'
+assert 4 'fn main() -> i32 { let x = 4; let p = &x as *const i32; let y = unsafe { unsafe { *p } }; y }'
+assert 9 'fn main() -> i32 { let x = 4; let p = &mut x as *mut i32; unsafe { *p = *p + 5; }; x }'
+assert 10 'fn main() -> i32 { let a = [1, 2, 3, 4]; let p = &a[0] as *const i32; unsafe { *p + *p.add(1) + *p.add(3).offset(-1) + *p.offset(3) } }'
+assert 20 'fn sum(p: *const i32, n: usize) -> i32 { if n == 0 { return 0; } unsafe { *p + sum(p.add(1), n - 1) } } fn main() -> i32 { let a = [2, 4, 6, 8]; sum(&a[0] as *const i32, 4) }'
+assert 6 'fn main() -> i32 { let a = [5, 6]; let p = &a[0] as *const i32; let q = (p as usize + 4) as *const i32; unsafe { *q } }'
+assert 2 'fn main() -> i32 { let x = 1; let p = &mut x as *mut i32; x = 2; unsafe { *p } }'
+assert_stdout 'true true false' 'fn main() -> () { let x = 1; let p: *const i32 = core::ptr::null(); let q: *mut i32 = std::ptr::null_mut(); println!("{} {} {}", p.is_null(), q.is_null(), (&x as *const i32).is_null()); }'
+assert_stdout '0 7 true' 'fn main() -> () { let p: *const i32 = core::ptr::null(); let n: usize = 3; let m = n * 2 + 1; println!("{} {} {}", p as usize, m as i32, m > n); }'
+assert 94 'fn sub(a: i32, b: i32, c: i32, d: i32) -> i32 { a - b - c - d } fn main() -> i32 { sub(100, 3, 2, 1) }'
+# Box
+assert 5 'fn main() -> i32 { let b = Box::new(5); let c = b; *c }'
+assert 8 'fn inc(b: Box<i32>) -> Box<i32> { *b = *b + 1; b } fn main() -> i32 { let b = inc(Box::new(7)); *b }'
+assert 3 'fn main() -> i32 { let b = Box::new(Box::new(3)); **b }'
+assert 4 'fn main() -> i32 { let b; b = Box::new(1); b = Box::new(4); *b }'
+assert 3 'fn main() -> i32 { let a = Box::new(1); let b = a; a = Box::new(2); *a + *b }'
+assert 30 'struct P { x: i32, y: i32 } fn mk() -> Box<P> { Box::new(P { x: 10, y: 20 }) } fn main() -> i32 { let p = *mk(); mk().y + p.x }'
+assert 7 'struct Node { val: i32, next: Box<i32> } fn take(n: Node) -> i32 { n.val + *n.next } fn main() -> i32 { let n = Node { val: 3, next: Box::new(4) }; let m = n; take(m) }'
+assert 7 'struct P { x: i32, b: Box<i32> } fn main() -> i32 { let p = P { x: 1, b: Box::new(2) }; let q = P { x: 5, ..p }; q.x + *q.b }'
+assert 4 'fn main() -> i32 { let a = [Box::new(1), Box::new(2), Box::new(3)]; Box::new(0); *a[0] + *a[2] }'
+assert 3 'struct Node { val: i32, next: Box<Node> } fn second(n: Node) -> i32 { n.next.val } fn main() -> i32 { 3 }'
+assert 55 'struct S { a: i32, b: Box<i32> } fn f(c: bool) -> S { let x = Box::new(1); if c == true { let s = S { a: 2, b: Box::new(3) }; return s; } S { a: 4, b: x } } fn main() -> i32 { let s = f(true); let t = f(false); s.a + *s.b + t.a * 10 + *t.b * 10 }'
+assert_stdout $'5 7 "s"\n8' 'fn main() -> () { let x = 5; let r = &x; let b = Box::new(7); println!("{} {} {:?}", r, b, &&"s"); let c = b; println!("{}", *c + 1); }'
+assert 101 'fn main() -> i32 { let b = Box::new(Box::new(3)); let r = &b; panic!("{} {:?}", r, &true) }'
+# Drop
+D='struct D { n: i32 } impl Drop for D { fn drop(&mut self) { println!("drop {}", self.n); } }'
+assert_stdout $'drop 3\ndrop 2\ndrop 1' "$D"' fn main() -> () { let a = D { n: 1 }; let b = D { n: 2 }; let c = D { n: 3 }; }'
+assert_stdout $'drop 2\ndrop 1' "$D"' fn f(c: bool) -> i32 { let a = D { n: 1 }; if c == true { let b = D { n: 2 }; return 0; } 1 } fn main() -> () { f(true); }'
+assert_stdout $'drop 1\nend\ndrop 2' "$D"' fn main() -> () { let a = D { n: 1 }; a = D { n: 2 }; println!("end"); }'
+assert_stdout $'take 1\ndrop 1\ndrop 2' "$D"' struct P { x: D, y: D } fn take(d: D) -> () { println!("take {}", d.n); } fn main() -> () { let p = P { x: D { n: 1 }, y: D { n: 2 } }; take(p.x); }'
+assert_stdout $'end\ndrop 1' "$D"' fn take(d: D) -> () { } fn main() -> () { let a = D { n: 1 }; if false == true { take(a); } else { }; println!("end"); }'
+assert_stdout $'drop 1\nend' "$D"' fn take(d: D) -> () { } fn main() -> () { let a = D { n: 1 }; if true == true { take(a); } else { }; println!("end"); }'
+assert_stdout $'drop 4\nend\ndrop 5' "$D"' fn main() -> () { let b = Box::new(D { n: 4 }); let d = *b; d = D { n: 5 }; println!("end"); }'
+assert_stdout $'s 7\nd 1\nd 2' 'struct D { n: i32 } impl Drop for D { fn drop(&mut self) { println!("d {}", self.n); } } struct S { a: D, b: D } impl Drop for S { fn drop(&mut self) { println!("s {}", self.a.n + self.b.n * 3); } } fn main() -> () { let s = S { a: D { n: 1 }, b: D { n: 2 } }; }'
+assert_stdout $'drop 3\nend' "$D"' fn main() -> () { { D { n: 3 }; } println!("end"); }'
+# `_` binds nothing: temporaries are dropped at once, and places are not moved
+assert_stdout $'drop 1\ndrop 2\nend\ndrop 3' "$D"' fn mk(n: i32) -> D { D { n: n } } fn main() -> () { let _ = mk(1); let _ = mk(2); println!("end"); let c = mk(3); }'
+assert_stdout $'end\ndrop 1' "$D"' fn main() -> () { let a = D { n: 1 }; let _ = a; println!("end"); let b = a; }'
+assert_stdout $'drop 1\n2\ndrop 2' "$D"' fn take(d: D) -> () { } fn main() -> () { let a = D { n: 1 }; take(a); a = D { n: 2 }; println!("{}", a.n); }'
+# References
+assert 3 'fn main() -> i32 { let x = 3; let r = &x; *r }'
+assert 5 'fn inc(r: &mut i32) -> () { *r = *r + 1; } fn main() -> i32 { let x = 3; inc(&mut x); inc(&mut x); x }'
+assert 5 'fn get(x: i32) -> i32 { let r = &x; *r + 1 } fn main() -> i32 { get(4) }'
+assert 33 'struct P { x: i32, y: i32 } fn sum(p: &P) -> i32 { p.x + p.y } fn main() -> i32 { let p = P { x: 1, y: 2 }; sum(&p) + sum(&P { x: 10, y: 20 }) }'
+assert 12 'fn first(a: &[i32; 3]) -> &i32 { &a[0] } fn main() -> i32 { let a = [7, 8, 9]; let r = first(&a); *r + *&5 }'
+assert 3 'fn main() -> i32 { let b = Box::new([1, 2]); let r = &b; r[1] + b[0] }'
+assert 7 'struct P { a: i32, b: i32 } fn main() -> i32 { let p = P { a: 1, b: 2 }; let r = &mut p.a; p.b = 5; *r = 2; p.a + p.b }'
+assert 4 'fn main() -> i32 { let x = 1; let r = &x; let y = *r + x; x = 2; y + x }'
+assert 2 'fn main() -> i32 { let x = 1; let p = &mut x; let r = &mut *p; *r = 2; *p }'
+assert_stdout $'x 3\ndrop 3\ndrop 2\ndrop 1' "$D"' fn get(d: &D) -> i32 { d.n } fn main() -> () { let a = D { n: 1 }; let x = get(&D { n: 2 }) + get(&a); println!("x {}", x); let b = &Box::new(D { n: 3 }); }'
+
+# Lifetimes
+assert 5 'fn max<'$QT'a>(x: &'$QT'a i32, y: &'$QT'a i32) -> &'$QT'a i32 { if (*x > *y) { x } else { y } } fn main() -> i32 { let a = 3; let b = 5; *max(&a, &b) }'
+assert 1 'fn first<'$QT'a>(x: &'$QT'a i32, y: &i32) -> &'$QT'a i32 { x } fn main() -> i32 { let a = 1; let r; { let b = 2; r = first(&a, &b); }; *r }'
+assert 4 'struct S<'$QT'a> { r: &'$QT'a i32 } fn get<'$QT'a>(s: S<'$QT'a>) -> &'$QT'a i32 { s.r } fn main() -> i32 { let x = 4; let s = S { r: &x }; *get(s) }'
+assert 6 'struct S<'$QT'a> { r: &'$QT'a i32 } fn get<'$QT'a>(s: &S<'$QT'a>) -> &'$QT'a i32 { s.r } fn main() -> i32 { let x = 6; let r; { let s = S { r: &x }; r = get(&s); }; *r }'
+assert_stdout 'hi' 'fn name(x: &i32) -> &'$QT'static str { "hi" } fn main() -> () { let s; { let x = 1; s = name(&x); }; println!("{}", s); }'
+
+# Divergence
+assert 3 'fn main() -> i32 { return 3; 2 }'
+assert 6 'fn main() -> i32 { let x = { return 6; 2 }; x }'
+assert 7 'fn f(a: i32, b: i32) -> i32 { a } fn main() -> i32 { f(1, return 7) }'
+assert 12 'fn f(c: bool) -> i32 { let x = if c == true { return 1; 5 } else { 2 }; x } fn main() -> i32 { f(false) + f(true) * 10 }'
+assert_stdout 'drop 1' "$D"' fn main() -> i32 { let a = D { n: 1 }; return 0; let b = D { n: 2 }; }'
+assert_stdout $'g\n3' 'fn g() -> () { println!("g"); } fn f(u: ()) -> i32 { 3 } fn main() -> () { println!("{}", f(g())); }'
+
+# Division
+assert 3 'fn main() -> i32 { 7 / 2 }'
+assert 5 'fn main() -> i32 { 10 - 3 - 2 }'
+assert_stdout '-3 -1 2 3' 'fn main() -> () { let a: i8 = -7; let b: u8 = 250; println!("{} {} {} {}", a / 2, a % 3, b % 8, (7.5f64 / 2.5) as i32); }'
+assert 101 'fn div(a: i32, b: i32) -> i32 { a / b } fn main() -> i32 { div(1, 0) }'
+assert 101 'fn rem(a: i8, b: i8) -> i8 { a % b } fn main() -> i32 { rem(-128, -1) as i32 }'
+
+# Constants
+assert 7 'const N: i32 = 3 + 4; fn main() -> i32 { N }'
+assert 4 'fn len(a: [i32; 4]) -> i32 { a[3] } fn main() -> i32 { let a: [i32; 2 + 2] = [1, 2, 3, 4]; len(a) }'
+assert 24 'const N: usize = fact(4) as usize; const fn fact(n: u64) -> u64 { if n == 0 { 1 } else { n * fact(n - 1) } } fn main() -> i32 { let _a: [u8; N]; N as i32 }'
+assert_stdout '-128 true c 6' 'const A: i8 = -128; const B: bool = A < 0; const C: char = (98 + 1) as u8 as char; struct S { a: [i32; M * 3] } const M: usize = L / 2; const L: usize = 4; fn main() -> () { let s = S { a: [1, 2, 3, 4, 5, 6] }; println!("{} {} {} {}", A, B, C, s.a[5]); }'
+assert 9 'const fn sq(x: i32) -> i32 { let y = x * x; if y > 100 { return 100; } y } const N: i32 = sq(3); fn main() -> i32 { sq(N) - 72 }'
+
+# Statics
+assert 12 'static mut COUNT: i32 = 0; fn bump() -> i32 { unsafe { COUNT = COUNT + 1; COUNT } } fn main() -> i32 { bump(); bump(); let p = unsafe { &mut COUNT }; *p = *p + 10; unsafe { COUNT } }' -A unused_results
+assert_stdout '250 true x -5' 'const N: u8 = 50; static LIMIT: u8 = 200 + N; static FLAG: bool = true; static C: char = '"'"'x'"'"'; mod m { static mut X: i64 = -5; } fn main() -> () { let r = &LIMIT; println!("{} {} {} {}", *r, FLAG, C, unsafe { m::X }); }'
+assert 7 'static X: i32 = 7; fn f() -> &'"'"'static i32 { &X } fn main() -> i32 { *f() }'
+assert 254 'static mut X: u8 = 255; unsafe fn dec() -> () { X = X - 1; } fn main() -> i32 { unsafe { dec(); X as i32 } }' -O
+
+# Lints
+assert 0 '#[allow(unused_variables)] fn main() -> i32 { let x = 1; 0 }' -D warnings
+assert 0 '#[deny(unused_variables)] fn main() -> i32 { let _x = 1; 0 }'
+assert 1 '#[allow(dead_code)] mod m { fn f() -> () { } } fn f(x: i32) -> i32 { x } fn main() -> i32 { let x; x = 2; f(1) }' -A unused_variables -D dead_code
+assert 0 '#[deny(dead_code)] fn _f() -> () { } fn g() -> () { } struct D { n: i32 } impl Drop for D { fn drop(&mut self) -> () { g(); } } fn main() -> i32 { let d = D { n: 0 }; d.n }'
+assert 3 'fn f() -> i32 { 3 } #[allow(warnings)] fn main() -> i32 { f(); unsafe { }; f() }' -W unused_results
+assert 0 'fn f() -> i32 { 1 } fn main() -> i32 { #[allow(unused_variables)] let x = 1; #[allow(unused_results)] f(); 0 }' -D warnings
+assert 1 'mod m { type T = i32; } fn main() -> i32 { let x: m::T = 1; x }' -D warnings
+assert 0 'mod m { struct S { a: i32 } } mod n { type U = crate::m::S; } fn f(s: &n::U) -> i32 { s.a } fn main() -> i32 { f(&crate::m::S { a: 0 }) }' -D warnings
+assert 22 'struct P { a: i32 } fn main() -> i32 { let mut b = 2; b = b + 1; let mut c = P { a: 1 }; c.a = 3; let mut d = [1, 2]; let r = &mut d; r[0] = 5; let mut e = Box::new(1); *e = 4; let mut f = 0; let g = &mut f; *g = 1; let mut h; h = 3; let mut _i = 0; let mut j = [1]; j[0] = 2; 1 + b + c.a + d[0] + *e + f + h + j[0] }' -D warnings
+assert 44 '#[allow(overflowing_literals)] fn main() -> i32 { let x: u8 = 300; x as i32 }'
+
+# Optimizations
+assert 9 'fn main() -> i32 { 11 + 8 * 2 - 3 * (1 + 5) }' -O
+assert 3 'fn main() -> i32 { let a = 1; let b = a + 2; if b == 3 { b } else { 0 } }' -O
+assert 255 'fn main() -> i32 { let a: u8 = 250; let m: i8 = -1; (a + 10) as i32 + m as u8 as i32 - 4 }' -O
+assert 101 'fn main() -> i32 { let z = 0; 1 / z }' -O
+assert 7 'struct P { x: i32, y: i32 } fn get(p: &P) -> i32 { p.x } fn main() -> i32 { let p = P { x: 1 + 2, y: 4 }; get(&p) + p.y }' -O
+assert_stdout $'3 true x\ndrop' 'struct D { n: i32 } impl Drop for D { fn drop(&mut self) -> () { println!("drop"); } } fn main() -> () { let d = Box::new(D { n: 1 + 2 }); let c = 120 as u8 as char; println!("{} {} {}", d.n, 2 > 1, c); }' -O
+assert 15 'struct P { x: i32, y: i32 } fn mk(x: i32) -> P { P { x: x, y: x * 2 } } #[inline(never)] fn sum(p: P) -> i32 { p.x + p.y } fn fact(n: i32) -> i32 { if n == 0 { 1 } else { n * fact(n - 1) } } fn main() -> i32 { let p = mk(3); sum(p) + fact(3) }' -O
+assert 101 '#[inline(always)] fn div(a: i32, b: i32) -> i32 { a / b } fn main() -> i32 { div(1, 0) }' -O
+assert 101 'fn fail(code: i32) -> ! { println!("{}", code); panic!("fail"); } fn check(a: i32) -> i32 { if a == 2 { fail(a) } else { a } } fn main() -> i32 { check(1) + check(2) }' -O
+assert_stdout $'3\ndrop 1' 'struct D { n: i32 } impl Drop for D { fn drop(&mut self) -> () { println!("drop {}", self.n); } } struct W { d: D, k: i32 } #[inline] fn wrap(n: i32) -> W { W { d: D { n: n }, k: n + 2 } } fn k(w: &W) -> i32 { w.k } fn main() -> () { let w = wrap(1); println!("{}", k(&w)); }' -O
@@ -12,7 +12,7 @@ NC='\033[0m' # No Color
 
 compile_fail() {
   input="$1"
-  $RUSTC "$input" #>&/dev/null
+  $RUSTC "$input" "${@:2}" #>&/dev/null
   code="$?"
   if [ "$code" = 1 ]; then
     echo -e "[${GREEN}OK${NC}] $input"
@@ -22,6 +22,20 @@ compile_fail() {
   fi
 }
 
+# the compilation fails and the expected message is reported
+compile_fail_with() {
+  expected="$1"
+  input="$2"
+  msg=$($RUSTC "$input" "${@:3}" 2>&1 >/dev/null)
+  code="$?"
+  if [ "$code" = 1 ] && grep -qF -- "$expected" <<<"$msg"; then
+    echo -e "[${GREEN}OK${NC}] $input"
+  else
+    echo -e "[${RED}ERROR${NC}] $input ${GRAY}=> Exit with code $code without \`$expected\`${NC}"
+    exit 1
+  fi
+}
+
 QT="'"
 
 echo "===== Failure Tests ====="
@@ -31,8 +45,9 @@ compile_fail 'fn main() -> i32 { a; return 0; }'
 compile_fail 'fn main() -> i32 { }'
 # assign number to bool
 compile_fail 'fn main() -> i32 { let b: bool; b = 100; }'
-# assign ! to ()
-compile_fail 'fn main() -> i32 { let u: (); u = (return 0); }'
+# falling off the end of the body which does not diverge
+compile_fail 'fn main() -> i32 { let u: (); u = (); }'
+compile_fail 'fn main() -> i32 { if true == true { return 0; } else { }; }'
 # ill-typed arithmetic
 compile_fail 'fn main() -> i32 { return (1+true)*2; }'
 # unexpected type of return value
@@ -43,6 +58,9 @@ compile_fail 'fn main() -> i32 { let a: i32; a = { 1; true }; }'
 compile_fail 'fn take_three(a: i32, b: i32, c: i32) -> () { } fn main() -> i32 { take_three(1, 2); 0 }'
 # mismatch type of argument
 compile_fail 'fn take_bool(b: bool) -> () { } fn main() -> i32 { take_bool(0); 0 }'
+# functions are not values
+compile_fail_with 'Cannot use function `g` as a value, only call it at <anon>:1:54' 'fn g(x: i32) -> i32 { x } fn main() -> i32 { let f = g; 0 }'
+compile_fail 'fn g() -> i32 { 1 } fn h(x: i32) -> i32 { x } fn main() -> i32 { h(g) }'
 # type of let statement
 compile_fail 'fn main() -> i32 { { let unit: () = (); } }'
 # array expr with no element
@@ -69,3 +87,230 @@ compile_fail 'fn main() -> () { { { let a: (); } }  { a } }'
 compile_fail 'fn main() -> () { a; let a: (); }'
 compile_fail 'fn main() -> () { let a: i32 = { { { let b: i32 = 0 } } b }; }'
 compile_fail 'fn main() -> () { let a: i32 = 0; let b: i32 = { { a + b } }; }'
+# macros
+compile_fail 'fn main() -> () { undefined_macro!(); }'
+compile_fail 'fn main() -> () { panic!(1); }'
+compile_fail 'fn main() -> () { assert!(1); }'
+compile_fail 'fn main() -> () { assert_eq!(1, true); }'
+compile_fail 'fn main() -> () { panic!("{}"); }'
+compile_fail 'fn main() -> () { panic!("msg", 1); }'
+compile_fail 'fn main() -> () { unreachable!("{} {}", 1); }'
+compile_fail 'fn main() -> () { assert_eq!(1); }'
+compile_fail 'fn main() -> () { let p: *const f64 = core::ptr::null(); println!("{:?}", &p); }'
+compile_fail 'macro_rules! m { (a) => { 1 }; } fn main() -> i32 { m!(b) }'
+compile_fail 'macro_rules! m { () => { m!() }; } fn main() -> i32 { m!() }'
+compile_fail 'fn main() -> i32 { m!() } macro_rules! m { () => { 1 }; }'
+compile_fail 'fn f() -> i32 { macro_rules! m { () => { 1 } } m!() } fn main() -> i32 { m!() }'
+compile_fail 'macro_rules! m { ($e:expr) => { $e }; } fn main() -> i32 { m!(1 +) }'
+# names renamed by hygiene are reported as written
+compile_fail_with 'Unused variable: `x` at' 'macro_rules! m { () => { let x = 1; }; } fn main() -> () { m!(); }' -D unused_variables
+compile_fail_with 'Use of moved value `b` at' 'macro_rules! m { () => { let b = Box::new(1); let c = b; let d = b; }; } fn main() -> () { m!(); }'
+compile_fail_with 'Cannot borrow `x` as immutable' 'macro_rules! m { () => { let x = 1; let r = &mut x; let s = &x; *r = 2; }; } fn main() -> () { m!(); }'
+compile_fail 'fn main() -> () { println!("{}"); }'
+compile_fail 'fn main() -> () { println!("{}", 1, 2); }'
+compile_fail 'fn main() -> () { println!("{1}", 1); }'
+compile_fail 'fn main() -> () { println!("{", 1); }'
+compile_fail 'fn main() -> () { println!("}"); }'
+compile_fail 'fn main() -> () { println!("{:^5}", 1); }'
+compile_fail 'fn main() -> () { println!(1); }'
+compile_fail 'fn main() -> () { print!(); }'
+compile_fail 'fn main() -> () { println!("{}", ()); }'
+compile_fail 'struct S { a: i32 } fn main() -> () { let s: S = S { a: 1 }; println!("{:?}", s); }'
+# cfg
+compile_fail '#[cfg(debug)] fn f() -> i32 { 1 } fn main() -> i32 { f() }'
+compile_fail '#[cfg(not(debug))] fn f() -> i32 { 1 } fn main() -> i32 { f() }' --cfg debug
+compile_fail 'struct S { #[cfg(debug)] a: i32 } fn main() -> i32 { let s: S = S { a: 1 }; s.a }'
+compile_fail '#[cfg] fn main() -> () { }'
+compile_fail '#[cfg(a, b)] fn main() -> () { }'
+compile_fail '#[cfg(foo(a))] fn main() -> () { }'
+compile_fail '#[cfg(a)] fn main() -> () { ( }'
+compile_fail '#[cfg(a) fn main() -> () { }'
+compile_fail 'fn main() -> () { }' --cfg 'feature=x'
+# type inference
+compile_fail 'fn main() -> i32 { let x = 3; let y: bool = x; 0 }'
+compile_fail 'fn main() -> i32 { let x; 0 }'
+compile_fail 'fn main() -> i32 { let x = []; 0 }'
+compile_fail 'fn main() -> i32 { let x; x = true; x }'
+compile_fail 'fn main() -> i32 { if true { 1 } else { false } }'
+compile_fail 'fn f(a: bool) -> i32 { 0 } fn main() -> i32 { f(1) }'
+compile_fail 'fn main() -> i32 { let x; let y: bool = x; let z: i32 = x; 0 }'
+compile_fail 'fn main() -> i32 { let a = [1, true]; 0 }'
+# type aliases
+compile_fail 'type A = B; type B = A; fn main() -> () { }'
+compile_fail 'type A = [A; 2]; fn main() -> () { }'
+compile_fail 'type A = Nope; fn main() -> () { }'
+compile_fail 'type A = i32; fn main() -> A { true }'
+compile_fail 'type A = i32 fn main() -> () { }'
+compile_fail 'type A = i32; fn main() -> i32 { A { x: 1 }; 0 }'
+compile_fail 'type A = B; type B = A; fn main() -> i32 { A(1); 0 }'
+compile_fail 'type A = [i32; 1]; fn main() -> i32 { A(1); 0 }'
+# tuple structs, unit structs and struct update syntax
+compile_fail 'struct P { a: i32, b: i32 } fn main() -> i32 { let p = P { a: 1 }; 0 }'
+compile_fail 'struct P { a: i32 } fn main() -> i32 { let p = P { a: 1, a: 2 }; 0 }'
+compile_fail 'struct W(i32); fn main() -> i32 { let w = W(true); 0 }'
+compile_fail 'struct W(i32); fn main() -> i32 { let w = W(1, 2); 0 }'
+compile_fail 'struct W(i32); fn main() -> i32 { let w = W(1); w.1 }'
+compile_fail 'struct P { a: i32 } struct Q { a: i32 } fn main() -> i32 { let q = Q { a: 1 }; let p = P { ..q }; 0 }'
+compile_fail 'struct W(i32) fn main() -> () { }'
+compile_fail_with 'Cannot use struct `M` as a value, only call it at <anon>:1:43' 'struct M(i32); fn main() -> i32 { let f = M; 0 }'
+# floating-point numbers
+compile_fail 'fn main() -> i32 { let x = 1 + 1.5; 0 }'
+compile_fail 'fn main() -> i32 { let x: i32 = 1.5; 0 }'
+compile_fail 'fn main() -> i32 { let x: f32 = 1.5; let y: f64 = x; 0 }'
+compile_fail 'fn main() -> i32 { println!("{:.}", 1.5); 0 }'
+# Box
+compile_fail 'fn main() -> i32 { let x = 1; *x }'
+compile_fail 'fn main() -> i32 { let b: Box<bool> = Box::new(1); 0 }'
+compile_fail 'struct Node { val: i32, next: Node } fn main() -> i32 { 0 }'
+compile_fail 'struct A { b: B } struct B { a: [A; 2] } fn main() -> i32 { 0 }'
+compile_fail 'fn main() -> i32 { let b = Box::new(1, 2); 0 }'
+# moved-from boxes are not freed again
+compile_fail 'fn main() -> i32 { let a = Box::new(1); let b = a; *a }'
+compile_fail 'fn take(b: Box<i32>) -> i32 { *b } fn main() -> i32 { let a = Box::new(1); take(a); *a }'
+compile_fail 'fn main() -> i32 { let b = Box::new(Box::new(1)); let c = *b; **b }'
+# Drop
+D='struct D { n: i32, b: Box<i32> } impl Drop for D { fn drop(&mut self) { } }'
+compile_fail "$D"' fn main() -> i32 { let d = D { n: 1, b: Box::new(2) }; let b = d.b; 0 }'
+compile_fail "$D"' fn main() -> i32 { let d = D { n: 1, b: Box::new(2) }; let e = D { n: 2, ..d }; 0 }'
+compile_fail 'fn main() -> i32 { let a = [Box::new(1)]; let b = a[0]; 0 }'
+compile_fail 'struct S { b: Box<i32> } impl Drop for S { fn drop(&mut self) { let b = self.b; } } fn main() -> i32 { 0 }'
+compile_fail 'struct S; impl Clone for S { fn clone(&self) {} } fn main() -> i32 { 0 }'
+compile_fail 'impl Drop for i32 { fn drop(&mut self) {} } fn main() -> i32 { 0 }'
+compile_fail 'struct S; impl Drop for S { fn drop(&self) {} } fn main() -> i32 { 0 }'
+compile_fail 'struct S; impl Drop for S { fn drop(&mut self) {} } impl Drop for S { fn drop(&mut self) {} } fn main() -> i32 { 0 }'
+compile_fail 'struct S; impl Drop for S { fn drop(&mut self) {} fn foo(&mut self) {} } fn main() -> i32 { 0 }'
+compile_fail 'struct S; impl Drop for S { } fn main() -> i32 { 0 }'
+compile_fail 'fn f(x: Self) {} fn main() -> i32 { 0 }'
+# use of moved values
+compile_fail "$D"' fn main() -> i32 { let a = D { n: 1, b: Box::new(2) }; let b = a; let c = a; 0 }'
+compile_fail "$D"' fn take(d: D) {} fn main() -> i32 { let a = D { n: 1, b: Box::new(2) }; take(a); take(a); 0 }'
+compile_fail 'fn main() -> i32 { let a = Box::new(1); let b = a; let c = a; *c }'
+compile_fail 'fn take(a: Box<i32>, b: Box<i32>) {} fn main() -> i32 { let a = Box::new(1); take(a, a); 0 }'
+compile_fail "$D"' fn main() -> i32 { let a = D { n: 1, b: Box::new(2) }; if true == true { let b = a; } else { }; let c = a; 0 }'
+compile_fail "$D"' fn main() -> i32 { let a = D { n: 1, b: Box::new(2) }; let b = a; a.n }'
+compile_fail "$D"' fn main() -> i32 { let a = D { n: 1, b: Box::new(2) }; let b = a; let r = &a; 0 }'
+compile_fail "$D"' fn main() -> i32 { let a = D { n: 1, b: Box::new(2) }; let b = a; a.n = 2; 0 }'
+compile_fail 'struct W { a: Box<i32>, b: Box<i32> } fn main() -> i32 { let w = W { a: Box::new(1), b: Box::new(2) }; let a = w.a; let v = w; 0 }'
+compile_fail 'fn main() -> i32 { let b = Box::new(Box::new(1)); let c = *b; let d = b; 0 }'
+compile_fail_with 'Use of moved value `b` at <anon>:1:86' 'fn take(b: Box<i32>) -> i32 { *b } fn main() -> i32 { let b = Box::new(1); take(b) + *b }'
+compile_fail_with 'value moved here at <anon>:1:81' 'fn take(b: Box<i32>) -> i32 { *b } fn main() -> i32 { let b = Box::new(1); take(b) + *b }'
+# Borrow checking
+compile_fail 'fn main() -> i32 { let x = 1; let r = &x; x = 2; *r }'
+compile_fail 'fn main() -> i32 { let x = 1; let a = &mut x; let b = &mut x; *a + *b }'
+compile_fail 'fn main() -> i32 { let x = 1; let a = &mut x; let b = &x; *a }'
+compile_fail 'fn main() -> i32 { let x = 1; let a = &mut x; let y = x; *a = 2; y }'
+compile_fail 'fn take(a: &mut i32, b: &i32) -> () {} fn main() -> i32 { let x = 1; take(&mut x, &x); 0 }'
+compile_fail 'fn f() -> &i32 { let x = 1; &x } fn main() -> i32 { 0 }'
+compile_fail 'fn f(x: i32) -> &i32 { if x == 1 { return &x; } else { }; &x } fn main() -> i32 { 0 }'
+compile_fail 'fn f(p: &i32) -> &i32 { p } fn main() -> i32 { let r; { let x = 1; r = f(&x); }; *r }'
+compile_fail 'fn main() -> i32 { let r; { r = &5; }; *r }'
+compile_fail 'fn main() -> i32 { let a = 1; let b = 2; let r = &a; if true == true { r = &b; } else { }; a = 5; *r }'
+compile_fail 'fn main() -> i32 { let b = Box::new(1); let r = &b; let c = b; **r }'
+compile_fail 'fn main() -> i32 { let x = 1; let p = &mut x; let r = &mut *p; *p = 2; *r }'
+compile_fail 'struct G { r: &i32 } impl Drop for G { fn drop(&mut self) { } } fn main() -> i32 { let g; let x = 1; g = G { r: &x }; 0 }'
+compile_fail 'struct G<'$QT'a> { r: &'$QT'a i32 } impl<'$QT'a> Drop for G<'$QT'a> { fn drop(&mut self) { } } fn main() -> i32 { let g; let x = 1; g = G { r: &x }; 0 }'
+compile_fail 'fn f<'$QT'a>() -> &'$QT'a i32 { let x = 1; &x } fn main() -> i32 { 0 }'
+
+# Lifetimes
+compile_fail 'fn f(x: &'$QT'a i32) -> i32 { *x } fn main() -> i32 { 0 }'
+compile_fail 'fn f<'$QT'a>(x: &'$QT'a i32) -> i32 { let y: &'$QT'b i32 = x; *y } fn main() -> i32 { 0 }'
+compile_fail 'fn f<'$QT'a, '$QT'a>(x: &'$QT'a i32) -> () { } fn main() -> i32 { 0 }'
+compile_fail 'struct S { r: &i32 } fn main() -> i32 { 0 }'
+compile_fail 'struct S<'$QT'a> { r: &'$QT'a i32 } struct T { s: S } fn main() -> i32 { 0 }'
+compile_fail 'type R = &i32; fn main() -> i32 { 0 }'
+compile_fail 'fn f() -> &i32 { f() } fn main() -> i32 { 0 }'
+compile_fail 'fn f(a: &i32, b: &i32) -> &i32 { a } fn main() -> i32 { 0 }'
+compile_fail 'struct S<'$QT'a> { r: &'$QT'a i32 } fn get(s: &S) -> &i32 { s.r } fn main() -> i32 { 0 }'
+compile_fail 'struct S<'$QT'a> { r: &'$QT'a i32 } fn get<'$QT'a>(s: &S<'$QT'a>) -> &i32 { s.r } fn main() -> i32 { 0 }'
+compile_fail 'fn f<'$QT'a, '$QT'b>(x: &'$QT'a i32, y: &'$QT'b i32) -> &'$QT'a i32 { y } fn main() -> i32 { 0 }'
+compile_fail 'fn f(x: &i32) -> &'$QT'static i32 { x } fn main() -> i32 { 0 }'
+compile_fail 'fn first<'$QT'a>(x: &'$QT'a i32, y: &i32) -> &'$QT'a i32 { x } fn main() -> i32 { let b = 1; let r; { let a = 2; r = first(&a, &b); }; *r }'
+
+# Unsafety
+compile_fail 'extern "C" { fn abs(x: i32) -> i32; } fn main() -> i32 { abs(-3) }'
+compile_fail 'unsafe fn f() -> i32 { 3 } fn main() -> i32 { f() }'
+compile_fail 'fn main() -> i32 { let x = 4; let p = &x as *const i32; *p }'
+compile_fail 'unsafe fn f() -> i32 { 3 } fn main() -> i32 { let x = unsafe { 1 }; f() + x }'
+compile_fail 'fn main() -> i32 { let x = Box::new(4); let p = &x as *const Box<i32>; let y = unsafe { *p }; 0 }'
+compile_fail_with 'Use of mutable static is unsafe' 'static mut X: i32 = 0; fn main() -> i32 { X }'
+compile_fail_with 'Use of mutable static is unsafe' 'static mut X: i32 = 0; fn main() -> i32 { X = 1; 0 }'
+compile_fail_with 'Use of mutable static is unsafe' 'static mut X: i32 = 0; fn main() -> i32 { let r = &X; 0 }'
+compile_fail 'static X: i32 = 0; fn main() -> i32 { unsafe { X } }' -D unused_unsafe
+
+# Raw pointers
+compile_fail 'fn main() -> i32 { let x = 1; let p = &x as *mut i32; 0 }'
+compile_fail 'fn main() -> i32 { let a = [1, 2]; let p = &a[0] as *const i32; let q = p.add(1); 0 }'
+compile_fail 'fn main() -> i32 { let x = 1; let i: i32 = 0; let p = &x as *const i32; unsafe { *p.add(i) } }'
+compile_fail 'fn main() -> i32 { let x = 1; let p = &x as *const i32; let f = p as f64; 0 }'
+compile_fail 'fn main() -> i32 { let x = 1; let p = &x as *const i32; if p.is_null(1) { 1 } else { 0 } }'
+compile_fail 'fn main() -> i32 { let x = 1; x.add(1) }'
+compile_fail 'fn main() -> i32 { let n: usize = 1; let m = -n; 0 }'
+compile_fail 'fn main() -> i32 { let p = core::ptr::null(); 0 }'
+
+# Casts
+compile_fail 'fn main() -> i32 { let b = 1 as bool; 0 }'
+compile_fail 'fn main() -> i32 { let x: i32 = 65; let c = x as char; 0 }'
+compile_fail 'fn main() -> i32 { let f = 1.5 as char; 0 }'
+compile_fail 'fn main() -> i32 { let f = '$QT'a'$QT' as f64; 0 }'
+compile_fail 'fn main() -> i32 { let f = true as f32; 0 }'
+compile_fail 'fn main() -> i32 { let x = 1; &x as i32 }'
+compile_fail 'fn main() -> i32 { let x: u8 = 1; let y = -x; 0 }'
+compile_fail 'fn main() -> i32 { let x = -1; let y: u8 = x; 0 }'
+compile_fail 'fn f(a: u32) -> () { } fn main() -> () { f(-5); }'
+compile_fail 'fn main() -> () { let x = 1; let y = -x; let z: u64 = y; }'
+
+# Constants
+compile_fail 'const A: u8 = 200 + 100; fn main() -> () { }'
+compile_fail 'const A: i32 = 1 / 0; fn main() -> () { }'
+compile_fail 'const A: i8 = -128 % -1; fn main() -> () { }'
+compile_fail 'const A: u8 = 256; fn main() -> () { }'
+compile_fail 'const A: i32 = B; const B: i32 = A; fn main() -> () { }'
+compile_fail 'fn f() -> usize { 2 } fn main() -> () { let a: [i32; f()] = [1, 2]; }'
+compile_fail 'fn main() -> () { let n: usize = 2; let a: [i32; n] = [1, 2]; }'
+compile_fail 'const A: i32 = 2; fn main() -> () { let a: [i32; A] = [1, 2]; }'
+compile_fail 'fn main() -> () { let a: [i32; 2 + 1] = [1, 2]; }'
+compile_fail 'const fn f(n: i32) -> i32 { f(n + 1) } const A: i32 = f(0); fn main() -> () { }'
+compile_fail 'const fn f() -> i32 { panic!("no") } const A: i32 = f(); fn main() -> () { }'
+compile_fail 'const A: &str = "a"; fn main() -> () { }'
+compile_fail '#[deny(dead_code)] const A: i32 = 1; fn main() -> () { }'
+
+# Statics
+compile_fail_with 'Cannot assign to immutable static item `X`' 'static X: i32 = 0; fn main() -> i32 { X = 1; 0 }'
+compile_fail_with 'Cannot borrow immutable static item `X` as mutable' 'static X: i32 = 0; fn main() -> i32 { let r = &mut X; 0 }'
+compile_fail_with 'Statics of type `f64` are not supported' 'static X: f64 = 1.0; fn main() -> () { }'
+compile_fail 'static X: u8 = 255 + 1; fn main() -> () { }'
+compile_fail 'static X: i32 = 1; const C: i32 = X; fn main() -> () { }'
+compile_fail 'fn f() -> i32 { 1 } static X: i32 = f(); fn main() -> () { }'
+compile_fail '#[deny(dead_code)] static X: i32 = 1; fn main() -> () { }'
+
+# Lints
+compile_fail '#[deny(unused_variables)] fn main() -> () { let x = 1; }'
+compile_fail '#[deny(dead_code)] mod m { fn f() -> () { } } fn main() -> () { }'
+compile_fail 'fn f() -> i32 { 1 } fn main() -> () { f(); }' -D unused_results
+compile_fail 'fn main() -> () { unsafe { } }' -D warnings
+compile_fail '#[deny(warnings)] fn main() -> () { let x = 1; }'
+compile_fail '#[allow(unused_variables)] fn main() -> () { let x = 1; }' -D unused_variables -D dead_code -A nope
+compile_fail 'fn f() -> i32 { 1 } fn main() -> () { #[deny(unused_results)] f(); }'
+compile_fail '#[allow(unused_variables)] fn main() -> () { #[deny(unused_variables)] let x = 1; }'
+compile_fail 'fn main() -> () { #[allow] let x = 1; }'
+compile_fail 'type T = i32; fn main() -> () { }' -D dead_code
+compile_fail 'mod m { struct S { a: i32 } } fn f(s: &m::S) -> () { } fn main() -> () { }' -D dead_code
+compile_fail 'fn main() -> () { let x: u8 = 256; }'
+compile_fail_with 'Variable `x` does not need to be mutable at <anon>:1:28' 'fn main() -> i32 { let mut x = 1; x }' -D unused_mut
+compile_fail '#[deny(unused_mut)] fn f(r: &mut [i32; 1]) -> () { let mut s = r; s[0] = 1; } fn main() -> () { }'
+compile_fail '#[deny(unused_mut)] fn main() -> () { let mut p = &mut 0; *p = 1; }'
+compile_fail 'fn main() -> () { let x = 3000000000; }'
+compile_fail 'fn main() -> () { let x: i8 = -129; }'
+compile_fail 'fn f(x: u32) -> () { } fn main() -> () { f(4294967296); }'
+compile_fail 'fn main() -> () { let x: u64 = 18446744073709551616; }'
+compile_fail '#[allow] fn main() -> () { }'
+compile_fail '#[allow(dead_code = "x")] fn main() -> () { }'
+compile_fail 'mod m { } fn main() -> () { let x = m; }'
+compile_fail 'fn f() -> () { } fn main() -> () { let x: f = f(); }'
+compile_fail 'struct S { a: i32 } fn main() -> () { let s = S; }'
+compile_fail 'type T = i32; fn main() -> () { let x = T { }; }'
+# inline attributes
+compile_fail '#[inline(sometimes)] fn f() -> () { } fn main() -> () { f(); }'
+compile_fail '#[inline = "always"] fn f() -> () { } fn main() -> () { f(); }'
+compile_fail '#[inline(always, never)] fn f() -> () { } fn main() -> () { f(); }'
+compile_fail '#[inline] struct S { a: i32 } fn main() -> () { }'
@@ -0,0 +1,65 @@
+#!/bin/bash
+cd $(dirname $0)
+RUSTC="../target/debug/mini-rustc"
+
+RED='\033[0;31m'
+GREEN='\033[0;32m'
+GRAY='\033[0;30m'
+NC='\033[0m' # No Color
+
+# Interpret the program and check the exit code and stdout
+assert_run() {
+  expected_code="$1"
+  expected_stdout="$2"
+  input="$3"
+  actual_stdout=$($RUSTC "$input" "${@:4}" --run)
+  code="$?"
+  if [ "$code" = "$expected_code" ] && [ "$actual_stdout" = "$expected_stdout" ]; then
+    echo -e "[${GREEN}OK${NC}] $input ${GRAY}=> $code${NC}"
+  else
+    echo -e "[${RED}ERROR${NC}] $input ${GRAY}=> $expected_code and \"$expected_stdout\" expected, but got $code and \"$actual_stdout\"${NC}"
+    exit 1
+  fi
+}
+
+# Interpret the program, which must stop with undefined behavior
+assert_ub() {
+  input="$1"
+  stderr=$($RUSTC "$input" "${@:2}" --run 2>&1 >/dev/null)
+  code="$?"
+  if [ "$code" = 1 ] && [[ "$stderr" == *"Undefined behavior"* ]]; then
+    echo -e "[${GREEN}OK${NC}] $input"
+  else
+    echo -e "[${RED}ERROR${NC}] $input ${GRAY}=> Undefined behavior expected, but got $code and \"$stderr\"${NC}"
+    exit 1
+  fi
+}
+
+QT="'"
+
+echo "===== Interpreter Tests ====="
+# libc shims
+assert_run 4 'abc' 'extern "C" { fn puts(s: &str) -> i32; } fn main() -> i32 { unsafe { puts("abc") } }'
+assert_run 14 '[   42|7  |%]' 'extern "C" { fn printf(s: &str, a: i32, b: i32) -> i32; } fn main() -> i32 { unsafe { printf("[%5d|%-3d|%%]\n", 42, 7) } }'
+assert_run 5 '' 'extern "C" { fn strlen(s: &str) -> usize; } fn main() -> i32 { unsafe { strlen("hello") as i32 } }'
+assert_run 42 '' 'extern "C" { fn malloc(n: usize) -> *mut i32; fn free(p: *mut i32); } fn main() -> i32 { unsafe { let p = malloc(8); *p = 20; *p.add(1) = 22; let x = *p + *p.add(1); free(p); x } }'
+assert_run 7 'a' 'extern "C" { fn exit(c: i32); } fn main() -> i32 { println!("a"); unsafe { exit(7); } 0 }'
+# the exit code is the return value of `main`
+assert_run 0 'hi' 'fn main() { println!("hi"); }'
+assert_run 255 '' 'fn main() -> i32 { -1 }'
+# foreign functions which are not emulated cannot be called
+assert_run 1 '' 'extern "C" { fn foo(); } fn main() -> i32 { unsafe { foo(); } 0 }'
+
+# out-of-bounds accesses
+assert_ub 'fn main() -> i32 { let a = [1, 2, 3]; let i = 5; a[i] }'
+assert_ub 'extern "C" { fn malloc(n: usize) -> *mut i32; } fn main() -> i32 { unsafe { let p = malloc(8); *p.add(2) = 1; } 0 }'
+# reads of uninitialized memory
+assert_ub 'extern "C" { fn malloc(n: usize) -> *mut i32; } fn main() -> i32 { unsafe { let p = malloc(4); *p } }'
+assert_ub 'fn main() -> i32 { let a: i32; let p = &a as *const i32; unsafe { *p } }' -A unused_variables
+# invalid frees and uses after free
+assert_ub 'extern "C" { fn malloc(n: usize) -> *mut i32; fn free(p: *mut i32); } fn main() -> i32 { unsafe { let p = malloc(4); free(p); free(p); } 0 }'
+assert_ub 'extern "C" { fn malloc(n: usize) -> *mut i32; fn free(p: *mut i32); } fn main() -> i32 { unsafe { let p = malloc(4); *p = 3; free(p); *p } }'
+assert_ub 'extern "C" { fn free(p: *const i32); } fn main() -> i32 { let a = 1; unsafe { free(&a as *const i32); } 0 }'
+# dangling and null pointers
+assert_ub 'fn f() -> *const i32 { let a = 1; &a as *const i32 } fn main() -> i32 { let p = f(); unsafe { *p } }'
+assert_ub 'fn main() -> i32 { let p: *const i32 = core::ptr::null(); unsafe { *p } }'